@@ -15,6 +15,27 @@ fn enter_to_continue() {
     requestty::prompt_one(question).unwrap();
 }
 
+/// Print the network state in YAML
+pub async fn show_state(grpc_addr: Arc<&str>) -> Result<()> {
+    let state = services::nm::show_netstate(grpc_addr).await?;
+    print!("{}", state);
+    Ok(())
+}
+
+/// Apply the network state of the YAML file and print the changes, they are
+/// only planned in a dry run.
+pub async fn apply_state(grpc_addr: Arc<&str>, file: &str, dry_run: bool) -> Result<()> {
+    let state = std::fs::read_to_string(file)?;
+    let changes = services::nm::apply_netstate(grpc_addr, state, dry_run).await?;
+    if changes.is_empty() {
+        println!("The network state is already applied");
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
 pub async fn mainloop(grpc_addr: Arc<&str>) -> Result<()> {
     loop {
         let terminal = terminal::stdout();
@@ -6,7 +6,11 @@
 use std::sync::Arc;
 
 use eyre::{ContextCompat, Result};
-use orbuculum_grpc::{ConnectionBody, ConnectionUuidRequest, NetworkClient};
+use orbuculum_grpc::{
+    CheckpointIdRequest, CheckpointRequest, ConnectionBody, ConnectionUuidRequest,
+    CreateConnectionBody, KeyfileExportRequest, KeyfileImportRequest, LegacyConfigRequest,
+    ManageBody, NetstateRequest, NetworkClient, RenameConnectionBody,
+};
 use serde_json::Value;
 
 pub async fn get_devices(grpc_addr: Arc<&str>) -> Result<Vec<Value>> {
@@ -17,9 +21,7 @@ pub async fn get_devices(grpc_addr: Arc<&str>) -> Result<Vec<Value>> {
         .into_inner()
         .data
         .into_iter()
-        .filter_map(|x| {
-            serde_json::to_value(x).ok()
-        })
+        .filter_map(|x| serde_json::to_value(x).ok())
         .collect();
     Ok(devices)
 }
@@ -51,6 +53,8 @@ pub fn connection_json2info(connection: &Value) -> Result<String> {
     let ipv6_addresses: Vec<String> =
         serde_json::from_value(connection["ip6info"]["addresses"].clone())?;
     let ipv6_dns: Vec<String> = serde_json::from_value(connection["ip6info"]["dns"].clone())?;
+    let ipv4_search = json_strings(&connection["ip4info"]["dns_search"]);
+    let ipv6_search = json_strings(&connection["ip6info"]["dns_search"]);
     let info = format!(
         "Connection: {}\n\
         UUID: {} \n\
@@ -60,26 +64,65 @@ pub fn connection_json2info(connection: &Value) -> Result<String> {
         Addresses: {}\n\
         Gateway: {}\n\
         DNS: {}\n\
+        DNS Search: {}\n\
         \n\
         IPv6:\n\
         Method: {}\n\
         Addresses: {}\n\
         Gateway: {}\n\
-        DNS: {}\n",
+        DNS: {}\n\
+        DNS Search: {}\n",
         connection["name"].as_str().unwrap_or(""),
         connection["uuid"].as_str().unwrap_or(""),
         connection["ip4info"]["method"].as_str().unwrap_or(""),
         ipv4_addresses.join(","),
         connection["ip4info"]["gateway"].as_str().unwrap_or(""),
         ipv4_dns.join(","),
+        ipv4_search.join(","),
         connection["ip6info"]["method"].as_str().unwrap_or(""),
         ipv6_addresses.join(","),
         connection["ip6info"]["gateway"].as_str().unwrap_or(""),
         ipv6_dns.join(","),
+        ipv6_search.join(","),
     );
     Ok(info)
 }
 
+fn json_strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|x| {
+            x.iter()
+                .filter_map(|x| x.as_str().map(|x| x.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The dns settings besides the dns servers
+pub struct DnsSettings {
+    pub search: Vec<String>,
+    pub options: Vec<String>,
+    pub priority: Option<i32>,
+    pub ignore_auto_dns: bool,
+}
+
+pub fn edit_dns(ipversion: &str, connection: &Value, settings: DnsSettings) -> Value {
+    let mut new_connection = connection.clone();
+    let key = if ipversion == "IPv4" {
+        "ip4info"
+    } else {
+        "ip6info"
+    };
+    new_connection[key]["dns_search"] = serde_json::to_value(settings.search).unwrap();
+    new_connection[key]["dns_options"] = serde_json::to_value(settings.options).unwrap();
+    if let Some(priority) = settings.priority {
+        new_connection[key]["dns_priority"] = priority.into();
+    }
+    new_connection[key]["ignore_auto_dns"] = settings.ignore_auto_dns.into();
+    new_connection
+}
+
 pub fn edit_connection<T>(method: &str, ipversion: &str, connection: &Value, func: T) -> Value
 where
     T: FnOnce(&str) -> (Vec<String>, String, Vec<String>),
@@ -115,9 +158,179 @@ where
     new_connection
 }
 
-pub async fn restart_connection(grpc_addr: Arc<&str>, uuid: String) -> Result<()> {
+/// Apply the saved connection to its device, the strategy used is returned.
+pub async fn restart_connection(grpc_addr: Arc<&str>, uuid: String) -> Result<String> {
     let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
     let request = tonic::Request::new(ConnectionUuidRequest { uuid });
-    client.reactive_connection(request).await?;
+    let response = client.reactive_connection(request).await?;
+    Ok(response.into_inner().strategy)
+}
+
+/// Create a wired connection, the device is the interface name or mac address.
+pub async fn create_connection(
+    grpc_addr: Arc<&str>,
+    name: String,
+    device: String,
+) -> Result<Value> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(CreateConnectionBody { name, device });
+    let response = client.create_connection(request).await?;
+    let connection = response
+        .into_inner()
+        .data
+        .wrap_err("Failed to create connection")?;
+    let connection = serde_json::to_value(&connection)?;
+    Ok(connection)
+}
+
+pub async fn rename_connection(grpc_addr: Arc<&str>, uuid: String, name: String) -> Result<()> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(RenameConnectionBody { uuid, name });
+    client.rename_connection(request).await?;
+    Ok(())
+}
+
+pub async fn delete_connection(grpc_addr: Arc<&str>, uuid: String) -> Result<()> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(ConnectionUuidRequest { uuid });
+    client.delete_connection_by_uuid(request).await?;
+    Ok(())
+}
+
+pub async fn set_manage(grpc_addr: Arc<&str>, device: String, is_managed: bool) -> Result<()> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(ManageBody { device, is_managed });
+    client.set_manage(request).await?;
     Ok(())
 }
+
+/// Create a checkpoint of the devices, the id of it is returned. The changes
+/// made after it are rolled back after `rollback_timeout` seconds unless it
+/// is confirmed.
+pub async fn begin_checkpoint(
+    grpc_addr: Arc<&str>,
+    devices: Vec<String>,
+    rollback_timeout: u32,
+) -> Result<String> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(CheckpointRequest {
+        devices,
+        rollback_timeout,
+    });
+    let response = client.begin_checkpoint(request).await?;
+    let checkpoint = response
+        .into_inner()
+        .data
+        .wrap_err("Failed to create checkpoint")?;
+    Ok(checkpoint.id)
+}
+
+pub async fn confirm_checkpoint(grpc_addr: Arc<&str>, id: String) -> Result<()> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(CheckpointIdRequest { id });
+    client.confirm_checkpoint(request).await?;
+    Ok(())
+}
+
+pub async fn rollback_checkpoint(grpc_addr: Arc<&str>, id: String) -> Result<()> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(CheckpointIdRequest { id });
+    client.rollback_checkpoint(request).await?;
+    Ok(())
+}
+
+/// Export the connection with the uuid, or all the connections, as keyfiles.
+/// The names and contents of the keyfiles are returned, the secrets are
+/// redacted unless `include_secrets` is set.
+pub async fn export_keyfiles(
+    grpc_addr: Arc<&str>,
+    uuid: Option<String>,
+    include_secrets: bool,
+) -> Result<Vec<(String, String)>> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(KeyfileExportRequest {
+        uuid,
+        include_secrets,
+    });
+    let response = client.export_keyfiles(request).await?;
+    let keyfiles = response
+        .into_inner()
+        .data
+        .into_iter()
+        .map(|x| (x.name, x.content))
+        .collect();
+    Ok(keyfiles)
+}
+
+/// Import the keyfile text, the uuid of the connection is returned.
+pub async fn import_keyfile(grpc_addr: Arc<&str>, content: String) -> Result<String> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(KeyfileImportRequest { content });
+    let response = client.import_keyfile(request).await?;
+    Ok(response.into_inner().uuid)
+}
+
+/// Parse the legacy configuration file, the profiles it would create and the
+/// unsupported directives are returned, nothing is changed.
+pub async fn preview_legacy_config(
+    grpc_addr: Arc<&str>,
+    format: String,
+    name: String,
+    content: String,
+) -> Result<Value> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(LegacyConfigRequest {
+        format,
+        name,
+        content,
+    });
+    let response = client.preview_legacy_config(request).await?;
+    let config = response
+        .into_inner()
+        .data
+        .wrap_err("Failed to preview legacy config")?;
+    Ok(serde_json::to_value(&config)?)
+}
+
+/// Create the profiles of the legacy configuration file, they are returned
+/// with their uuids.
+pub async fn apply_legacy_config(
+    grpc_addr: Arc<&str>,
+    format: String,
+    name: String,
+    content: String,
+) -> Result<Value> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(LegacyConfigRequest {
+        format,
+        name,
+        content,
+    });
+    let response = client.apply_legacy_config(request).await?;
+    let config = response
+        .into_inner()
+        .data
+        .wrap_err("Failed to apply legacy config")?;
+    Ok(serde_json::to_value(&config)?)
+}
+
+/// The network state in YAML
+pub async fn show_netstate(grpc_addr: Arc<&str>) -> Result<String> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(().into());
+    let response = client.show_netstate(request).await?;
+    Ok(response.into_inner().state)
+}
+
+/// Apply the network state in YAML, the changes are only planned in a dry
+/// run.
+pub async fn apply_netstate(
+    grpc_addr: Arc<&str>,
+    state: String,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let mut client = NetworkClient::connect(grpc_addr.to_string()).await?;
+    let request = tonic::Request::new(NetstateRequest { state, dry_run });
+    let response = client.apply_netstate(request).await?;
+    Ok(response.into_inner().changes)
+}
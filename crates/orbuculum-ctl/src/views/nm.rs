@@ -1,28 +1,72 @@
 //! The Network view
 use crate::services::nm::{
-    connection_json2info, edit_connection, get_connection, get_devices, restart_connection, update_connection
+    apply_legacy_config, begin_checkpoint, confirm_checkpoint, connection_json2info,
+    create_connection, delete_connection, edit_connection, edit_dns, export_keyfiles,
+    get_connection, get_devices, import_keyfile, preview_legacy_config, rename_connection,
+    restart_connection, rollback_checkpoint, set_manage, update_connection, DnsSettings,
 };
 use crate::utils::{QuestionOnce, QuestionText};
 use eyre::{ContextCompat, Result};
+use log::info;
 use requestty::{prompt_one, Question};
+use std::path::Path;
 use std::sync::Arc;
-use log::info;
+
+/// The seconds to confirm the changes within by default
+const DEFAULT_ROLLBACK_TIMEOUT: u32 = 60;
 
 pub async fn draw_nm_ui(grpc_addr: Arc<&str>) -> Result<()> {
-    let devices = get_devices(grpc_addr.clone()).await?;
-    let devices: Vec<serde_json::Value> = devices.into_iter().filter_map(|x| {
-        if x["connection"]["id"].is_null() {
-            return None;
-        }
-        if x["virtual"].as_bool().unwrap_or(false) {
-            return None;
-        }
-        serde_json::to_value(x).ok()
-    }).collect();
+    let selections = vec![
+        "Edit connection".to_string(),
+        "Create connection".to_string(),
+        "Rename connection".to_string(),
+        "Delete connection".to_string(),
+        "Set device managed".to_string(),
+        "Edit DNS settings".to_string(),
+        "Export keyfiles".to_string(),
+        "Import keyfile".to_string(),
+        "Import legacy config".to_string(),
+    ];
+    let once_question =
+        QuestionText::new("action", "Please select action:", &selections, &selections);
+    let action = once_question.execute()?;
+    match action.as_str() {
+        "Create connection" => draw_create_connection(grpc_addr).await,
+        "Rename connection" => draw_rename_connection(grpc_addr).await,
+        "Delete connection" => draw_delete_connection(grpc_addr).await,
+        "Set device managed" => draw_set_manage(grpc_addr).await,
+        "Edit DNS settings" => draw_edit_dns(grpc_addr).await,
+        "Export keyfiles" => draw_export_keyfiles(grpc_addr).await,
+        "Import keyfile" => draw_import_keyfile(grpc_addr).await,
+        "Import legacy config" => draw_import_legacy_config(grpc_addr).await,
+        _ => draw_edit_connection(grpc_addr).await,
+    }
+}
+
+/// Select a physical network device, the devices without connections are
+/// listed only if `with_connection` is false.
+async fn select_device(grpc_addr: Arc<&str>, with_connection: bool) -> Result<serde_json::Value> {
+    let devices = get_devices(grpc_addr).await?;
+    let devices: Vec<serde_json::Value> = devices
+        .into_iter()
+        .filter_map(|x| {
+            if with_connection && x["connection"]["id"].is_null() {
+                return None;
+            }
+            if x["virtual"].as_bool().unwrap_or(false) {
+                return None;
+            }
+            serde_json::to_value(x).ok()
+        })
+        .collect();
     let devices_info: Vec<String> = devices
         .iter()
         .filter_map(|device| {
-            device["connection"]["id"].as_str().map(|x| x.to_string())
+            if with_connection {
+                device["connection"]["id"].as_str().map(|x| x.to_string())
+            } else {
+                device["name"].as_str().map(|x| x.to_string())
+            }
         })
         .collect();
     let choices = devices_info.into_iter().map(|x| x.into()).collect();
@@ -33,10 +77,110 @@ pub async fn draw_nm_ui(grpc_addr: Arc<&str>) -> Result<()> {
         &devices,
     );
     let device = once_question.execute()?;
+    Ok(device.clone())
+}
+
+fn ask_for_text(name: &str, message: &str) -> Result<String> {
+    let question = Question::input(name).message(message).build();
+    let answer = prompt_one(question)?;
+    let text = answer.as_string().unwrap_or_default().trim().to_string();
+    Ok(text)
+}
+
+fn ask_for_confirm(name: &str, message: &str) -> Result<bool> {
+    let question = Question::confirm(name).message(message).build();
+    let answer = prompt_one(question)?;
+    Ok(answer.as_bool().unwrap_or_default())
+}
+
+async fn draw_create_connection(grpc_addr: Arc<&str>) -> Result<()> {
+    let device = select_device(grpc_addr.clone(), false).await?;
+    let device_name = device["name"]
+        .as_str()
+        .wrap_err("The device has no interface name.")?;
+    let name = ask_for_text("name", "Please enter the connection name:")?;
+    if name.is_empty() {
+        println!("The connection name must not be empty");
+        return Ok(());
+    }
+    match create_connection(grpc_addr, name, device_name.to_string()).await {
+        Ok(connection) => {
+            println!("Connection created");
+            println!("{}", connection_json2info(&connection)?);
+        }
+        Err(err) => println!("Connection created failed: {}", err),
+    }
+    Ok(())
+}
 
+async fn draw_rename_connection(grpc_addr: Arc<&str>) -> Result<()> {
+    let device = select_device(grpc_addr.clone(), true).await?;
     let conn_uuid = device["connection"]["uuid"]
         .as_str()
         .wrap_err("The connection doesn't exist.")?;
+    let name = ask_for_text("name", "Please enter the new connection name:")?;
+    if name.is_empty() {
+        println!("The connection name must not be empty");
+        return Ok(());
+    }
+    match rename_connection(grpc_addr, conn_uuid.to_string(), name).await {
+        Ok(()) => println!("Connection renamed"),
+        Err(err) => println!("Connection renamed failed: {}", err),
+    }
+    Ok(())
+}
+
+async fn draw_delete_connection(grpc_addr: Arc<&str>) -> Result<()> {
+    let device = select_device(grpc_addr.clone(), true).await?;
+    let conn_uuid = device["connection"]["uuid"]
+        .as_str()
+        .wrap_err("The connection doesn't exist.")?;
+    let message = format!(
+        "Do you want to delete the connection {}?",
+        device["connection"]["id"].as_str().unwrap_or(conn_uuid)
+    );
+    if ask_for_confirm("delete", &message)? {
+        match delete_connection(grpc_addr, conn_uuid.to_string()).await {
+            Ok(()) => println!("Connection deleted"),
+            Err(err) => println!("Connection deleted failed: {}", err),
+        }
+    }
+    Ok(())
+}
+
+async fn draw_set_manage(grpc_addr: Arc<&str>) -> Result<()> {
+    let device = select_device(grpc_addr.clone(), false).await?;
+    let device_name = device["name"]
+        .as_str()
+        .wrap_err("The device has no interface name.")?;
+    let is_managed = device["is_managed"].as_bool().unwrap_or_default();
+    let message = if is_managed {
+        format!(
+            "The device {} is managed, do you want to unmanage it?",
+            device_name
+        )
+    } else {
+        format!(
+            "The device {} is unmanaged, do you want to manage it?",
+            device_name
+        )
+    };
+    if ask_for_confirm("manage", &message)? {
+        match set_manage(grpc_addr, device_name.to_string(), !is_managed).await {
+            Ok(()) => println!("Device updated"),
+            Err(err) => println!("Device updated failed: {}", err),
+        }
+    }
+    Ok(())
+}
+
+async fn draw_edit_connection(grpc_addr: Arc<&str>) -> Result<()> {
+    let device = select_device(grpc_addr.clone(), true).await?;
+
+    let conn_uuid = device["connection"]["uuid"]
+        .as_str()
+        .wrap_err("The connection doesn't exist.")?;
+    let device_name = device["name"].as_str().unwrap_or_default();
     let connection = get_connection(grpc_addr.clone(), conn_uuid.to_string()).await?;
     let connection_string = connection_json2info(&connection)?;
     println!("{}", connection_string);
@@ -64,11 +208,8 @@ pub async fn draw_nm_ui(grpc_addr: Arc<&str>) -> Result<()> {
         );
         let ipmethod = once_question.execute()?;
         let new_connection = edit_connection(ipmethod, ipversion, &connection, ask_for_connection);
-        match update_connection(grpc_addr.clone(), &new_connection).await {
-            Ok(()) => {
-                println!("Connection updated");
-                ask_for_restart(grpc_addr.clone(), conn_uuid).await?;
-            }
+        match apply_connection(grpc_addr.clone(), device_name, conn_uuid, &new_connection).await {
+            Ok(()) => (),
             _ => {
                 println!("Connection updated failed");
                 println!("Please check the connection information");
@@ -81,6 +222,284 @@ pub async fn draw_nm_ui(grpc_addr: Arc<&str>) -> Result<()> {
     Ok(())
 }
 
+async fn draw_edit_dns(grpc_addr: Arc<&str>) -> Result<()> {
+    let device = select_device(grpc_addr.clone(), true).await?;
+    let conn_uuid = device["connection"]["uuid"]
+        .as_str()
+        .wrap_err("The connection doesn't exist.")?;
+    let device_name = device["name"].as_str().unwrap_or_default();
+    let connection = get_connection(grpc_addr.clone(), conn_uuid.to_string()).await?;
+    let selections = vec!["IPv4".to_string(), "IPv6".to_string()];
+    let once_question = QuestionText::new(
+        "ipversion",
+        "Please select netowrk IP version:",
+        &selections,
+        &selections,
+    );
+    let ipversion = once_question.execute()?;
+    let search = ask_for_list(
+        "search",
+        "Please enter dns search domains, e.g. example.com,lab.local:",
+    )?;
+    let options = ask_for_list(
+        "options",
+        "Please enter dns options, e.g. rotate,timeout:2:",
+    )?;
+    let priority = ask_for_text("priority", "Please enter dns priority (empty to keep):")?;
+    let priority = if priority.is_empty() {
+        None
+    } else {
+        match priority.parse::<i32>() {
+            Ok(priority) => Some(priority),
+            Err(_) => {
+                println!("The dns priority must be an integer");
+                return Ok(());
+            }
+        }
+    };
+    let ignore_auto_dns = ask_for_confirm("ignore_auto_dns", "Ignore the dns servers from DHCP?")?;
+    let settings = DnsSettings {
+        search,
+        options,
+        priority,
+        ignore_auto_dns,
+    };
+    let new_connection = edit_dns(ipversion, &connection, settings);
+    match apply_connection(grpc_addr.clone(), device_name, conn_uuid, &new_connection).await {
+        Ok(()) => (),
+        Err(err) => println!("DNS settings updated failed: {}", err),
+    }
+    Ok(())
+}
+
+/// Write the keyfiles of all the connections into a directory, as
+/// NetworkManager saves them in `/etc/NetworkManager/system-connections`.
+async fn draw_export_keyfiles(grpc_addr: Arc<&str>) -> Result<()> {
+    let directory = ask_for_text(
+        "directory",
+        "Please enter the directory to export to (empty for the current one):",
+    )?;
+    let directory = if directory.is_empty() {
+        ".".to_string()
+    } else {
+        directory
+    };
+    let include_secrets = ask_for_confirm(
+        "secrets",
+        "Include the secrets, e.g. passwords and private keys?",
+    )?;
+    let keyfiles = match export_keyfiles(grpc_addr, None, include_secrets).await {
+        Ok(keyfiles) => keyfiles,
+        Err(err) => {
+            println!("Keyfiles exported failed: {}", err);
+            return Ok(());
+        }
+    };
+    for (name, content) in keyfiles {
+        let path = Path::new(&directory).join(format!("{}.nmconnection", name.replace('/', "_")));
+        match std::fs::write(&path, content) {
+            Ok(()) => println!("Exported {}", path.display()),
+            Err(err) => println!("Exported {} failed: {}", path.display(), err),
+        }
+    }
+    Ok(())
+}
+
+async fn draw_import_keyfile(grpc_addr: Arc<&str>) -> Result<()> {
+    let path = ask_for_text("path", "Please enter the keyfile path:")?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) => {
+            println!("Failed to read {}: {}", path, err);
+            return Ok(());
+        }
+    };
+    match import_keyfile(grpc_addr, content).await {
+        Ok(uuid) => println!("Connection {} imported", uuid),
+        Err(err) => println!("Keyfile imported failed: {}", err),
+    }
+    Ok(())
+}
+
+/// Preview the profiles of an ifcfg, netplan or interfaces file, and create
+/// them once confirmed.
+async fn draw_import_legacy_config(grpc_addr: Arc<&str>) -> Result<()> {
+    let selections = vec![
+        "ifcfg".to_string(),
+        "netplan".to_string(),
+        "interfaces".to_string(),
+    ];
+    let once_question = QuestionText::new(
+        "format",
+        "Please select the format of the legacy config:",
+        &selections,
+        &selections,
+    );
+    let format = once_question.execute()?.to_string();
+    let path = ask_for_text("path", "Please enter the legacy config path:")?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) => {
+            println!("Failed to read {}: {}", path, err);
+            return Ok(());
+        }
+    };
+    let name = Path::new(&path)
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let config = match preview_legacy_config(
+        grpc_addr.clone(),
+        format.clone(),
+        name.clone(),
+        content.clone(),
+    )
+    .await
+    {
+        Ok(config) => config,
+        Err(err) => {
+            println!("Legacy config parsed failed: {}", err);
+            return Ok(());
+        }
+    };
+    let connections = config["connections"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    for connection in connections.iter() {
+        println!("{}", connection_json2info(connection)?);
+    }
+    let unsupported = config["unsupported"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    if !unsupported.is_empty() {
+        println!("Unsupported directives, they are not migrated:");
+        for directive in unsupported.iter() {
+            println!(
+                "  {}: {} ({})",
+                directive["location"].as_str().unwrap_or_default(),
+                directive["directive"].as_str().unwrap_or_default(),
+                directive["reason"].as_str().unwrap_or_default()
+            );
+        }
+    }
+    if connections.is_empty() {
+        println!("The legacy config has no connections to import");
+        return Ok(());
+    }
+    let message = format!(
+        "Do you want to create the {} connections?",
+        connections.len()
+    );
+    if ask_for_confirm("import", &message)? {
+        match apply_legacy_config(grpc_addr, format, name, content).await {
+            Ok(config) => {
+                for connection in config["connections"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default()
+                {
+                    println!(
+                        "Connection {} imported as {}",
+                        connection["name"].as_str().unwrap_or_default(),
+                        connection["uuid"].as_str().unwrap_or_default()
+                    );
+                }
+            }
+            Err(err) => println!("Legacy config imported failed: {}", err),
+        }
+    }
+    Ok(())
+}
+
+fn ask_for_list(name: &str, message: &str) -> Result<Vec<String>> {
+    let text = ask_for_text(name, message)?;
+    Ok(text
+        .split(',')
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect())
+}
+
+/// Update the connection, and restart it under a checkpoint of the device.
+///
+/// The changes are rolled back by NetworkManager unless they are confirmed
+/// within the seconds given, so a change which cuts off the access to the
+/// machine is undone by itself. The connection is only saved if the seconds
+/// are 0. Nothing is changed if the device of the connection is unknown.
+async fn apply_connection(
+    grpc_addr: Arc<&str>,
+    device_name: &str,
+    uuid: &str,
+    connection: &serde_json::Value,
+) -> Result<()> {
+    if device_name.is_empty() {
+        println!(
+            "The device of the connection {} is unknown, the changes are not applied",
+            uuid
+        );
+        return Ok(());
+    }
+    let message = format!(
+        "Please enter the seconds to confirm the changes within (empty for {}, 0 to save only):",
+        DEFAULT_ROLLBACK_TIMEOUT
+    );
+    let timeout = ask_for_text("timeout", &message)?;
+    let timeout = if timeout.is_empty() {
+        DEFAULT_ROLLBACK_TIMEOUT
+    } else {
+        match timeout.parse::<u32>() {
+            Ok(timeout) => timeout,
+            Err(_) => {
+                println!("The seconds must be an integer");
+                return Ok(());
+            }
+        }
+    };
+    if timeout == 0 {
+        update_connection(grpc_addr.clone(), connection).await?;
+        println!("Connection updated");
+        return ask_for_restart(grpc_addr, uuid).await;
+    }
+
+    let checkpoint =
+        begin_checkpoint(grpc_addr.clone(), vec![device_name.to_string()], timeout).await?;
+    let applied = match update_connection(grpc_addr.clone(), connection).await {
+        Ok(()) => restart_connection(grpc_addr.clone(), uuid.to_string()).await,
+        Err(err) => Err(err),
+    };
+    let strategy = match applied {
+        Ok(strategy) => strategy,
+        Err(err) => {
+            rollback_checkpoint(grpc_addr, checkpoint).await?;
+            return Err(err);
+        }
+    };
+    info!(
+        "Connection {} applied by {} under checkpoint {}",
+        uuid, strategy, checkpoint
+    );
+    println!("Connection updated and applied by {}", strategy);
+
+    let message = format!(
+        "Do you want to keep the changes? They are rolled back in {} seconds without confirmation.",
+        timeout
+    );
+    if ask_for_confirm("keep", &message)? {
+        match confirm_checkpoint(grpc_addr, checkpoint).await {
+            Ok(()) => println!("Changes confirmed"),
+            Err(err) => println!("The changes are rolled back already: {}", err),
+        }
+    } else {
+        match rollback_checkpoint(grpc_addr, checkpoint).await {
+            Ok(()) => println!("Changes rolled back"),
+            Err(err) => println!("Changes rolled back failed: {}", err),
+        }
+    }
+    Ok(())
+}
+
 async fn ask_for_restart(grpc_addr: Arc<&str>, uuid: &str) -> Result<()> {
     let question = Question::confirm("restart")
         .message("Do you want to restart the connection now?")
@@ -88,7 +507,8 @@ async fn ask_for_restart(grpc_addr: Arc<&str>, uuid: &str) -> Result<()> {
     let answer = prompt_one(question)?;
     let if_restart = answer.as_bool().unwrap_or(false);
     if if_restart {
-        restart_connection(grpc_addr, uuid.to_string()).await?;
+        let strategy = restart_connection(grpc_addr, uuid.to_string()).await?;
+        println!("Connection applied by {}", strategy);
     }
     Ok(())
 }
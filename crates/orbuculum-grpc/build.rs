@@ -15,7 +15,38 @@ fn main() {
             "#[derive(serde::Deserialize,serde::Serialize)]",
         )
         .type_attribute("Routes", "#[derive(serde::Deserialize,serde::Serialize)]")
+        .field_attribute("Routes.attributes", "#[serde(default)]")
+        .field_attribute("Routes.default_metric", "#[serde(default)]")
+        // The list is the value of `Netinfo.route_list` in JSON
+        .type_attribute(
+            "RouteList",
+            "#[derive(serde::Deserialize,serde::Serialize)] #[serde(transparent)]",
+        )
         .type_attribute("Netinfo", "#[derive(serde::Deserialize,serde::Serialize)]")
+        .field_attribute("Netinfo.routes", "#[serde(default)]")
+        .field_attribute("Netinfo.route_list", "#[serde(default)]")
+        .field_attribute("Netinfo.dns_search", "#[serde(default)]")
+        .field_attribute("Netinfo.dns_options", "#[serde(default)]")
+        .field_attribute("Netinfo.route_table", "#[serde(default)]")
+        .field_attribute("Netinfo.routing_rules", "#[serde(default)]")
+        .type_attribute(
+            "RoutingRule",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        // The list is the value of `Netinfo.routing_rules` in JSON
+        .type_attribute(
+            "RoutingRuleList",
+            "#[derive(serde::Deserialize,serde::Serialize)] #[serde(transparent)]",
+        )
+        // The lists are the values of `Netinfo.dns_search` and `Netinfo.dns_options` in JSON
+        .type_attribute(
+            "DnsSearchList",
+            "#[derive(serde::Deserialize,serde::Serialize)] #[serde(transparent)]",
+        )
+        .type_attribute(
+            "DnsOptionList",
+            "#[derive(serde::Deserialize,serde::Serialize)] #[serde(transparent)]",
+        )
         .type_attribute(
             "NetworkingStateBody",
             "#[derive(serde::Deserialize,serde::Serialize)]",
@@ -36,10 +67,31 @@ fn main() {
             "ConnectionBody",
             "#[derive(serde::Deserialize,serde::Serialize)]",
         )
+        .type_attribute(
+            "WiredSettings",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "WakeOnLan",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("WakeOnLan.flags", "#[serde(default)]")
         .type_attribute(
             "ConnectionReply",
             "#[derive(serde::Deserialize,serde::Serialize)]",
         )
+        .type_attribute(
+            "ActiveConnectionBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "ActiveConnectionsReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "ReactiveConnectionReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
         .type_attribute(
             "ConnectionsReply",
             "#[derive(serde::Deserialize,serde::Serialize)]",
@@ -48,6 +100,205 @@ fn main() {
             "ConnectionItem",
             "#[derive(serde::Deserialize,serde::Serialize)]",
         )
+        .type_attribute(
+            "ConnectionNameRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "CreateConnectionBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "RenameConnectionBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "DeleteConnectionReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "ManageBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "ManageReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "RouteRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute("VlanBody", "#[derive(serde::Deserialize,serde::Serialize)]")
+        .field_attribute("VlanBody.uuid", "#[serde(default)]")
+        .field_attribute("VlanBody.flags", "#[serde(default)]")
+        .field_attribute("VlanBody.ingress_priority_map", "#[serde(default)]")
+        .field_attribute("VlanBody.egress_priority_map", "#[serde(default)]")
+        .type_attribute(
+            "VlanReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute("BondBody", "#[derive(serde::Deserialize,serde::Serialize)]")
+        .field_attribute("BondBody.uuid", "#[serde(default)]")
+        .field_attribute("BondBody.ports", "#[serde(default)]")
+        .type_attribute(
+            "BondPortRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "BondReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "BridgeBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("BridgeBody.uuid", "#[serde(default)]")
+        .field_attribute("BridgeBody.ports", "#[serde(default)]")
+        .type_attribute(
+            "BridgePort",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "BridgePortRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("BridgePortRequest.migrate_ip", "#[serde(default)]")
+        .type_attribute(
+            "BridgeReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "WireGuardBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("WireGuardBody.uuid", "#[serde(default)]")
+        .field_attribute("WireGuardBody.peers", "#[serde(default)]")
+        // The list is the value of `WireGuardBody.peers` in JSON
+        .type_attribute(
+            "WireGuardPeerList",
+            "#[derive(serde::Deserialize,serde::Serialize)] #[serde(transparent)]",
+        )
+        .type_attribute(
+            "WireGuardPeer",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("WireGuardPeer.allowed_ips", "#[serde(default)]")
+        .type_attribute(
+            "WireGuardReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "WireGuardPeerStats",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("DevicesReplyBody.wireguard_peers", "#[serde(default)]")
+        .type_attribute(
+            "IpTunnelBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("IpTunnelBody.uuid", "#[serde(default)]")
+        .type_attribute(
+            "IpTunnelReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "VxlanBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("VxlanBody.uuid", "#[serde(default)]")
+        .type_attribute(
+            "VxlanReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "CheckpointRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("CheckpointRequest.devices", "#[serde(default)]")
+        .type_attribute(
+            "CheckpointIdRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "CheckpointBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "CheckpointReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "DeviceConnectivity",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "ConnectivityBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "ConnectivityReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "KeyfileExportRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("KeyfileExportRequest.uuid", "#[serde(default)]")
+        .field_attribute("KeyfileExportRequest.include_secrets", "#[serde(default)]")
+        .type_attribute(
+            "KeyfileBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "KeyfilesReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "KeyfileImportRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "KeyfileImportReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "LegacyConfigRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("LegacyConfigRequest.name", "#[serde(default)]")
+        .type_attribute(
+            "UnsupportedDirective",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "LegacyConfigBody",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "LegacyConfigReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "NetstateRequest",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .field_attribute("NetstateRequest.dry_run", "#[serde(default)]")
+        .type_attribute(
+            "NetstateReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "NetstateChangesReply",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "DeviceEvent",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
+        .type_attribute(
+            "ConnectionEvent",
+            "#[derive(serde::Deserialize,serde::Serialize)]",
+        )
         .file_descriptor_set_path(out_dir.join("network_descriptor.bin"))
         .compile(
             &["proto/network.proto"],
@@ -0,0 +1,734 @@
+//! The convert module maps the orbuculum-nm structures to the protobuf
+//! messages and back, and the `NetworkError` to the `tonic::Status`.
+//!
+//! Every field is mapped explicitly, so that a field added to or removed from
+//! either side breaks the build instead of the request at runtime.
+use crate::network_grpc::{
+    self, ActiveConnectionBody, BondBody, BridgeBody, CheckpointBody, ConnectionBody,
+    ConnectivityBody, DevicesReplyBody, IpTunnelBody, KeyfileBody, LegacyConfigBody, Netinfo,
+    Routes, VlanBody, VxlanBody, WireGuardBody,
+};
+use eyre::{bail, Result};
+use ipnet::IpNet;
+use orbuculum_nm::{
+    ActiveConnectionInfo, BondConnection, BridgeConnection, BridgePort, CheckpointInfo, Connection,
+    ConnectivityInfo, IpTunnelConnection, Keyfile, LegacyConfig, NetDevice, NetInfo, NetworkError,
+    Route, RoutingRule, UnsupportedDirective, VlanConnection, VxlanConnection, WireGuardConnection,
+    WireGuardPeer, WireGuardPeerStats, WiredSettings,
+};
+use std::net::IpAddr;
+use tonic::Status;
+
+const AF_INET: i32 = 2;
+const AF_INET6: i32 = 10;
+
+impl From<orbuculum_nm::ConnectionItem> for network_grpc::ConnectionItem {
+    fn from(item: orbuculum_nm::ConnectionItem) -> Self {
+        network_grpc::ConnectionItem {
+            id: item.id,
+            uuid: item.uuid,
+        }
+    }
+}
+
+impl From<Route> for Routes {
+    fn from(route: Route) -> Self {
+        let family = match route.family {
+            AF_INET6 => 6,
+            _ => 4,
+        };
+        Routes {
+            dest: route.dest.to_string(),
+            family,
+            metric: u32::try_from(route.metric).unwrap_or_default(),
+            default_metric: route.metric < 0,
+            next_hop: route.next_hop.map(|x| x.to_string()),
+            table: route.table,
+            attributes: route.attributes.into_iter().collect(),
+        }
+    }
+}
+
+impl TryFrom<Routes> for Route {
+    type Error = eyre::ErrReport;
+
+    fn try_from(routes: Routes) -> Result<Self> {
+        let family = match routes.family {
+            4 => AF_INET,
+            6 => AF_INET6,
+            other => bail!("Only v4 or v6 family is valid, got {}", other),
+        };
+        let next_hop = match routes.next_hop {
+            Some(next_hop) if !next_hop.is_empty() => Some(next_hop.parse()?),
+            _ => None,
+        };
+        Ok(Route {
+            family,
+            dest: routes.dest.parse()?,
+            next_hop,
+            metric: if routes.default_metric {
+                -1
+            } else {
+                routes.metric as i64
+            },
+            table: routes.table,
+            attributes: routes.attributes.into_iter().collect(),
+        })
+    }
+}
+
+impl From<RoutingRule> for network_grpc::RoutingRule {
+    fn from(rule: RoutingRule) -> Self {
+        network_grpc::RoutingRule {
+            priority: rule.priority,
+            from: rule.from.map(|x| x.to_string()),
+            to: rule.to.map(|x| x.to_string()),
+            table: rule.table,
+            iif: rule.iif,
+            oif: rule.oif,
+            fwmark: rule.fwmark,
+            fwmask: rule.fwmask,
+        }
+    }
+}
+
+impl TryFrom<network_grpc::RoutingRule> for RoutingRule {
+    type Error = eyre::ErrReport;
+
+    fn try_from(rule: network_grpc::RoutingRule) -> Result<Self> {
+        let selector = |addr: Option<String>| -> Result<Option<IpNet>> {
+            match addr {
+                Some(addr) if !addr.is_empty() => Ok(Some(addr.parse()?)),
+                _ => Ok(None),
+            }
+        };
+        Ok(RoutingRule {
+            priority: rule.priority,
+            from: selector(rule.from)?,
+            to: selector(rule.to)?,
+            table: rule.table,
+            iif: rule.iif,
+            oif: rule.oif,
+            fwmark: rule.fwmark,
+            fwmask: rule.fwmask,
+        })
+    }
+}
+
+impl From<NetInfo> for Netinfo {
+    fn from(netinfo: NetInfo) -> Self {
+        Netinfo {
+            addresses: netinfo.addresses.iter().map(|x| x.to_string()).collect(),
+            dns: netinfo.dns.iter().map(|x| x.to_string()).collect(),
+            dns_search: netinfo
+                .dns_search
+                .map(|domains| network_grpc::DnsSearchList { domains }),
+            dns_options: netinfo
+                .dns_options
+                .map(|options| network_grpc::DnsOptionList { options }),
+            dns_priority: netinfo.dns_priority,
+            ignore_auto_dns: netinfo.ignore_auto_dns,
+            never_default: netinfo.never_default,
+            may_fail: netinfo.may_fail,
+            dhcp_client_id: netinfo.dhcp_client_id,
+            dhcp_hostname: netinfo.dhcp_hostname,
+            dhcp_send_hostname: netinfo.dhcp_send_hostname,
+            dhcp_timeout: netinfo.dhcp_timeout,
+            addr_gen_mode: netinfo.addr_gen_mode,
+            ip6_privacy: netinfo.ip6_privacy,
+            gateway: netinfo.gateway.map(|x| x.to_string()),
+            method: Some(netinfo.method),
+            routes: netinfo
+                .routes
+                .iter()
+                .flatten()
+                .cloned()
+                .map(Routes::from)
+                .collect(),
+            route_list: netinfo.routes.map(|routes| network_grpc::RouteList {
+                routes: routes.into_iter().map(Routes::from).collect(),
+            }),
+            route_table: netinfo.route_table,
+            routing_rules: netinfo
+                .routing_rules
+                .map(|rules| network_grpc::RoutingRuleList {
+                    rules: rules
+                        .into_iter()
+                        .map(network_grpc::RoutingRule::from)
+                        .collect(),
+                }),
+        }
+    }
+}
+
+/// The routes to set, the `route_list` is preferred, the `routes` of the older
+/// clients are only taken if they are not empty.
+fn route_list(
+    route_list: Option<network_grpc::RouteList>,
+    routes: Vec<Routes>,
+) -> Result<Option<Vec<Route>>> {
+    let routes = match route_list {
+        Some(route_list) => route_list.routes,
+        None if !routes.is_empty() => routes,
+        None => return Ok(None),
+    };
+    let routes = routes
+        .into_iter()
+        .map(Route::try_from)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(routes))
+}
+
+impl TryFrom<Netinfo> for NetInfo {
+    type Error = eyre::ErrReport;
+
+    fn try_from(netinfo: Netinfo) -> Result<Self> {
+        let gateway = match netinfo.gateway {
+            Some(gateway) if !gateway.is_empty() => Some(gateway.parse()?),
+            _ => None,
+        };
+        Ok(NetInfo {
+            method: netinfo.method.unwrap_or_default(),
+            addresses: netinfo
+                .addresses
+                .iter()
+                .map(|x| x.parse())
+                .collect::<Result<Vec<_>, _>>()?,
+            gateway,
+            dns: netinfo
+                .dns
+                .iter()
+                .map(|x| x.parse())
+                .collect::<Result<Vec<_>, _>>()?,
+            dns_search: netinfo.dns_search.map(|x| x.domains),
+            dns_options: netinfo.dns_options.map(|x| x.options),
+            dns_priority: netinfo.dns_priority,
+            ignore_auto_dns: netinfo.ignore_auto_dns,
+            never_default: netinfo.never_default,
+            may_fail: netinfo.may_fail,
+            dhcp_client_id: netinfo.dhcp_client_id,
+            dhcp_hostname: netinfo.dhcp_hostname,
+            dhcp_send_hostname: netinfo.dhcp_send_hostname,
+            dhcp_timeout: netinfo.dhcp_timeout,
+            addr_gen_mode: netinfo.addr_gen_mode,
+            ip6_privacy: netinfo.ip6_privacy,
+            routes: route_list(netinfo.route_list, netinfo.routes)?,
+            route_table: netinfo.route_table,
+            routing_rules: netinfo
+                .routing_rules
+                .map(|x| {
+                    x.rules
+                        .into_iter()
+                        .map(RoutingRule::try_from)
+                        .collect::<Result<Vec<_>>>()
+                })
+                .transpose()?,
+        })
+    }
+}
+
+impl From<WiredSettings> for network_grpc::WiredSettings {
+    fn from(wired: WiredSettings) -> Self {
+        network_grpc::WiredSettings {
+            mtu: wired.mtu,
+            cloned_mac: wired.cloned_mac,
+            speed: wired.speed,
+            duplex: wired.duplex,
+            auto_negotiate: wired.auto_negotiate,
+            wake_on_lan: wired
+                .wake_on_lan
+                .map(|flags| network_grpc::WakeOnLan { flags }),
+            wake_on_lan_password: wired.wake_on_lan_password,
+        }
+    }
+}
+
+impl From<network_grpc::WiredSettings> for WiredSettings {
+    fn from(wired: network_grpc::WiredSettings) -> Self {
+        WiredSettings {
+            mtu: wired.mtu,
+            cloned_mac: wired.cloned_mac,
+            speed: wired.speed,
+            duplex: wired.duplex,
+            auto_negotiate: wired.auto_negotiate,
+            wake_on_lan: wired.wake_on_lan.map(|x| x.flags),
+            wake_on_lan_password: wired.wake_on_lan_password,
+        }
+    }
+}
+
+impl From<ActiveConnectionInfo> for ActiveConnectionBody {
+    fn from(connection: ActiveConnectionInfo) -> Self {
+        ActiveConnectionBody {
+            name: connection.name,
+            uuid: connection.uuid,
+            connection_type: connection.connection_type,
+            state: connection.state,
+            state_reason: connection.state_reason,
+            devices: connection.devices,
+            default4: connection.default4,
+            default6: connection.default6,
+            ip4info: connection.ip4info.map(Netinfo::from),
+            ip6info: connection.ip6info.map(Netinfo::from),
+        }
+    }
+}
+
+impl From<Connection> for ConnectionBody {
+    fn from(connection: Connection) -> Self {
+        ConnectionBody {
+            name: connection.name,
+            uuid: connection.uuid,
+            interface: connection.interface,
+            mac: connection.mac,
+            ip4info: Some(connection.ip4info.into()),
+            ip6info: Some(connection.ip6info.into()),
+            wired: connection.wired.map(network_grpc::WiredSettings::from),
+        }
+    }
+}
+
+impl TryFrom<ConnectionBody> for Connection {
+    type Error = eyre::ErrReport;
+
+    fn try_from(body: ConnectionBody) -> Result<Self> {
+        Ok(Connection {
+            name: body.name,
+            uuid: body.uuid,
+            interface: body.interface,
+            mac: body.mac,
+            ip4info: body
+                .ip4info
+                .map(NetInfo::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            ip6info: body
+                .ip6info
+                .map(NetInfo::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            wired: body.wired.map(WiredSettings::from),
+        })
+    }
+}
+
+impl From<VlanConnection> for VlanBody {
+    fn from(vlan: VlanConnection) -> Self {
+        VlanBody {
+            name: vlan.name,
+            uuid: vlan.uuid,
+            interface: vlan.interface,
+            parent: vlan.parent,
+            id: vlan.id,
+            flags: vlan.flags,
+            ingress_priority_map: vlan.ingress_priority_map,
+            egress_priority_map: vlan.egress_priority_map,
+            ip4info: vlan.ip4info.map(Netinfo::from),
+            ip6info: vlan.ip6info.map(Netinfo::from),
+        }
+    }
+}
+
+impl TryFrom<VlanBody> for VlanConnection {
+    type Error = eyre::ErrReport;
+
+    fn try_from(body: VlanBody) -> Result<Self> {
+        Ok(VlanConnection {
+            name: body.name,
+            uuid: body.uuid,
+            interface: body.interface,
+            parent: body.parent,
+            id: body.id,
+            flags: body.flags,
+            ingress_priority_map: body.ingress_priority_map,
+            egress_priority_map: body.egress_priority_map,
+            ip4info: body.ip4info.map(NetInfo::try_from).transpose()?,
+            ip6info: body.ip6info.map(NetInfo::try_from).transpose()?,
+        })
+    }
+}
+
+impl From<BondConnection> for BondBody {
+    fn from(bond: BondConnection) -> Self {
+        BondBody {
+            name: bond.name,
+            uuid: bond.uuid,
+            interface: bond.interface,
+            mode: bond.mode,
+            miimon: bond.miimon,
+            lacp_rate: bond.lacp_rate,
+            xmit_hash_policy: bond.xmit_hash_policy,
+            primary: bond.primary,
+            ports: bond.ports,
+            ip4info: bond.ip4info.map(Netinfo::from),
+            ip6info: bond.ip6info.map(Netinfo::from),
+        }
+    }
+}
+
+impl TryFrom<BondBody> for BondConnection {
+    type Error = eyre::ErrReport;
+
+    fn try_from(body: BondBody) -> Result<Self> {
+        Ok(BondConnection {
+            name: body.name,
+            uuid: body.uuid,
+            interface: body.interface,
+            mode: body.mode,
+            miimon: body.miimon,
+            lacp_rate: body.lacp_rate,
+            xmit_hash_policy: body.xmit_hash_policy,
+            primary: body.primary,
+            ports: body.ports,
+            ip4info: body.ip4info.map(NetInfo::try_from).transpose()?,
+            ip6info: body.ip6info.map(NetInfo::try_from).transpose()?,
+        })
+    }
+}
+
+impl From<BridgePort> for network_grpc::BridgePort {
+    fn from(port: BridgePort) -> Self {
+        network_grpc::BridgePort {
+            device: port.device,
+            path_cost: port.path_cost,
+            priority: port.priority,
+            hairpin: port.hairpin,
+        }
+    }
+}
+
+impl From<network_grpc::BridgePort> for BridgePort {
+    fn from(port: network_grpc::BridgePort) -> Self {
+        BridgePort {
+            device: port.device,
+            path_cost: port.path_cost,
+            priority: port.priority,
+            hairpin: port.hairpin,
+        }
+    }
+}
+
+impl From<BridgeConnection> for BridgeBody {
+    fn from(bridge: BridgeConnection) -> Self {
+        BridgeBody {
+            name: bridge.name,
+            uuid: bridge.uuid,
+            interface: bridge.interface,
+            stp: bridge.stp,
+            forward_delay: bridge.forward_delay,
+            priority: bridge.priority,
+            vlan_filtering: bridge.vlan_filtering,
+            ports: bridge
+                .ports
+                .into_iter()
+                .map(network_grpc::BridgePort::from)
+                .collect(),
+            ip4info: bridge.ip4info.map(Netinfo::from),
+            ip6info: bridge.ip6info.map(Netinfo::from),
+        }
+    }
+}
+
+impl TryFrom<BridgeBody> for BridgeConnection {
+    type Error = eyre::ErrReport;
+
+    fn try_from(body: BridgeBody) -> Result<Self> {
+        Ok(BridgeConnection {
+            name: body.name,
+            uuid: body.uuid,
+            interface: body.interface,
+            stp: body.stp,
+            forward_delay: body.forward_delay,
+            priority: body.priority,
+            vlan_filtering: body.vlan_filtering,
+            ports: body.ports.into_iter().map(BridgePort::from).collect(),
+            ip4info: body.ip4info.map(NetInfo::try_from).transpose()?,
+            ip6info: body.ip6info.map(NetInfo::try_from).transpose()?,
+        })
+    }
+}
+
+impl From<WireGuardPeer> for network_grpc::WireGuardPeer {
+    fn from(peer: WireGuardPeer) -> Self {
+        network_grpc::WireGuardPeer {
+            public_key: peer.public_key,
+            allowed_ips: peer.allowed_ips.iter().map(|x| x.to_string()).collect(),
+            endpoint: peer.endpoint,
+            persistent_keepalive: peer.persistent_keepalive,
+        }
+    }
+}
+
+impl TryFrom<network_grpc::WireGuardPeer> for WireGuardPeer {
+    type Error = eyre::ErrReport;
+
+    fn try_from(peer: network_grpc::WireGuardPeer) -> Result<Self> {
+        Ok(WireGuardPeer {
+            public_key: peer.public_key,
+            allowed_ips: peer
+                .allowed_ips
+                .iter()
+                .map(|x| x.parse())
+                .collect::<Result<Vec<_>, _>>()?,
+            endpoint: peer.endpoint,
+            persistent_keepalive: peer.persistent_keepalive,
+        })
+    }
+}
+
+impl From<WireGuardConnection> for WireGuardBody {
+    fn from(wireguard: WireGuardConnection) -> Self {
+        WireGuardBody {
+            name: wireguard.name,
+            uuid: wireguard.uuid,
+            interface: wireguard.interface,
+            // The private key is never returned
+            private_key: None,
+            public_key: wireguard.public_key,
+            listen_port: wireguard.listen_port,
+            fwmark: wireguard.fwmark,
+            peers: wireguard
+                .peers
+                .map(|peers| network_grpc::WireGuardPeerList {
+                    peers: peers
+                        .into_iter()
+                        .map(network_grpc::WireGuardPeer::from)
+                        .collect(),
+                }),
+            ip4info: wireguard.ip4info.map(Netinfo::from),
+            ip6info: wireguard.ip6info.map(Netinfo::from),
+        }
+    }
+}
+
+impl TryFrom<WireGuardBody> for WireGuardConnection {
+    type Error = eyre::ErrReport;
+
+    fn try_from(body: WireGuardBody) -> Result<Self> {
+        Ok(WireGuardConnection {
+            name: body.name,
+            uuid: body.uuid,
+            interface: body.interface,
+            private_key: body.private_key,
+            public_key: body.public_key,
+            listen_port: body.listen_port,
+            fwmark: body.fwmark,
+            peers: body
+                .peers
+                .map(|x| {
+                    x.peers
+                        .into_iter()
+                        .map(WireGuardPeer::try_from)
+                        .collect::<Result<Vec<_>>>()
+                })
+                .transpose()?,
+            ip4info: body.ip4info.map(NetInfo::try_from).transpose()?,
+            ip6info: body.ip6info.map(NetInfo::try_from).transpose()?,
+        })
+    }
+}
+
+/// Parse the optional address, the empty one is the same as not given.
+fn optional_addr(addr: Option<String>) -> Result<Option<IpAddr>> {
+    match addr {
+        Some(addr) if !addr.is_empty() => Ok(Some(addr.parse()?)),
+        _ => Ok(None),
+    }
+}
+
+impl From<IpTunnelConnection> for IpTunnelBody {
+    fn from(tunnel: IpTunnelConnection) -> Self {
+        IpTunnelBody {
+            name: tunnel.name,
+            uuid: tunnel.uuid,
+            interface: tunnel.interface,
+            mode: tunnel.mode,
+            local: tunnel.local.map(|x| x.to_string()),
+            remote: tunnel.remote.map(|x| x.to_string()),
+            ttl: tunnel.ttl,
+            key: tunnel.key,
+            ip4info: tunnel.ip4info.map(Netinfo::from),
+            ip6info: tunnel.ip6info.map(Netinfo::from),
+        }
+    }
+}
+
+impl TryFrom<IpTunnelBody> for IpTunnelConnection {
+    type Error = eyre::ErrReport;
+
+    fn try_from(body: IpTunnelBody) -> Result<Self> {
+        Ok(IpTunnelConnection {
+            name: body.name,
+            uuid: body.uuid,
+            interface: body.interface,
+            mode: body.mode,
+            local: optional_addr(body.local)?,
+            remote: optional_addr(body.remote)?,
+            ttl: body.ttl,
+            key: body.key,
+            ip4info: body.ip4info.map(NetInfo::try_from).transpose()?,
+            ip6info: body.ip6info.map(NetInfo::try_from).transpose()?,
+        })
+    }
+}
+
+impl From<VxlanConnection> for VxlanBody {
+    fn from(vxlan: VxlanConnection) -> Self {
+        VxlanBody {
+            name: vxlan.name,
+            uuid: vxlan.uuid,
+            interface: vxlan.interface,
+            id: vxlan.id,
+            local: vxlan.local.map(|x| x.to_string()),
+            remote: vxlan.remote.map(|x| x.to_string()),
+            destination_port: vxlan.destination_port,
+            parent: vxlan.parent,
+            ip4info: vxlan.ip4info.map(Netinfo::from),
+            ip6info: vxlan.ip6info.map(Netinfo::from),
+        }
+    }
+}
+
+impl TryFrom<VxlanBody> for VxlanConnection {
+    type Error = eyre::ErrReport;
+
+    fn try_from(body: VxlanBody) -> Result<Self> {
+        Ok(VxlanConnection {
+            name: body.name,
+            uuid: body.uuid,
+            interface: body.interface,
+            id: body.id,
+            local: optional_addr(body.local)?,
+            remote: optional_addr(body.remote)?,
+            destination_port: body.destination_port,
+            parent: body.parent,
+            ip4info: body.ip4info.map(NetInfo::try_from).transpose()?,
+            ip6info: body.ip6info.map(NetInfo::try_from).transpose()?,
+        })
+    }
+}
+
+impl From<CheckpointInfo> for CheckpointBody {
+    fn from(checkpoint: CheckpointInfo) -> Self {
+        CheckpointBody {
+            id: checkpoint.id,
+            devices: checkpoint.devices,
+            rollback_timeout: checkpoint.rollback_timeout,
+        }
+    }
+}
+
+impl From<ConnectivityInfo> for ConnectivityBody {
+    fn from(info: ConnectivityInfo) -> Self {
+        ConnectivityBody {
+            state: info.state,
+            check_enabled: info.check_enabled,
+            primary_connection: info.primary_connection.map(|x| x.into()),
+            metered: info.metered,
+            devices: info
+                .devices
+                .into_iter()
+                .map(|x| network_grpc::DeviceConnectivity {
+                    device: x.device,
+                    ip4: x.ip4,
+                    ip6: x.ip6,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Keyfile> for KeyfileBody {
+    fn from(keyfile: Keyfile) -> Self {
+        KeyfileBody {
+            name: keyfile.name,
+            uuid: keyfile.uuid,
+            content: keyfile.content,
+        }
+    }
+}
+
+impl From<UnsupportedDirective> for network_grpc::UnsupportedDirective {
+    fn from(directive: UnsupportedDirective) -> Self {
+        network_grpc::UnsupportedDirective {
+            location: directive.location,
+            directive: directive.directive,
+            reason: directive.reason,
+        }
+    }
+}
+
+impl From<LegacyConfig> for LegacyConfigBody {
+    fn from(config: LegacyConfig) -> Self {
+        LegacyConfigBody {
+            connections: config.connections.into_iter().map(Into::into).collect(),
+            unsupported: config.unsupported.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<WireGuardPeerStats> for network_grpc::WireGuardPeerStats {
+    fn from(stats: WireGuardPeerStats) -> Self {
+        network_grpc::WireGuardPeerStats {
+            public_key: stats.public_key,
+            endpoint: stats.endpoint,
+            last_handshake: stats.last_handshake,
+            rx_bytes: stats.rx_bytes,
+            tx_bytes: stats.tx_bytes,
+        }
+    }
+}
+
+impl From<NetDevice> for DevicesReplyBody {
+    fn from(device: NetDevice) -> Self {
+        DevicesReplyBody {
+            conn: device.conn,
+            dev_path: device.dev_path,
+            device_type: Some(device.device_type),
+            id_path: device.id_path,
+            ip4info: device.ip4info.map(Netinfo::from),
+            ip6info: device.ip6info.map(Netinfo::from),
+            is_managed: device.is_managed,
+            mac: device.mac,
+            conn_name: device.connection.id.clone(),
+            name: device.name,
+            state: device.state,
+            r#virtual: device.r#virtual,
+            net_link_modes: device.net_link_modes,
+            driver: device.driver,
+            connection: Some(device.connection.into()),
+            product_name: device.product_name.unwrap_or_default(),
+            controller: device.controller,
+            wireguard_peers: device
+                .wireguard_peers
+                .into_iter()
+                .map(network_grpc::WireGuardPeerStats::from)
+                .collect(),
+        }
+    }
+}
+
+/// Map the error of the network command to the gRPC status.
+pub(crate) fn into_status(err: NetworkError) -> Status {
+    let msg = err.message().to_string();
+    match err {
+        NetworkError::NotFound(_) => Status::not_found(msg),
+        NetworkError::NmUnavailable(_) => Status::unavailable(msg),
+        NetworkError::InvalidArgument(_) | NetworkError::InvalidSettings(..) => {
+            Status::invalid_argument(msg)
+        }
+        NetworkError::Timeout(_) => Status::deadline_exceeded(msg),
+        NetworkError::Conflict(_) => Status::failed_precondition(msg),
+        NetworkError::Backend(_) => Status::internal(msg),
+    }
+}
+
+/// The status for a response that doesn't match the command.
+pub(crate) fn unexpected_response() -> Status {
+    Status::internal("Unexpected response from the network thread")
+}
+
+/// The status for a request that can't be converted into orbuculum-nm structures.
+pub(crate) fn invalid_request(err: eyre::ErrReport) -> Status {
+    Status::invalid_argument(format!("Failed to parse request data: {}", err))
+}
@@ -1,10 +1,10 @@
 use eyre::{ContextCompat, Result};
-use orbuculum_nm::{send_command, NetworkCommand, State};
+use orbuculum_nm::{send_command, NetDevice, NetInfo, NetworkCommand, State};
 use orbuculum_rules::get_desired_devices;
 use serde_json::{json, Value};
 use std::sync::Arc;
 
-fn get_ip_config(config: &Value, key: &str) -> Result<Value> {
+fn get_ip_config(config: &Value, key: &str) -> Result<NetInfo> {
     let addresses: Vec<String> = config[key]["addresses"]
         .as_array()
         .map(|values| {
@@ -42,29 +42,34 @@ fn get_ip_config(config: &Value, key: &str) -> Result<Value> {
         .as_str()
         .map(|x| x.to_string())
         .wrap_err("Failed get method")?;
-    Ok(json!({"addresses": addresses, "dns": dns, "gateway": gateway, "method": method}))
+    let netinfo = serde_json::from_value(
+        json!({"addresses": addresses, "dns": dns, "gateway": gateway, "method": method}),
+    )?;
+    Ok(netinfo)
+}
+
+/// Apply the method, addresses, gateway and dns of the configuration file, the
+/// routing settings of the connection are kept.
+fn merge_ip_config(current: &mut NetInfo, config: NetInfo) {
+    current.method = config.method;
+    current.addresses = config.addresses;
+    current.gateway = config.gateway;
+    current.dns = config.dns;
 }
 
 struct Initlizer {
     nicrule_file: String,
-    devices: Vec<Value>,
+    devices: Vec<NetDevice>,
     state: Arc<State>,
 }
 
 impl Initlizer {
     async fn new_future(nicrule_file: String, state: Arc<State>) -> Result<Self> {
         let cloned_state = state.clone();
-        let opt_devices = send_command(cloned_state, NetworkCommand::ListDeivces)
-            .await
-            .and_then(|resp| {
-                Ok(resp.into_value().and_then(|value| {
-                    value.as_array().and_then(|items| {
-                        let items: Vec<Value> = items.iter().map(|x| x.clone()).collect();
-                        Some(items)
-                    })
-                }))
-            })?;
-        let devices = opt_devices.unwrap_or_default();
+        let devices = send_command(cloned_state, NetworkCommand::ListDeivces)
+            .await?
+            .into_devices()
+            .unwrap_or_default();
         Ok(Self {
             nicrule_file,
             devices,
@@ -81,70 +86,72 @@ impl Initlizer {
         Ok(())
     }
 
-    async fn update_configuration(&self, device_info: &Value, uuid: &str) {
+    async fn update_configuration(&self, device_info: &Value, uuid: &str) -> Result<()> {
         if device_info["ip4info"].is_object() || device_info["ip6info"].is_object() {
             let resp = send_command(
                 self.state.clone(),
                 NetworkCommand::GetConnection(uuid.to_string()),
             )
-            .await
-            .unwrap();
-            let mut connection = resp.into_value().expect("Failed to get connection by uuid");
-            match get_ip_config(device_info, "ip4info") {
-                Ok(ip4info) => connection["ip4info"] = ip4info,
-                _ => (),
+            .await?;
+            let mut connection = resp
+                .into_connection()
+                .wrap_err("Failed to get connection by uuid")?;
+            if let Ok(ip4info) = get_ip_config(device_info, "ip4info") {
+                merge_ip_config(&mut connection.ip4info, ip4info);
             }
-
-            match get_ip_config(device_info, "ip6info") {
-                Ok(ip6info) => connection["ip6info"] = ip6info,
-                _ => (),
+            if let Ok(ip6info) = get_ip_config(device_info, "ip6info") {
+                merge_ip_config(&mut connection.ip6info, ip6info);
             }
+            // The nic rules have no link settings, leave them unchanged.
+            connection.wired = None;
             send_command(
                 self.state.clone(),
-                NetworkCommand::UpdateConnection(connection.to_owned()),
+                NetworkCommand::UpdateConnection(connection),
             )
-            .await
-            .unwrap();
-            match send_command(
+            .await?;
+            if let Err(err) = send_command(
                 self.state.clone(),
                 NetworkCommand::Reactive(uuid.to_owned()),
             )
             .await
             {
-                _ => eprintln!("Failed to activate {}", uuid),
+                eprintln!("Failed to activate {}: {}", uuid, err);
             }
         }
+        Ok(())
     }
 
-    async fn update_managed_state(&self, device_info: &Value) {
+    async fn update_managed_state(&self, device_info: &Value) -> Result<()> {
         let managed_state = device_info["is_managed"].as_bool().unwrap_or(false);
-        let device = device_info["name"].as_str().map(|x| x.to_string()).unwrap();
+        let device = device_info["name"]
+            .as_str()
+            .map(|x| x.to_string())
+            .wrap_err("Failed to get name when initilising")?;
         send_command(
             self.state.clone(),
             NetworkCommand::SetManage(device, managed_state),
         )
-        .await
-        .unwrap();
+        .await?;
+        Ok(())
     }
 
     async fn init_connections(&self) -> Result<bool> {
         let devices_val = serde_json::to_value(&self.devices)?;
-        let sorted_devices = get_desired_devices(&self.nicrule_file, &devices_val)
-            .expect("Fail to get devices info");
+        let sorted_devices = get_desired_devices(&self.nicrule_file, &devices_val)?;
         let mut need_start = false;
         for device_info in sorted_devices {
             let device_name = device_info["name"]
                 .as_str()
-                .expect("Failed to get name when initilising");
+                .wrap_err("Failed to get name when initilising")?;
             let conn_name = device_info["con_name"]
                 .as_str()
-                .expect("Failed to get con_name when initilising");
+                .wrap_err("Failed to get con_name when initilising")?;
             let current_connection = device_info["connection"]
                 .as_object()
-                .expect("Failed to get connection when initilising");
+                .wrap_err("Failed to get connection when initilising")?;
             let current_uuid = current_connection
                 .get("uuid")
-                .expect("no uuid in connection object");
+                .wrap_err("no uuid in connection object")?;
             if let Some(uuid) = current_uuid.as_str() {
                 let current_name = current_connection["id"].as_str().unwrap_or("");
                 if current_name != conn_name {
@@ -171,27 +178,22 @@ impl Initlizer {
                 )
                 .await?;
                 let uuid = resp
-                    .into_value()
-                    .map(|value| {
-                        value
-                            .as_str()
-                            .map(|uuid| uuid.to_owned())
-                            .expect("Failed to get uuid of the new connection")
-                    })
-                    .expect("Failed to initilise new connection");
+                    .into_uuid()
+                    .wrap_err("Failed to initilise new connection")?;
 
-                self.update_configuration(&device_info, &uuid).await;
+                self.update_configuration(&device_info, &uuid).await?;
             };
-            self.update_managed_state(&device_info).await;
+            self.update_managed_state(&device_info).await?;
         }
         Ok(need_start)
     }
 }
 
-pub async fn initialize_network_manager(state: Arc<State>, nicrule_file: String) {
-    let initializer = Initlizer::new_future(nicrule_file, state).await.unwrap();
-    let need_start = initializer.init_connections().await.unwrap();
+pub async fn initialize_network_manager(state: Arc<State>, nicrule_file: String) -> Result<()> {
+    let initializer = Initlizer::new_future(nicrule_file, state).await?;
+    let need_start = initializer.init_connections().await?;
     if need_start {
-        initializer.restart_networking().await.unwrap();
+        initializer.restart_networking().await?;
     }
+    Ok(())
 }
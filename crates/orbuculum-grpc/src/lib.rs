@@ -1,5 +1,4 @@
-#[macro_use]
-extern crate eyre;
+mod convert;
 mod initialize;
 mod server;
 mod services;
@@ -15,6 +14,16 @@ pub mod network_grpc {
 pub use network_grpc::network_client::NetworkClient;
 use network_grpc::network_server::{Network, NetworkServer};
 pub use network_grpc::{
-    ConnectionBody, ConnectionReply, ConnectionUuidRequest, ConnectionsReply, DevicesReply,
-    DevicesReplyBody, HostnameBody, NetworkingStateBody, NetworkingStateReply,
+    ActiveConnectionBody, ActiveConnectionsReply, BondBody, BondPortRequest, BondReply, BridgeBody,
+    BridgePort, BridgePortRequest, BridgeReply, ChangeKind, CheckpointBody, CheckpointIdRequest,
+    CheckpointReply, CheckpointRequest, ConnectionBody, ConnectionEvent, ConnectionNameRequest,
+    ConnectionReply, ConnectionUuidRequest, ConnectionsReply, ConnectivityBody, ConnectivityReply,
+    CreateConnectionBody, DeleteConnectionReply, DeviceConnectivity, DeviceEvent, DevicesReply,
+    DevicesReplyBody, HostnameBody, IpTunnelBody, IpTunnelReply, KeyfileBody, KeyfileExportRequest,
+    KeyfileImportReply, KeyfileImportRequest, KeyfilesReply, LegacyConfigBody, LegacyConfigReply,
+    LegacyConfigRequest, ManageBody, ManageReply, NetstateChangesReply, NetstateReply,
+    NetstateRequest, NetworkingStateBody, NetworkingStateReply, ReactiveConnectionReply,
+    RenameConnectionBody, RouteRequest, Routes, UnsupportedDirective, VlanBody, VlanReply,
+    VxlanBody, VxlanReply, WakeOnLan, WireGuardBody, WireGuardPeer, WireGuardPeerStats,
+    WireGuardReply, WiredSettings,
 };
@@ -1 +1,2 @@
 pub mod nm;
+mod watch;
@@ -1,11 +1,23 @@
+use crate::convert::{into_status, invalid_request, unexpected_response};
 use crate::network_grpc::{
-    ConnectionsReply, HostnameBody, HostnameReply, NetworkingStateBody, NetworkingStateReply,
+    ActiveConnectionsReply, BondBody, BondPortRequest, BondReply, BridgeBody, BridgePortRequest,
+    BridgeReply, CheckpointIdRequest, CheckpointReply, CheckpointRequest, ConnectionEvent,
+    ConnectionNameRequest, ConnectionsReply, ConnectivityReply, CreateConnectionBody,
+    DeleteConnectionReply, DeviceEvent, HostnameBody, HostnameReply, IpTunnelBody, IpTunnelReply,
+    KeyfileExportRequest, KeyfileImportReply, KeyfileImportRequest, KeyfilesReply,
+    LegacyConfigReply, LegacyConfigRequest, ManageBody, ManageReply, NetstateChangesReply,
+    NetstateReply, NetstateRequest, NetworkingStateBody, NetworkingStateReply,
+    ReactiveConnectionReply, RenameConnectionBody, RouteRequest, VlanBody, VlanReply, VxlanBody,
+    VxlanReply, WireGuardBody, WireGuardReply,
 };
+use crate::services::watch::{watch_connections, watch_devices, WatchStream};
 
 use super::super::{ConnectionBody, ConnectionReply, ConnectionUuidRequest, DevicesReply, Network};
-use eyre::{ContextCompat, Result};
-use orbuculum_nm::{send_command, NetworkCommand, State};
-use serde_json::json;
+use orbuculum_nm::{
+    parse_legacy_config, send_command, BondConnection, BridgeConnection, BridgePort, Connection,
+    IpTunnelConnection, LegacyFormat, NetworkCommand, NetworkError, NetworkState, Route, State,
+    VlanConnection, VxlanConnection, WireGuardConnection,
+};
 
 use std::sync::Arc;
 use tonic::{Request, Response, Status};
@@ -15,25 +27,22 @@ pub struct NetworkService {}
 
 #[tonic::async_trait]
 impl Network for NetworkService {
+    type WatchDevicesStream = WatchStream<DeviceEvent>;
+    type WatchConnectionsStream = WatchStream<ConnectionEvent>;
+
     async fn list_devices(&self, request: Request<()>) -> Result<Response<DevicesReply>, Status> {
         let shared_state = request.extensions().get::<Arc<State>>().unwrap();
         let shared_state = Arc::clone(shared_state);
-        let resp = send_command(shared_state, NetworkCommand::ListDeivces)
+        let devices = send_command(shared_state, NetworkCommand::ListDeivces)
             .await
-            .and_then(|x| {
-                if let Some(devices) = x.into_value() {
-                    let data = serde_json::from_value(devices).unwrap();
-                    Ok(Response::new(DevicesReply {
-                        code: 0,
-                        msg: "Sucessful".into(),
-                        data,
-                    }))
-                } else {
-                    bail!("")
-                }
-            })
-            .unwrap();
-        Ok(resp)
+            .map_err(into_status)?
+            .into_devices()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(DevicesReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: devices.into_iter().map(Into::into).collect(),
+        }))
     }
 
     async fn get_connection_by_uuid(
@@ -43,50 +52,41 @@ impl Network for NetworkService {
         let shared_state = request.extensions().get::<Arc<State>>().unwrap();
         let shared_state = Arc::clone(shared_state);
         let uuid = request.into_inner().uuid;
-        let resp = send_command(shared_state, NetworkCommand::GetConnection(uuid.clone()))
-            .await
-            .and_then(|x| {
-                if let Some(connection) = x.into_value() {
-                    let data = serde_json::from_value(connection).unwrap();
-                    Ok(Response::new(ConnectionReply {
-                        code: 0,
-                        msg: "Sucessful".into(),
-                        data,
-                    }))
-                } else {
-                    bail!("Failed to get connection with uuid {}", uuid)
-                }
-            })
-            .unwrap();
-        Ok(resp)
+        let connection = send_command(shared_state, NetworkCommand::GetConnection(uuid))
+            .await
+            .map_err(into_status)?
+            .into_connection()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(connection.into()),
+        }))
     }
 
     async fn reactive_connection(
         &self,
         request: Request<ConnectionUuidRequest>,
-    ) -> Result<Response<ConnectionReply>, Status> {
+    ) -> Result<Response<ReactiveConnectionReply>, Status> {
         let shared_state = request.extensions().get::<Arc<State>>().unwrap();
         let shared_state = Arc::clone(shared_state);
         let uuid = request.into_inner().uuid;
-        send_command(shared_state.clone(), NetworkCommand::Reactive(uuid.clone()))
-            .await
-            .unwrap();
-        let resp = send_command(shared_state, NetworkCommand::GetConnection(uuid.clone()))
-            .await
-            .and_then(|x| {
-                if let Some(connection) = x.into_value() {
-                    let data = serde_json::from_value(connection).unwrap();
-                    Ok(Response::new(ConnectionReply {
-                        code: 0,
-                        msg: "Sucessful".into(),
-                        data,
-                    }))
-                } else {
-                    bail!("Failed to get connection with uuid {}", uuid)
-                }
-            })
-            .unwrap();
-        Ok(resp)
+        let strategy = send_command(shared_state.clone(), NetworkCommand::Reactive(uuid.clone()))
+            .await
+            .map_err(into_status)?
+            .into_applied()
+            .ok_or_else(unexpected_response)?;
+        let connection = send_command(shared_state, NetworkCommand::GetConnection(uuid))
+            .await
+            .map_err(into_status)?
+            .into_connection()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ReactiveConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(connection.into()),
+            strategy: strategy.to_string(),
+        }))
     }
 
     async fn list_connections(
@@ -95,22 +95,34 @@ impl Network for NetworkService {
     ) -> Result<Response<ConnectionsReply>, Status> {
         let shared_state = request.extensions().get::<Arc<State>>().unwrap();
         let shared_state = Arc::clone(shared_state);
-        let resp = send_command(shared_state, NetworkCommand::ListConnections)
+        let connections = send_command(shared_state, NetworkCommand::ListConnections)
             .await
-            .and_then(|x| {
-                if let Some(connections) = x.into_value() {
-                    let data = serde_json::from_value(connections).unwrap();
-                    Ok(Response::new(ConnectionsReply {
-                        code: 0,
-                        msg: "Sucessful".into(),
-                        data,
-                    }))
-                } else {
-                    bail!("")
-                }
-            })
-            .unwrap();
-        Ok(resp)
+            .map_err(into_status)?
+            .into_connections()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ConnectionsReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: connections.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn list_active_connections(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<ActiveConnectionsReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let connections = send_command(shared_state, NetworkCommand::ListActiveConnections)
+            .await
+            .map_err(into_status)?
+            .into_active_connections()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ActiveConnectionsReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: connections.into_iter().map(Into::into).collect(),
+        }))
     }
 
     async fn update_connection(
@@ -119,52 +131,32 @@ impl Network for NetworkService {
     ) -> Result<Response<ConnectionReply>, Status> {
         let shared_state = request.extensions().get::<Arc<State>>().unwrap();
         let shared_state = Arc::clone(shared_state);
-        match serde_json::to_value(request.into_inner()) {
-            Ok(connection) => {
-                let resp = send_command(shared_state, NetworkCommand::UpdateConnection(connection))
-                    .await
-                    .and_then(|x| {
-                        if let Some(connection) = x.into_value() {
-                            let data = serde_json::from_value(connection).unwrap();
-                            Ok(Response::new(ConnectionReply {
-                                code: 0,
-                                msg: "Sucessful".into(),
-                                data,
-                            }))
-                        } else {
-                            bail!("Failed to update connection")
-                        }
-                    })
-                    .unwrap();
-                Ok(resp)
-            }
-            _ => Err(Status::invalid_argument("Failed to parse request data")),
-        }
+        let connection = Connection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let connection = send_command(shared_state, NetworkCommand::UpdateConnection(connection))
+            .await
+            .map_err(into_status)?
+            .into_connection()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(connection.into()),
+        }))
     }
 
     async fn get_hostname(&self, request: Request<()>) -> Result<Response<HostnameReply>, Status> {
         let shared_state = request.extensions().get::<Arc<State>>().unwrap();
         let shared_state = Arc::clone(shared_state);
-        let resp = send_command(shared_state, NetworkCommand::GetHostname)
-            .await
-            .and_then(|x| {
-                if let Some(hostname) = x.into_value() {
-                    let hostname = hostname
-                        .as_str()
-                        .map(|x| x.to_string())
-                        .wrap_err("Failed to parse hostname from request")?;
-                    let data = serde_json::from_value(json!({ "hostname": hostname }))?;
-                    Ok(Response::new(HostnameReply {
-                        code: 0,
-                        msg: "Sucessful".into(),
-                        data,
-                    }))
-                } else {
-                    bail!("Failed to get hostname")
-                }
-            })
-            .unwrap();
-        Ok(resp)
+        let hostname = send_command(shared_state, NetworkCommand::GetHostname)
+            .await
+            .map_err(into_status)?
+            .into_hostname()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(HostnameReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(HostnameBody { hostname }),
+        }))
     }
 
     async fn set_hostname(
@@ -173,28 +165,15 @@ impl Network for NetworkService {
     ) -> Result<Response<HostnameReply>, Status> {
         let shared_state = request.extensions().get::<Arc<State>>().unwrap();
         let shared_state = Arc::clone(shared_state);
-        match serde_json::to_value(request.into_inner()) {
-            Ok(hostname) => {
-                let hostname = hostname["hostname"]
-                    .as_str()
-                    .map(|x| x.to_string())
-                    .unwrap();
-                let hostname_clone = hostname.clone();
-                let resp = send_command(shared_state, NetworkCommand::SetHostname(hostname))
-                    .await
-                    .and_then(|_| {
-                        let data = serde_json::from_value(json!({ "hostname": hostname_clone }))?;
-                        Ok(Response::new(HostnameReply {
-                            code: 0,
-                            msg: "Sucessful".into(),
-                            data,
-                        }))
-                    })
-                    .unwrap();
-                Ok(resp)
-            }
-            _ => Err(Status::invalid_argument("Failed to parse request data")),
-        }
+        let hostname = request.into_inner().hostname;
+        send_command(shared_state, NetworkCommand::SetHostname(hostname.clone()))
+            .await
+            .map_err(into_status)?;
+        Ok(Response::new(HostnameReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(HostnameBody { hostname }),
+        }))
     }
 
     async fn get_networking(
@@ -203,15 +182,51 @@ impl Network for NetworkService {
     ) -> Result<Response<NetworkingStateReply>, Status> {
         let shared_state = request.extensions().get::<Arc<State>>().unwrap();
         let shared_state = Arc::clone(shared_state);
-        let resp = send_command(shared_state, NetworkCommand::GetNetworking)
+        let state = send_command(shared_state, NetworkCommand::GetNetworking)
             .await
-            .unwrap();
-        let value = resp.into_value().unwrap();
-        let data = serde_json::from_value(value).unwrap();
+            .map_err(into_status)?
+            .into_networking()
+            .ok_or_else(unexpected_response)?;
         Ok(Response::new(NetworkingStateReply {
             code: 0,
             msg: "Sucessful".into(),
-            data,
+            data: Some(NetworkingStateBody { state }),
+        }))
+    }
+
+    async fn get_connectivity(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<ConnectivityReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let info = send_command(shared_state, NetworkCommand::GetConnectivity)
+            .await
+            .map_err(into_status)?
+            .into_connectivity()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ConnectivityReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(info.into()),
+        }))
+    }
+
+    async fn check_connectivity(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<ConnectivityReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let info = send_command(shared_state, NetworkCommand::CheckConnectivity)
+            .await
+            .map_err(into_status)?
+            .into_connectivity()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ConnectivityReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(info.into()),
         }))
     }
 
@@ -224,12 +239,13 @@ impl Network for NetworkService {
         let request_state = request.into_inner().state;
         send_command(shared_state, NetworkCommand::SetNetworking(request_state))
             .await
-            .unwrap();
-        let data = serde_json::from_value(json!({ "state": request_state })).unwrap();
+            .map_err(into_status)?;
         Ok(Response::new(NetworkingStateReply {
             code: 0,
             msg: "Sucessful".into(),
-            data,
+            data: Some(NetworkingStateBody {
+                state: request_state,
+            }),
         }))
     }
 
@@ -239,28 +255,867 @@ impl Network for NetworkService {
     ) -> Result<Response<NetworkingStateReply>, Status> {
         let shared_state = request.extensions().get::<Arc<State>>().unwrap();
         let shared_state = Arc::clone(shared_state);
-        let resp = send_command(shared_state.clone(), NetworkCommand::GetNetworking)
+        let cur_state = send_command(shared_state.clone(), NetworkCommand::GetNetworking)
             .await
-            .unwrap();
-        let value = resp.into_value().unwrap();
-        let cur_state = value["state"].as_bool().unwrap();
+            .map_err(into_status)?
+            .into_networking()
+            .ok_or_else(unexpected_response)?;
         send_command(
             shared_state.clone(),
             NetworkCommand::SetNetworking(!cur_state),
         )
         .await
-        .unwrap();
+        .map_err(into_status)?;
         send_command(
             shared_state.clone(),
             NetworkCommand::SetNetworking(cur_state),
         )
         .await
-        .unwrap();
-        let data = serde_json::from_value(value).unwrap();
+        .map_err(into_status)?;
         Ok(Response::new(NetworkingStateReply {
             code: 0,
             msg: "Sucessful".into(),
-            data,
+            data: Some(NetworkingStateBody { state: cur_state }),
+        }))
+    }
+
+    async fn create_connection(
+        &self,
+        request: Request<CreateConnectionBody>,
+    ) -> Result<Response<ConnectionReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let CreateConnectionBody { name, device } = request.into_inner();
+        if name.is_empty() || device.is_empty() {
+            return Err(Status::invalid_argument(
+                "Both the connection name and the device are required",
+            ));
+        }
+        let uuid = send_command(
+            shared_state.clone(),
+            NetworkCommand::CreateWiredConnection(name.clone(), device.clone()),
+        )
+        .await
+        .map_err(into_status)?
+        .into_uuid()
+        .ok_or_else(unexpected_response)?;
+        if uuid.is_empty() {
+            return Err(Status::internal(format!(
+                "The connection {} is created without uuid",
+                name
+            )));
+        }
+        // The connection is created, so the reply is built from the uuid if
+        // it can't be read again, an error would make the client create it
+        // once more.
+        let connection = send_command(shared_state, NetworkCommand::GetConnection(uuid.clone()))
+            .await
+            .ok()
+            .and_then(|x| x.into_connection());
+        let data = match connection {
+            Some(connection) => connection.into(),
+            None => {
+                let (interface, mac) = if device.contains(':') {
+                    (None, Some(device))
+                } else {
+                    (Some(device), None)
+                };
+                ConnectionBody {
+                    name,
+                    uuid,
+                    interface,
+                    mac,
+                    ..Default::default()
+                }
+            }
+        };
+        Ok(Response::new(ConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(data),
+        }))
+    }
+
+    async fn rename_connection(
+        &self,
+        request: Request<RenameConnectionBody>,
+    ) -> Result<Response<ConnectionReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let RenameConnectionBody { uuid, name } = request.into_inner();
+        if name.is_empty() {
+            return Err(Status::invalid_argument(
+                "The connection name must not be empty",
+            ));
+        }
+        send_command(
+            shared_state.clone(),
+            NetworkCommand::RenameConnection(uuid.clone(), name),
+        )
+        .await
+        .map_err(into_status)?;
+        let connection = send_command(shared_state, NetworkCommand::GetConnection(uuid))
+            .await
+            .map_err(into_status)?
+            .into_connection()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(connection.into()),
+        }))
+    }
+
+    async fn delete_connection_by_uuid(
+        &self,
+        request: Request<ConnectionUuidRequest>,
+    ) -> Result<Response<DeleteConnectionReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let uuid = request.into_inner().uuid;
+        send_command(shared_state, NetworkCommand::DeleteConnectionByUuid(uuid))
+            .await
+            .map_err(into_status)?;
+        Ok(Response::new(DeleteConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
         }))
     }
+
+    async fn delete_connection_by_name(
+        &self,
+        request: Request<ConnectionNameRequest>,
+    ) -> Result<Response<DeleteConnectionReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let name = request.into_inner().name;
+        send_command(shared_state, NetworkCommand::DeleteConnection(name))
+            .await
+            .map_err(into_status)?;
+        Ok(Response::new(DeleteConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
+        }))
+    }
+
+    async fn set_manage(
+        &self,
+        request: Request<ManageBody>,
+    ) -> Result<Response<ManageReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let body = request.into_inner();
+        send_command(
+            shared_state,
+            NetworkCommand::SetManage(body.device.clone(), body.is_managed),
+        )
+        .await
+        .map_err(into_status)?;
+        Ok(Response::new(ManageReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(body),
+        }))
+    }
+
+    async fn add_route(
+        &self,
+        request: Request<RouteRequest>,
+    ) -> Result<Response<ConnectionReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let (uuid, route) = route_request(request.into_inner())?;
+        let connection = send_command(shared_state, NetworkCommand::AddRoute(uuid, route))
+            .await
+            .map_err(into_status)?
+            .into_connection()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(connection.into()),
+        }))
+    }
+
+    async fn remove_route(
+        &self,
+        request: Request<RouteRequest>,
+    ) -> Result<Response<ConnectionReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let (uuid, route) = route_request(request.into_inner())?;
+        let connection = send_command(shared_state, NetworkCommand::RemoveRoute(uuid, route))
+            .await
+            .map_err(into_status)?
+            .into_connection()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(ConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(connection.into()),
+        }))
+    }
+
+    async fn watch_devices(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<Self::WatchDevicesStream>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let stream = watch_devices(Arc::clone(shared_state)).await?;
+        Ok(Response::new(stream))
+    }
+
+    async fn watch_connections(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<Self::WatchConnectionsStream>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let stream = watch_connections(Arc::clone(shared_state)).await?;
+        Ok(Response::new(stream))
+    }
+
+    async fn create_vlan(&self, request: Request<VlanBody>) -> Result<Response<VlanReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let vlan = VlanConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let uuid = send_command(shared_state.clone(), NetworkCommand::CreateVlan(vlan))
+            .await
+            .map_err(into_status)?
+            .into_uuid()
+            .ok_or_else(unexpected_response)?;
+        let vlan = send_command(shared_state, NetworkCommand::GetVlan(uuid))
+            .await
+            .map_err(into_status)?
+            .into_vlan()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(VlanReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(vlan.into()),
+        }))
+    }
+
+    async fn update_vlan(&self, request: Request<VlanBody>) -> Result<Response<VlanReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let vlan = VlanConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let vlan = send_command(shared_state, NetworkCommand::UpdateVlan(vlan))
+            .await
+            .map_err(into_status)?
+            .into_vlan()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(VlanReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(vlan.into()),
+        }))
+    }
+
+    async fn get_vlan(
+        &self,
+        request: Request<ConnectionUuidRequest>,
+    ) -> Result<Response<VlanReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let uuid = request.into_inner().uuid;
+        let vlan = send_command(shared_state, NetworkCommand::GetVlan(uuid))
+            .await
+            .map_err(into_status)?
+            .into_vlan()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(VlanReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(vlan.into()),
+        }))
+    }
+
+    async fn delete_vlan(
+        &self,
+        request: Request<ConnectionUuidRequest>,
+    ) -> Result<Response<DeleteConnectionReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let uuid = request.into_inner().uuid;
+        send_command(shared_state, NetworkCommand::DeleteVlan(uuid))
+            .await
+            .map_err(into_status)?;
+        Ok(Response::new(DeleteConnectionReply {
+            code: 0,
+            msg: "Sucessful".into(),
+        }))
+    }
+
+    async fn create_bond(&self, request: Request<BondBody>) -> Result<Response<BondReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let bond = BondConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let uuid = send_command(shared_state.clone(), NetworkCommand::CreateBond(bond))
+            .await
+            .map_err(into_status)?
+            .into_uuid()
+            .ok_or_else(unexpected_response)?;
+        let bond = send_command(shared_state, NetworkCommand::GetBond(uuid))
+            .await
+            .map_err(into_status)?
+            .into_bond()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(BondReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(bond.into()),
+        }))
+    }
+
+    async fn get_bond(
+        &self,
+        request: Request<ConnectionUuidRequest>,
+    ) -> Result<Response<BondReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let uuid = request.into_inner().uuid;
+        let bond = send_command(shared_state, NetworkCommand::GetBond(uuid))
+            .await
+            .map_err(into_status)?
+            .into_bond()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(BondReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(bond.into()),
+        }))
+    }
+
+    async fn attach_bond_port(
+        &self,
+        request: Request<BondPortRequest>,
+    ) -> Result<Response<BondReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let BondPortRequest { uuid, device } = request.into_inner();
+        let bond = send_command(shared_state, NetworkCommand::AttachBondPort(uuid, device))
+            .await
+            .map_err(into_status)?
+            .into_bond()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(BondReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(bond.into()),
+        }))
+    }
+
+    async fn detach_bond_port(
+        &self,
+        request: Request<BondPortRequest>,
+    ) -> Result<Response<BondReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let BondPortRequest { uuid, device } = request.into_inner();
+        let bond = send_command(shared_state, NetworkCommand::DetachBondPort(uuid, device))
+            .await
+            .map_err(into_status)?
+            .into_bond()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(BondReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(bond.into()),
+        }))
+    }
+
+    async fn create_bridge(
+        &self,
+        request: Request<BridgeBody>,
+    ) -> Result<Response<BridgeReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let bridge = BridgeConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let uuid = send_command(shared_state.clone(), NetworkCommand::CreateBridge(bridge))
+            .await
+            .map_err(into_status)?
+            .into_uuid()
+            .ok_or_else(unexpected_response)?;
+        let bridge = send_command(shared_state, NetworkCommand::GetBridge(uuid))
+            .await
+            .map_err(into_status)?
+            .into_bridge()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(BridgeReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(bridge.into()),
+        }))
+    }
+
+    async fn update_bridge(
+        &self,
+        request: Request<BridgeBody>,
+    ) -> Result<Response<BridgeReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let bridge = BridgeConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let bridge = send_command(shared_state, NetworkCommand::UpdateBridge(bridge))
+            .await
+            .map_err(into_status)?
+            .into_bridge()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(BridgeReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(bridge.into()),
+        }))
+    }
+
+    async fn get_bridge(
+        &self,
+        request: Request<ConnectionUuidRequest>,
+    ) -> Result<Response<BridgeReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let uuid = request.into_inner().uuid;
+        let bridge = send_command(shared_state, NetworkCommand::GetBridge(uuid))
+            .await
+            .map_err(into_status)?
+            .into_bridge()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(BridgeReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(bridge.into()),
+        }))
+    }
+
+    async fn attach_bridge_port(
+        &self,
+        request: Request<BridgePortRequest>,
+    ) -> Result<Response<BridgeReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let BridgePortRequest {
+            uuid,
+            port,
+            migrate_ip,
+        } = request.into_inner();
+        let port = port
+            .map(BridgePort::from)
+            .ok_or_else(|| Status::invalid_argument("The port is required"))?;
+        let bridge = send_command(
+            shared_state,
+            NetworkCommand::AttachBridgePort(uuid, port, migrate_ip),
+        )
+        .await
+        .map_err(into_status)?
+        .into_bridge()
+        .ok_or_else(unexpected_response)?;
+        Ok(Response::new(BridgeReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(bridge.into()),
+        }))
+    }
+
+    async fn detach_bridge_port(
+        &self,
+        request: Request<BridgePortRequest>,
+    ) -> Result<Response<BridgeReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let BridgePortRequest { uuid, port, .. } = request.into_inner();
+        let device = port
+            .map(|x| x.device)
+            .ok_or_else(|| Status::invalid_argument("The port is required"))?;
+        let bridge = send_command(shared_state, NetworkCommand::DetachBridgePort(uuid, device))
+            .await
+            .map_err(into_status)?
+            .into_bridge()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(BridgeReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(bridge.into()),
+        }))
+    }
+
+    async fn create_wire_guard(
+        &self,
+        request: Request<WireGuardBody>,
+    ) -> Result<Response<WireGuardReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let wireguard =
+            WireGuardConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let uuid = send_command(
+            shared_state.clone(),
+            NetworkCommand::CreateWireGuard(wireguard),
+        )
+        .await
+        .map_err(into_status)?
+        .into_uuid()
+        .ok_or_else(unexpected_response)?;
+        let wireguard = send_command(shared_state, NetworkCommand::GetWireGuard(uuid))
+            .await
+            .map_err(into_status)?
+            .into_wireguard()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(WireGuardReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(wireguard.into()),
+        }))
+    }
+
+    async fn update_wire_guard(
+        &self,
+        request: Request<WireGuardBody>,
+    ) -> Result<Response<WireGuardReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let wireguard =
+            WireGuardConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let wireguard = send_command(shared_state, NetworkCommand::UpdateWireGuard(wireguard))
+            .await
+            .map_err(into_status)?
+            .into_wireguard()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(WireGuardReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(wireguard.into()),
+        }))
+    }
+
+    async fn get_wire_guard(
+        &self,
+        request: Request<ConnectionUuidRequest>,
+    ) -> Result<Response<WireGuardReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let uuid = request.into_inner().uuid;
+        let wireguard = send_command(shared_state, NetworkCommand::GetWireGuard(uuid))
+            .await
+            .map_err(into_status)?
+            .into_wireguard()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(WireGuardReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(wireguard.into()),
+        }))
+    }
+
+    async fn create_ip_tunnel(
+        &self,
+        request: Request<IpTunnelBody>,
+    ) -> Result<Response<IpTunnelReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let tunnel = IpTunnelConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let uuid = send_command(shared_state.clone(), NetworkCommand::CreateIpTunnel(tunnel))
+            .await
+            .map_err(into_status)?
+            .into_uuid()
+            .ok_or_else(unexpected_response)?;
+        let tunnel = send_command(shared_state, NetworkCommand::GetIpTunnel(uuid))
+            .await
+            .map_err(into_status)?
+            .into_ip_tunnel()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(IpTunnelReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(tunnel.into()),
+        }))
+    }
+
+    async fn update_ip_tunnel(
+        &self,
+        request: Request<IpTunnelBody>,
+    ) -> Result<Response<IpTunnelReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let tunnel = IpTunnelConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let tunnel = send_command(shared_state, NetworkCommand::UpdateIpTunnel(tunnel))
+            .await
+            .map_err(into_status)?
+            .into_ip_tunnel()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(IpTunnelReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(tunnel.into()),
+        }))
+    }
+
+    async fn get_ip_tunnel(
+        &self,
+        request: Request<ConnectionUuidRequest>,
+    ) -> Result<Response<IpTunnelReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let uuid = request.into_inner().uuid;
+        let tunnel = send_command(shared_state, NetworkCommand::GetIpTunnel(uuid))
+            .await
+            .map_err(into_status)?
+            .into_ip_tunnel()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(IpTunnelReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(tunnel.into()),
+        }))
+    }
+
+    async fn create_vxlan(
+        &self,
+        request: Request<VxlanBody>,
+    ) -> Result<Response<VxlanReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let vxlan = VxlanConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let uuid = send_command(shared_state.clone(), NetworkCommand::CreateVxlan(vxlan))
+            .await
+            .map_err(into_status)?
+            .into_uuid()
+            .ok_or_else(unexpected_response)?;
+        let vxlan = send_command(shared_state, NetworkCommand::GetVxlan(uuid))
+            .await
+            .map_err(into_status)?
+            .into_vxlan()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(VxlanReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(vxlan.into()),
+        }))
+    }
+
+    async fn update_vxlan(
+        &self,
+        request: Request<VxlanBody>,
+    ) -> Result<Response<VxlanReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let vxlan = VxlanConnection::try_from(request.into_inner()).map_err(invalid_request)?;
+        let vxlan = send_command(shared_state, NetworkCommand::UpdateVxlan(vxlan))
+            .await
+            .map_err(into_status)?
+            .into_vxlan()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(VxlanReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(vxlan.into()),
+        }))
+    }
+
+    async fn get_vxlan(
+        &self,
+        request: Request<ConnectionUuidRequest>,
+    ) -> Result<Response<VxlanReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let uuid = request.into_inner().uuid;
+        let vxlan = send_command(shared_state, NetworkCommand::GetVxlan(uuid))
+            .await
+            .map_err(into_status)?
+            .into_vxlan()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(VxlanReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(vxlan.into()),
+        }))
+    }
+
+    async fn begin_checkpoint(
+        &self,
+        request: Request<CheckpointRequest>,
+    ) -> Result<Response<CheckpointReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let request = request.into_inner();
+        let checkpoint = send_command(
+            shared_state,
+            NetworkCommand::BeginCheckpoint(request.devices, request.rollback_timeout),
+        )
+        .await
+        .map_err(into_status)?
+        .into_checkpoint()
+        .ok_or_else(unexpected_response)?;
+        Ok(Response::new(CheckpointReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(checkpoint.into()),
+        }))
+    }
+
+    async fn confirm_checkpoint(
+        &self,
+        request: Request<CheckpointIdRequest>,
+    ) -> Result<Response<CheckpointReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let id = request.into_inner().id;
+        send_command(shared_state, NetworkCommand::ConfirmCheckpoint(id))
+            .await
+            .map_err(into_status)?;
+        Ok(Response::new(CheckpointReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: None,
+        }))
+    }
+
+    async fn rollback_checkpoint(
+        &self,
+        request: Request<CheckpointIdRequest>,
+    ) -> Result<Response<CheckpointReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let id = request.into_inner().id;
+        send_command(shared_state, NetworkCommand::RollbackCheckpoint(id))
+            .await
+            .map_err(into_status)?;
+        Ok(Response::new(CheckpointReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: None,
+        }))
+    }
+
+    async fn export_keyfiles(
+        &self,
+        request: Request<KeyfileExportRequest>,
+    ) -> Result<Response<KeyfilesReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let request = request.into_inner();
+        let keyfiles = send_command(
+            shared_state,
+            NetworkCommand::ExportKeyfiles(request.uuid, request.include_secrets),
+        )
+        .await
+        .map_err(into_status)?
+        .into_keyfiles()
+        .ok_or_else(unexpected_response)?;
+        Ok(Response::new(KeyfilesReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: keyfiles.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn import_keyfile(
+        &self,
+        request: Request<KeyfileImportRequest>,
+    ) -> Result<Response<KeyfileImportReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let content = request.into_inner().content;
+        let uuid = send_command(shared_state, NetworkCommand::ImportKeyfile(content))
+            .await
+            .map_err(into_status)?
+            .into_uuid()
+            .ok_or_else(unexpected_response)?;
+        Ok(Response::new(KeyfileImportReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            uuid,
+        }))
+    }
+
+    async fn preview_legacy_config(
+        &self,
+        request: Request<LegacyConfigRequest>,
+    ) -> Result<Response<LegacyConfigReply>, Status> {
+        let request = request.into_inner();
+        let format = request
+            .format
+            .parse::<LegacyFormat>()
+            .map_err(into_status)?;
+        let config = parse_legacy_config(format, &request.name, &request.content)
+            .map_err(|err| into_status(NetworkError::from(err)))?;
+        Ok(Response::new(LegacyConfigReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(config.into()),
+        }))
+    }
+
+    async fn apply_legacy_config(
+        &self,
+        request: Request<LegacyConfigRequest>,
+    ) -> Result<Response<LegacyConfigReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let request = request.into_inner();
+        let format = request
+            .format
+            .parse::<LegacyFormat>()
+            .map_err(into_status)?;
+        let config = send_command(
+            shared_state,
+            NetworkCommand::ImportLegacyConfig(format, request.name, request.content),
+        )
+        .await
+        .map_err(into_status)?
+        .into_legacy_config()
+        .ok_or_else(unexpected_response)?;
+        Ok(Response::new(LegacyConfigReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            data: Some(config.into()),
+        }))
+    }
+
+    async fn show_netstate(&self, request: Request<()>) -> Result<Response<NetstateReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let network_state = send_command(shared_state, NetworkCommand::ShowNetworkState)
+            .await
+            .map_err(into_status)?
+            .into_network_state()
+            .ok_or_else(unexpected_response)?;
+        let state = network_state
+            .to_yaml()
+            .map_err(|err| into_status(NetworkError::from(err)))?;
+        Ok(Response::new(NetstateReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            state,
+        }))
+    }
+
+    async fn apply_netstate(
+        &self,
+        request: Request<NetstateRequest>,
+    ) -> Result<Response<NetstateChangesReply>, Status> {
+        let shared_state = request.extensions().get::<Arc<State>>().unwrap();
+        let shared_state = Arc::clone(shared_state);
+        let request = request.into_inner();
+        let network_state = NetworkState::from_yaml(&request.state)
+            .map_err(|err| into_status(NetworkError::from(err)))?;
+        let changes = send_command(
+            shared_state,
+            NetworkCommand::ApplyNetworkState(network_state, request.dry_run),
+        )
+        .await
+        .map_err(into_status)?
+        .into_state_changes()
+        .ok_or_else(unexpected_response)?;
+        Ok(Response::new(NetstateChangesReply {
+            code: 0,
+            msg: "Sucessful".into(),
+            changes: changes.iter().map(ToString::to_string).collect(),
+        }))
+    }
+}
+
+fn route_request(request: RouteRequest) -> Result<(String, Route), Status> {
+    let route = request
+        .route
+        .ok_or_else(|| Status::invalid_argument("The route is required"))?;
+    let route = Route::try_from(route).map_err(invalid_request)?;
+    Ok((request.uuid, route))
 }
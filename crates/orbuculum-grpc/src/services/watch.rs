@@ -0,0 +1,192 @@
+//! The watch module turns the network events of orbuculum-nm into the
+//! streams of the watch RPCs.
+//!
+//! Every stream starts with a snapshot, then the events are sent as
+//! ADDED/CHANGED/REMOVED items. The changed object is read again by the
+//! event, instead of listing all of them. If the watcher falls behind the
+//! events, a new snapshot is sent. An object that could not be read again is
+//! logged and skipped, the stream is kept open. The stream task ends as soon
+//! as the watcher disconnects.
+use crate::convert::{into_status, unexpected_response};
+use crate::network_grpc::{
+    ChangeKind, ConnectionBody, ConnectionEvent, DeviceEvent, DevicesReplyBody,
+};
+use futures::Stream;
+use log::warn;
+use orbuculum_nm::{send_command, NetworkCommand, NetworkError, NetworkEvent, State};
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+
+/// The number of items buffered for a watcher.
+const WATCH_BUFFER: usize = 32;
+
+pub type WatchStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/// The items to send for an event, `None` if the event is not related.
+type Items<T> = Option<Result<Vec<T>, Status>>;
+
+pub(crate) async fn watch_devices(state: Arc<State>) -> Result<WatchStream<DeviceEvent>, Status> {
+    watch(state, device_snapshot, device_items).await
+}
+
+pub(crate) async fn watch_connections(
+    state: Arc<State>,
+) -> Result<WatchStream<ConnectionEvent>, Status> {
+    watch(state, connection_snapshot, connection_items).await
+}
+
+/// Subscribe the events before the snapshot, so that no change is lost
+/// between them.
+async fn watch<T, S, SF, E, EF>(
+    state: Arc<State>,
+    snapshot: S,
+    items: E,
+) -> Result<WatchStream<T>, Status>
+where
+    T: Send + 'static,
+    S: Fn(Arc<State>) -> SF + Send + 'static,
+    SF: std::future::Future<Output = Result<Vec<T>, Status>> + Send,
+    E: Fn(Arc<State>, NetworkEvent) -> EF + Send + 'static,
+    EF: std::future::Future<Output = Items<T>> + Send,
+{
+    let mut events = state.subscribe();
+    let initial = snapshot(Arc::clone(&state)).await?;
+    let (sender, receiver) = mpsc::channel(WATCH_BUFFER);
+
+    tokio::spawn(async move {
+        let mut pending = Ok(initial);
+        loop {
+            match pending {
+                Ok(batch) => {
+                    for item in batch {
+                        if sender.send(Ok(item)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(status) => {
+                    let _ = sender.send(Err(status)).await;
+                    return;
+                }
+            }
+            pending = loop {
+                // The watcher may be gone while no event comes
+                let received = tokio::select! {
+                    _ = sender.closed() => return,
+                    received = events.recv() => received,
+                };
+                match received {
+                    Ok(event) => {
+                        if let Some(items) = items(Arc::clone(&state), event).await {
+                            break items;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => break snapshot(Arc::clone(&state)).await,
+                    Err(RecvError::Closed) => return,
+                }
+            };
+        }
+    });
+
+    Ok(Box::pin(ReceiverStream::new(receiver)))
+}
+
+fn device_event(kind: ChangeKind, device: DevicesReplyBody) -> DeviceEvent {
+    DeviceEvent {
+        kind: kind as i32,
+        device: Some(device),
+    }
+}
+
+fn connection_event(kind: ChangeKind, connection: ConnectionBody) -> ConnectionEvent {
+    ConnectionEvent {
+        kind: kind as i32,
+        connection: Some(connection),
+    }
+}
+
+async fn device_snapshot(state: Arc<State>) -> Result<Vec<DeviceEvent>, Status> {
+    let devices = send_command(state, NetworkCommand::ListDeivces)
+        .await
+        .map_err(into_status)?
+        .into_devices()
+        .ok_or_else(unexpected_response)?;
+    Ok(devices
+        .into_iter()
+        .map(|x| device_event(ChangeKind::Snapshot, x.into()))
+        .collect())
+}
+
+async fn device_items(state: Arc<State>, event: NetworkEvent) -> Items<DeviceEvent> {
+    let (kind, name) = match event {
+        NetworkEvent::DeviceAdded { name } => (ChangeKind::Added, name),
+        NetworkEvent::DeviceStateChanged { name, .. }
+        | NetworkEvent::CarrierChanged { name, .. } => (ChangeKind::Changed, name),
+        NetworkEvent::DeviceRemoved { name } => {
+            let device = DevicesReplyBody {
+                name,
+                ..Default::default()
+            };
+            return Some(Ok(vec![device_event(ChangeKind::Removed, device)]));
+        }
+        _ => return None,
+    };
+    match send_command(state, NetworkCommand::GetDevice(name.clone())).await {
+        Ok(resp) => Some(
+            resp.into_device()
+                .map(|x| vec![device_event(kind, x.into())])
+                .ok_or_else(unexpected_response),
+        ),
+        // The device is gone or not listed by `ListDevices`.
+        Err(NetworkError::NotFound(_)) => None,
+        Err(err) => {
+            warn!("Failed to read the changed device {}: {}", name, err);
+            None
+        }
+    }
+}
+
+async fn connection_snapshot(state: Arc<State>) -> Result<Vec<ConnectionEvent>, Status> {
+    let connections = send_command(state, NetworkCommand::ListConnections)
+        .await
+        .map_err(into_status)?
+        .into_connections()
+        .ok_or_else(unexpected_response)?;
+    Ok(connections
+        .into_iter()
+        .map(|x| connection_event(ChangeKind::Snapshot, x.into()))
+        .collect())
+}
+
+async fn connection_items(state: Arc<State>, event: NetworkEvent) -> Items<ConnectionEvent> {
+    let (kind, uuid) = match event {
+        NetworkEvent::ConnectionAdded { uuid, .. } => (ChangeKind::Added, uuid),
+        NetworkEvent::ConnectionChanged { uuid, .. } => (ChangeKind::Changed, uuid),
+        NetworkEvent::ConnectionRemoved { uuid, name } => {
+            let connection = ConnectionBody {
+                name,
+                uuid,
+                ..Default::default()
+            };
+            return Some(Ok(vec![connection_event(ChangeKind::Removed, connection)]));
+        }
+        _ => return None,
+    };
+    match send_command(state, NetworkCommand::GetConnection(uuid.clone())).await {
+        Ok(resp) => Some(
+            resp.into_connection()
+                .map(|x| vec![connection_event(kind, x.into())])
+                .ok_or_else(unexpected_response),
+        ),
+        // The connection is deleted before it is read.
+        Err(NetworkError::NotFound(_)) => None,
+        Err(err) => {
+            warn!("Failed to read the changed connection {}: {}", uuid, err);
+            None
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Fake server tests module
+//!
+//! The tests start the gRPC server on the in-memory backend and call it with
+//! the client, so they don't need a NetworkManager daemon.
+use orbuculum_grpc::{
+    create_server, CheckpointRequest, ConnectionUuidRequest, CreateConnectionBody, HostnameBody,
+    NetworkClient, VlanBody,
+};
+use orbuculum_nm::{create_channel, run_network_backend_loop, FakeBackend, NetDevice, State};
+use rstest::{fixture, rstest};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+
+/// Start the server on a free port of the localhost, the address to connect
+/// is returned.
+#[fixture]
+fn start_fake_server() -> String {
+    let (glib_sender, glib_receiver) = create_channel();
+    let backend = FakeBackend::new()
+        .with_hostname("fake")
+        .with_device(NetDevice {
+            name: "eth1".into(),
+            mac: "52:54:5E:13:7F:43".into(),
+            state: "Disconnected".into(),
+            device_type: "Ethernet".into(),
+            is_managed: true,
+            ..Default::default()
+        });
+    let state = Arc::new(State::new(glib_sender));
+    let events = state.event_sender();
+    thread::spawn(move || {
+        run_network_backend_loop(glib_receiver, backend, events);
+    });
+
+    let bind_address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|x| x.local_addr())
+        .unwrap()
+        .to_string();
+    let address = format!("http://{}", bind_address);
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(create_server(state, bind_address))
+            .unwrap();
+    });
+    address
+}
+
+/// Connect the server, which may not listen yet.
+async fn connect(address: &str) -> NetworkClient<Channel> {
+    for _ in 0..50 {
+        if let Ok(client) = NetworkClient::connect(address.to_string()).await {
+            return client;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Failed to connect the server {}", address)
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_grpc_hostname(start_fake_server: String) {
+    let mut client = connect(&start_fake_server).await;
+    let devices = client
+        .list_devices(Request::new(()))
+        .await
+        .unwrap()
+        .into_inner()
+        .data;
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].name, "eth1");
+
+    let reply = client
+        .get_hostname(Request::new(()))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(reply.code, 0);
+    assert_eq!(reply.data.unwrap().hostname, "fake");
+    client
+        .set_hostname(Request::new(HostnameBody {
+            hostname: "orbuculum".into(),
+        }))
+        .await
+        .unwrap();
+    let reply = client
+        .get_hostname(Request::new(()))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(reply.data.unwrap().hostname, "orbuculum");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_grpc_connection(start_fake_server: String) {
+    let mut client = connect(&start_fake_server).await;
+    let reply = client
+        .create_connection(Request::new(CreateConnectionBody {
+            name: "eth1-conn".into(),
+            device: "eth1".into(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(reply.code, 0);
+    assert_eq!(reply.msg, "Sucessful");
+    let connection = reply.data.unwrap();
+    assert_eq!(connection.name, "eth1-conn");
+    assert_eq!(connection.interface.as_deref(), Some("eth1"));
+
+    let uuid = connection.uuid;
+    let reply = client
+        .get_connection_by_uuid(Request::new(ConnectionUuidRequest { uuid: uuid.clone() }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(reply.data.unwrap().name, "eth1-conn");
+    let devices = client
+        .list_devices(Request::new(()))
+        .await
+        .unwrap()
+        .into_inner()
+        .data;
+    assert_eq!(devices[0].name, "eth1");
+    assert_eq!(devices[0].conn, vec![uuid.clone()]);
+
+    client
+        .delete_connection_by_uuid(Request::new(ConnectionUuidRequest { uuid: uuid.clone() }))
+        .await
+        .unwrap();
+    let status = client
+        .get_connection_by_uuid(Request::new(ConnectionUuidRequest { uuid }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_grpc_error_status(start_fake_server: String) {
+    let mut client = connect(&start_fake_server).await;
+
+    // The missing object of the backend
+    let status = client
+        .get_connection_by_uuid(Request::new(ConnectionUuidRequest {
+            uuid: "missing".into(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    // The request is refused before it reaches the backend
+    let status = client
+        .create_connection(Request::new(CreateConnectionBody {
+            name: "eth1-conn".into(),
+            device: "".into(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // The invalid settings of the backend
+    let status = client
+        .create_vlan(Request::new(VlanBody {
+            name: "storage".into(),
+            parent: "eth1".into(),
+            id: 4095,
+            ..Default::default()
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // The conflict of the backend
+    let request = || {
+        Request::new(CheckpointRequest {
+            devices: vec!["eth1".into()],
+            rollback_timeout: 0,
+        })
+    };
+    let reply = client
+        .begin_checkpoint(request())
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(reply.data.unwrap().devices, vec!["eth1".to_string()]);
+    let status = client.begin_checkpoint(request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
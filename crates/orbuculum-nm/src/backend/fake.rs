@@ -0,0 +1,1638 @@
+//! The in-memory backend
+//!
+//! The backend keeps devices, connections, the hostname and the networking
+//! state in memory. It behaves like a NetworkManager daemon with the given
+//! devices, so the higher layers could be tested without NetworkManager.
+use super::NetworkBackend;
+use crate::dispatch::active_connections::ActiveConnectionInfo;
+use crate::dispatch::bond::BondConnection;
+use crate::dispatch::bridge::{BridgeConnection, BridgePort};
+use crate::dispatch::checkpoint::CheckpointInfo;
+use crate::dispatch::connections::{ApplyStrategy, Connection};
+use crate::dispatch::connectivity::{ConnectivityInfo, DeviceConnectivity};
+use crate::dispatch::devices::{ConnectionItem, NetDevice};
+use crate::dispatch::ip_tunnel::IpTunnelConnection;
+use crate::dispatch::keyfile::Keyfile;
+use crate::dispatch::settings::{
+    check_document, document_uuid, ip_family, parse_addresses, SettingsDocument,
+};
+use crate::dispatch::virtual_devices::VirtualDevice;
+use crate::dispatch::vlan::VlanConnection;
+use crate::dispatch::vxlan::VxlanConnection;
+use crate::dispatch::wireguard::WireGuardConnection;
+use crate::error::NetworkError;
+use crate::events::{publish, EventSender, NetworkEvent};
+use crate::legacy::{import_legacy_config, LegacyFormat};
+use crate::net::{NetInfo, Route};
+use crate::netstate::{apply_network_state, show_network_state, NetworkState};
+use crate::utils::public_key;
+use crate::NetworkResponse;
+use eyre::Result;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// The saved devices and connections of a checkpoint. The fake restores all
+/// of them on rollback, not only the covered devices.
+#[derive(Clone, Default)]
+struct FakeSnapshot {
+    devices: Vec<NetDevice>,
+    connections: Vec<Connection>,
+    vlans: Vec<VlanConnection>,
+    bonds: Vec<BondConnection>,
+    bridges: Vec<BridgeConnection>,
+    wireguards: Vec<WireGuardConnection>,
+    ip_tunnels: Vec<IpTunnelConnection>,
+    vxlans: Vec<VxlanConnection>,
+    virtual_devices: Vec<VirtualDevice>,
+}
+
+struct FakeCheckpoint {
+    info: CheckpointInfo,
+    created: Instant,
+    snapshot: FakeSnapshot,
+}
+
+#[derive(Default)]
+struct FakeNetwork {
+    devices: Vec<NetDevice>,
+    connections: Vec<Connection>,
+    vlans: Vec<VlanConnection>,
+    bonds: Vec<BondConnection>,
+    bridges: Vec<BridgeConnection>,
+    /// The WireGuard connections with their private keys
+    wireguards: Vec<WireGuardConnection>,
+    ip_tunnels: Vec<IpTunnelConnection>,
+    vxlans: Vec<VxlanConnection>,
+    virtual_devices: Vec<VirtualDevice>,
+    checkpoints: Vec<FakeCheckpoint>,
+    next_checkpoint: u64,
+    hostname: String,
+    networking: bool,
+    /// The global connectivity state while the networking is enabled
+    connectivity: String,
+    next_uuid: u64,
+    events: Option<EventSender>,
+}
+
+impl FakeNetwork {
+    /// Create or replace a connection from the document, every connection
+    /// type is kept as a wired connection with the interface, mac and ip
+    /// settings.
+    fn put_document(&mut self, document: &SettingsDocument) -> Result<String> {
+        let errors = check_document(document);
+        if !errors.is_empty() {
+            bail!(NetworkError::invalid_settings(errors))
+        }
+        let property = |setting: &str, property: &str| {
+            document
+                .get(setting)
+                .and_then(|x| x.get(property))
+                .and_then(|x| x.as_str())
+                .map(|x| x.to_string())
+        };
+        let uuid = document_uuid(document).unwrap_or_else(|| self.new_uuid());
+        let connection = Connection {
+            name: property("connection", "id").unwrap_or_default(),
+            uuid: uuid.clone(),
+            interface: property("connection", "interface-name"),
+            mac: property("802-3-ethernet", "mac-address"),
+            ip4info: document_netinfo(document, "ipv4"),
+            ip6info: document_netinfo(document, "ipv6"),
+            wired: None,
+        };
+        match self.connection_mut(&uuid) {
+            Some(current) => *current = connection,
+            None => {
+                attach_connection(&mut self.devices, &connection);
+                self.publish(NetworkEvent::ConnectionAdded {
+                    uuid: uuid.clone(),
+                    name: connection.name.clone(),
+                });
+                self.connections.push(connection);
+            }
+        }
+        Ok(uuid)
+    }
+
+    /// The devices with an active connection have the global state, the
+    /// others have none. The first of them holds the primary connection.
+    fn connectivity_info(&self) -> ConnectivityInfo {
+        let state = if self.networking {
+            self.connectivity.clone()
+        } else {
+            "none".to_string()
+        };
+        let devices = self
+            .devices
+            .iter()
+            .map(|device| {
+                let state = if device.connection.uuid.is_some() {
+                    state.clone()
+                } else {
+                    "none".to_string()
+                };
+                DeviceConnectivity {
+                    device: device.name.clone(),
+                    ip4: state.clone(),
+                    ip6: state,
+                }
+            })
+            .collect();
+        let primary_connection = self
+            .devices
+            .iter()
+            .find(|x| x.connection.uuid.is_some())
+            .filter(|_| self.networking)
+            .map(|x| x.connection.clone());
+        ConnectivityInfo {
+            state,
+            check_enabled: true,
+            primary_connection,
+            metered: "unknown".into(),
+            devices,
+        }
+    }
+
+    fn new_uuid(&mut self) -> String {
+        self.next_uuid += 1;
+        format!("00000000-0000-4000-8000-{:012x}", self.next_uuid)
+    }
+
+    fn connection_mut(&mut self, uuid: &str) -> Option<&mut Connection> {
+        self.connections.iter_mut().find(|x| x.uuid == uuid)
+    }
+
+    fn vlan_mut(&mut self, uuid: &str) -> Result<&mut VlanConnection> {
+        match self.vlans.iter_mut().find(|x| x.uuid == uuid) {
+            Some(vlan) => Ok(vlan),
+            None => bail!(NetworkError::NotFound(format!(
+                "VLAN connection {} not found",
+                uuid
+            ))),
+        }
+    }
+
+    fn bond_mut(&mut self, uuid: &str) -> Result<&mut BondConnection> {
+        match self.bonds.iter_mut().find(|x| x.uuid == uuid) {
+            Some(bond) => Ok(bond),
+            None => bail!(NetworkError::NotFound(format!(
+                "Bond connection {} not found",
+                uuid
+            ))),
+        }
+    }
+
+    fn device_mut(&mut self, device_name: &str) -> Result<&mut NetDevice> {
+        match self.devices.iter_mut().find(|x| x.name == device_name) {
+            Some(device) => Ok(device),
+            None => bail!(NetworkError::NotFound(format!(
+                "The given network device {} is not found.",
+                device_name
+            ))),
+        }
+    }
+
+    fn bridge_mut(&mut self, uuid: &str) -> Result<&mut BridgeConnection> {
+        match self.bridges.iter_mut().find(|x| x.uuid == uuid) {
+            Some(bridge) => Ok(bridge),
+            None => bail!(NetworkError::NotFound(format!(
+                "Bridge connection {} not found",
+                uuid
+            ))),
+        }
+    }
+
+    fn wireguard_mut(&mut self, uuid: &str) -> Result<&mut WireGuardConnection> {
+        match self.wireguards.iter_mut().find(|x| x.uuid == uuid) {
+            Some(wireguard) => Ok(wireguard),
+            None => bail!(NetworkError::NotFound(format!(
+                "WireGuard connection {} not found",
+                uuid
+            ))),
+        }
+    }
+
+    fn ip_tunnel_mut(&mut self, uuid: &str) -> Result<&mut IpTunnelConnection> {
+        match self.ip_tunnels.iter_mut().find(|x| x.uuid == uuid) {
+            Some(tunnel) => Ok(tunnel),
+            None => bail!(NetworkError::NotFound(format!(
+                "IP tunnel connection {} not found",
+                uuid
+            ))),
+        }
+    }
+
+    fn vxlan_mut(&mut self, uuid: &str) -> Result<&mut VxlanConnection> {
+        match self.vxlans.iter_mut().find(|x| x.uuid == uuid) {
+            Some(vxlan) => Ok(vxlan),
+            None => bail!(NetworkError::NotFound(format!(
+                "VXLAN connection {} not found",
+                uuid
+            ))),
+        }
+    }
+
+    /// The connection the device is configured by.
+    fn device_connection(&mut self, device_name: &str) -> Result<Connection> {
+        let uuid = self.device_mut(device_name)?.connection.uuid.clone();
+        match self
+            .connections
+            .iter()
+            .find(|x| Some(&x.uuid) == uuid.as_ref())
+        {
+            Some(connection) => Ok(connection.clone()),
+            None => bail!(NetworkError::NotFound(format!(
+                "No connection to migrate on the device {}",
+                device_name
+            ))),
+        }
+    }
+
+    /// Make the ethernet device a port of the controller interface.
+    fn attach_port(&mut self, device_name: &str, controller: &str) -> Result<()> {
+        let device = self.device_mut(device_name)?;
+        if device.device_type != "Ethernet" {
+            bail!(NetworkError::InvalidArgument(format!(
+                "The device {} is not an ethernet device",
+                device_name
+            )))
+        }
+        if device.controller.is_some() {
+            bail!(NetworkError::Conflict(format!(
+                "The device {} is already a port",
+                device_name
+            )))
+        }
+        device.controller = Some(controller.to_string());
+        Ok(())
+    }
+
+    /// Release the device from the controller interface.
+    fn detach_port(&mut self, device_name: &str, controller: &str) -> Result<()> {
+        let device = self.device_mut(device_name)?;
+        if device.controller.as_deref() != Some(controller) {
+            bail!(NetworkError::NotFound(format!(
+                "The device {} is not a port of {}",
+                device_name, controller
+            )))
+        }
+        device.controller = None;
+        Ok(())
+    }
+
+    fn snapshot(&self) -> FakeSnapshot {
+        FakeSnapshot {
+            devices: self.devices.clone(),
+            connections: self.connections.clone(),
+            vlans: self.vlans.clone(),
+            bonds: self.bonds.clone(),
+            bridges: self.bridges.clone(),
+            wireguards: self.wireguards.clone(),
+            ip_tunnels: self.ip_tunnels.clone(),
+            vxlans: self.vxlans.clone(),
+            virtual_devices: self.virtual_devices.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: FakeSnapshot) {
+        self.devices = snapshot.devices;
+        self.connections = snapshot.connections;
+        self.vlans = snapshot.vlans;
+        self.bonds = snapshot.bonds;
+        self.bridges = snapshot.bridges;
+        self.wireguards = snapshot.wireguards;
+        self.ip_tunnels = snapshot.ip_tunnels;
+        self.vxlans = snapshot.vxlans;
+        self.virtual_devices = snapshot.virtual_devices;
+    }
+
+    /// Roll back the checkpoints whose timeout is expired. NetworkManager does
+    /// it with a timer, the fake does it when a checkpoint command comes.
+    fn expire_checkpoints(&mut self) {
+        while let Some(position) = self.checkpoints.iter().position(|x| {
+            x.info.rollback_timeout > 0
+                && x.created.elapsed() >= Duration::from_secs(x.info.rollback_timeout as u64)
+        }) {
+            let checkpoint = self.checkpoints.remove(position);
+            self.restore(checkpoint.snapshot);
+        }
+    }
+
+    fn take_checkpoint(&mut self, id: &str) -> Result<FakeCheckpoint> {
+        self.expire_checkpoints();
+        match self.checkpoints.iter().position(|x| x.info.id == id) {
+            Some(position) => Ok(self.checkpoints.remove(position)),
+            None => bail!(NetworkError::NotFound(format!(
+                "Checkpoint {} not found, it may be rolled back already",
+                id
+            ))),
+        }
+    }
+
+    fn publish(&self, event: NetworkEvent) {
+        if let Some(events) = self.events.as_ref() {
+            publish(events, event);
+        }
+    }
+}
+
+/// The backend without NetworkManager.
+///
+/// ```rust
+/// use orbuculum_nm::{FakeBackend, NetDevice};
+///
+/// let backend = FakeBackend::new()
+///     .with_hostname("fake")
+///     .with_device(NetDevice {
+///         name: "eth0".into(),
+///         mac: "52:54:00:00:00:01".into(),
+///         device_type: "Ethernet".into(),
+///         is_managed: true,
+///         ..Default::default()
+///     });
+/// ```
+pub struct FakeBackend {
+    network: RefCell<FakeNetwork>,
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeBackend {
+    /// Create a backend without any devices, the networking is enabled.
+    pub fn new() -> Self {
+        let network = FakeNetwork {
+            hostname: "localhost".into(),
+            networking: true,
+            connectivity: "full".into(),
+            ..Default::default()
+        };
+        FakeBackend {
+            network: RefCell::new(network),
+        }
+    }
+
+    pub fn with_hostname(self, hostname: &str) -> Self {
+        self.network.borrow_mut().hostname = hostname.to_string();
+        self
+    }
+
+    /// Set the connectivity state reported while the networking is enabled,
+    /// e.g. `portal` or `limited`.
+    pub fn with_connectivity(self, state: &str) -> Self {
+        self.network.borrow_mut().connectivity = state.to_string();
+        self
+    }
+
+    pub fn with_device(self, device: NetDevice) -> Self {
+        self.network.borrow_mut().devices.push(device);
+        self
+    }
+
+    /// Add a connection, the connection is attached to the device whose name
+    /// or mac address matches the interface or mac of the connection.
+    pub fn with_connection(self, connection: Connection) -> Self {
+        {
+            let mut network = self.network.borrow_mut();
+            attach_connection(&mut network.devices, &connection);
+            network.connections.push(connection);
+        }
+        self
+    }
+}
+
+fn attach_connection(devices: &mut [NetDevice], connection: &Connection) {
+    let device = devices.iter_mut().find(|device| {
+        connection.interface.as_ref() == Some(&device.name)
+            || connection.mac.as_ref() == Some(&device.mac)
+    });
+    if let Some(device) = device {
+        device.conn.push(connection.uuid.clone());
+        if device.connection.uuid.is_none() {
+            device.connection = ConnectionItem {
+                id: Some(connection.name.clone()),
+                uuid: Some(connection.uuid.clone()),
+            };
+        }
+    }
+}
+
+/// The sections of a keyfile by name, and their keys by name
+type KeyfileSections = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Parse the keyfile text into sections. The comments and empty lines are
+/// skipped, a key out of any section is refused.
+fn parse_keyfile(content: &str) -> Result<KeyfileSections> {
+    let mut sections = KeyfileSections::new();
+    let mut current: Option<String> = None;
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            sections.entry(name.to_string()).or_default();
+            current = Some(name.to_string());
+            continue;
+        }
+        let (key, value) = match (current.as_ref(), line.split_once('=')) {
+            (Some(_), Some((key, value))) => (key.trim(), value.trim()),
+            _ => bail!(NetworkError::InvalidArgument(format!(
+                "Invalid keyfile line {}: {}",
+                number + 1,
+                line
+            ))),
+        };
+        if let Some(section) = current.as_ref().and_then(|x| sections.get_mut(x)) {
+            section.insert(key.to_string(), value.to_string());
+        }
+    }
+    Ok(sections)
+}
+
+/// Render the sections as keyfile text, the `connection` section goes first
+/// as NetworkManager writes it.
+fn render_keyfile(sections: &KeyfileSections) -> String {
+    let connection = sections.get_key_value("connection");
+    let others = sections.iter().filter(|(name, _)| *name != "connection");
+    connection
+        .into_iter()
+        .chain(others)
+        .map(|(name, keys)| {
+            let keys: String = keys
+                .iter()
+                .map(|(key, value)| format!("{}={}\n", key, value))
+                .collect();
+            format!("[{}]\n{}", name, keys)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Map the keyfile sections to a settings document: `ethernet` is the
+/// `802-3-ethernet` setting, the `address1`, `address2`... keys are the
+/// `addresses`, the gateway following an address is the `gateway` unless it
+/// is set, and the `dns` and `dns-search` lists are split by `;`.
+fn keyfile_document(sections: &KeyfileSections) -> SettingsDocument {
+    let mut document = SettingsDocument::new();
+    for (name, keys) in sections.iter() {
+        let name = if name == "ethernet" {
+            "802-3-ethernet"
+        } else {
+            name.as_str()
+        };
+        let setting = document.entry(name.to_string()).or_default();
+        let mut addresses = vec![];
+        let mut address_gateway = None;
+        for (key, value) in keys.iter() {
+            let value = if key.starts_with("address") {
+                // The address may be followed by its gateway, e.g. `192.0.2.1/24,192.0.2.254`
+                let mut parts = value.split(',');
+                addresses.push(Value::from(parts.next().unwrap_or_default()));
+                if let Some(gateway) = parts.next().filter(|x| !x.is_empty()) {
+                    address_gateway.get_or_insert_with(|| gateway.to_string());
+                }
+                continue;
+            } else if key == "dns" || key == "dns-search" {
+                value
+                    .split(';')
+                    .filter(|x| !x.is_empty())
+                    .map(Value::from)
+                    .collect()
+            } else if name == "connection" && key == "type" && value == "ethernet" {
+                Value::from("802-3-ethernet")
+            } else {
+                Value::from(value.as_str())
+            };
+            setting.insert(key.to_string(), value);
+        }
+        if !addresses.is_empty() {
+            setting.insert("addresses".into(), addresses.into());
+        }
+        if let Some(gateway) = address_gateway {
+            setting
+                .entry("gateway".to_string())
+                .or_insert_with(|| Value::from(gateway));
+        }
+    }
+    document
+}
+
+fn ip_sections(sections: &mut KeyfileSections, name: &str, ipinfo: &NetInfo) {
+    let section = sections.entry(name.to_string()).or_default();
+    let method = if ipinfo.method.is_empty() {
+        "auto"
+    } else {
+        ipinfo.method.as_str()
+    };
+    section.insert("method".into(), method.into());
+    for (index, address) in ipinfo.addresses.iter().enumerate() {
+        section.insert(format!("address{}", index + 1), address.to_string());
+    }
+    if let Some(gateway) = ipinfo.gateway {
+        section.insert("gateway".into(), gateway.to_string());
+    }
+    if !ipinfo.dns.is_empty() {
+        let dns: String = ipinfo.dns.iter().map(|x| format!("{};", x)).collect();
+        section.insert("dns".into(), dns);
+    }
+}
+
+fn connection_sections(connection: &Connection) -> KeyfileSections {
+    let mut sections = KeyfileSections::new();
+    let section = sections.entry("connection".into()).or_default();
+    section.insert("id".into(), connection.name.clone());
+    section.insert("uuid".into(), connection.uuid.clone());
+    section.insert("type".into(), "ethernet".into());
+    if let Some(interface) = connection.interface.as_ref() {
+        section.insert("interface-name".into(), interface.clone());
+    }
+    let section = sections.entry("ethernet".into()).or_default();
+    if let Some(mac) = connection.mac.as_ref() {
+        section.insert("mac-address".into(), mac.clone());
+    }
+    ip_sections(&mut sections, "ipv4", &connection.ip4info);
+    ip_sections(&mut sections, "ipv6", &connection.ip6info);
+    sections
+}
+
+fn wireguard_sections(wireguard: &WireGuardConnection, include_secrets: bool) -> KeyfileSections {
+    let mut sections = KeyfileSections::new();
+    let section = sections.entry("connection".into()).or_default();
+    section.insert("id".into(), wireguard.name.clone());
+    section.insert("uuid".into(), wireguard.uuid.clone());
+    section.insert("type".into(), "wireguard".into());
+    section.insert("interface-name".into(), wireguard.interface.clone());
+    let section = sections.entry("wireguard".into()).or_default();
+    if let Some(port) = wireguard.listen_port {
+        section.insert("listen-port".into(), port.to_string());
+    }
+    if let (Some(key), true) = (wireguard.private_key.as_ref(), include_secrets) {
+        section.insert("private-key".into(), key.clone());
+    }
+    ip_sections(
+        &mut sections,
+        "ipv4",
+        &wireguard.ip4info.clone().unwrap_or_default(),
+    );
+    ip_sections(
+        &mut sections,
+        "ipv6",
+        &wireguard.ip6info.clone().unwrap_or_default(),
+    );
+    sections
+}
+
+/// The method, addresses, gateway and dns of the ip setting in the document,
+/// the document is checked already.
+fn document_netinfo(document: &SettingsDocument, setting: &str) -> NetInfo {
+    let properties = match document.get(setting) {
+        Some(properties) => properties,
+        None => return NetInfo::default(),
+    };
+    let family = ip_family(setting).unwrap_or(4);
+    let addresses = properties
+        .get("addresses")
+        .and_then(|x| parse_addresses(family, x).ok())
+        .unwrap_or_default();
+    let dns = properties
+        .get("dns")
+        .and_then(|x| x.as_array())
+        .map(|x| {
+            x.iter()
+                .filter_map(|x| x.as_str().and_then(|x| x.parse::<IpAddr>().ok()))
+                .collect()
+        })
+        .unwrap_or_default();
+    NetInfo {
+        method: properties
+            .get("method")
+            .and_then(|x| x.as_str())
+            .unwrap_or("auto")
+            .to_string(),
+        addresses,
+        gateway: properties
+            .get("gateway")
+            .and_then(|x| x.as_str())
+            .and_then(|x| x.parse().ok()),
+        dns,
+        ..Default::default()
+    }
+}
+
+/// The optional settings which are not given keep their current values, the
+/// same as `update_ip_config` does.
+fn merge_ip_info(current: &NetInfo, mut ipinfo: NetInfo) -> NetInfo {
+    ipinfo.dns_search = ipinfo.dns_search.or_else(|| current.dns_search.clone());
+    ipinfo.dns_options = ipinfo.dns_options.or_else(|| current.dns_options.clone());
+    ipinfo.dns_priority = ipinfo.dns_priority.or(current.dns_priority);
+    ipinfo.ignore_auto_dns = ipinfo.ignore_auto_dns.or(current.ignore_auto_dns);
+    ipinfo.never_default = ipinfo.never_default.or(current.never_default);
+    ipinfo.may_fail = ipinfo.may_fail.or(current.may_fail);
+    ipinfo.dhcp_client_id = ipinfo
+        .dhcp_client_id
+        .or_else(|| current.dhcp_client_id.clone());
+    ipinfo.dhcp_hostname = ipinfo
+        .dhcp_hostname
+        .or_else(|| current.dhcp_hostname.clone());
+    ipinfo.dhcp_send_hostname = ipinfo.dhcp_send_hostname.or(current.dhcp_send_hostname);
+    ipinfo.dhcp_timeout = ipinfo.dhcp_timeout.or(current.dhcp_timeout);
+    ipinfo.addr_gen_mode = ipinfo
+        .addr_gen_mode
+        .or_else(|| current.addr_gen_mode.clone());
+    ipinfo.ip6_privacy = ipinfo.ip6_privacy.or_else(|| current.ip6_privacy.clone());
+    ipinfo.routes = ipinfo.routes.or_else(|| current.routes.clone());
+    ipinfo.route_table = ipinfo.route_table.or(current.route_table);
+    ipinfo.routing_rules = ipinfo
+        .routing_rules
+        .or_else(|| current.routing_rules.clone());
+    // An empty string unsets the value
+    ipinfo.dhcp_client_id = ipinfo.dhcp_client_id.filter(|x| !x.is_empty());
+    ipinfo.dhcp_hostname = ipinfo.dhcp_hostname.filter(|x| !x.is_empty());
+    ipinfo
+}
+
+/// Merge the optional ip configuration of an update into the current one.
+fn merge_opt_ip_info(current: &Option<NetInfo>, ipinfo: Option<NetInfo>) -> Option<NetInfo> {
+    match ipinfo {
+        Some(ipinfo) => Some(merge_ip_info(&current.clone().unwrap_or_default(), ipinfo)),
+        None => current.clone(),
+    }
+}
+
+fn route_ip_info<'a>(connection: &'a mut Connection, route: &Route) -> &'a mut NetInfo {
+    if route.family == libc::AF_INET6 {
+        &mut connection.ip6info
+    } else {
+        &mut connection.ip4info
+    }
+}
+
+fn detach_connection(devices: &mut [NetDevice], uuid: &str) {
+    for device in devices.iter_mut() {
+        device.conn.retain(|x| x != uuid);
+        if device.connection.uuid.as_deref() == Some(uuid) {
+            device.connection = ConnectionItem::default();
+        }
+    }
+}
+
+/// The device type reported by NetworkManager for the virtual device kind,
+/// the ipvlan devices are generic ones to libnm 1.40.
+fn virtual_device_type(kind: &str) -> &'static str {
+    match kind {
+        "macvlan" => "Macvlan",
+        "dummy" => "Dummy",
+        "veth" => "Veth",
+        _ => "Generic",
+    }
+}
+
+/// The WireGuard connection as it is read back, the public key is derived
+/// from the private key, which is never returned.
+fn without_private_key(wireguard: WireGuardConnection) -> WireGuardConnection {
+    WireGuardConnection {
+        public_key: wireguard
+            .private_key
+            .as_deref()
+            .and_then(|x| public_key(x).ok()),
+        private_key: None,
+        ..wireguard
+    }
+}
+
+impl NetworkBackend for FakeBackend {
+    async fn list_devices(&self) -> Result<NetworkResponse> {
+        let devices = self.network.borrow().devices.clone();
+        Ok(NetworkResponse::Devices(devices))
+    }
+
+    async fn get_device(&self, device_name: String) -> Result<NetworkResponse> {
+        let network = self.network.borrow();
+        match network.devices.iter().find(|x| x.name == device_name) {
+            Some(device) => Ok(NetworkResponse::Device(device.clone())),
+            None => bail!(NetworkError::NotFound(format!(
+                "The given network device {} is not found.",
+                device_name
+            ))),
+        }
+    }
+
+    async fn list_connections(&self) -> Result<NetworkResponse> {
+        let connections = self.network.borrow().connections.clone();
+        Ok(NetworkResponse::ListConnection(connections))
+    }
+
+    /// The fake checks the document without libnm, every connection type is
+    /// kept as a wired connection with the interface, mac and ip settings.
+    async fn put_connection_settings(&self, document: SettingsDocument) -> Result<NetworkResponse> {
+        let uuid = self.network.borrow_mut().put_document(&document)?;
+        Ok(NetworkResponse::Created { uuid })
+    }
+
+    /// The fake exports the wired and WireGuard connections, with the
+    /// interface, mac, ip settings and the WireGuard private key.
+    async fn export_keyfiles(
+        &self,
+        uuid: Option<String>,
+        include_secrets: bool,
+    ) -> Result<NetworkResponse> {
+        let network = self.network.borrow();
+        let wired = network
+            .connections
+            .iter()
+            .map(|x| (x.name.clone(), x.uuid.clone(), connection_sections(x)));
+        let wireguards = network.wireguards.iter().map(|x| {
+            let sections = wireguard_sections(x, include_secrets);
+            (x.name.clone(), x.uuid.clone(), sections)
+        });
+        let keyfiles: Vec<Keyfile> = wired
+            .chain(wireguards)
+            .filter(|(_, x, _)| uuid.is_none() || uuid.as_ref() == Some(x))
+            .map(|(name, uuid, sections)| Keyfile {
+                name,
+                uuid,
+                content: render_keyfile(&sections),
+            })
+            .collect();
+        if let (Some(uuid), true) = (uuid, keyfiles.is_empty()) {
+            bail!(NetworkError::NotFound(format!(
+                "Connection {} not found",
+                uuid
+            )))
+        }
+        Ok(NetworkResponse::Keyfiles(keyfiles))
+    }
+
+    /// The keyfile is imported as a settings document.
+    async fn import_keyfile(&self, content: String) -> Result<NetworkResponse> {
+        let document = keyfile_document(&parse_keyfile(&content)?);
+        let uuid = self.network.borrow_mut().put_document(&document)?;
+        Ok(NetworkResponse::Created { uuid })
+    }
+
+    async fn import_legacy_config(
+        &self,
+        format: LegacyFormat,
+        name: String,
+        content: String,
+    ) -> Result<NetworkResponse> {
+        import_legacy_config(self, format, name, content).await
+    }
+
+    async fn show_network_state(&self) -> Result<NetworkResponse> {
+        show_network_state(self).await
+    }
+
+    async fn apply_network_state(
+        &self,
+        state: NetworkState,
+        dry_run: bool,
+    ) -> Result<NetworkResponse> {
+        apply_network_state(self, state, dry_run).await
+    }
+
+    /// The connections of the devices are active while the networking is
+    /// enabled, they are reported as ethernet ones with the saved addresses.
+    /// The first of them holds the default routes.
+    async fn list_active_connections(&self) -> Result<NetworkResponse> {
+        let network = self.network.borrow();
+        if !network.networking {
+            return Ok(NetworkResponse::ActiveConnections(vec![]));
+        }
+        let mut connections = vec![];
+        for device in network.devices.iter() {
+            let uuid = match device.connection.uuid.as_deref() {
+                Some(uuid) => uuid,
+                None => continue,
+            };
+            let connection = network.connections.iter().find(|x| x.uuid == uuid);
+            let is_default = connections.is_empty();
+            connections.push(ActiveConnectionInfo {
+                name: device.connection.id.clone().unwrap_or_default(),
+                uuid: uuid.to_string(),
+                connection_type: "802-3-ethernet".into(),
+                state: "activated".into(),
+                state_reason: "None".into(),
+                devices: vec![device.name.clone()],
+                default4: is_default,
+                default6: is_default,
+                ip4info: connection.map(|x| x.ip4info.clone()),
+                ip6info: connection.map(|x| x.ip6info.clone()),
+            });
+        }
+        Ok(NetworkResponse::ActiveConnections(connections))
+    }
+
+    async fn get_connection(&self, uuid: String) -> Result<NetworkResponse> {
+        let network = self.network.borrow();
+        match network.connections.iter().find(|x| x.uuid == uuid) {
+            Some(connection) => Ok(NetworkResponse::Connection(connection.clone())),
+            None => bail!(NetworkError::NotFound(format!(
+                "Failed to get connection with uuid {}",
+                uuid
+            ))),
+        }
+    }
+
+    async fn create_wired_connection(
+        &self,
+        conn_name: String,
+        device: String,
+    ) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let uuid = network.new_uuid();
+        let (interface, mac) = if device.contains(':') {
+            (None, Some(device))
+        } else {
+            (Some(device), None)
+        };
+        let connection = Connection {
+            name: conn_name,
+            uuid: uuid.clone(),
+            interface,
+            mac,
+            ip4info: Default::default(),
+            ip6info: Default::default(),
+            wired: Some(Default::default()),
+        };
+        attach_connection(&mut network.devices, &connection);
+        network.publish(NetworkEvent::ConnectionAdded {
+            uuid: uuid.clone(),
+            name: connection.name.clone(),
+        });
+        network.connections.push(connection);
+        Ok(NetworkResponse::Created { uuid })
+    }
+
+    async fn update_connection(&self, connection: Connection) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let link_modes = network
+            .devices
+            .iter()
+            .find(|x| x.conn.contains(&connection.uuid))
+            .map(|x| x.net_link_modes.clone())
+            .unwrap_or_default();
+        match network.connection_mut(&connection.uuid) {
+            Some(current) => {
+                if let Some(wired) = connection.wired {
+                    let wired = wired.merge(&current.wired.clone().unwrap_or_default());
+                    wired.validate(&link_modes)?;
+                    current.wired = Some(wired);
+                }
+                current.ip4info = merge_ip_info(&current.ip4info, connection.ip4info);
+                current.ip6info = merge_ip_info(&current.ip6info, connection.ip6info);
+                let current = current.clone();
+                network.publish(NetworkEvent::ConnectionChanged {
+                    uuid: current.uuid.clone(),
+                    name: current.name.clone(),
+                });
+                Ok(NetworkResponse::Connection(current))
+            }
+            None => bail!(NetworkError::NotFound(format!(
+                "Uuid {} not found",
+                connection.uuid
+            ))),
+        }
+    }
+
+    async fn add_route(&self, uuid: String, route: Route) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let connection = match network.connection_mut(&uuid) {
+            Some(connection) => connection,
+            None => bail!(NetworkError::NotFound(format!("Uuid {} not found", uuid))),
+        };
+        let routes = route_ip_info(connection, &route)
+            .routes
+            .get_or_insert_with(Vec::new);
+        if routes.iter().any(|x| x.matches(&route)) {
+            bail!(NetworkError::Conflict(format!(
+                "The route to {} already exists",
+                route.dest
+            )))
+        }
+        routes.push(route);
+        let connection = connection.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid,
+            name: connection.name.clone(),
+        });
+        Ok(NetworkResponse::Connection(connection))
+    }
+
+    async fn remove_route(&self, uuid: String, route: Route) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let connection = match network.connection_mut(&uuid) {
+            Some(connection) => connection,
+            None => bail!(NetworkError::NotFound(format!("Uuid {} not found", uuid))),
+        };
+        let routes = route_ip_info(connection, &route)
+            .routes
+            .get_or_insert_with(Vec::new);
+        let count = routes.len();
+        routes.retain(|x| !x.matches(&route));
+        if routes.len() == count {
+            bail!(NetworkError::NotFound(format!(
+                "The route to {} is not found",
+                route.dest
+            )))
+        }
+        let connection = connection.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid,
+            name: connection.name.clone(),
+        });
+        Ok(NetworkResponse::Connection(connection))
+    }
+
+    async fn rename_connection(&self, uuid: String, new_name: String) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        match network.connection_mut(&uuid) {
+            Some(connection) => connection.name = new_name.clone(),
+            None => bail!(NetworkError::NotFound(format!("Uuid {} not found", uuid))),
+        }
+        for device in network.devices.iter_mut() {
+            if device.connection.uuid.as_ref() == Some(&uuid) {
+                device.connection.id = Some(new_name.clone());
+            }
+        }
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid,
+            name: new_name,
+        });
+        Ok(NetworkResponse::Success)
+    }
+
+    async fn delete_connection(&self, conn_name: String) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let uuids: Vec<String> = network
+            .connections
+            .iter()
+            .filter(|x| x.name == conn_name)
+            .map(|x| x.uuid.clone())
+            .collect();
+        for uuid in uuids.into_iter() {
+            detach_connection(&mut network.devices, &uuid);
+            network.publish(NetworkEvent::ConnectionRemoved {
+                uuid,
+                name: conn_name.clone(),
+            });
+        }
+        network.connections.retain(|x| x.name != conn_name);
+        Ok(NetworkResponse::Success)
+    }
+
+    async fn delete_connection_by_uuid(&self, uuid: String) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let position = network.connections.iter().position(|x| x.uuid == uuid);
+        match position {
+            Some(position) => {
+                let connection = network.connections.remove(position);
+                detach_connection(&mut network.devices, &uuid);
+                network.publish(NetworkEvent::ConnectionRemoved {
+                    uuid,
+                    name: connection.name,
+                });
+            }
+            None => bail!(NetworkError::NotFound(format!("Uuid {} not found", uuid))),
+        }
+        Ok(NetworkResponse::Success)
+    }
+
+    /// The fake reapplies the connection if it is the one of its device, and
+    /// activates it otherwise, it never needs a reactivation.
+    async fn reactive_connection(&self, uuid: String) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let connection = match network.connections.iter().find(|x| x.uuid == uuid) {
+            Some(connection) => connection.clone(),
+            None => bail!(NetworkError::NotFound(format!(
+                "Failed to get connection {} when reactive",
+                uuid
+            ))),
+        };
+        let device = network.devices.iter_mut().find(|x| x.conn.contains(&uuid));
+        let device = match device {
+            Some(device) => device,
+            None => bail!(NetworkError::Conflict(format!(
+                "The connection {} has no device to apply to",
+                uuid
+            ))),
+        };
+        if device.connection.uuid.as_deref() == Some(uuid.as_str()) {
+            return Ok(NetworkResponse::Applied(ApplyStrategy::Reapply));
+        }
+        device.connection = ConnectionItem {
+            id: Some(connection.name),
+            uuid: Some(uuid),
+        };
+        Ok(NetworkResponse::Applied(ApplyStrategy::Activate))
+    }
+
+    async fn get_hostname(&self) -> Result<NetworkResponse> {
+        let hostname = self.network.borrow().hostname.clone();
+        Ok(NetworkResponse::Hostname(hostname))
+    }
+
+    async fn set_hostname(&self, hostname: String) -> Result<NetworkResponse> {
+        self.network.borrow_mut().hostname = hostname;
+        Ok(NetworkResponse::Success)
+    }
+
+    async fn get_networking(&self) -> Result<NetworkResponse> {
+        let state = self.network.borrow().networking;
+        Ok(NetworkResponse::Networking(state))
+    }
+
+    async fn set_networking(&self, state: bool) -> Result<NetworkResponse> {
+        self.network.borrow_mut().networking = state;
+        Ok(NetworkResponse::Success)
+    }
+
+    async fn get_connectivity(&self) -> Result<NetworkResponse> {
+        let info = self.network.borrow().connectivity_info();
+        Ok(NetworkResponse::Connectivity(info))
+    }
+
+    async fn check_connectivity(&self) -> Result<NetworkResponse> {
+        let info = self.network.borrow().connectivity_info();
+        Ok(NetworkResponse::Connectivity(info))
+    }
+
+    async fn set_manage(&self, device_name: String, is_managed: bool) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let state = match network.devices.iter_mut().find(|x| x.name == device_name) {
+            Some(device) => {
+                device.is_managed = is_managed;
+                device.state = if is_managed {
+                    "Disconnected"
+                } else {
+                    "Unmanaged"
+                }
+                .into();
+                device.state.clone()
+            }
+            None => bail!(NetworkError::NotFound(format!(
+                "The given network device {} is not found.",
+                device_name
+            ))),
+        };
+        network.publish(NetworkEvent::DeviceStateChanged {
+            name: device_name,
+            state,
+        });
+        Ok(NetworkResponse::Success)
+    }
+
+    async fn create_vlan(&self, vlan: VlanConnection) -> Result<NetworkResponse> {
+        vlan.validate()?;
+        let mut network = self.network.borrow_mut();
+        let uuid = network.new_uuid();
+        let vlan = VlanConnection {
+            uuid: uuid.clone(),
+            interface: Some(vlan.interface_name()),
+            ip4info: Some(vlan.ip4info.unwrap_or_default()),
+            ip6info: Some(vlan.ip6info.unwrap_or_default()),
+            ..vlan
+        };
+        network.publish(NetworkEvent::ConnectionAdded {
+            uuid: uuid.clone(),
+            name: vlan.name.clone(),
+        });
+        network.vlans.push(vlan);
+        Ok(NetworkResponse::Created { uuid })
+    }
+
+    async fn update_vlan(&self, vlan: VlanConnection) -> Result<NetworkResponse> {
+        vlan.validate()?;
+        let mut network = self.network.borrow_mut();
+        let current = network.vlan_mut(&vlan.uuid)?;
+        *current = VlanConnection {
+            interface: Some(vlan.interface_name()),
+            ip4info: merge_opt_ip_info(&current.ip4info, vlan.ip4info.clone()),
+            ip6info: merge_opt_ip_info(&current.ip6info, vlan.ip6info.clone()),
+            ..vlan
+        };
+        let current = current.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid: current.uuid.clone(),
+            name: current.name.clone(),
+        });
+        Ok(NetworkResponse::Vlan(current))
+    }
+
+    async fn get_vlan(&self, uuid: String) -> Result<NetworkResponse> {
+        let vlan = self.network.borrow_mut().vlan_mut(&uuid)?.clone();
+        Ok(NetworkResponse::Vlan(vlan))
+    }
+
+    async fn delete_vlan(&self, uuid: String) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let name = network.vlan_mut(&uuid)?.name.clone();
+        network.vlans.retain(|x| x.uuid != uuid);
+        network.publish(NetworkEvent::ConnectionRemoved { uuid, name });
+        Ok(NetworkResponse::Success)
+    }
+
+    async fn create_bond(&self, bond: BondConnection) -> Result<NetworkResponse> {
+        bond.validate()?;
+        let mut network = self.network.borrow_mut();
+        let uuid = network.new_uuid();
+        let bond = BondConnection {
+            uuid: uuid.clone(),
+            ports: vec![],
+            ip4info: Some(bond.ip4info.unwrap_or_default()),
+            ip6info: Some(bond.ip6info.unwrap_or_default()),
+            ..bond
+        };
+        network.publish(NetworkEvent::ConnectionAdded {
+            uuid: uuid.clone(),
+            name: bond.name.clone(),
+        });
+        network.bonds.push(bond);
+        Ok(NetworkResponse::Created { uuid })
+    }
+
+    async fn get_bond(&self, uuid: String) -> Result<NetworkResponse> {
+        let bond = self.network.borrow_mut().bond_mut(&uuid)?.clone();
+        Ok(NetworkResponse::Bond(bond))
+    }
+
+    async fn attach_bond_port(&self, uuid: String, device_name: String) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let interface = network.bond_mut(&uuid)?.interface.clone();
+        network.attach_port(&device_name, &interface)?;
+        let bond = network.bond_mut(&uuid)?;
+        bond.ports.push(device_name);
+        let bond = bond.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid: bond.uuid.clone(),
+            name: bond.name.clone(),
+        });
+        Ok(NetworkResponse::Bond(bond))
+    }
+
+    async fn detach_bond_port(&self, uuid: String, device_name: String) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let interface = network.bond_mut(&uuid)?.interface.clone();
+        network.detach_port(&device_name, &interface)?;
+        let bond = network.bond_mut(&uuid)?;
+        bond.ports.retain(|x| *x != device_name);
+        let bond = bond.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid: bond.uuid.clone(),
+            name: bond.name.clone(),
+        });
+        Ok(NetworkResponse::Bond(bond))
+    }
+
+    async fn create_bridge(&self, bridge: BridgeConnection) -> Result<NetworkResponse> {
+        bridge.validate()?;
+        let mut network = self.network.borrow_mut();
+        let uuid = network.new_uuid();
+        let bridge = BridgeConnection {
+            uuid: uuid.clone(),
+            stp: Some(bridge.stp.unwrap_or(true)),
+            forward_delay: Some(bridge.forward_delay.unwrap_or(15)),
+            priority: Some(bridge.priority.unwrap_or(32768)),
+            vlan_filtering: Some(bridge.vlan_filtering.unwrap_or(false)),
+            ports: vec![],
+            ip4info: Some(bridge.ip4info.unwrap_or_default()),
+            ip6info: Some(bridge.ip6info.unwrap_or_default()),
+            ..bridge
+        };
+        network.publish(NetworkEvent::ConnectionAdded {
+            uuid: uuid.clone(),
+            name: bridge.name.clone(),
+        });
+        network.bridges.push(bridge);
+        Ok(NetworkResponse::Created { uuid })
+    }
+
+    async fn update_bridge(&self, bridge: BridgeConnection) -> Result<NetworkResponse> {
+        bridge.validate()?;
+        let mut network = self.network.borrow_mut();
+        let current = network.bridge_mut(&bridge.uuid)?;
+        *current = BridgeConnection {
+            interface: current.interface.clone(),
+            stp: bridge.stp.or(current.stp),
+            forward_delay: bridge.forward_delay.or(current.forward_delay),
+            priority: bridge.priority.or(current.priority),
+            vlan_filtering: bridge.vlan_filtering.or(current.vlan_filtering),
+            ports: current.ports.clone(),
+            ip4info: merge_opt_ip_info(&current.ip4info, bridge.ip4info.clone()),
+            ip6info: merge_opt_ip_info(&current.ip6info, bridge.ip6info.clone()),
+            ..bridge
+        };
+        let current = current.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid: current.uuid.clone(),
+            name: current.name.clone(),
+        });
+        Ok(NetworkResponse::Bridge(current))
+    }
+
+    async fn get_bridge(&self, uuid: String) -> Result<NetworkResponse> {
+        let bridge = self.network.borrow_mut().bridge_mut(&uuid)?.clone();
+        Ok(NetworkResponse::Bridge(bridge))
+    }
+
+    async fn attach_bridge_port(
+        &self,
+        uuid: String,
+        port: BridgePort,
+        migrate_ip: bool,
+    ) -> Result<NetworkResponse> {
+        port.validate()?;
+        let mut network = self.network.borrow_mut();
+        let interface = network.bridge_mut(&uuid)?.interface.clone();
+        let migrated = if migrate_ip {
+            Some(network.device_connection(&port.device)?)
+        } else {
+            None
+        };
+        network.attach_port(&port.device, &interface)?;
+        if let Some(connection) = migrated {
+            let bridge = network.bridge_mut(&uuid)?;
+            bridge.ip4info = Some(connection.ip4info);
+            bridge.ip6info = Some(connection.ip6info);
+        }
+        let bridge = network.bridge_mut(&uuid)?;
+        bridge.ports.push(BridgePort {
+            path_cost: Some(port.path_cost.unwrap_or(100)),
+            priority: Some(port.priority.unwrap_or(32)),
+            hairpin: Some(port.hairpin.unwrap_or(false)),
+            ..port
+        });
+        let bridge = bridge.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid: bridge.uuid.clone(),
+            name: bridge.name.clone(),
+        });
+        Ok(NetworkResponse::Bridge(bridge))
+    }
+
+    async fn detach_bridge_port(
+        &self,
+        uuid: String,
+        device_name: String,
+    ) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let interface = network.bridge_mut(&uuid)?.interface.clone();
+        network.detach_port(&device_name, &interface)?;
+        let bridge = network.bridge_mut(&uuid)?;
+        bridge.ports.retain(|x| x.device != device_name);
+        let bridge = bridge.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid: bridge.uuid.clone(),
+            name: bridge.name.clone(),
+        });
+        Ok(NetworkResponse::Bridge(bridge))
+    }
+
+    async fn create_wireguard(&self, wireguard: WireGuardConnection) -> Result<NetworkResponse> {
+        wireguard.validate()?;
+        let mut network = self.network.borrow_mut();
+        let uuid = network.new_uuid();
+        let wireguard = WireGuardConnection {
+            uuid: uuid.clone(),
+            private_key: wireguard.private_key_to_write(true),
+            listen_port: Some(wireguard.listen_port.unwrap_or(0)),
+            fwmark: Some(wireguard.fwmark.unwrap_or(0)),
+            peers: Some(wireguard.peers.unwrap_or_default()),
+            ip4info: Some(wireguard.ip4info.unwrap_or_default()),
+            ip6info: Some(wireguard.ip6info.unwrap_or_default()),
+            ..wireguard
+        };
+        network.publish(NetworkEvent::ConnectionAdded {
+            uuid: uuid.clone(),
+            name: wireguard.name.clone(),
+        });
+        network.wireguards.push(wireguard);
+        Ok(NetworkResponse::Created { uuid })
+    }
+
+    async fn update_wireguard(&self, wireguard: WireGuardConnection) -> Result<NetworkResponse> {
+        wireguard.validate()?;
+        let mut network = self.network.borrow_mut();
+        let current = network.wireguard_mut(&wireguard.uuid)?;
+        *current = WireGuardConnection {
+            private_key: wireguard
+                .private_key_to_write(false)
+                .or_else(|| current.private_key.clone()),
+            listen_port: wireguard.listen_port.or(current.listen_port),
+            fwmark: wireguard.fwmark.or(current.fwmark),
+            peers: wireguard.peers.clone().or_else(|| current.peers.clone()),
+            ip4info: merge_opt_ip_info(&current.ip4info, wireguard.ip4info.clone()),
+            ip6info: merge_opt_ip_info(&current.ip6info, wireguard.ip6info.clone()),
+            ..wireguard
+        };
+        let current = current.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid: current.uuid.clone(),
+            name: current.name.clone(),
+        });
+        Ok(NetworkResponse::WireGuard(without_private_key(current)))
+    }
+
+    async fn get_wireguard(&self, uuid: String) -> Result<NetworkResponse> {
+        let wireguard = self.network.borrow_mut().wireguard_mut(&uuid)?.clone();
+        Ok(NetworkResponse::WireGuard(without_private_key(wireguard)))
+    }
+
+    async fn create_ip_tunnel(&self, tunnel: IpTunnelConnection) -> Result<NetworkResponse> {
+        tunnel.validate()?;
+        let mut network = self.network.borrow_mut();
+        let uuid = network.new_uuid();
+        let tunnel = IpTunnelConnection {
+            uuid: uuid.clone(),
+            ttl: Some(tunnel.ttl.unwrap_or(0)),
+            ip4info: Some(tunnel.ip4info.unwrap_or_default()),
+            ip6info: Some(tunnel.ip6info.unwrap_or_default()),
+            ..tunnel
+        };
+        network.publish(NetworkEvent::ConnectionAdded {
+            uuid: uuid.clone(),
+            name: tunnel.name.clone(),
+        });
+        network.ip_tunnels.push(tunnel);
+        Ok(NetworkResponse::Created { uuid })
+    }
+
+    async fn update_ip_tunnel(&self, tunnel: IpTunnelConnection) -> Result<NetworkResponse> {
+        tunnel.validate()?;
+        let mut network = self.network.borrow_mut();
+        let current = network.ip_tunnel_mut(&tunnel.uuid)?;
+        *current = IpTunnelConnection {
+            local: tunnel.local.or(current.local),
+            ttl: tunnel.ttl.or(current.ttl),
+            key: tunnel.key.clone().or_else(|| current.key.clone()),
+            ip4info: merge_opt_ip_info(&current.ip4info, tunnel.ip4info.clone()),
+            ip6info: merge_opt_ip_info(&current.ip6info, tunnel.ip6info.clone()),
+            ..tunnel
+        };
+        let current = current.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid: current.uuid.clone(),
+            name: current.name.clone(),
+        });
+        Ok(NetworkResponse::IpTunnel(current))
+    }
+
+    async fn get_ip_tunnel(&self, uuid: String) -> Result<NetworkResponse> {
+        let tunnel = self.network.borrow_mut().ip_tunnel_mut(&uuid)?.clone();
+        Ok(NetworkResponse::IpTunnel(tunnel))
+    }
+
+    async fn create_vxlan(&self, vxlan: VxlanConnection) -> Result<NetworkResponse> {
+        vxlan.validate()?;
+        let mut network = self.network.borrow_mut();
+        let uuid = network.new_uuid();
+        let vxlan = VxlanConnection {
+            uuid: uuid.clone(),
+            destination_port: Some(vxlan.destination_port.unwrap_or(8472)),
+            ip4info: Some(vxlan.ip4info.unwrap_or_default()),
+            ip6info: Some(vxlan.ip6info.unwrap_or_default()),
+            ..vxlan
+        };
+        network.publish(NetworkEvent::ConnectionAdded {
+            uuid: uuid.clone(),
+            name: vxlan.name.clone(),
+        });
+        network.vxlans.push(vxlan);
+        Ok(NetworkResponse::Created { uuid })
+    }
+
+    async fn update_vxlan(&self, vxlan: VxlanConnection) -> Result<NetworkResponse> {
+        vxlan.validate()?;
+        let mut network = self.network.borrow_mut();
+        let current = network.vxlan_mut(&vxlan.uuid)?;
+        *current = VxlanConnection {
+            local: vxlan.local.or(current.local),
+            destination_port: vxlan.destination_port.or(current.destination_port),
+            parent: vxlan.parent.clone().or_else(|| current.parent.clone()),
+            ip4info: merge_opt_ip_info(&current.ip4info, vxlan.ip4info.clone()),
+            ip6info: merge_opt_ip_info(&current.ip6info, vxlan.ip6info.clone()),
+            ..vxlan
+        };
+        let current = current.clone();
+        network.publish(NetworkEvent::ConnectionChanged {
+            uuid: current.uuid.clone(),
+            name: current.name.clone(),
+        });
+        Ok(NetworkResponse::Vxlan(current))
+    }
+
+    async fn get_vxlan(&self, uuid: String) -> Result<NetworkResponse> {
+        let vxlan = self.network.borrow_mut().vxlan_mut(&uuid)?.clone();
+        Ok(NetworkResponse::Vxlan(vxlan))
+    }
+
+    async fn create_virtual_device(&self, device: VirtualDevice) -> Result<NetworkResponse> {
+        device.validate()?;
+        let mut network = self.network.borrow_mut();
+        let interfaces: Vec<String> = [Some(&device.interface), device.peer.as_ref()]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+        for interface in interfaces.iter() {
+            if network.devices.iter().any(|x| &x.name == interface) {
+                bail!(NetworkError::Conflict(format!(
+                    "The network device {} already exists",
+                    interface
+                )))
+            }
+        }
+        if let Some(parent) = device.parent.as_deref() {
+            network.device_mut(parent)?;
+        }
+
+        let uuid = if device.kind == "ipvlan" {
+            String::new()
+        } else {
+            network.new_uuid()
+        };
+        let device = VirtualDevice {
+            uuid: uuid.clone(),
+            mode: device.device_mode()?.map(|x| x.to_string()),
+            ..device
+        };
+        for interface in interfaces {
+            let connection = if uuid.is_empty() {
+                ConnectionItem::default()
+            } else {
+                ConnectionItem {
+                    id: Some(device.name.clone()),
+                    uuid: Some(uuid.clone()),
+                }
+            };
+            network.devices.push(NetDevice {
+                name: interface.clone(),
+                conn: connection.uuid.iter().cloned().collect(),
+                connection,
+                state: "Activated".into(),
+                device_type: virtual_device_type(&device.kind).into(),
+                r#virtual: true,
+                is_managed: !uuid.is_empty(),
+                ..Default::default()
+            });
+            network.publish(NetworkEvent::DeviceAdded { name: interface });
+        }
+        if !uuid.is_empty() {
+            network.publish(NetworkEvent::ConnectionAdded {
+                uuid,
+                name: device.name.clone(),
+            });
+        }
+        network.virtual_devices.push(device.clone());
+        Ok(NetworkResponse::VirtualDevice(device))
+    }
+
+    async fn delete_virtual_device(&self, interface: String) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let position = network
+            .virtual_devices
+            .iter()
+            .position(|x| x.interface == interface || x.peer.as_ref() == Some(&interface));
+        let device = match position {
+            Some(position) => network.virtual_devices.remove(position),
+            None => bail!(NetworkError::NotFound(format!(
+                "Virtual device {} not found",
+                interface
+            ))),
+        };
+        // Both ends of a veth pair are removed with the profile
+        let interfaces: Vec<String> = [Some(device.interface), device.peer]
+            .into_iter()
+            .flatten()
+            .collect();
+        network.devices.retain(|x| !interfaces.contains(&x.name));
+        for name in interfaces {
+            network.publish(NetworkEvent::DeviceRemoved { name });
+        }
+        if !device.uuid.is_empty() {
+            network.publish(NetworkEvent::ConnectionRemoved {
+                uuid: device.uuid,
+                name: device.name,
+            });
+        }
+        Ok(NetworkResponse::Success)
+    }
+
+    async fn begin_checkpoint(
+        &self,
+        devices: Vec<String>,
+        rollback_timeout: u32,
+    ) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        network.expire_checkpoints();
+        for device_name in devices.iter() {
+            network.device_mut(device_name)?;
+        }
+        let devices = if devices.is_empty() {
+            network.devices.iter().map(|x| x.name.clone()).collect()
+        } else {
+            devices
+        };
+        let covered = network
+            .checkpoints
+            .iter()
+            .flat_map(|x| x.info.devices.iter())
+            .find(|x| devices.contains(x));
+        if let Some(device_name) = covered {
+            bail!(NetworkError::Conflict(format!(
+                "The device {} is covered by another checkpoint",
+                device_name
+            )))
+        }
+
+        network.next_checkpoint += 1;
+        let info = CheckpointInfo {
+            id: format!(
+                "/org/freedesktop/NetworkManager/Checkpoint/{}",
+                network.next_checkpoint
+            ),
+            devices,
+            rollback_timeout,
+        };
+        let snapshot = network.snapshot();
+        network.checkpoints.push(FakeCheckpoint {
+            info: info.clone(),
+            created: Instant::now(),
+            snapshot,
+        });
+        Ok(NetworkResponse::Checkpoint(info))
+    }
+
+    async fn confirm_checkpoint(&self, id: String) -> Result<NetworkResponse> {
+        self.network.borrow_mut().take_checkpoint(&id)?;
+        Ok(NetworkResponse::Success)
+    }
+
+    async fn rollback_checkpoint(&self, id: String) -> Result<NetworkResponse> {
+        let mut network = self.network.borrow_mut();
+        let checkpoint = network.take_checkpoint(&id)?;
+        network.restore(checkpoint.snapshot);
+        Ok(NetworkResponse::Success)
+    }
+
+    fn subscribe_events(&self, events: EventSender) {
+        self.network.borrow_mut().events = Some(events);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    const KEYFILE: &str = "[connection]
+id=lan
+uuid=8f0c5e94-3e53-4f0a-b5b0-2d7cba4b0c11
+type=ethernet
+interface-name=eth0
+
+# the static address
+[ipv4]
+method=manual
+address1=192.0.2.10/24,192.0.2.254
+dns=192.0.2.53;198.51.100.53;
+
+[ipv6]
+method=disabled
+";
+
+    #[test]
+    fn test_parse_and_render_keyfile() {
+        let sections = parse_keyfile(KEYFILE).unwrap();
+        assert_eq!(sections["connection"]["id"], "lan");
+        assert_eq!(sections["ipv4"]["address1"], "192.0.2.10/24,192.0.2.254");
+        let rendered = render_keyfile(&sections);
+        assert!(rendered.starts_with("[connection]\n"));
+        assert_eq!(parse_keyfile(&rendered).unwrap(), sections);
+    }
+
+    #[test]
+    fn test_parse_invalid_keyfile() {
+        assert!(parse_keyfile("id=lan\n").is_err());
+        assert!(parse_keyfile("[connection]\nid\n").is_err());
+    }
+
+    #[test]
+    fn test_keyfile_document() {
+        let document = keyfile_document(&parse_keyfile(KEYFILE).unwrap());
+        assert_eq!(document["connection"]["type"], json!("802-3-ethernet"));
+        assert_eq!(document["ipv4"]["addresses"], json!(["192.0.2.10/24"]));
+        assert_eq!(document["ipv4"]["gateway"], json!("192.0.2.254"));
+        assert_eq!(
+            document["ipv4"]["dns"],
+            json!(["192.0.2.53", "198.51.100.53"])
+        );
+    }
+}
@@ -0,0 +1,315 @@
+//! The libnm backend
+//!
+//! The backend forwards every operation to the functions in the `dispatch`
+//! sub modules, which talk to the NetworkManager daemon.
+use super::{with_checkpoint, NetworkBackend};
+use crate::dispatch::active_connections::list_active_connections;
+use crate::dispatch::bond::{attach_bond_port, create_bond, detach_bond_port, get_bond};
+use crate::dispatch::bridge::{
+    attach_bridge_port, create_bridge, detach_bridge_port, get_bridge, update_bridge,
+};
+use crate::dispatch::checkpoint::{begin_checkpoint, confirm_checkpoint, rollback_checkpoint};
+use crate::dispatch::connections::{
+    add_route, create_wired_connection, delete_connection, delete_connection_by_uuid,
+    get_connection, list_connections, reactive_connection, remove_route, rename_connection,
+    update_connection,
+};
+use crate::dispatch::connectivity::{check_connectivity, get_connectivity};
+use crate::dispatch::devices::{get_device, list_ether_devices, set_manage};
+use crate::dispatch::hostname::{get_hostname, set_hostname};
+use crate::dispatch::ip_tunnel::{create_ip_tunnel, get_ip_tunnel, update_ip_tunnel};
+use crate::dispatch::keyfile::{export_keyfiles, import_keyfile};
+use crate::dispatch::networking::{get_networking, set_networking};
+use crate::dispatch::settings::{put_connection_settings, SettingsDocument};
+use crate::dispatch::signals::subscribe_signals;
+use crate::dispatch::virtual_devices::{create_virtual_device, delete_virtual_device};
+use crate::dispatch::vlan::{create_vlan, delete_vlan, get_vlan, update_vlan};
+use crate::dispatch::vxlan::{create_vxlan, get_vxlan, update_vxlan};
+use crate::dispatch::wireguard::{create_wireguard, get_wireguard, update_wireguard};
+use crate::events::EventSender;
+use crate::legacy::{import_legacy_config, LegacyFormat};
+use crate::netstate::{apply_network_state, show_network_state, NetworkState};
+use crate::{
+    BondConnection, BridgeConnection, BridgePort, Connection, IpTunnelConnection, NetworkResponse,
+    Route, VirtualDevice, VlanConnection, VxlanConnection, WireGuardConnection,
+};
+use eyre::Result;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// The backend using the NetworkManager daemon.
+pub struct LibnmBackend {
+    link_modes: Arc<Value>,
+}
+
+impl LibnmBackend {
+    /// Create the backend with the link modes gathered by `gather_link_modes`.
+    pub fn new(link_modes: Arc<Value>) -> Self {
+        LibnmBackend { link_modes }
+    }
+}
+
+impl NetworkBackend for LibnmBackend {
+    async fn list_devices(&self) -> Result<NetworkResponse> {
+        list_ether_devices(Arc::clone(&self.link_modes)).await
+    }
+
+    async fn get_device(&self, device_name: String) -> Result<NetworkResponse> {
+        get_device(device_name, Arc::clone(&self.link_modes)).await
+    }
+
+    async fn list_connections(&self) -> Result<NetworkResponse> {
+        list_connections().await
+    }
+
+    async fn put_connection_settings(&self, document: SettingsDocument) -> Result<NetworkResponse> {
+        put_connection_settings(document).await
+    }
+
+    async fn export_keyfiles(
+        &self,
+        uuid: Option<String>,
+        include_secrets: bool,
+    ) -> Result<NetworkResponse> {
+        export_keyfiles(uuid, include_secrets).await
+    }
+
+    async fn import_keyfile(&self, content: String) -> Result<NetworkResponse> {
+        import_keyfile(content).await
+    }
+
+    async fn import_legacy_config(
+        &self,
+        format: LegacyFormat,
+        name: String,
+        content: String,
+    ) -> Result<NetworkResponse> {
+        import_legacy_config(self, format, name, content).await
+    }
+
+    async fn show_network_state(&self) -> Result<NetworkResponse> {
+        show_network_state(self).await
+    }
+
+    async fn apply_network_state(
+        &self,
+        state: NetworkState,
+        dry_run: bool,
+    ) -> Result<NetworkResponse> {
+        apply_network_state(self, state, dry_run).await
+    }
+
+    async fn list_active_connections(&self) -> Result<NetworkResponse> {
+        list_active_connections().await
+    }
+
+    async fn get_connection(&self, uuid: String) -> Result<NetworkResponse> {
+        get_connection(uuid).await
+    }
+
+    async fn create_wired_connection(
+        &self,
+        conn_name: String,
+        device: String,
+    ) -> Result<NetworkResponse> {
+        create_wired_connection(conn_name, device).await
+    }
+
+    async fn update_connection(&self, connection: Connection) -> Result<NetworkResponse> {
+        update_connection(connection, Arc::clone(&self.link_modes)).await
+    }
+
+    async fn add_route(&self, uuid: String, route: Route) -> Result<NetworkResponse> {
+        add_route(uuid, route).await
+    }
+
+    async fn remove_route(&self, uuid: String, route: Route) -> Result<NetworkResponse> {
+        remove_route(uuid, route).await
+    }
+
+    async fn rename_connection(&self, uuid: String, new_name: String) -> Result<NetworkResponse> {
+        rename_connection(uuid, new_name).await
+    }
+
+    async fn delete_connection(&self, conn_name: String) -> Result<NetworkResponse> {
+        delete_connection(conn_name).await
+    }
+
+    async fn delete_connection_by_uuid(&self, uuid: String) -> Result<NetworkResponse> {
+        delete_connection_by_uuid(uuid).await
+    }
+
+    async fn reactive_connection(&self, uuid: String) -> Result<NetworkResponse> {
+        reactive_connection(uuid).await
+    }
+
+    async fn get_hostname(&self) -> Result<NetworkResponse> {
+        get_hostname().await
+    }
+
+    async fn set_hostname(&self, hostname: String) -> Result<NetworkResponse> {
+        set_hostname(hostname).await
+    }
+
+    async fn get_networking(&self) -> Result<NetworkResponse> {
+        get_networking().await
+    }
+
+    async fn set_networking(&self, state: bool) -> Result<NetworkResponse> {
+        set_networking(state).await
+    }
+
+    async fn get_connectivity(&self) -> Result<NetworkResponse> {
+        get_connectivity().await
+    }
+
+    async fn check_connectivity(&self) -> Result<NetworkResponse> {
+        check_connectivity().await
+    }
+
+    async fn set_manage(&self, device_name: String, is_managed: bool) -> Result<NetworkResponse> {
+        set_manage(device_name, is_managed).await
+    }
+
+    async fn create_vlan(&self, vlan: VlanConnection) -> Result<NetworkResponse> {
+        create_vlan(vlan).await
+    }
+
+    async fn update_vlan(&self, vlan: VlanConnection) -> Result<NetworkResponse> {
+        update_vlan(vlan).await
+    }
+
+    async fn get_vlan(&self, uuid: String) -> Result<NetworkResponse> {
+        get_vlan(uuid).await
+    }
+
+    async fn delete_vlan(&self, uuid: String) -> Result<NetworkResponse> {
+        delete_vlan(uuid).await
+    }
+
+    async fn create_bond(&self, bond: BondConnection) -> Result<NetworkResponse> {
+        create_bond(bond).await
+    }
+
+    async fn get_bond(&self, uuid: String) -> Result<NetworkResponse> {
+        get_bond(uuid).await
+    }
+
+    async fn attach_bond_port(&self, uuid: String, device_name: String) -> Result<NetworkResponse> {
+        attach_bond_port(uuid, device_name).await
+    }
+
+    async fn detach_bond_port(&self, uuid: String, device_name: String) -> Result<NetworkResponse> {
+        detach_bond_port(uuid, device_name).await
+    }
+
+    async fn create_bridge(&self, bridge: BridgeConnection) -> Result<NetworkResponse> {
+        create_bridge(bridge).await
+    }
+
+    async fn update_bridge(&self, bridge: BridgeConnection) -> Result<NetworkResponse> {
+        update_bridge(bridge).await
+    }
+
+    async fn get_bridge(&self, uuid: String) -> Result<NetworkResponse> {
+        get_bridge(uuid).await
+    }
+
+    async fn attach_bridge_port(
+        &self,
+        uuid: String,
+        port: BridgePort,
+        migrate_ip: bool,
+    ) -> Result<NetworkResponse> {
+        if !migrate_ip {
+            return attach_bridge_port(uuid, port, migrate_ip).await;
+        }
+        // The ip configurations are moved onto the bridge and the device
+        // connection loses its autoconnect before the port is activated, they
+        // are rolled back together if the attach fails.
+        let interface = get_bridge(uuid.clone())
+            .await?
+            .into_bridge()
+            .map(|x| x.interface)
+            .unwrap_or_default();
+        let interfaces = [interface, port.device.clone()];
+        with_checkpoint(
+            self,
+            &interfaces,
+            attach_bridge_port(uuid, port, migrate_ip),
+        )
+        .await
+    }
+
+    async fn detach_bridge_port(
+        &self,
+        uuid: String,
+        device_name: String,
+    ) -> Result<NetworkResponse> {
+        detach_bridge_port(uuid, device_name).await
+    }
+
+    async fn create_wireguard(&self, wireguard: WireGuardConnection) -> Result<NetworkResponse> {
+        create_wireguard(wireguard).await
+    }
+
+    async fn update_wireguard(&self, wireguard: WireGuardConnection) -> Result<NetworkResponse> {
+        update_wireguard(wireguard).await
+    }
+
+    async fn get_wireguard(&self, uuid: String) -> Result<NetworkResponse> {
+        get_wireguard(uuid).await
+    }
+
+    async fn create_ip_tunnel(&self, tunnel: IpTunnelConnection) -> Result<NetworkResponse> {
+        create_ip_tunnel(tunnel).await
+    }
+
+    async fn update_ip_tunnel(&self, tunnel: IpTunnelConnection) -> Result<NetworkResponse> {
+        update_ip_tunnel(tunnel).await
+    }
+
+    async fn get_ip_tunnel(&self, uuid: String) -> Result<NetworkResponse> {
+        get_ip_tunnel(uuid).await
+    }
+
+    async fn create_vxlan(&self, vxlan: VxlanConnection) -> Result<NetworkResponse> {
+        create_vxlan(vxlan).await
+    }
+
+    async fn update_vxlan(&self, vxlan: VxlanConnection) -> Result<NetworkResponse> {
+        update_vxlan(vxlan).await
+    }
+
+    async fn get_vxlan(&self, uuid: String) -> Result<NetworkResponse> {
+        get_vxlan(uuid).await
+    }
+
+    async fn create_virtual_device(&self, device: VirtualDevice) -> Result<NetworkResponse> {
+        create_virtual_device(device).await
+    }
+
+    async fn delete_virtual_device(&self, interface: String) -> Result<NetworkResponse> {
+        delete_virtual_device(interface).await
+    }
+
+    async fn begin_checkpoint(
+        &self,
+        devices: Vec<String>,
+        rollback_timeout: u32,
+    ) -> Result<NetworkResponse> {
+        begin_checkpoint(devices, rollback_timeout).await
+    }
+
+    async fn confirm_checkpoint(&self, id: String) -> Result<NetworkResponse> {
+        confirm_checkpoint(id).await
+    }
+
+    async fn rollback_checkpoint(&self, id: String) -> Result<NetworkResponse> {
+        rollback_checkpoint(id).await
+    }
+
+    fn subscribe_events(&self, events: EventSender) {
+        subscribe_signals(events);
+    }
+}
@@ -0,0 +1,392 @@
+//! Backend Module
+//!
+//! The backend module defines the `NetworkBackend` trait, which is the set of
+//! operations the dispatch routers need from a network manager. Every
+//! `NetworkCommand` is answered by exactly one method of the trait.
+//!
+//! Implementations:
+//! - `LibnmBackend`: talks to the NetworkManager daemon through libnm.
+//! - `FakeBackend`: keeps devices and connections in memory, which is used to
+//!   run the whole stack without a NetworkManager daemon, e.g. in tests.
+//!
+//! All the futures are executed in the glib main context, so they are not
+//! required to be `Send`.
+mod fake;
+mod libnm;
+
+pub use fake::FakeBackend;
+pub use libnm::LibnmBackend;
+
+use crate::error::NetworkError;
+use crate::events::EventSender;
+use crate::{
+    BondConnection, BridgeConnection, BridgePort, Connection, IpTunnelConnection, LegacyFormat,
+    NetworkResponse, NetworkState, Route, SettingsDocument, VirtualDevice, VlanConnection,
+    VxlanConnection, WireGuardConnection,
+};
+use eyre::Result;
+use std::future::Future;
+
+/// The operations provided by a network manager backend.
+pub trait NetworkBackend {
+    /// List all network devices.
+    fn list_devices(&self) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get a network device by the interface name.
+    fn get_device(&self, device_name: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// List all connections.
+    fn list_connections(&self) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create a connection from a settings document, or replace the settings
+    /// of the connection with the uuid of the document.
+    fn put_connection_settings(
+        &self,
+        document: SettingsDocument,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Export the connection with the uuid, or all the connections, as
+    /// keyfiles. The secrets are redacted unless `include_secrets` is set.
+    fn export_keyfiles(
+        &self,
+        uuid: Option<String>,
+        include_secrets: bool,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Import the keyfile text, the connection with the same uuid is
+    /// replaced.
+    fn import_keyfile(&self, content: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create the connections parsed from a legacy configuration, the
+    /// directives which are not migrated are reported with them.
+    fn import_legacy_config(
+        &self,
+        format: LegacyFormat,
+        name: String,
+        content: String,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Show the declarative network state of the ethernet devices, their
+    /// connections and the hostname.
+    fn show_network_state(&self) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Plan the changes which reach the network state, and make them unless
+    /// `dry_run` is set. The planned changes are returned.
+    fn apply_network_state(
+        &self,
+        state: NetworkState,
+        dry_run: bool,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// List the connections active on the devices.
+    fn list_active_connections(&self) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get a connection by the connection uuid.
+    fn get_connection(&self, uuid: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create a wired connection bound to a device name or a mac address.
+    fn create_wired_connection(
+        &self,
+        conn_name: String,
+        device: String,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Update the ip configurations of a connection.
+    fn update_connection(
+        &self,
+        connection: Connection,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Add a static route to the connection with the given uuid.
+    fn add_route(
+        &self,
+        uuid: String,
+        route: Route,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Remove the matching static routes from the connection with the given uuid.
+    fn remove_route(
+        &self,
+        uuid: String,
+        route: Route,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Rename the connection with the given uuid.
+    fn rename_connection(
+        &self,
+        uuid: String,
+        new_name: String,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Delete all the connections with the given name.
+    fn delete_connection(&self, conn_name: String)
+        -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Delete the connection with the given uuid.
+    fn delete_connection_by_uuid(
+        &self,
+        uuid: String,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Apply the saved changes of the connection with the given uuid to its
+    /// device, the strategy used is returned.
+    fn reactive_connection(&self, uuid: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get the hostname.
+    fn get_hostname(&self) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Set the hostname.
+    fn set_hostname(&self, hostname: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get the overall networking state.
+    fn get_networking(&self) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Set the overall networking state.
+    fn set_networking(&self, state: bool) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get the global and per device connectivity of the last check.
+    fn get_connectivity(&self) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Check the connectivity again and return the result.
+    fn check_connectivity(&self) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Set whether the device is managed by the backend.
+    fn set_manage(
+        &self,
+        device_name: String,
+        is_managed: bool,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create a VLAN connection.
+    fn create_vlan(&self, vlan: VlanConnection) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Update the VLAN connection with the uuid of the given one.
+    fn update_vlan(&self, vlan: VlanConnection) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get the VLAN connection with the given uuid.
+    fn get_vlan(&self, uuid: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Delete the VLAN connection with the given uuid.
+    fn delete_vlan(&self, uuid: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create a bond connection without ports.
+    fn create_bond(&self, bond: BondConnection) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get the bond connection with the given uuid and its ports.
+    fn get_bond(&self, uuid: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Attach the ethernet device to the bond with the given uuid.
+    fn attach_bond_port(
+        &self,
+        uuid: String,
+        device_name: String,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Detach the device from the bond with the given uuid.
+    fn detach_bond_port(
+        &self,
+        uuid: String,
+        device_name: String,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create a bridge connection without ports.
+    fn create_bridge(
+        &self,
+        bridge: BridgeConnection,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Update the bridge connection with the uuid of the given one.
+    fn update_bridge(
+        &self,
+        bridge: BridgeConnection,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get the bridge connection with the given uuid and its ports.
+    fn get_bridge(&self, uuid: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Attach the ethernet device of the port to the bridge with the given
+    /// uuid, the ip configurations of the device are moved onto the bridge
+    /// if `migrate_ip` is true.
+    fn attach_bridge_port(
+        &self,
+        uuid: String,
+        port: BridgePort,
+        migrate_ip: bool,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Detach the device from the bridge with the given uuid.
+    fn detach_bridge_port(
+        &self,
+        uuid: String,
+        device_name: String,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create a WireGuard connection, the private key is generated if it is
+    /// not given.
+    fn create_wireguard(
+        &self,
+        wireguard: WireGuardConnection,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Update the WireGuard connection with the uuid of the given one.
+    fn update_wireguard(
+        &self,
+        wireguard: WireGuardConnection,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get the WireGuard connection with the given uuid, the private key is
+    /// never returned.
+    fn get_wireguard(&self, uuid: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create an IP tunnel connection.
+    fn create_ip_tunnel(
+        &self,
+        tunnel: IpTunnelConnection,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Update the IP tunnel connection with the uuid of the given one.
+    fn update_ip_tunnel(
+        &self,
+        tunnel: IpTunnelConnection,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get the IP tunnel connection with the given uuid.
+    fn get_ip_tunnel(&self, uuid: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create a VXLAN connection.
+    fn create_vxlan(&self, vxlan: VxlanConnection)
+        -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Update the VXLAN connection with the uuid of the given one.
+    fn update_vxlan(&self, vxlan: VxlanConnection)
+        -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Get the VXLAN connection with the given uuid.
+    fn get_vxlan(&self, uuid: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create a macvlan, ipvlan, dummy or veth device.
+    fn create_virtual_device(
+        &self,
+        device: VirtualDevice,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Delete the virtual device with the given interface name.
+    fn delete_virtual_device(
+        &self,
+        interface: String,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Create a checkpoint of the devices, the changes made after it are
+    /// rolled back when the timeout expires unless it is confirmed.
+    fn begin_checkpoint(
+        &self,
+        devices: Vec<String>,
+        rollback_timeout: u32,
+    ) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Keep the changes made after the checkpoint with the given id.
+    fn confirm_checkpoint(&self, id: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Roll back the changes made after the checkpoint with the given id.
+    fn rollback_checkpoint(&self, id: String) -> impl Future<Output = Result<NetworkResponse>>;
+
+    /// Start publishing the network events to the given sender, it is called
+    /// once in the glib main context before any command is dispatched.
+    fn subscribe_events(&self, events: EventSender);
+}
+
+/// Make the changes under a checkpoint of the existing devices among the
+/// given interface names or mac addresses, all the devices are covered if
+/// none of them exists. The checkpoint is confirmed when the changes succeed,
+/// otherwise it is rolled back and the error of the changes is returned.
+pub(crate) async fn with_checkpoint<B, T>(
+    backend: &B,
+    interfaces: &[String],
+    changes: impl Future<Output = Result<T>>,
+) -> Result<T>
+where
+    B: NetworkBackend,
+{
+    let devices: Vec<String> = backend
+        .list_devices()
+        .await?
+        .into_devices()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|device| {
+            interfaces
+                .iter()
+                .any(|x| *x == device.name || x.eq_ignore_ascii_case(&device.mac))
+        })
+        .map(|device| device.name)
+        .collect();
+    let checkpoint = backend
+        .begin_checkpoint(devices, 0)
+        .await?
+        .into_checkpoint()
+        .ok_or_else(|| NetworkError::Backend("The checkpoint is not created".into()))?;
+    match changes.await {
+        Ok(value) => {
+            backend.confirm_checkpoint(checkpoint.id).await?;
+            Ok(value)
+        }
+        Err(err) => {
+            // The failure of the changes is more useful than the one of the rollback
+            let _ = backend.rollback_checkpoint(checkpoint.id).await;
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::NetDevice;
+
+    fn fake_backend() -> FakeBackend {
+        FakeBackend::new().with_device(NetDevice {
+            name: "eth1".into(),
+            mac: "52:54:5E:13:7F:43".into(),
+            device_type: "Ethernet".into(),
+            ..Default::default()
+        })
+    }
+
+    fn document(name: &str) -> SettingsDocument {
+        serde_json::from_value(serde_json::json!({
+            "connection": {"id": name, "type": "802-3-ethernet", "interface-name": "eth1"},
+        }))
+        .unwrap()
+    }
+
+    async fn connection_count(backend: &FakeBackend) -> usize {
+        let response = backend.list_connections().await.unwrap();
+        response.into_connections().unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn test_with_checkpoint() {
+        let backend = fake_backend();
+        let interfaces = vec!["52:54:5e:13:7f:43".to_string()];
+        let result = with_checkpoint(&backend, &interfaces, async {
+            backend.put_connection_settings(document("lan")).await?;
+            Ok(())
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(connection_count(&backend).await, 1);
+
+        // The changes before the failure are rolled back
+        let result: Result<()> = with_checkpoint(&backend, &interfaces, async {
+            backend.put_connection_settings(document("wan")).await?;
+            bail!(NetworkError::Backend("failed".into()))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(connection_count(&backend).await, 1);
+
+        // Both checkpoints are gone
+        let response = backend.begin_checkpoint(vec!["eth1".into()], 0).await;
+        assert!(response.is_ok());
+    }
+}
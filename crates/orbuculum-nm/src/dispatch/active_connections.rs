@@ -0,0 +1,88 @@
+//! Active Connections Module
+//!
+//! The module lists the connections which are active on the devices, with
+//! their runtime addresses. The saved profiles are listed by the
+//! `connections` module.
+//!
+//! Functions:
+//! - list_active_connections
+use super::{create_client, NetworkResponse};
+use crate::net::NetInfo;
+use crate::utils::nm_display;
+use eyre::Result;
+use nm::{ActiveConnectionExt, ActiveConnectionState, DeviceExt};
+use serde::{Deserialize, Serialize};
+
+const ACTIVE_STATES: &[(ActiveConnectionState, &str)] = &[
+    (ActiveConnectionState::Unknown, "unknown"),
+    (ActiveConnectionState::Activating, "activating"),
+    (ActiveConnectionState::Activated, "activated"),
+    (ActiveConnectionState::Deactivating, "deactivating"),
+    (ActiveConnectionState::Deactivated, "deactivated"),
+];
+
+/// The active connection
+///
+/// * `connection_type`: the setting name of the connection type, e.g.
+///   `802-3-ethernet` or `bond`.
+/// * `state`: `unknown`, `activating`, `activated`, `deactivating` or
+///   `deactivated`.
+/// * `state_reason`: why the connection is in the state, e.g. `None` or
+///   `DeviceDisconnected`.
+/// * `devices`: the interface names of the devices the connection is active
+///   on.
+/// * `default4` and `default6`: whether the connection holds the IPv4 and
+///   IPv6 default routes.
+/// * `ip4info` and `ip6info`: the addresses, routes and DNS in use, `None`
+///   until the configuration is done.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ActiveConnectionInfo {
+    pub name: String,
+    pub uuid: String,
+    pub connection_type: String,
+    pub state: String,
+    pub state_reason: String,
+    pub devices: Vec<String>,
+    pub default4: bool,
+    pub default6: bool,
+    pub ip4info: Option<NetInfo>,
+    pub ip6info: Option<NetInfo>,
+}
+
+fn state_name(state: ActiveConnectionState) -> String {
+    ACTIVE_STATES
+        .iter()
+        .find(|(x, _)| *x == state)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| "unknown".into())
+}
+
+fn read_active_connection(active: &nm::ActiveConnection) -> Option<ActiveConnectionInfo> {
+    Some(ActiveConnectionInfo {
+        name: active.id()?.to_string(),
+        uuid: active.uuid()?.to_string(),
+        connection_type: active.connection_type().unwrap_or_default().to_string(),
+        state: state_name(active.state()),
+        state_reason: nm_display(active.state_reason()),
+        devices: active
+            .devices()
+            .iter()
+            .filter_map(|x| x.iface().map(|x| x.to_string()))
+            .collect(),
+        default4: active.is_default(),
+        default6: active.is_default6(),
+        ip4info: active.ip4_config().and_then(|x| NetInfo::try_from(x).ok()),
+        ip6info: active.ip6_config().and_then(|x| NetInfo::try_from(x).ok()),
+    })
+}
+
+/// List the active connections.
+pub async fn list_active_connections() -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let connections = client
+        .active_connections()
+        .iter()
+        .filter_map(read_active_connection)
+        .collect();
+    Ok(NetworkResponse::ActiveConnections(connections))
+}
@@ -0,0 +1,324 @@
+//! Bond Module
+//!
+//! The module provides the api about bond connections for the NetworkManager.
+//!
+//! A bond port is an ethernet connection whose controller is the bond, the
+//! port connections are created and deleted when the devices are attached to
+//! and detached from the bond.
+//!
+//! Functions:
+//! - create_bond
+//! - get_bond
+//! - attach_bond_port
+//! - detach_bond_port
+use super::connections::{add_connection, get_ip_config, set_ip_configs};
+use super::ports::{activate_port, delete_ports, port_connection, port_connections, port_device};
+use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
+use crate::net::NetInfo;
+use eyre::Result;
+use nm::{
+    ConnectionExt, SettingBond, SettingConnection, SimpleConnection, SETTING_BOND_SETTING_NAME,
+};
+use serde::{Deserialize, Serialize};
+
+/// The bond modes supported by the kernel
+const BOND_MODES: &[&str] = &[
+    "balance-rr",
+    "active-backup",
+    "balance-xor",
+    "broadcast",
+    "802.3ad",
+    "balance-tlb",
+    "balance-alb",
+];
+
+const LACP_RATES: &[&str] = &["slow", "fast"];
+
+const XMIT_HASH_POLICIES: &[&str] = &[
+    "layer2",
+    "layer2+3",
+    "layer3+4",
+    "encap2+3",
+    "encap3+4",
+    "vlan+srcmac",
+];
+
+/// The bond connection
+///
+/// * `interface`: the bond interface name, e.g. `bond0`.
+/// * `mode`: one of `balance-rr`, `active-backup`, `balance-xor`,
+///   `broadcast`, `802.3ad`, `balance-tlb` and `balance-alb`.
+/// * `miimon`: the MII link monitoring interval in milliseconds.
+/// * `lacp_rate`: `slow` or `fast`, only for `802.3ad`.
+/// * `xmit_hash_policy`: e.g. `layer2+3`, only for `balance-xor`, `802.3ad`,
+///   `balance-tlb` and `balance-alb`.
+/// * `primary`: the preferred port interface, only for `active-backup`,
+///   `balance-tlb` and `balance-alb`.
+/// * `ports`: the interface names of the ports, it is ignored when the bond
+///   is created, the ports are attached one by one.
+///
+/// The `uuid` is ignored when the connection is created.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct BondConnection {
+    pub name: String,
+    #[serde(default)]
+    pub uuid: String,
+    pub interface: String,
+    pub mode: String,
+    #[serde(default)]
+    pub miimon: Option<u32>,
+    #[serde(default)]
+    pub lacp_rate: Option<String>,
+    #[serde(default)]
+    pub xmit_hash_policy: Option<String>,
+    #[serde(default)]
+    pub primary: Option<String>,
+    #[serde(default)]
+    pub ports: Vec<String>,
+    #[serde(default)]
+    pub ip4info: Option<NetInfo>,
+    #[serde(default)]
+    pub ip6info: Option<NetInfo>,
+}
+
+impl BondConnection {
+    /// Check the bond options before they are sent to NetworkManager.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.interface.is_empty() {
+            bail!(NetworkError::InvalidArgument(
+                "Both the connection name and the interface are required".into()
+            ))
+        }
+        let mode = self.mode.as_str();
+        check_option("bond mode", mode, BOND_MODES)?;
+        if let Some(lacp_rate) = self.lacp_rate.as_deref() {
+            check_option("lacp rate", lacp_rate, LACP_RATES)?;
+            check_mode("lacp_rate", mode, &["802.3ad"])?;
+        }
+        if let Some(policy) = self.xmit_hash_policy.as_deref() {
+            check_option("xmit hash policy", policy, XMIT_HASH_POLICIES)?;
+            check_mode(
+                "xmit_hash_policy",
+                mode,
+                &["balance-xor", "802.3ad", "balance-tlb", "balance-alb"],
+            )?;
+        }
+        if self.primary.is_some() {
+            check_mode(
+                "primary",
+                mode,
+                &["active-backup", "balance-tlb", "balance-alb"],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The bond options in the NetworkManager names.
+    fn options(&self) -> Vec<(&'static str, String)> {
+        let mut options = vec![("mode", self.mode.clone())];
+        if let Some(miimon) = self.miimon {
+            options.push(("miimon", miimon.to_string()));
+        }
+        if let Some(lacp_rate) = self.lacp_rate.as_ref() {
+            options.push(("lacp_rate", lacp_rate.clone()));
+        }
+        if let Some(policy) = self.xmit_hash_policy.as_ref() {
+            options.push(("xmit_hash_policy", policy.clone()));
+        }
+        if let Some(primary) = self.primary.as_ref() {
+            options.push(("primary", primary.clone()));
+        }
+        options
+    }
+}
+
+fn check_option(kind: &str, value: &str, values: &[&str]) -> Result<()> {
+    if !values.contains(&value) {
+        bail!(NetworkError::InvalidArgument(format!(
+            "Invalid {} {}, expected one of {}",
+            kind,
+            value,
+            values.join(", ")
+        )))
+    }
+    Ok(())
+}
+
+fn check_mode(option: &str, mode: &str, modes: &[&str]) -> Result<()> {
+    if !modes.contains(&mode) {
+        bail!(NetworkError::InvalidArgument(format!(
+            "The bond option {} is not supported by the mode {}",
+            option, mode
+        )))
+    }
+    Ok(())
+}
+
+fn read_bond(client: &nm::Client, nm_connection: &nm::RemoteConnection) -> Option<BondConnection> {
+    let setting = nm_connection.setting_bond()?;
+    let option = |name: &str| setting.option_by_name(name).map(|x| x.to_string());
+    let uuid = nm_connection.uuid()?.to_string();
+    let interface = nm_connection.interface_name()?.to_string();
+    let ports = port_connections(client, SETTING_BOND_SETTING_NAME, &interface, &uuid)
+        .iter()
+        .filter_map(|x| x.interface_name().map(|x| x.to_string()))
+        .collect();
+    Some(BondConnection {
+        name: nm_connection.id()?.to_string(),
+        uuid,
+        interface,
+        mode: option("mode").unwrap_or_else(|| "balance-rr".into()),
+        miimon: option("miimon").and_then(|x| x.parse().ok()),
+        lacp_rate: option("lacp_rate"),
+        xmit_hash_policy: option("xmit_hash_policy"),
+        primary: option("primary"),
+        ports,
+        ip4info: get_ip_config(nm_connection, 4).ok(),
+        ip6info: get_ip_config(nm_connection, 6).ok(),
+    })
+}
+
+/// Find the bond connection with the uuid.
+fn bond_connection(client: &nm::Client, uuid: &str) -> Result<nm::RemoteConnection> {
+    match client.connection_by_uuid(uuid) {
+        Some(connection) if connection.setting_bond().is_some() => Ok(connection),
+        _ => bail!(NetworkError::NotFound(format!(
+            "Bond connection {} not found",
+            uuid
+        ))),
+    }
+}
+
+fn bond_response(
+    client: &nm::Client,
+    nm_connection: &nm::RemoteConnection,
+) -> Result<NetworkResponse> {
+    let bond = read_bond(client, nm_connection).ok_or_else(|| {
+        NetworkError::Backend(format!(
+            "Failed to read the bond connection {}",
+            nm_connection.uuid().unwrap_or_default()
+        ))
+    })?;
+    Ok(NetworkResponse::Bond(bond))
+}
+
+/// Create a bond connection without ports.
+pub async fn create_bond(bond: BondConnection) -> Result<NetworkResponse> {
+    bond.validate()?;
+    let client = create_client().await?;
+
+    let connection = SimpleConnection::new();
+    let s_connection = SettingConnection::new();
+    s_connection.set_type(Some(&SETTING_BOND_SETTING_NAME));
+    s_connection.set_id(Some(&bond.name));
+    s_connection.set_interface_name(Some(&bond.interface));
+    s_connection.set_autoconnect(true);
+    connection.add_setting(s_connection);
+
+    let s_bond = SettingBond::new();
+    for (name, value) in bond.options() {
+        if !s_bond.add_option(name, &value) {
+            bail!(NetworkError::InvalidArgument(format!(
+                "Invalid bond option {}={}",
+                name, value
+            )))
+        }
+    }
+    connection.add_setting(s_bond);
+    set_ip_configs(&connection, bond.ip4info.as_ref(), bond.ip6info.as_ref())?;
+
+    let uuid = add_connection(&client, &connection, &bond.name).await?;
+    Ok(NetworkResponse::Created { uuid })
+}
+
+/// Get the bond connection with the uuid.
+pub async fn get_bond(uuid: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = bond_connection(&client, &uuid)?;
+    bond_response(&client, &nm_connection)
+}
+
+/// Attach the ethernet device to the bond, the port connection is created and
+/// activated.
+pub async fn attach_bond_port(uuid: String, device_name: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = bond_connection(&client, &uuid)?;
+    let interface = nm_connection
+        .interface_name()
+        .map(|x| x.to_string())
+        .unwrap_or_default();
+
+    let device = port_device(&client, &device_name)?;
+    let connection = port_connection(&interface, SETTING_BOND_SETTING_NAME, &device_name);
+    activate_port(&client, &connection, &device).await?;
+    bond_response(&client, &nm_connection)
+}
+
+/// Detach the device from the bond, the port connections are deleted.
+pub async fn detach_bond_port(uuid: String, device_name: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = bond_connection(&client, &uuid)?;
+    let interface = nm_connection
+        .interface_name()
+        .map(|x| x.to_string())
+        .unwrap_or_default();
+
+    delete_ports(
+        &client,
+        SETTING_BOND_SETTING_NAME,
+        &interface,
+        &uuid,
+        &device_name,
+    )
+    .await?;
+    bond_response(&client, &nm_connection)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    fn bond(mode: &str) -> BondConnection {
+        BondConnection {
+            name: "bond".into(),
+            interface: "bond0".into(),
+            mode: mode.into(),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(bond("active-backup"), true)]
+    #[case(bond("802.3ad"), true)]
+    #[case(bond("lacp"), false)]
+    #[case(BondConnection { interface: "".into(), ..bond("802.3ad") }, false)]
+    #[case(BondConnection { lacp_rate: Some("fast".into()), ..bond("802.3ad") }, true)]
+    #[case(BondConnection { lacp_rate: Some("fast".into()), ..bond("active-backup") }, false)]
+    #[case(BondConnection { lacp_rate: Some("quick".into()), ..bond("802.3ad") }, false)]
+    #[case(BondConnection { xmit_hash_policy: Some("layer3+4".into()), ..bond("802.3ad") }, true)]
+    #[case(BondConnection { xmit_hash_policy: Some("layer3+4".into()), ..bond("active-backup") }, false)]
+    #[case(BondConnection { primary: Some("eth0".into()), ..bond("active-backup") }, true)]
+    #[case(BondConnection { primary: Some("eth0".into()), ..bond("802.3ad") }, false)]
+    fn test_validate(#[case] bond: BondConnection, #[case] valid: bool) {
+        assert_eq!(bond.validate().is_ok(), valid);
+    }
+
+    #[test]
+    fn test_options() {
+        let bond = BondConnection {
+            miimon: Some(100),
+            lacp_rate: Some("fast".into()),
+            ..bond("802.3ad")
+        };
+        assert_eq!(
+            bond.options(),
+            vec![
+                ("mode", "802.3ad".to_string()),
+                ("miimon", "100".to_string()),
+                ("lacp_rate", "fast".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! Bridge Module
+//!
+//! The module provides the api about bridge connections for the
+//! NetworkManager.
+//!
+//! A bridge port is an ethernet connection whose controller is the bridge,
+//! the port settings are kept in the port connection.
+//!
+//! Functions:
+//! - create_bridge
+//! - update_bridge
+//! - get_bridge
+//! - attach_bridge_port
+//! - detach_bridge_port
+use super::connections::{add_connection, get_ip_config, set_ip_configs};
+use super::ports::{activate_port, delete_ports, port_connection, port_connections, port_device};
+use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
+use crate::net::NetInfo;
+use eyre::Result;
+use glib::IsA;
+use nm::{
+    ActiveConnectionExt, ConnectionExt, DeviceExt, SettingBridge, SettingBridgePort,
+    SettingConnection, SimpleConnection, SETTING_BRIDGE_SETTING_NAME,
+};
+use serde::{Deserialize, Serialize};
+
+/// The forward delay range in seconds
+const FORWARD_DELAY: (u32, u32) = (2, 30);
+const MAX_BRIDGE_PRIORITY: u32 = 65535;
+const MAX_PORT_PRIORITY: u32 = 63;
+const PATH_COST: (u32, u32) = (1, 65535);
+
+/// The bridge connection
+///
+/// * `interface`: the bridge interface name, e.g. `br0`.
+/// * `stp`: whether the Spanning Tree Protocol is enabled.
+/// * `forward_delay`: the STP forward delay in seconds, 2 to 30.
+/// * `priority`: the STP bridge priority, 0 to 65535.
+/// * `vlan_filtering`: whether the VLAN filtering is enabled.
+/// * `ports`: the ports with their settings, it is ignored when the bridge
+///   is created or updated, the ports are attached one by one.
+///
+/// The `uuid` is ignored when the connection is created. The settings not
+/// given are left unchanged by an update.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct BridgeConnection {
+    pub name: String,
+    #[serde(default)]
+    pub uuid: String,
+    pub interface: String,
+    #[serde(default)]
+    pub stp: Option<bool>,
+    #[serde(default)]
+    pub forward_delay: Option<u32>,
+    #[serde(default)]
+    pub priority: Option<u32>,
+    #[serde(default)]
+    pub vlan_filtering: Option<bool>,
+    #[serde(default)]
+    pub ports: Vec<BridgePort>,
+    #[serde(default)]
+    pub ip4info: Option<NetInfo>,
+    #[serde(default)]
+    pub ip6info: Option<NetInfo>,
+}
+
+/// The bridge port
+///
+/// * `device`: the interface name of the ethernet device.
+/// * `path_cost`: the STP path cost, 1 to 65535.
+/// * `priority`: the STP port priority, 0 to 63.
+/// * `hairpin`: whether the frames could be sent back through the port they
+///   were received on.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct BridgePort {
+    pub device: String,
+    #[serde(default)]
+    pub path_cost: Option<u32>,
+    #[serde(default)]
+    pub priority: Option<u32>,
+    #[serde(default)]
+    pub hairpin: Option<bool>,
+}
+
+impl BridgeConnection {
+    /// Check the bridge settings before they are sent to NetworkManager.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.interface.is_empty() {
+            bail!(NetworkError::InvalidArgument(
+                "Both the connection name and the interface are required".into()
+            ))
+        }
+        if let Some(forward_delay) = self.forward_delay {
+            check_range("forward delay", forward_delay, FORWARD_DELAY)?;
+        }
+        if let Some(priority) = self.priority {
+            check_range("bridge priority", priority, (0, MAX_BRIDGE_PRIORITY))?;
+        }
+        Ok(())
+    }
+}
+
+impl BridgePort {
+    /// Check the port settings before they are sent to NetworkManager.
+    pub fn validate(&self) -> Result<()> {
+        if self.device.is_empty() {
+            bail!(NetworkError::InvalidArgument(
+                "The port device is required".into()
+            ))
+        }
+        if let Some(path_cost) = self.path_cost {
+            check_range("path cost", path_cost, PATH_COST)?;
+        }
+        if let Some(priority) = self.priority {
+            check_range("port priority", priority, (0, MAX_PORT_PRIORITY))?;
+        }
+        Ok(())
+    }
+}
+
+fn check_range(kind: &str, value: u32, (min, max): (u32, u32)) -> Result<()> {
+    if value < min || value > max {
+        bail!(NetworkError::InvalidArgument(format!(
+            "Invalid {} {}, expected {} to {}",
+            kind, value, min, max
+        )))
+    }
+    Ok(())
+}
+
+/// Write the bridge settings which are given into the connection.
+fn write_bridge_settings(bridge: &BridgeConnection, connection: &impl IsA<nm::Connection>) {
+    let setting = match connection.setting_bridge() {
+        Some(setting) => setting,
+        None => {
+            let setting = SettingBridge::new();
+            connection.add_setting(setting.clone());
+            setting
+        }
+    };
+    if let Some(stp) = bridge.stp {
+        setting.set_stp(stp);
+    }
+    if let Some(forward_delay) = bridge.forward_delay {
+        setting.set_forward_delay(forward_delay);
+    }
+    if let Some(priority) = bridge.priority {
+        setting.set_priority(priority);
+    }
+    if let Some(vlan_filtering) = bridge.vlan_filtering {
+        setting.set_vlan_filtering(vlan_filtering);
+    }
+}
+
+fn read_port(connection: &nm::RemoteConnection) -> Option<BridgePort> {
+    let setting = connection.setting_bridge_port();
+    Some(BridgePort {
+        device: connection.interface_name()?.to_string(),
+        path_cost: setting.as_ref().map(|x| x.path_cost()),
+        priority: setting.as_ref().map(|x| x.priority()),
+        hairpin: setting.as_ref().map(|x| x.is_hairpin_mode()),
+    })
+}
+
+fn read_bridge(
+    client: &nm::Client,
+    nm_connection: &nm::RemoteConnection,
+) -> Option<BridgeConnection> {
+    let setting = nm_connection.setting_bridge()?;
+    let uuid = nm_connection.uuid()?.to_string();
+    let interface = nm_connection.interface_name()?.to_string();
+    let ports = port_connections(client, SETTING_BRIDGE_SETTING_NAME, &interface, &uuid)
+        .iter()
+        .filter_map(read_port)
+        .collect();
+    Some(BridgeConnection {
+        name: nm_connection.id()?.to_string(),
+        uuid,
+        interface,
+        stp: Some(setting.is_stp()),
+        forward_delay: Some(setting.forward_delay()),
+        priority: Some(setting.priority()),
+        vlan_filtering: Some(setting.is_vlan_filtering()),
+        ports,
+        ip4info: get_ip_config(nm_connection, 4).ok(),
+        ip6info: get_ip_config(nm_connection, 6).ok(),
+    })
+}
+
+/// Find the bridge connection with the uuid.
+fn bridge_connection(client: &nm::Client, uuid: &str) -> Result<nm::RemoteConnection> {
+    match client.connection_by_uuid(uuid) {
+        Some(connection) if connection.setting_bridge().is_some() => Ok(connection),
+        _ => bail!(NetworkError::NotFound(format!(
+            "Bridge connection {} not found",
+            uuid
+        ))),
+    }
+}
+
+fn bridge_response(
+    client: &nm::Client,
+    nm_connection: &nm::RemoteConnection,
+) -> Result<NetworkResponse> {
+    let bridge = read_bridge(client, nm_connection).ok_or_else(|| {
+        NetworkError::Backend(format!(
+            "Failed to read the bridge connection {}",
+            nm_connection.uuid().unwrap_or_default()
+        ))
+    })?;
+    Ok(NetworkResponse::Bridge(bridge))
+}
+
+/// The connection the device is configured by, the active one or the latest
+/// ethernet connection bound to the device.
+fn device_connection(client: &nm::Client, device: &nm::Device) -> Option<nm::RemoteConnection> {
+    if let Some(connection) = device.active_connection().and_then(|x| x.connection()) {
+        return Some(connection);
+    }
+    let interface = device.interface()?;
+    client
+        .connections()
+        .into_iter()
+        .filter(|x| {
+            x.interface_name().as_ref() == Some(&interface)
+                && x.setting_wired().is_some()
+                && x.setting_connection()
+                    .and_then(|x| x.slave_type())
+                    .is_none()
+        })
+        .max_by_key(|x| {
+            x.setting_connection()
+                .map(|x| x.timestamp())
+                .unwrap_or(0u64)
+        })
+}
+
+/// Move the ip configurations of the device connection onto the bridge, the
+/// device connection is kept without autoconnect, so that it could be
+/// restored by hand.
+async fn migrate_ip_configs(
+    client: &nm::Client,
+    bridge: &nm::RemoteConnection,
+    device: &nm::Device,
+) -> Result<()> {
+    let connection = device_connection(client, device).ok_or_else(|| {
+        NetworkError::NotFound(format!(
+            "No connection to migrate on the device {}",
+            device.interface().unwrap_or_default()
+        ))
+    })?;
+    let ip4info = get_ip_config(&connection, 4).ok();
+    let ip6info = get_ip_config(&connection, 6).ok();
+    set_ip_configs(bridge, ip4info.as_ref(), ip6info.as_ref())?;
+    bridge.commit_changes_future(true).await?;
+
+    if let Some(setting) = connection.setting_connection() {
+        setting.set_autoconnect(false);
+    }
+    connection.commit_changes_future(true).await?;
+    Ok(())
+}
+
+/// Create a bridge connection without ports.
+pub async fn create_bridge(bridge: BridgeConnection) -> Result<NetworkResponse> {
+    bridge.validate()?;
+    let client = create_client().await?;
+
+    let connection = SimpleConnection::new();
+    let s_connection = SettingConnection::new();
+    s_connection.set_type(Some(&SETTING_BRIDGE_SETTING_NAME));
+    s_connection.set_id(Some(&bridge.name));
+    s_connection.set_interface_name(Some(&bridge.interface));
+    s_connection.set_autoconnect(true);
+    connection.add_setting(s_connection);
+    write_bridge_settings(&bridge, &connection);
+    set_ip_configs(
+        &connection,
+        bridge.ip4info.as_ref(),
+        bridge.ip6info.as_ref(),
+    )?;
+
+    let uuid = add_connection(&client, &connection, &bridge.name).await?;
+    Ok(NetworkResponse::Created { uuid })
+}
+
+/// Update the bridge connection with the uuid, the ports are left unchanged.
+pub async fn update_bridge(bridge: BridgeConnection) -> Result<NetworkResponse> {
+    bridge.validate()?;
+    let client = create_client().await?;
+    let nm_connection = bridge_connection(&client, &bridge.uuid)?;
+
+    if let Some(setting) = nm_connection.setting_connection() {
+        setting.set_id(Some(&bridge.name));
+    }
+    write_bridge_settings(&bridge, &nm_connection);
+    set_ip_configs(
+        &nm_connection,
+        bridge.ip4info.as_ref(),
+        bridge.ip6info.as_ref(),
+    )?;
+    nm_connection.commit_changes_future(true).await?;
+    bridge_response(&client, &nm_connection)
+}
+
+/// Get the bridge connection with the uuid.
+pub async fn get_bridge(uuid: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = bridge_connection(&client, &uuid)?;
+    bridge_response(&client, &nm_connection)
+}
+
+/// Attach the ethernet device to the bridge, the port connection is created
+/// and activated.
+///
+/// If `migrate_ip` is true, the ip configurations of the device connection
+/// are moved onto the bridge, and the bridge is activated again. The backend
+/// makes the migration under a checkpoint, so it is rolled back on failure.
+pub async fn attach_bridge_port(
+    uuid: String,
+    port: BridgePort,
+    migrate_ip: bool,
+) -> Result<NetworkResponse> {
+    port.validate()?;
+    let client = create_client().await?;
+    let nm_connection = bridge_connection(&client, &uuid)?;
+    let interface = nm_connection
+        .interface_name()
+        .map(|x| x.to_string())
+        .unwrap_or_default();
+
+    let device = port_device(&client, &port.device)?;
+    if migrate_ip {
+        migrate_ip_configs(&client, &nm_connection, &device).await?;
+    }
+
+    let connection = port_connection(&interface, SETTING_BRIDGE_SETTING_NAME, &port.device);
+    let s_port = SettingBridgePort::new();
+    if let Some(path_cost) = port.path_cost {
+        s_port.set_path_cost(path_cost);
+    }
+    if let Some(priority) = port.priority {
+        s_port.set_priority(priority);
+    }
+    if let Some(hairpin) = port.hairpin {
+        s_port.set_hairpin_mode(hairpin);
+    }
+    connection.add_setting(s_port);
+    activate_port(&client, &connection, &device).await?;
+
+    if migrate_ip {
+        client
+            .activate_connection_future(Some(&nm_connection), None::<&nm::Device>, None)
+            .await?;
+    }
+    bridge_response(&client, &nm_connection)
+}
+
+/// Detach the device from the bridge, the port connections are deleted.
+pub async fn detach_bridge_port(uuid: String, device_name: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = bridge_connection(&client, &uuid)?;
+    let interface = nm_connection
+        .interface_name()
+        .map(|x| x.to_string())
+        .unwrap_or_default();
+
+    delete_ports(
+        &client,
+        SETTING_BRIDGE_SETTING_NAME,
+        &interface,
+        &uuid,
+        &device_name,
+    )
+    .await?;
+    bridge_response(&client, &nm_connection)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    fn bridge() -> BridgeConnection {
+        BridgeConnection {
+            name: "bridge".into(),
+            interface: "br0".into(),
+            ..Default::default()
+        }
+    }
+
+    fn port() -> BridgePort {
+        BridgePort {
+            device: "eth0".into(),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(bridge(), true)]
+    #[case(BridgeConnection { interface: "".into(), ..bridge() }, false)]
+    #[case(BridgeConnection { stp: Some(true), forward_delay: Some(15), ..bridge() }, true)]
+    #[case(BridgeConnection { forward_delay: Some(1), ..bridge() }, false)]
+    #[case(BridgeConnection { forward_delay: Some(31), ..bridge() }, false)]
+    #[case(BridgeConnection { priority: Some(32768), ..bridge() }, true)]
+    #[case(BridgeConnection { priority: Some(65536), ..bridge() }, false)]
+    fn test_validate_bridge(#[case] bridge: BridgeConnection, #[case] valid: bool) {
+        assert_eq!(bridge.validate().is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case(port(), true)]
+    #[case(BridgePort { device: "".into(), ..port() }, false)]
+    #[case(BridgePort { path_cost: Some(100), priority: Some(32), hairpin: Some(true), ..port() }, true)]
+    #[case(BridgePort { path_cost: Some(0), ..port() }, false)]
+    #[case(BridgePort { priority: Some(64), ..port() }, false)]
+    fn test_validate_port(#[case] port: BridgePort, #[case] valid: bool) {
+        assert_eq!(port.validate().is_ok(), valid);
+    }
+}
@@ -0,0 +1,130 @@
+//! Checkpoint Module
+//!
+//! The module provides the api about the NetworkManager checkpoints. A
+//! checkpoint saves the state of the devices and their connections, the
+//! changes made after it are rolled back by NetworkManager itself unless the
+//! checkpoint is confirmed within the rollback timeout. So a change which cuts
+//! off the remote access is undone even if the client never comes back. The
+//! connections created after the checkpoint are deleted by the rollback.
+//!
+//! Functions:
+//! - begin_checkpoint
+//! - confirm_checkpoint
+//! - rollback_checkpoint
+use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
+use eyre::Result;
+use nm::{CheckpointCreateFlags, DeviceExt, ObjectExt};
+use serde::{Deserialize, Serialize};
+
+/// The NetworkManager rollback result of a device which is restored
+const ROLLBACK_RESULT_OK: u32 = 0;
+
+/// The checkpoint
+///
+/// * `id`: the D-Bus path of the checkpoint, e.g.
+///   `/org/freedesktop/NetworkManager/Checkpoint/1`.
+/// * `devices`: the interface names of the devices covered.
+/// * `rollback_timeout`: the seconds after which the changes are rolled back,
+///   0 means the checkpoint is never rolled back automatically.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct CheckpointInfo {
+    pub id: String,
+    pub devices: Vec<String>,
+    pub rollback_timeout: u32,
+}
+
+/// Find the checkpoint with the id.
+fn find_checkpoint(client: &nm::Client, id: &str) -> Result<nm::Checkpoint> {
+    match client
+        .checkpoints()
+        .into_iter()
+        .find(|x| x.path().as_deref() == Some(id))
+    {
+        Some(checkpoint) => Ok(checkpoint),
+        None => bail!(NetworkError::NotFound(format!(
+            "Checkpoint {} not found, it may be rolled back already",
+            id
+        ))),
+    }
+}
+
+/// Create a checkpoint of the devices with the interface names, all the
+/// devices are covered if none is given.
+///
+/// A device covered by another checkpoint is refused, the other checkpoint
+/// has to be confirmed or rolled back first.
+pub async fn begin_checkpoint(
+    devices: Vec<String>,
+    rollback_timeout: u32,
+) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let mut nm_devices = vec![];
+    for device_name in devices.iter() {
+        match client.device_by_iface(device_name) {
+            Some(device) => nm_devices.push(device),
+            None => bail!(NetworkError::NotFound(format!(
+                "The given network device {} is not found.",
+                device_name
+            ))),
+        }
+    }
+    let covered: Vec<String> = client
+        .checkpoints()
+        .iter()
+        .flat_map(|x| x.devices())
+        .filter_map(|x| x.iface().map(|x| x.to_string()))
+        .collect();
+    if let Some(device_name) = devices.iter().find(|x| covered.contains(x)) {
+        bail!(NetworkError::Conflict(format!(
+            "The device {} is covered by another checkpoint",
+            device_name
+        )))
+    }
+
+    let checkpoint = client
+        .checkpoint_create_future(
+            &nm_devices,
+            rollback_timeout,
+            CheckpointCreateFlags::DELETE_NEW_CONNECTIONS,
+        )
+        .await
+        .map_err(|e| NetworkError::Conflict(format!("Failed to create checkpoint: {}", e)))?;
+    Ok(NetworkResponse::Checkpoint(CheckpointInfo {
+        id: checkpoint.path().map(|x| x.to_string()).unwrap_or_default(),
+        devices: checkpoint
+            .devices()
+            .iter()
+            .filter_map(|x| x.iface().map(|x| x.to_string()))
+            .collect(),
+        rollback_timeout: checkpoint.rollback_timeout(),
+    }))
+}
+
+/// Keep the changes made after the checkpoint, the checkpoint is destroyed.
+pub async fn confirm_checkpoint(id: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    find_checkpoint(&client, &id)?;
+    client.checkpoint_destroy_future(&id).await?;
+    Ok(NetworkResponse::Success)
+}
+
+/// Restore the devices to the state of the checkpoint now, the devices which
+/// failed to be restored are reported.
+pub async fn rollback_checkpoint(id: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    find_checkpoint(&client, &id)?;
+    let results = client.checkpoint_rollback_future(&id).await?;
+    let failed: Vec<String> = results
+        .iter()
+        .filter(|(_, result)| **result != ROLLBACK_RESULT_OK)
+        .map(|(device, _)| device.to_string())
+        .collect();
+    if !failed.is_empty() {
+        bail!(NetworkError::Backend(format!(
+            "Failed to roll back the devices {}",
+            failed.join(", ")
+        )))
+    }
+    Ok(NetworkResponse::Success)
+}
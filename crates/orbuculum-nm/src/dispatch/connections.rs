@@ -6,31 +6,67 @@
 //! - create_wired_connection
 //! - list_wired_connection
 //! - delete_connection
+//! - add_route / remove_route
+use super::devices::device_link_modes;
+use super::wired::{get_wired_settings, update_wired_settings, WiredSettings};
 use super::{create_client, NetworkResponse};
-use crate::net::NetInfo;
-use eyre::{ContextCompat, Result};
-use glib::future_with_timeout;
+use crate::error::NetworkError;
+use crate::net::{addr_gen_mode_from_human, ip6_privacy_from_human, NetInfo, Route};
+use crate::utils::non_empty;
+use eyre::Result;
+use glib::translate::IntoGlib;
+use glib::{future_with_timeout, Cast, IsA};
 use ipnet::IpNet;
 use libc::{AF_INET, AF_INET6};
 use nm::{
-    ConnectionExt, DeviceExt, IPAddress, SettingConnection, SettingIP4Config, SettingIP6Config,
-    SettingIPConfig, SettingIPConfigExt, SettingWired, SimpleConnection,
-    SETTING_WIRED_SETTING_NAME,
+    ActiveConnectionExt, ConnectionExt, DeviceError, DeviceExt, IPAddress, IPRoute,
+    SettingConnection, SettingIP4Config, SettingIP6Config, SettingIPConfig, SettingIPConfigExt,
+    SettingWired, SimpleConnection, SETTING_WIRED_SETTING_NAME,
 };
 use serde::{Deserialize, Serialize};
 use std::boxed::Box;
+use std::sync::Arc;
+
+/// How the changes of a connection are applied to its device
+///
+/// * `Reapply`: the changes are applied to the active connection in place,
+///   the link stays up.
+/// * `Reactivate`: the changes are not supported by the reapply, e.g. the
+///   interface name is changed, the connection is deactivated and activated.
+/// * `Activate`: the device has no active connection or another one, the
+///   connection is activated on it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApplyStrategy {
+    Reapply,
+    Reactivate,
+    Activate,
+}
+
+impl std::fmt::Display for ApplyStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let strategy = match self {
+            ApplyStrategy::Reapply => "reapply",
+            ApplyStrategy::Reactivate => "reactivate",
+            ApplyStrategy::Activate => "activate",
+        };
+        write!(f, "{}", strategy)
+    }
+}
 
 /// The simplified connection struct
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Connection {
     pub name: String,
     pub uuid: String,
-    #[serde(skip_deserializing)]
     pub interface: Option<String>,
-    #[serde(skip_deserializing)]
     pub mac: Option<String>,
     pub ip4info: NetInfo,
     pub ip6info: NetInfo,
+    /// The link settings, `None` if it is not a wired connection or the
+    /// settings are left unchanged.
+    #[serde(default)]
+    pub wired: Option<WiredSettings>,
 }
 
 impl Connection {
@@ -41,6 +77,7 @@ impl Connection {
         mac: Option<String>,
         ip4info: NetInfo,
         ip6info: NetInfo,
+        wired: Option<WiredSettings>,
     ) -> Self {
         Self {
             name,
@@ -49,36 +86,40 @@ impl Connection {
             interface,
             ip4info,
             ip6info,
+            wired,
         }
     }
 
+    /// Read the connection, the profile bound to a mac address has no
+    /// interface, its `mac` is the bound address. Otherwise the `mac` is the
+    /// address of the device of the interface.
     fn from_nm_connection(
         nm_connection: &nm::RemoteConnection,
         client: &nm::Client,
     ) -> Option<Self> {
-        let mut connection = None;
-        if let Some(name) = nm_connection.id() {
-            if let Some(uuid) = nm_connection.uuid() {
-                if let Some(interface) = nm_connection.interface_name() {
-                    if let Ok(ip4config) = get_ip_config(nm_connection, 4) {
-                        if let Ok(ip6config) = get_ip_config(nm_connection, 6) {
-                            let mac = client
-                                .device_by_iface(&interface)
-                                .and_then(|x| x.hw_address().map(|x| x.to_string()));
-                            connection = Some(Connection::new(
-                                name.to_string(),
-                                uuid.to_string(),
-                                Some(interface.to_string()),
-                                mac,
-                                ip4config,
-                                ip6config,
-                            ))
-                        }
-                    }
-                }
-            }
-        }
-        connection
+        let name = nm_connection.id()?;
+        let uuid = nm_connection.uuid()?;
+        let ip4config = get_ip_config(nm_connection, 4).ok()?;
+        let ip6config = get_ip_config(nm_connection, 6).ok()?;
+        let interface = nm_connection.interface_name().map(|x| x.to_string());
+        let mac = match interface.as_deref() {
+            Some(interface) => client
+                .device_by_iface(interface)
+                .and_then(|x| x.hw_address().map(|x| x.to_string())),
+            None => nm_connection
+                .setting_wired()
+                .and_then(|x| x.mac_address())
+                .map(|x| x.to_string()),
+        };
+        Some(Connection::new(
+            name.to_string(),
+            uuid.to_string(),
+            interface,
+            mac,
+            ip4config,
+            ip6config,
+            get_wired_settings(nm_connection),
+        ))
     }
 }
 
@@ -87,40 +128,88 @@ pub async fn rename_connection(conn_uuid: String, new_name: String) -> Result<Ne
     let client = create_client().await?;
     match client.connection_by_uuid(&conn_uuid) {
         Some(connection) => {
-            let setting = connection
-                .setting_connection()
-                .expect("Failed to get connection setting");
+            let setting = connection.setting_connection().ok_or_else(|| {
+                NetworkError::Backend(format!(
+                    "The connection {} has no connection setting",
+                    conn_uuid
+                ))
+            })?;
             setting.set_id(Some(&new_name));
             connection.commit_changes_future(true).await?;
         }
-        _ => bail!("Uuid {} not found", conn_uuid),
+        _ => bail!(NetworkError::NotFound(format!(
+            "Uuid {} not found",
+            conn_uuid
+        ))),
     }
     Ok(NetworkResponse::Success)
 }
 
-/// Reactive a connection by UUID
+/// The device the connection is applied to, it is found by the interface
+/// name of the connection, or by the devices the connection is available to.
+fn connection_device(client: &nm::Client, connection: &nm::RemoteConnection) -> Result<nm::Device> {
+    if let Some(interface) = connection.interface_name() {
+        return client.device_by_iface(&interface).ok_or_else(|| {
+            NetworkError::NotFound(format!("The interface {} is not found", interface)).into()
+        });
+    }
+    let uuid = connection.uuid().map(|x| x.to_string()).unwrap_or_default();
+    client
+        .devices()
+        .into_iter()
+        .find(|device| {
+            device
+                .available_connections()
+                .iter()
+                .any(|x| x.uuid().as_deref() == Some(uuid.as_str()))
+        })
+        .ok_or_else(|| {
+            NetworkError::Conflict(format!("The connection {} has no device to apply to", uuid))
+                .into()
+        })
+}
+
+/// Apply the saved changes of a connection by UUID to its device.
+///
+/// The changes are reapplied to the active connection if NetworkManager
+/// supports it, which keeps the link and the sessions up. Otherwise the
+/// connection is reactivated, or activated if it is not active yet. The
+/// strategy used is returned.
 pub async fn reactive_connection(uuid: String) -> Result<NetworkResponse> {
     let client = create_client().await?;
-    let connection = client
-        .connection_by_uuid(&uuid)
-        .wrap_err("Failed to get connection uuid when reactive")?;
-    let interface = connection
-        .interface_name()
-        .map(|x| x.to_string())
-        .wrap_err("Failed to get interface")?;
-    let device = client
-        .device_by_iface(&interface)
-        .wrap_err("Failed to get interface from connection")?;
+    let connection = client.connection_by_uuid(&uuid).ok_or_else(|| {
+        NetworkError::NotFound(format!("Failed to get connection {} when reactive", uuid))
+    })?;
+    let device = connection_device(&client, &connection)?;
     let active_connection = device
         .active_connection()
-        .wrap_err("The interface has no active connection")?;
-    client
-        .deactivate_connection_future(&active_connection)
-        .await?;
-    client
-        .activate_connection_future(Some(&connection), Some(&device), None)
-        .await?;
-    Ok(NetworkResponse::Success)
+        .filter(|x| x.uuid().as_deref() == Some(uuid.as_str()));
+    let active_connection = match active_connection {
+        Some(active_connection) => active_connection,
+        None => {
+            client
+                .activate_connection_future(Some(&connection), Some(&device), None)
+                .await?;
+            return Ok(NetworkResponse::Applied(ApplyStrategy::Activate));
+        }
+    };
+
+    match device.reapply_future(Some(&connection), 0, 0).await {
+        Ok(()) => Ok(NetworkResponse::Applied(ApplyStrategy::Reapply)),
+        Err(err) if err.kind::<DeviceError>() == Some(DeviceError::IncompatibleConnection) => {
+            client
+                .deactivate_connection_future(&active_connection)
+                .await?;
+            client
+                .activate_connection_future(Some(&connection), Some(&device), None)
+                .await?;
+            Ok(NetworkResponse::Applied(ApplyStrategy::Reactivate))
+        }
+        Err(err) => bail!(NetworkError::Backend(format!(
+            "Failed to reapply connection {}: {}",
+            uuid, err
+        ))),
+    }
 }
 
 /// Create a new connection via `Connection Name` and `Device Name`
@@ -132,7 +221,6 @@ pub async fn create_wired_connection(conn_name: String, device: String) -> Resul
 
     let connection = SimpleConnection::new();
     let s_connection = SettingConnection::new();
-    let mut uuid = String::new();
 
     s_connection.set_type(Some(&SETTING_WIRED_SETTING_NAME));
     s_connection.set_id(Some(&conn_name));
@@ -146,27 +234,69 @@ pub async fn create_wired_connection(conn_name: String, device: String) -> Resul
     }
     connection.add_setting(s_connection);
 
+    let uuid = add_connection(&client, &connection, &conn_name).await?;
+    Ok(NetworkResponse::Created { uuid })
+}
+
+/// Add the connection to NetworkManager and return the uuid of it.
+///
+/// If NetworkManager doesn't answer in time, the connection is looked up by
+/// the name, and the timeout is returned if it is not found.
+pub(crate) async fn add_connection(
+    client: &nm::Client,
+    connection: &SimpleConnection,
+    conn_name: &str,
+) -> Result<String> {
     match future_with_timeout(std::time::Duration::from_millis(1000), async {
-        client.add_connection_future(&connection, true).await
+        client.add_connection_future(connection, true).await
     })
     .await
     {
-        Ok(Ok(connection)) => {
-            uuid = connection.uuid().map(|x| x.to_string()).unwrap_or(uuid);
-        }
-        _ => {
-            eprintln!("add connection {} timeout", conn_name);
+        Ok(Ok(connection)) => match connection.uuid() {
+            Some(uuid) => Ok(uuid.to_string()),
+            None => bail!(NetworkError::Backend(format!(
+                "The connection {} is added without uuid",
+                conn_name
+            ))),
+        },
+        Ok(Err(err)) => bail!(NetworkError::Backend(format!(
+            "Failed to add connection {}: {}",
+            conn_name, err
+        ))),
+        Err(_) => {
             let client = create_client().await?;
-            uuid = client
-                .connection_by_id(&conn_name)
-                .and_then(|x| Some(x.uuid().map(|x| x.to_string()).unwrap_or_default()))
-                .unwrap_or_default();
+            match client.connection_by_id(conn_name).and_then(|x| x.uuid()) {
+                Some(uuid) => Ok(uuid.to_string()),
+                None => bail!(NetworkError::Timeout(format!(
+                    "Adding connection {} is not answered in time",
+                    conn_name
+                ))),
+            }
         }
     }
+}
 
-    Ok(NetworkResponse::Return(
-        serde_json::to_value(&uuid).unwrap(),
-    ))
+/// Write the given ip configurations into the connection, the settings are
+/// added if the connection has none. The configuration not given is left
+/// unchanged.
+pub(crate) fn set_ip_configs(
+    connection: &impl IsA<nm::Connection>,
+    ip4info: Option<&NetInfo>,
+    ip6info: Option<&NetInfo>,
+) -> Result<()> {
+    if let Some(ip4info) = ip4info {
+        if connection.setting_ip4_config().is_none() {
+            connection.add_setting(SettingIP4Config::new());
+        }
+        update_ip_config(ip4info, connection, 4)?;
+    }
+    if let Some(ip6info) = ip6info {
+        if connection.setting_ip6_config().is_none() {
+            connection.add_setting(SettingIP6Config::new());
+        }
+        update_ip_config(ip6info, connection, 6)?;
+    }
+    Ok(())
 }
 
 /// List all connections in NetworkManager.
@@ -177,8 +307,7 @@ pub async fn list_connections() -> Result<NetworkResponse> {
         .iter()
         .filter_map(|x| Connection::from_nm_connection(x, &client))
         .collect();
-    let nm_connecionts = serde_json::to_value(nm_connecionts)?;
-    Ok(NetworkResponse::Return(nm_connecionts))
+    Ok(NetworkResponse::ListConnection(nm_connecionts))
 }
 
 /// Get a connection by connection uuid
@@ -188,10 +317,12 @@ pub async fn get_connection(uuid: String) -> Result<NetworkResponse> {
         .connection_by_uuid(&uuid)
         .map(|x| Connection::from_nm_connection(&x, &client))
     {
-        let connection = serde_json::to_value(&connection)?;
-        Ok(NetworkResponse::Return(connection))
+        Ok(NetworkResponse::Connection(connection))
     } else {
-        bail!("Failed to get connection with uuid {}", uuid)
+        bail!(NetworkError::NotFound(format!(
+            "Failed to get connection with uuid {}",
+            uuid
+        )))
     }
 }
 
@@ -218,7 +349,17 @@ pub async fn delete_connection(conn_name: String) -> Result<NetworkResponse> {
     Ok(NetworkResponse::Success)
 }
 
-fn ipnet2ipaddr(ipnet: IpNet) -> Result<IPAddress> {
+/// Delete a connection by UUID
+pub async fn delete_connection_by_uuid(uuid: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let connection = client
+        .connection_by_uuid(&uuid)
+        .ok_or_else(|| NetworkError::NotFound(format!("Uuid {} not found", uuid)))?;
+    connection.delete_future().await?;
+    Ok(NetworkResponse::Success)
+}
+
+pub(crate) fn ipnet2ipaddr(ipnet: IpNet) -> Result<IPAddress> {
     let ipaddress: IPAddress;
     match ipnet {
         IpNet::V4(v4) => {
@@ -232,7 +373,7 @@ fn ipnet2ipaddr(ipnet: IpNet) -> Result<IPAddress> {
 }
 
 /// Get the configuration via connection name and ip family
-fn get_ip_config(connection: &nm::RemoteConnection, family: i32) -> Result<NetInfo> {
+pub(crate) fn get_ip_config(connection: &nm::RemoteConnection, family: i32) -> Result<NetInfo> {
     if family == 4 {
         if let Some(setting_ip4_config) = connection
             .setting_ip4_config()
@@ -254,7 +395,11 @@ fn get_ip_config(connection: &nm::RemoteConnection, family: i32) -> Result<NetIn
     }
 }
 
-fn update_ip_config(netinfo: &NetInfo, connection: &nm::RemoteConnection, family: i32) {
+fn update_ip_config(
+    netinfo: &NetInfo,
+    connection: &impl IsA<nm::Connection>,
+    family: i32,
+) -> Result<()> {
     let mut some_ipconfig: Option<SettingIPConfig> = None;
 
     // Parser configuration
@@ -273,41 +418,240 @@ fn update_ip_config(netinfo: &NetInfo, connection: &nm::RemoteConnection, family
             );
             ipconfig.clear_addresses();
             for address in netinfo.addresses.iter() {
-                ipconfig.add_address(&ipnet2ipaddr(*address).ok().unwrap());
+                let ipaddress = ipnet2ipaddr(*address).map_err(|_| {
+                    NetworkError::InvalidArgument(format!("Invalid address {}", address))
+                })?;
+                ipconfig.add_address(&ipaddress);
             }
             ipconfig.clear_dns();
 
             for dns in netinfo.dns.iter() {
                 ipconfig.add_dns(&dns.to_string());
             }
+            // The search domains and options not given are kept
+            if let Some(dns_search) = netinfo.dns_search.as_ref() {
+                ipconfig.clear_dns_searches();
+                for search in dns_search.iter() {
+                    ipconfig.add_dns_search(search);
+                }
+            }
+            // An empty list unsets the options, so the default options apply.
+            if let Some(dns_options) = netinfo.dns_options.as_ref() {
+                ipconfig.clear_dns_options(false);
+                for option in dns_options.iter() {
+                    if !ipconfig.add_dns_option(option) {
+                        bail!(NetworkError::InvalidArgument(format!(
+                            "Invalid or duplicated dns option {}",
+                            option
+                        )))
+                    }
+                }
+            }
+            if let Some(dns_priority) = netinfo.dns_priority {
+                ipconfig.set_dns_priority(dns_priority);
+            }
+            if let Some(ignore_auto_dns) = netinfo.ignore_auto_dns {
+                ipconfig.set_ignore_auto_dns(ignore_auto_dns);
+            }
 
-            ipconfig.clear_routes();
-            for route in netinfo.routes.clone().into_iter() {
-                ipconfig.add_route(&route.try_into().ok().unwrap());
+            // The routes not given are kept, they are managed by the
+            // add_route and remove_route too.
+            if let Some(routes) = netinfo.routes.as_ref() {
+                ipconfig.clear_routes();
+                for route in routes.iter().cloned() {
+                    ipconfig.add_route(&route_to_iproute(route)?);
+                }
+            }
+
+            if let Some(route_table) = netinfo.route_table {
+                ipconfig.set_route_table(route_table);
+            }
+            if let Some(routing_rules) = netinfo.routing_rules.as_ref() {
+                ipconfig.clear_routing_rules();
+                let rule_family = if family == 4 { AF_INET } else { AF_INET6 };
+                for rule in routing_rules.iter() {
+                    ipconfig.add_routing_rule(&rule.to_nm_rule(rule_family)?);
+                }
             }
+
+            update_ip_flags(netinfo, &ipconfig)?;
         }
         None => (),
     }
+    Ok(())
+}
+
+/// Update the method flags, the flags not given are left unchanged.
+fn update_ip_flags(netinfo: &NetInfo, ipconfig: &SettingIPConfig) -> Result<()> {
+    if let Some(never_default) = netinfo.never_default {
+        ipconfig.set_never_default(never_default);
+    }
+    if let Some(may_fail) = netinfo.may_fail {
+        ipconfig.set_may_fail(may_fail);
+    }
+    if let Some(dhcp_hostname) = netinfo.dhcp_hostname.as_deref() {
+        ipconfig.set_dhcp_hostname(non_empty(dhcp_hostname));
+    }
+    if let Some(dhcp_send_hostname) = netinfo.dhcp_send_hostname {
+        ipconfig.set_dhcp_send_hostname(dhcp_send_hostname);
+    }
+    if let Some(dhcp_timeout) = netinfo.dhcp_timeout {
+        ipconfig.set_dhcp_timeout(dhcp_timeout);
+    }
+
+    let ip4_config = ipconfig.downcast_ref::<SettingIP4Config>();
+    let ip6_config = ipconfig.downcast_ref::<SettingIP6Config>();
+    if let Some(dhcp_client_id) = netinfo.dhcp_client_id.as_deref() {
+        let ip4_config = ip4_config.ok_or_else(|| {
+            NetworkError::InvalidArgument("The dhcp client id is only for IPv4".into())
+        })?;
+        ip4_config.set_dhcp_client_id(non_empty(dhcp_client_id));
+    }
+    if let Some(addr_gen_mode) = netinfo.addr_gen_mode.as_deref() {
+        let ip6_config = ip6_config.ok_or_else(|| {
+            NetworkError::InvalidArgument("The addr-gen-mode is only for IPv6".into())
+        })?;
+        ip6_config.set_addr_gen_mode(addr_gen_mode_from_human(addr_gen_mode)?.into_glib());
+    }
+    if let Some(ip6_privacy) = netinfo.ip6_privacy.as_deref() {
+        let ip6_config = ip6_config.ok_or_else(|| {
+            NetworkError::InvalidArgument("The ip6-privacy is only for IPv6".into())
+        })?;
+        ip6_config.set_ip6_privacy(ip6_privacy_from_human(ip6_privacy)?);
+    }
+    Ok(())
 }
 
-/// Update the settings of IP configuration
-pub async fn update_connection(connection_json: serde_json::Value) -> Result<NetworkResponse> {
+/// Update the settings of IP configuration and the wired link settings
+///
+/// The wired settings are validated with the link modes of the interface.
+pub async fn update_connection(
+    connection: Connection,
+    link_modes: Arc<serde_json::Value>,
+) -> Result<NetworkResponse> {
     let client = create_client().await?;
-    let connection: Connection = serde_json::from_value(connection_json)?;
-    let nm_connection: Option<nm::RemoteConnection> = try {
-        let nm_connection: nm::RemoteConnection = client.connection_by_uuid(&connection.uuid)?;
+    let nm_connection = client
+        .connection_by_uuid(&connection.uuid)
+        .ok_or_else(|| NetworkError::NotFound(format!("Uuid {} not found", connection.uuid)))?;
 
-        update_ip_config(&connection.ip4info, &nm_connection, 4);
-        update_ip_config(&connection.ip6info, &nm_connection, 6);
+    update_ip_config(&connection.ip4info, &nm_connection, 4)?;
+    update_ip_config(&connection.ip6info, &nm_connection, 6)?;
+    if let Some(wired) = connection.wired {
+        let interface = connection_interface(&client, &nm_connection).unwrap_or_default();
+        let link_modes = device_link_modes(&link_modes, &interface);
+        update_wired_settings(wired, &nm_connection, &link_modes)?;
+    }
+
+    commit_connection(&nm_connection, &client).await
+}
+
+/// The interface of the connection, the connection bound to a mac address is
+/// resolved by the device with the address.
+fn connection_interface(client: &nm::Client, connection: &nm::RemoteConnection) -> Option<String> {
+    if let Some(interface) = connection.interface_name() {
+        return Some(interface.to_string());
+    }
+    let mac = connection.setting_wired()?.mac_address()?;
+    interface_by_mac(client, &mac)
+}
 
-        nm_connection.commit_changes_future(true).await.unwrap();
-        nm_connection
+/// The interface name of the device with the mac address
+fn interface_by_mac(client: &nm::Client, mac: &str) -> Option<String> {
+    client
+        .devices()
+        .into_iter()
+        .find(|x| {
+            x.hw_address()
+                .map(|x| x.eq_ignore_ascii_case(mac))
+                .unwrap_or(false)
+        })
+        .and_then(|x| x.interface())
+        .map(|x| x.to_string())
+}
+
+/// Save the changes of the connection and read it again.
+async fn commit_connection(
+    nm_connection: &nm::RemoteConnection,
+    client: &nm::Client,
+) -> Result<NetworkResponse> {
+    nm_connection.commit_changes_future(true).await?;
+    let conn = Connection::from_nm_connection(nm_connection, client).ok_or_else(|| {
+        NetworkError::Backend(format!(
+            "Failed to read the connection {} after updating",
+            nm_connection.uuid().unwrap_or_default()
+        ))
+    })?;
+    Ok(NetworkResponse::Connection(conn))
+}
+
+pub(crate) fn route_to_iproute(route: Route) -> Result<IPRoute> {
+    let dest = route.dest;
+    let iproute = route.try_into().map_err(|err| {
+        NetworkError::InvalidArgument(format!("Invalid route to {}: {}", dest, err))
+    })?;
+    Ok(iproute)
+}
+
+/// Get the ip configuration of the route family.
+fn route_ip_config(nm_connection: &nm::RemoteConnection, family: i32) -> Result<SettingIPConfig> {
+    let ipconfig: Option<SettingIPConfig> = if family == AF_INET {
+        nm_connection.setting_ip4_config().map(|x| x.into())
+    } else {
+        nm_connection.setting_ip6_config().map(|x| x.into())
     };
-    let conn = nm_connection
-        .and_then(|x| {
-            Connection::from_nm_connection(&x, &client)
-                .and_then(|y| Some(serde_json::to_value(y).unwrap()))
+    match ipconfig {
+        Some(ipconfig) => Ok(ipconfig),
+        None => bail!(NetworkError::Conflict(format!(
+            "The connection {} has no ip configuration for the route",
+            nm_connection.uuid().unwrap_or_default()
+        ))),
+    }
+}
+
+/// Add a static route to the connection, the other routes are kept.
+pub async fn add_route(uuid: String, route: Route) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = client
+        .connection_by_uuid(&uuid)
+        .ok_or_else(|| NetworkError::NotFound(format!("Uuid {} not found", uuid)))?;
+    let ipconfig = route_ip_config(&nm_connection, route.family)?;
+    let dest = route.dest;
+    if !ipconfig.add_route(&route_to_iproute(route)?) {
+        bail!(NetworkError::Conflict(format!(
+            "The route to {} already exists",
+            dest
+        )))
+    }
+    commit_connection(&nm_connection, &client).await
+}
+
+/// Remove the static routes matching the given route from the connection.
+///
+/// The routes are matched by family, destination and the next hop if it is
+/// given.
+pub async fn remove_route(uuid: String, route: Route) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = client
+        .connection_by_uuid(&uuid)
+        .ok_or_else(|| NetworkError::NotFound(format!("Uuid {} not found", uuid)))?;
+    let ipconfig = route_ip_config(&nm_connection, route.family)?;
+    let indexes: Vec<i32> = (0..ipconfig.num_routes() as i32)
+        .filter(|idx| {
+            ipconfig
+                .route(*idx)
+                .and_then(|x| Route::try_from(x).ok())
+                .map(|x| x.matches(&route))
+                .unwrap_or(false)
         })
-        .expect("Failed to get Connection by UUID");
-    Ok(NetworkResponse::Return(conn))
+        .collect();
+    if indexes.is_empty() {
+        bail!(NetworkError::NotFound(format!(
+            "The route to {} is not found",
+            route.dest
+        )))
+    }
+    for idx in indexes.into_iter().rev() {
+        ipconfig.remove_route(idx);
+    }
+    commit_connection(&nm_connection, &client).await
 }
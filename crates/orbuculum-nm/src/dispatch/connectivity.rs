@@ -0,0 +1,115 @@
+//! Connectivity Module
+//!
+//! The module reports whether the host is online as NetworkManager sees it,
+//! the global connectivity state is the result of the last connectivity
+//! check, and it is `unknown` if the check is disabled in the configuration.
+//!
+//! Functions:
+//! - get_connectivity
+//! - check_connectivity
+use super::devices::ConnectionItem;
+use super::{create_client, NetworkResponse};
+use eyre::Result;
+use libc::{AF_INET, AF_INET6};
+use nm::{ActiveConnectionExt, ConnectivityState, DeviceExt, Metered};
+use serde::{Deserialize, Serialize};
+
+const CONNECTIVITY_STATES: &[(ConnectivityState, &str)] = &[
+    (ConnectivityState::Unknown, "unknown"),
+    (ConnectivityState::None, "none"),
+    (ConnectivityState::Portal, "portal"),
+    (ConnectivityState::Limited, "limited"),
+    (ConnectivityState::Full, "full"),
+];
+
+const METERED_STATES: &[(Metered, &str)] = &[
+    (Metered::Unknown, "unknown"),
+    (Metered::Yes, "yes"),
+    (Metered::No, "no"),
+    (Metered::GuessYes, "guess-yes"),
+    (Metered::GuessNo, "guess-no"),
+];
+
+/// The IPv4 and IPv6 connectivity of a device
+///
+/// The states are `unknown`, `none`, `portal`, `limited` or `full`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct DeviceConnectivity {
+    pub device: String,
+    pub ip4: String,
+    pub ip6: String,
+}
+
+/// The global connectivity
+///
+/// * `state`: `unknown`, `none`, `portal` (behind a captive portal),
+///   `limited` (no route to the Internet) or `full`.
+/// * `check_enabled`: whether NetworkManager checks the connectivity.
+/// * `primary_connection`: the active connection with the default route.
+/// * `metered`: `unknown`, `yes`, `no`, `guess-yes` or `guess-no`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ConnectivityInfo {
+    pub state: String,
+    pub check_enabled: bool,
+    pub primary_connection: Option<ConnectionItem>,
+    pub metered: String,
+    pub devices: Vec<DeviceConnectivity>,
+}
+
+fn connectivity_name(state: ConnectivityState) -> String {
+    CONNECTIVITY_STATES
+        .iter()
+        .find(|(x, _)| *x == state)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| "unknown".into())
+}
+
+fn metered_name(metered: Metered) -> String {
+    METERED_STATES
+        .iter()
+        .find(|(x, _)| *x == metered)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| "unknown".into())
+}
+
+fn read_connectivity(client: &nm::Client, state: ConnectivityState) -> ConnectivityInfo {
+    let primary_connection = client.primary_connection().map(|x| ConnectionItem {
+        id: x.id().map(|x| x.to_string()),
+        uuid: x.uuid().map(|x| x.to_string()),
+    });
+    let devices = client
+        .devices()
+        .iter()
+        .filter(|x| x.is_real())
+        .filter_map(|device| {
+            Some(DeviceConnectivity {
+                device: device.iface()?.to_string(),
+                ip4: connectivity_name(device.connectivity(AF_INET)),
+                ip6: connectivity_name(device.connectivity(AF_INET6)),
+            })
+        })
+        .collect();
+    ConnectivityInfo {
+        state: connectivity_name(state),
+        check_enabled: client.is_connectivity_check_enabled(),
+        primary_connection,
+        metered: metered_name(client.metered()),
+        devices,
+    }
+}
+
+/// Get the connectivity of the last check.
+pub async fn get_connectivity() -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let info = read_connectivity(&client, client.connectivity());
+    Ok(NetworkResponse::Connectivity(info))
+}
+
+/// Check the connectivity again and wait for the result. The current state
+/// is returned as is if the check is disabled.
+pub async fn check_connectivity() -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let state = client.check_connectivity_future().await?;
+    let info = read_connectivity(&client, state);
+    Ok(NetworkResponse::Connectivity(info))
+}
@@ -2,22 +2,24 @@
 //!
 //! The module is used to provide the api about network devices for
 //! the NetworkManager.
+use super::wireguard::{wireguard_peer_stats, WireGuardPeerStats};
 use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
 use crate::utils::nm_display;
 use crate::{net::NetInfo, utils::get_dev_id_path};
 use eyre::Result;
-use nm::{ActiveConnectionExt, ConnectionExt, Device};
-use serde::Serialize;
+use nm::{ActiveConnectionExt, Client, ConnectionExt, Device, DeviceType};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-#[derive(Clone, Default, Debug, Serialize)]
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct ConnectionItem {
     pub id: Option<String>,
     pub uuid: Option<String>,
 }
 
 /// The network device structure
-#[derive(Clone, Default, Debug, Serialize)]
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct NetDevice {
     /// The network interface name, e.g. ens3, eth0
     pub name: String,
@@ -44,6 +46,12 @@ pub struct NetDevice {
     pub id_path: Option<String>,
     pub product_name: Option<String>,
     pub net_link_modes: Vec<String>,
+    /// The interface name of the bond or bridge the device is a port of
+    #[serde(default)]
+    pub controller: Option<String>,
+    /// The peer statistics of a WireGuard device
+    #[serde(default)]
+    pub wireguard_peers: Vec<WireGuardPeerStats>,
 }
 
 fn get_latest_connection(
@@ -73,113 +81,171 @@ pub fn get_managed_status(device: &Device) -> bool {
 /// The function shows all information about the interfaces, including interface
 /// name, device_type, associated connection names and ip addresses.
 ///
+/// The software devices, e.g. bridges or dummy devices, are listed too. The
+/// placeholders of the profiles whose devices are not created yet are not.
+///
 /// The returned result is not user friendly, high layer application should
 /// convert the result by themselfs.
 pub async fn list_ether_devices(link_modes: Arc<serde_json::Value>) -> Result<NetworkResponse> {
     use nm::DeviceExt;
     let client = create_client().await?;
 
-    let mut devices: Vec<NetDevice> = client
-        .devices()
-        .into_iter()
-        .map(|device| {
-            let mut net_dev = NetDevice::default();
-            if let Some(interface) = device.interface() {
-                if let Some(mac) = device.hw_address() {
-                    let state = nm_display(device.state());
-                    let is_managed = device.is_managed();
-                    let iface_clone = interface.clone();
-                    let device_type = nm_display(device.device_type());
-                    let product_name = Some(format!(
-                        "{} {}",
-                        device.vendor().map(|x| x.to_string()).unwrap_or_default(),
-                        device.product().map(|x| x.to_string()).unwrap_or_default()
-                    ));
-                    let conn = device
-                        .available_connections()
-                        .into_iter()
-                        .map(|x| x.uuid().map(|x| x.to_string()))
-                        .filter_map(|x| x)
-                        .collect();
-                    let connection = device
-                        .active_connection()
-                        .map(|x| {
-                            let id = x.id().map(|x| x.to_string());
-                            let uuid = x.uuid().map(|x| x.to_string());
-                            ConnectionItem { id, uuid }
-                        })
-                        .unwrap_or(
-                            get_latest_connection(
-                                &mut client
-                                    .connections()
-                                    .into_iter()
-                                    .filter_map(|x| {
-                                        if x.interface_name().as_ref() == Some(&iface_clone) {
-                                            Some(x)
-                                        } else {
-                                            None
-                                        }
-                                    })
-                                    .collect::<Vec<nm::RemoteConnection>>(),
-                            )
-                            .and_then(|y| {
-                                let id = y.id().map(|x| x.to_string());
-                                let uuid = y.uuid().map(|x| x.to_string());
-                                Some(ConnectionItem { id, uuid })
-                            })
-                            .unwrap_or_default(),
-                        );
-                    let ip4info = device
-                        .ip4_config()
-                        .map(|x| NetInfo::try_from(x).and_then(|x| Ok(x)).ok())
-                        .unwrap_or(None);
-                    let ip6info = device
-                        .ip6_config()
-                        .map(|x| NetInfo::try_from(x).and_then(|x| Ok(x)).ok())
-                        .unwrap_or(None);
-                    let dev_path = device.udi().map(|x| x.to_string());
-                    let dev_path_str = dev_path.as_ref().map(|x| x.as_str());
-                    let id_path = get_dev_id_path(dev_path_str);
-                    let driver = device.driver().map(|x| x.to_string());
-                    let net_link_modes: Vec<String> = link_modes[interface.to_string()]
-                        .as_array()
-                        .and_then(|x| {
-                            Some(
-                                x.iter()
-                                    .filter_map(|x| x.as_str().map(|x| x.to_string()))
-                                    .collect(),
-                            )
-                        })
-                        .unwrap_or(vec![]);
-                    net_dev = NetDevice {
-                        name: interface.to_string(),
-                        connection,
-                        ip4info,
-                        state,
-                        r#virtual: device.is_software(),
-                        is_managed,
-                        ip6info,
-                        driver,
-                        dev_path,
-                        id_path,
-                        device_type,
-                        product_name,
-                        mac: mac.to_string(),
-                        conn,
-                        net_link_modes,
-                    }
-                }
-            }
-            net_dev
+    let mut devices = vec![];
+    for device in client.devices().iter().filter(|device| device.is_real()) {
+        if let Some(mut net_dev) = build_net_device(&client, device, &link_modes) {
+            read_wireguard_stats(&mut net_dev, device).await;
+            devices.push(net_dev);
+        }
+    }
+    Ok(NetworkResponse::Devices(devices))
+}
+
+/// Get the network device with the interface name.
+///
+/// The device is built in the same way as `list_ether_devices`, but the
+/// other devices are not touched.
+pub async fn get_device(
+    device_name: String,
+    link_modes: Arc<serde_json::Value>,
+) -> Result<NetworkResponse> {
+    use nm::DeviceExt;
+    let client = create_client().await?;
+
+    let found = client
+        .device_by_iface(&device_name)
+        .filter(|device| device.is_real())
+        .and_then(|device| Some((build_net_device(&client, &device, &link_modes)?, device)));
+    match found {
+        Some((mut net_dev, device)) => {
+            read_wireguard_stats(&mut net_dev, &device).await;
+            Ok(NetworkResponse::Device(net_dev))
+        }
+        None => bail!(NetworkError::NotFound(format!(
+            "The given network device {} is not found.",
+            device_name
+        ))),
+    }
+}
+
+/// The link modes of the interface gathered by `gather_link_modes`.
+pub(crate) fn device_link_modes(link_modes: &serde_json::Value, interface: &str) -> Vec<String> {
+    link_modes[interface]
+        .as_array()
+        .and_then(|x| {
+            Some(
+                x.iter()
+                    .filter_map(|x| x.as_str().map(|x| x.to_string()))
+                    .collect(),
+            )
         })
-        .collect();
+        .unwrap_or(vec![])
+}
+
+/// Read the peer statistics of a WireGuard device, they are read apart from
+/// `build_net_device` as they take a subprocess.
+async fn read_wireguard_stats(net_dev: &mut NetDevice, device: &Device) {
+    use nm::DeviceExt;
+    if device.device_type() == DeviceType::WireGuard {
+        net_dev.wireguard_peers = wireguard_peer_stats(&net_dev.name).await;
+    }
+}
 
-    devices = devices
+/// Collect the information of a network device, the device without an
+/// interface name is skipped.
+///
+/// The software devices, e.g. dummy devices or ip tunnels, may have no mac
+/// address or udev path, they are reported all the same.
+fn build_net_device(
+    client: &Client,
+    device: &Device,
+    link_modes: &serde_json::Value,
+) -> Option<NetDevice> {
+    use nm::DeviceExt;
+    let interface = device.interface()?;
+    let mac = device
+        .hw_address()
+        .map(|x| x.to_string())
+        .unwrap_or_default();
+    let state = nm_display(device.state());
+    let is_managed = device.is_managed();
+    let iface_clone = interface.clone();
+    let device_type = nm_display(device.device_type());
+    let product_name = Some(format!(
+        "{} {}",
+        device.vendor().map(|x| x.to_string()).unwrap_or_default(),
+        device.product().map(|x| x.to_string()).unwrap_or_default()
+    ));
+    let conn = device
+        .available_connections()
         .into_iter()
-        .filter_map(|x| if x.dev_path.is_some() { Some(x) } else { None })
+        .map(|x| x.uuid().map(|x| x.to_string()))
+        .filter_map(|x| x)
         .collect();
-    let value = serde_json::to_value(devices)?;
-    Ok(NetworkResponse::Return(value))
+    let connection = device
+        .active_connection()
+        .map(|x| {
+            let id = x.id().map(|x| x.to_string());
+            let uuid = x.uuid().map(|x| x.to_string());
+            ConnectionItem { id, uuid }
+        })
+        .unwrap_or(
+            get_latest_connection(
+                &mut client
+                    .connections()
+                    .into_iter()
+                    .filter_map(|x| {
+                        if x.interface_name().as_ref() == Some(&iface_clone) {
+                            Some(x)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<Vec<nm::RemoteConnection>>(),
+            )
+            .and_then(|y| {
+                let id = y.id().map(|x| x.to_string());
+                let uuid = y.uuid().map(|x| x.to_string());
+                Some(ConnectionItem { id, uuid })
+            })
+            .unwrap_or_default(),
+        );
+    let ip4info = device
+        .ip4_config()
+        .map(|x| NetInfo::try_from(x).and_then(|x| Ok(x)).ok())
+        .unwrap_or(None);
+    let ip6info = device
+        .ip6_config()
+        .map(|x| NetInfo::try_from(x).and_then(|x| Ok(x)).ok())
+        .unwrap_or(None);
+    let dev_path = device.udi().map(|x| x.to_string());
+    let dev_path_str = dev_path.as_ref().map(|x| x.as_str());
+    let id_path = get_dev_id_path(dev_path_str);
+    let driver = device.driver().map(|x| x.to_string());
+    let net_link_modes = device_link_modes(link_modes, &interface);
+    let controller = device
+        .active_connection()
+        .and_then(|x| x.master())
+        .and_then(|x| x.interface())
+        .map(|x| x.to_string());
+    Some(NetDevice {
+        name: interface.to_string(),
+        connection,
+        ip4info,
+        state,
+        r#virtual: device.is_software(),
+        is_managed,
+        ip6info,
+        driver,
+        dev_path,
+        id_path,
+        device_type,
+        product_name,
+        mac,
+        conn,
+        net_link_modes,
+        controller,
+        wireguard_peers: vec![],
+    })
 }
 
 /// Change the manage status for a network device.
@@ -209,7 +275,10 @@ pub async fn set_manage(device_name: String, is_managed: bool) -> Result<Network
         //     }
         // }
     } else {
-        bail!("The given network device is not found.")
+        bail!(NetworkError::NotFound(format!(
+            "The given network device {} is not found.",
+            device_name
+        )))
     }
     Ok(NetworkResponse::Success)
 }
@@ -9,8 +9,7 @@ use eyre::Result;
 pub async fn get_hostname() -> Result<NetworkResponse> {
     let client = create_client().await?;
     let hostname = client.hostname().map(|x| x.to_string()).unwrap_or_default();
-    let ret = serde_json::to_value(hostname)?;
-    Ok(NetworkResponse::Return(ret))
+    Ok(NetworkResponse::Hostname(hostname))
 }
 
 pub async fn set_hostname(hostname: String) -> Result<NetworkResponse> {
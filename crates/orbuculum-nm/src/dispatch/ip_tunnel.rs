@@ -0,0 +1,298 @@
+//! IP Tunnel Module
+//!
+//! The module provides the api about IP tunnel connections, e.g. GRE, IPIP
+//! and SIT tunnels, for the NetworkManager.
+//!
+//! Functions:
+//! - create_ip_tunnel
+//! - update_ip_tunnel
+//! - get_ip_tunnel
+use super::connections::{add_connection, get_ip_config, set_ip_configs};
+use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
+use crate::net::NetInfo;
+use eyre::Result;
+use glib::translate::IntoGlib;
+use glib::IsA;
+use nm::{
+    ConnectionExt, IPTunnelMode, SettingConnection, SettingIPTunnel, SimpleConnection,
+    SETTING_IP_TUNNEL_SETTING_NAME,
+};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
+
+/// The tunnel modes, their names in `IpTunnelConnection`, and whether the
+/// outer addresses are IPv6 ones
+const TUNNEL_MODES: &[(IPTunnelMode, &str, bool)] = &[
+    (IPTunnelMode::Ipip, "ipip", false),
+    (IPTunnelMode::Gre, "gre", false),
+    (IPTunnelMode::Sit, "sit", false),
+    (IPTunnelMode::Isatap, "isatap", false),
+    (IPTunnelMode::Vti, "vti", false),
+    (IPTunnelMode::Gretap, "gretap", false),
+    (IPTunnelMode::Ip6ip6, "ip6ip6", true),
+    (IPTunnelMode::Ipip6, "ipip6", true),
+    (IPTunnelMode::Ip6gre, "ip6gre", true),
+    (IPTunnelMode::Vti6, "vti6", true),
+    (IPTunnelMode::Ip6gretap, "ip6gretap", true),
+];
+
+/// The modes which support the key
+const KEYED_MODES: &[&str] = &["gre", "gretap", "ip6gre", "ip6gretap"];
+
+const MAX_TTL: u32 = 255;
+
+/// The IP tunnel connection
+///
+/// * `interface`: the tunnel interface name, e.g. `gre1`.
+/// * `mode`: one of `ipip`, `gre`, `sit`, `isatap`, `vti`, `gretap`,
+///   `ip6ip6`, `ipip6`, `ip6gre`, `vti6` and `ip6gretap`.
+/// * `local` and `remote`: the outer addresses, IPv6 ones for the `ip6*` and
+///   `ipip6` modes, and IPv4 ones for the others.
+/// * `ttl`: the TTL of the tunneled packets, 0 means inherit.
+/// * `key`: the input and output key of the GRE modes, a 32 bits number or
+///   an IPv4 address like one.
+///
+/// The `uuid` is ignored when the connection is created. The options not
+/// given are left unchanged by an update.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct IpTunnelConnection {
+    pub name: String,
+    #[serde(default)]
+    pub uuid: String,
+    pub interface: String,
+    pub mode: String,
+    #[serde(default)]
+    pub local: Option<IpAddr>,
+    pub remote: Option<IpAddr>,
+    #[serde(default)]
+    pub ttl: Option<u32>,
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub ip4info: Option<NetInfo>,
+    #[serde(default)]
+    pub ip6info: Option<NetInfo>,
+}
+
+impl IpTunnelConnection {
+    /// Check the tunnel options before they are sent to NetworkManager.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.interface.is_empty() {
+            bail!(NetworkError::InvalidArgument(
+                "Both the connection name and the interface are required".into()
+            ))
+        }
+        let (_, mode, ipv6) = tunnel_mode(&self.mode)?;
+        let remote = match self.remote {
+            Some(remote) => remote,
+            None => bail!(NetworkError::InvalidArgument(
+                "The remote address of the tunnel is required".into()
+            )),
+        };
+        for addr in self.local.iter().chain([remote].iter()) {
+            if addr.is_ipv6() != ipv6 {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "The address {} doesn't match the tunnel mode {}",
+                    addr, mode
+                )))
+            }
+        }
+        if let Some(ttl) = self.ttl {
+            if ttl > MAX_TTL {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "Invalid TTL {}, expected 0 to {}",
+                    ttl, MAX_TTL
+                )))
+            }
+        }
+        if let Some(key) = self.key.as_deref() {
+            if !KEYED_MODES.contains(&mode) {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "The key is not supported by the tunnel mode {}",
+                    mode
+                )))
+            }
+            if key.parse::<u32>().is_err() && key.parse::<Ipv4Addr>().is_err() {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "Invalid tunnel key {}, expected a 32 bits number",
+                    key
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn tunnel_mode(name: &str) -> Result<(IPTunnelMode, &'static str, bool)> {
+    match TUNNEL_MODES.iter().find(|(_, x, _)| *x == name) {
+        Some(mode) => Ok(*mode),
+        None => bail!(NetworkError::InvalidArgument(format!(
+            "Invalid tunnel mode {}, expected one of {}",
+            name,
+            TUNNEL_MODES
+                .iter()
+                .map(|(_, x, _)| *x)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// Write the tunnel settings which are given into the connection.
+fn write_tunnel_settings(
+    tunnel: &IpTunnelConnection,
+    connection: &impl IsA<nm::Connection>,
+) -> Result<()> {
+    let setting = match connection.setting_ip_tunnel() {
+        Some(setting) => setting,
+        None => {
+            let setting = SettingIPTunnel::new();
+            connection.add_setting(setting.clone());
+            setting
+        }
+    };
+    let (mode, _, _) = tunnel_mode(&tunnel.mode)?;
+    setting.set_mode(mode.into_glib() as u32);
+    if let Some(local) = tunnel.local {
+        setting.set_local(Some(&local.to_string()));
+    }
+    if let Some(remote) = tunnel.remote {
+        setting.set_remote(Some(&remote.to_string()));
+    }
+    if let Some(ttl) = tunnel.ttl {
+        setting.set_ttl(ttl);
+    }
+    if let Some(key) = tunnel.key.as_deref() {
+        setting.set_input_key(Some(key));
+        setting.set_output_key(Some(key));
+    }
+    Ok(())
+}
+
+fn read_ip_tunnel(nm_connection: &nm::RemoteConnection) -> Option<IpTunnelConnection> {
+    let setting = nm_connection.setting_ip_tunnel()?;
+    let mode = setting.mode();
+    let mode = TUNNEL_MODES
+        .iter()
+        .find(|(x, _, _)| x.into_glib() as u32 == mode)
+        .map(|(_, x, _)| x.to_string())
+        .unwrap_or_default();
+    Some(IpTunnelConnection {
+        name: nm_connection.id()?.to_string(),
+        uuid: nm_connection.uuid()?.to_string(),
+        interface: nm_connection.interface_name()?.to_string(),
+        mode,
+        local: setting.local().and_then(|x| x.parse().ok()),
+        remote: setting.remote().and_then(|x| x.parse().ok()),
+        ttl: Some(setting.ttl()),
+        key: setting.input_key().map(|x| x.to_string()),
+        ip4info: get_ip_config(nm_connection, 4).ok(),
+        ip6info: get_ip_config(nm_connection, 6).ok(),
+    })
+}
+
+/// Find the IP tunnel connection with the uuid.
+fn ip_tunnel_connection(client: &nm::Client, uuid: &str) -> Result<nm::RemoteConnection> {
+    match client.connection_by_uuid(uuid) {
+        Some(connection) if connection.setting_ip_tunnel().is_some() => Ok(connection),
+        _ => bail!(NetworkError::NotFound(format!(
+            "IP tunnel connection {} not found",
+            uuid
+        ))),
+    }
+}
+
+fn ip_tunnel_response(nm_connection: &nm::RemoteConnection) -> Result<NetworkResponse> {
+    let tunnel = read_ip_tunnel(nm_connection).ok_or_else(|| {
+        NetworkError::Backend(format!(
+            "Failed to read the IP tunnel connection {}",
+            nm_connection.uuid().unwrap_or_default()
+        ))
+    })?;
+    Ok(NetworkResponse::IpTunnel(tunnel))
+}
+
+/// Create an IP tunnel connection.
+pub async fn create_ip_tunnel(tunnel: IpTunnelConnection) -> Result<NetworkResponse> {
+    tunnel.validate()?;
+    let client = create_client().await?;
+
+    let connection = SimpleConnection::new();
+    let s_connection = SettingConnection::new();
+    s_connection.set_type(Some(&SETTING_IP_TUNNEL_SETTING_NAME));
+    s_connection.set_id(Some(&tunnel.name));
+    s_connection.set_interface_name(Some(&tunnel.interface));
+    s_connection.set_autoconnect(true);
+    connection.add_setting(s_connection);
+    write_tunnel_settings(&tunnel, &connection)?;
+    set_ip_configs(
+        &connection,
+        tunnel.ip4info.as_ref(),
+        tunnel.ip6info.as_ref(),
+    )?;
+
+    let uuid = add_connection(&client, &connection, &tunnel.name).await?;
+    Ok(NetworkResponse::Created { uuid })
+}
+
+/// Update the IP tunnel connection with the uuid, and read it again.
+pub async fn update_ip_tunnel(tunnel: IpTunnelConnection) -> Result<NetworkResponse> {
+    tunnel.validate()?;
+    let client = create_client().await?;
+    let nm_connection = ip_tunnel_connection(&client, &tunnel.uuid)?;
+
+    if let Some(setting) = nm_connection.setting_connection() {
+        setting.set_id(Some(&tunnel.name));
+        setting.set_interface_name(Some(&tunnel.interface));
+    }
+    write_tunnel_settings(&tunnel, &nm_connection)?;
+    set_ip_configs(
+        &nm_connection,
+        tunnel.ip4info.as_ref(),
+        tunnel.ip6info.as_ref(),
+    )?;
+    nm_connection.commit_changes_future(true).await?;
+    ip_tunnel_response(&nm_connection)
+}
+
+/// Get the IP tunnel connection with the uuid.
+pub async fn get_ip_tunnel(uuid: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = ip_tunnel_connection(&client, &uuid)?;
+    ip_tunnel_response(&nm_connection)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    fn tunnel(mode: &str, remote: &str) -> IpTunnelConnection {
+        IpTunnelConnection {
+            name: "tunnel".into(),
+            interface: "tun1".into(),
+            mode: mode.into(),
+            remote: Some(remote.parse().unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(tunnel("gre", "203.0.113.1"), true)]
+    #[case(tunnel("ip6gre", "2001:db8::1"), true)]
+    #[case(tunnel("gre", "2001:db8::1"), false)]
+    #[case(tunnel("ipip6", "2001:db8::1"), true)]
+    #[case(tunnel("l2tp", "203.0.113.1"), false)]
+    #[case(IpTunnelConnection { remote: None, ..tunnel("sit", "203.0.113.1") }, false)]
+    #[case(IpTunnelConnection { local: Some("2001:db8::2".parse().unwrap()), ..tunnel("sit", "203.0.113.1") }, false)]
+    #[case(IpTunnelConnection { ttl: Some(64), ..tunnel("ipip", "203.0.113.1") }, true)]
+    #[case(IpTunnelConnection { ttl: Some(256), ..tunnel("ipip", "203.0.113.1") }, false)]
+    #[case(IpTunnelConnection { key: Some("42".into()), ..tunnel("gre", "203.0.113.1") }, true)]
+    #[case(IpTunnelConnection { key: Some("0.0.0.42".into()), ..tunnel("gretap", "203.0.113.1") }, true)]
+    #[case(IpTunnelConnection { key: Some("secret".into()), ..tunnel("gre", "203.0.113.1") }, false)]
+    #[case(IpTunnelConnection { key: Some("42".into()), ..tunnel("ipip", "203.0.113.1") }, false)]
+    fn test_validate(#[case] tunnel: IpTunnelConnection, #[case] valid: bool) {
+        assert_eq!(tunnel.validate().is_ok(), valid);
+    }
+}
@@ -0,0 +1,120 @@
+//! Keyfile Module
+//!
+//! The module exports the connections as NetworkManager keyfiles, the format
+//! of `/etc/NetworkManager/system-connections`, and imports them back. The
+//! secrets are left out of the export unless they are requested.
+//!
+//! Functions:
+//! - export_keyfiles
+//! - import_keyfile
+use super::connections::add_connection;
+use super::settings::verify_error;
+use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
+use eyre::Result;
+use glib::{Cast, KeyFile, KeyFileFlags};
+use nm::{ConnectionExt, KeyfileHandlerFlags, SettingExt, SimpleConnection};
+use serde::{Deserialize, Serialize};
+
+/// The directory the relative paths in a keyfile are resolved against, e.g.
+/// the certificates of 802.1X.
+const KEYFILE_DIR: &str = "/etc/NetworkManager/system-connections";
+
+/// The keyfile of a connection
+///
+/// * `name`: the connection name, the file is usually saved as
+///   `<name>.nmconnection`.
+/// * `content`: the keyfile text.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Keyfile {
+    pub name: String,
+    pub uuid: String,
+    pub content: String,
+}
+
+/// The copy of the connection to export, the secrets are fetched from
+/// NetworkManager if they are requested, and cleared otherwise.
+async fn export_connection(
+    remote: &nm::RemoteConnection,
+    include_secrets: bool,
+) -> Result<nm::Connection> {
+    let connection = SimpleConnection::new_clone(remote);
+    connection.clear_secrets();
+    if include_secrets {
+        for setting in connection.settings() {
+            let name = match setting.name() {
+                Some(name) => name,
+                None => continue,
+            };
+            // Only some settings have secrets, the others answer an error.
+            if let Ok(secrets) = remote.secrets_future(&name).await {
+                connection.update_secrets(Some(&name), &secrets)?;
+            }
+        }
+    }
+    Ok(connection)
+}
+
+/// Export the connection with the uuid, or all the connections if it is
+/// `None`, as keyfiles.
+pub async fn export_keyfiles(
+    uuid: Option<String>,
+    include_secrets: bool,
+) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let remotes = match uuid {
+        Some(uuid) => match client.connection_by_uuid(&uuid) {
+            Some(remote) => vec![remote],
+            None => bail!(NetworkError::NotFound(format!(
+                "Connection {} not found",
+                uuid
+            ))),
+        },
+        None => client.connections(),
+    };
+    let mut keyfiles = vec![];
+    for remote in remotes.iter() {
+        let connection = export_connection(remote, include_secrets).await?;
+        let keyfile = nm::keyfile_write(&connection, KeyfileHandlerFlags::NONE, None)?;
+        keyfiles.push(Keyfile {
+            name: remote.id().map(|x| x.to_string()).unwrap_or_default(),
+            uuid: remote.uuid().map(|x| x.to_string()).unwrap_or_default(),
+            content: keyfile.to_data().to_string(),
+        });
+    }
+    Ok(NetworkResponse::Keyfiles(keyfiles))
+}
+
+/// Import the keyfile text, the connection with the same uuid is replaced,
+/// otherwise a new connection is added. The uuid is returned.
+pub async fn import_keyfile(content: String) -> Result<NetworkResponse> {
+    let keyfile = KeyFile::new();
+    keyfile
+        .load_from_data(&content, KeyFileFlags::NONE)
+        .map_err(|err| NetworkError::InvalidArgument(format!("Invalid keyfile: {}", err)))?;
+    let connection = nm::keyfile_read(&keyfile, KEYFILE_DIR, KeyfileHandlerFlags::NONE, None)
+        .map_err(|err| NetworkError::InvalidArgument(format!("Invalid keyfile: {}", err)))?;
+    if let Err(err) = connection.verify() {
+        bail!(NetworkError::invalid_settings(vec![verify_error(
+            err.message()
+        )]))
+    }
+    let uuid = connection.uuid().map(|x| x.to_string()).unwrap_or_default();
+    let name = connection.id().map(|x| x.to_string()).unwrap_or_default();
+
+    let client = create_client().await?;
+    match client.connection_by_uuid(&uuid) {
+        Some(current) => {
+            current.replace_settings_from_connection(&connection);
+            current.commit_changes_future(true).await?;
+            Ok(NetworkResponse::Created { uuid })
+        }
+        None => {
+            let connection = connection.downcast::<SimpleConnection>().map_err(|_| {
+                NetworkError::Backend("The keyfile is not read as a simple connection".into())
+            })?;
+            let uuid = add_connection(&client, &connection, &name).await?;
+            Ok(NetworkResponse::Created { uuid })
+        }
+    }
+}
@@ -6,67 +6,267 @@
 //!
 //! The list of dispatch routers is in `dispatch_command_requests` function.
 //!
-//! All the NetworkManager core functions are in the sub modules.
+//! Every command is answered by the `NetworkBackend` given to the dispatch
+//! routers. The NetworkManager core functions used by `LibnmBackend` are in the
+//! sub modules.
 //!
 //! Sub Modules:
+//! - `active_connections`: lists the active connections with their runtime
+//!   addresses.
 //! - `devices`: provides functions related to network hardware device.
 //!     - List all network devices and their mac addresses.
+//! - `bridge`: provides functions related to bridge connections and their ports.
+//! - `connectivity`: reports whether the host is online.
 //! - `connections`: provides functions related to nm connections.
 //!     - List all connections.
 //!     - Create a new wired connection.
+//! - `bond`: provides functions related to bond connections and their ports.
+//! - `checkpoint`: the checkpoints which roll back the changes unless they are
+//!   confirmed.
 //! - `hostname`: provides functions related to the hostname.
+//! - `ip_tunnel`: provides functions related to IP tunnel connections.
+//! - `ports`: the port connections shared by bonds and bridges.
+//! - `keyfile`: exports and imports the connections as keyfiles.
 //! - `networking`: Control whether overall networking is enabled or disabled.
+//! - `settings`: creates or replaces a connection of any supported type from a
+//!   settings document.
+//! - `signals`: forwards the NetworkManager signals as `NetworkEvent`s.
+//! - `virtual_devices`: creates and deletes the macvlan, ipvlan, dummy and veth
+//!   devices.
+//! - `vlan`: provides functions related to VLAN connections.
+//! - `vxlan`: provides functions related to VXLAN connections.
+//! - `wired`: the link settings of wired connections.
+//! - `wireguard`: provides functions related to WireGuard connections.
+pub mod active_connections;
+pub mod bond;
+pub mod bridge;
+pub mod checkpoint;
 pub mod connections;
+pub mod connectivity;
 pub mod devices;
 pub mod hostname;
+pub mod ip_tunnel;
 pub mod ipconfigs;
+pub mod keyfile;
 pub mod networking;
-use self::connections::get_connection;
-use self::hostname::{get_hostname, set_hostname};
-use self::networking::{get_networking, set_networking};
+pub mod ports;
+pub mod settings;
+pub mod signals;
+pub mod virtual_devices;
+pub mod vlan;
+pub mod vxlan;
+pub mod wired;
+pub mod wireguard;
 
 use super::{NetworkCommand, NetworkRequest, NetworkResponse, TokioResponder};
-use connections::{
-    create_wired_connection, delete_connection, list_connections, reactive_connection,
-    rename_connection, update_connection,
-};
-use devices::{list_ether_devices, set_manage};
-use eyre::{Result, WrapErr};
+use crate::backend::NetworkBackend;
+use crate::error::NetworkError;
+use eyre::Result;
 use glib::ControlFlow;
 use glib::MainContext;
 use nm::Client;
-use serde_json::Value;
 use std::future::Future;
-use std::sync::Arc;
+use std::rc::Rc;
 
 /// Define the dispatch routers
-pub fn dispatch_command_requests(
+pub fn dispatch_command_requests<B: NetworkBackend + 'static>(
     command_request: NetworkRequest,
-    link_modes: Arc<Value>,
+    backend: Rc<B>,
 ) -> ControlFlow {
     let NetworkRequest { responder, command } = command_request;
     match command {
-        NetworkCommand::ListDeivces => spawn(list_ether_devices(link_modes), responder),
-        NetworkCommand::SetNetworking(state) => spawn(set_networking(state), responder),
-        NetworkCommand::GetNetworking => spawn(get_networking(), responder),
-        NetworkCommand::GetConnection(uuid) => spawn(get_connection(uuid), responder),
-        NetworkCommand::Reactive(uuid) => spawn(reactive_connection(uuid), responder),
-        NetworkCommand::GetHostname => spawn(get_hostname(), responder),
-        NetworkCommand::SetHostname(hostname) => spawn(set_hostname(hostname), responder),
-        NetworkCommand::UpdateConnection(connection) => {
-            spawn(update_connection(connection), responder)
-        }
-        NetworkCommand::RenameConnection(uuid, new_name) => {
-            spawn(rename_connection(uuid, new_name), responder)
-        }
-        NetworkCommand::CreateWiredConnection(conn, device) => {
-            spawn(create_wired_connection(conn, device), responder)
-        }
-        NetworkCommand::ListConnections => spawn(list_connections(), responder),
-        NetworkCommand::DeleteConnection(conn) => spawn(delete_connection(conn), responder),
-        NetworkCommand::SetManage(device_name, is_managed) => {
-            spawn(set_manage(device_name, is_managed), responder)
+        NetworkCommand::ListDeivces => {
+            spawn(async move { backend.list_devices().await }, responder)
         }
+        NetworkCommand::GetDevice(device_name) => spawn(
+            async move { backend.get_device(device_name).await },
+            responder,
+        ),
+        NetworkCommand::SetNetworking(state) => spawn(
+            async move { backend.set_networking(state).await },
+            responder,
+        ),
+        NetworkCommand::GetNetworking => {
+            spawn(async move { backend.get_networking().await }, responder)
+        }
+        NetworkCommand::GetConnectivity => {
+            spawn(async move { backend.get_connectivity().await }, responder)
+        }
+        NetworkCommand::CheckConnectivity => {
+            spawn(async move { backend.check_connectivity().await }, responder)
+        }
+        NetworkCommand::GetConnection(uuid) => {
+            spawn(async move { backend.get_connection(uuid).await }, responder)
+        }
+        NetworkCommand::Reactive(uuid) => spawn(
+            async move { backend.reactive_connection(uuid).await },
+            responder,
+        ),
+        NetworkCommand::GetHostname => {
+            spawn(async move { backend.get_hostname().await }, responder)
+        }
+        NetworkCommand::SetHostname(hostname) => spawn(
+            async move { backend.set_hostname(hostname).await },
+            responder,
+        ),
+        NetworkCommand::UpdateConnection(connection) => spawn(
+            async move { backend.update_connection(connection).await },
+            responder,
+        ),
+        NetworkCommand::RenameConnection(uuid, new_name) => spawn(
+            async move { backend.rename_connection(uuid, new_name).await },
+            responder,
+        ),
+        NetworkCommand::AddRoute(uuid, route) => spawn(
+            async move { backend.add_route(uuid, route).await },
+            responder,
+        ),
+        NetworkCommand::RemoveRoute(uuid, route) => spawn(
+            async move { backend.remove_route(uuid, route).await },
+            responder,
+        ),
+        NetworkCommand::CreateWiredConnection(conn, device) => spawn(
+            async move { backend.create_wired_connection(conn, device).await },
+            responder,
+        ),
+        NetworkCommand::ListConnections => {
+            spawn(async move { backend.list_connections().await }, responder)
+        }
+        NetworkCommand::PutConnectionSettings(document) => spawn(
+            async move { backend.put_connection_settings(document).await },
+            responder,
+        ),
+        NetworkCommand::ExportKeyfiles(uuid, include_secrets) => spawn(
+            async move { backend.export_keyfiles(uuid, include_secrets).await },
+            responder,
+        ),
+        NetworkCommand::ImportKeyfile(content) => spawn(
+            async move { backend.import_keyfile(content).await },
+            responder,
+        ),
+        NetworkCommand::ImportLegacyConfig(format, name, content) => spawn(
+            async move { backend.import_legacy_config(format, name, content).await },
+            responder,
+        ),
+        NetworkCommand::ShowNetworkState => {
+            spawn(async move { backend.show_network_state().await }, responder)
+        }
+        NetworkCommand::ApplyNetworkState(state, dry_run) => spawn(
+            async move { backend.apply_network_state(state, dry_run).await },
+            responder,
+        ),
+        NetworkCommand::ListActiveConnections => spawn(
+            async move { backend.list_active_connections().await },
+            responder,
+        ),
+        NetworkCommand::DeleteConnection(conn) => spawn(
+            async move { backend.delete_connection(conn).await },
+            responder,
+        ),
+        NetworkCommand::DeleteConnectionByUuid(uuid) => spawn(
+            async move { backend.delete_connection_by_uuid(uuid).await },
+            responder,
+        ),
+        NetworkCommand::SetManage(device_name, is_managed) => spawn(
+            async move { backend.set_manage(device_name, is_managed).await },
+            responder,
+        ),
+        NetworkCommand::CreateVlan(vlan) => {
+            spawn(async move { backend.create_vlan(vlan).await }, responder)
+        }
+        NetworkCommand::UpdateVlan(vlan) => {
+            spawn(async move { backend.update_vlan(vlan).await }, responder)
+        }
+        NetworkCommand::GetVlan(uuid) => {
+            spawn(async move { backend.get_vlan(uuid).await }, responder)
+        }
+        NetworkCommand::DeleteVlan(uuid) => {
+            spawn(async move { backend.delete_vlan(uuid).await }, responder)
+        }
+        NetworkCommand::CreateBond(bond) => {
+            spawn(async move { backend.create_bond(bond).await }, responder)
+        }
+        NetworkCommand::GetBond(uuid) => {
+            spawn(async move { backend.get_bond(uuid).await }, responder)
+        }
+        NetworkCommand::AttachBondPort(uuid, device) => spawn(
+            async move { backend.attach_bond_port(uuid, device).await },
+            responder,
+        ),
+        NetworkCommand::DetachBondPort(uuid, device) => spawn(
+            async move { backend.detach_bond_port(uuid, device).await },
+            responder,
+        ),
+        NetworkCommand::CreateBridge(bridge) => spawn(
+            async move { backend.create_bridge(bridge).await },
+            responder,
+        ),
+        NetworkCommand::UpdateBridge(bridge) => spawn(
+            async move { backend.update_bridge(bridge).await },
+            responder,
+        ),
+        NetworkCommand::GetBridge(uuid) => {
+            spawn(async move { backend.get_bridge(uuid).await }, responder)
+        }
+        NetworkCommand::AttachBridgePort(uuid, port, migrate_ip) => spawn(
+            async move { backend.attach_bridge_port(uuid, port, migrate_ip).await },
+            responder,
+        ),
+        NetworkCommand::DetachBridgePort(uuid, device) => spawn(
+            async move { backend.detach_bridge_port(uuid, device).await },
+            responder,
+        ),
+        NetworkCommand::CreateWireGuard(wireguard) => spawn(
+            async move { backend.create_wireguard(wireguard).await },
+            responder,
+        ),
+        NetworkCommand::UpdateWireGuard(wireguard) => spawn(
+            async move { backend.update_wireguard(wireguard).await },
+            responder,
+        ),
+        NetworkCommand::GetWireGuard(uuid) => {
+            spawn(async move { backend.get_wireguard(uuid).await }, responder)
+        }
+        NetworkCommand::CreateIpTunnel(tunnel) => spawn(
+            async move { backend.create_ip_tunnel(tunnel).await },
+            responder,
+        ),
+        NetworkCommand::UpdateIpTunnel(tunnel) => spawn(
+            async move { backend.update_ip_tunnel(tunnel).await },
+            responder,
+        ),
+        NetworkCommand::GetIpTunnel(uuid) => {
+            spawn(async move { backend.get_ip_tunnel(uuid).await }, responder)
+        }
+        NetworkCommand::CreateVxlan(vxlan) => {
+            spawn(async move { backend.create_vxlan(vxlan).await }, responder)
+        }
+        NetworkCommand::UpdateVxlan(vxlan) => {
+            spawn(async move { backend.update_vxlan(vxlan).await }, responder)
+        }
+        NetworkCommand::GetVxlan(uuid) => {
+            spawn(async move { backend.get_vxlan(uuid).await }, responder)
+        }
+        NetworkCommand::CreateVirtualDevice(device) => spawn(
+            async move { backend.create_virtual_device(device).await },
+            responder,
+        ),
+        NetworkCommand::DeleteVirtualDevice(interface) => spawn(
+            async move { backend.delete_virtual_device(interface).await },
+            responder,
+        ),
+        NetworkCommand::BeginCheckpoint(devices, rollback_timeout) => spawn(
+            async move { backend.begin_checkpoint(devices, rollback_timeout).await },
+            responder,
+        ),
+        NetworkCommand::ConfirmCheckpoint(id) => spawn(
+            async move { backend.confirm_checkpoint(id).await },
+            responder,
+        ),
+        NetworkCommand::RollbackCheckpoint(id) => spawn(
+            async move { backend.rollback_checkpoint(id).await },
+            responder,
+        ),
     };
     ControlFlow::Continue
 }
@@ -91,12 +291,14 @@ async fn execute_and_respond(
 /// Create the NetworkManager client in async way.
 /// If there is no NetworkManager daemon running, it will throw an error.
 async fn create_client() -> Result<Client> {
-    let client = Client::new_future()
-        .await
-        .context("Failed to create NetworkManager client")?;
+    let client = Client::new_future().await.map_err(|err| {
+        NetworkError::NmUnavailable(format!("Failed to create NetworkManager client: {}", err))
+    })?;
 
     if !client.is_nm_running() {
-        return Err(anyhow!("NetworkManager daemon is not running"));
+        bail!(NetworkError::NmUnavailable(
+            "NetworkManager daemon is not running".into()
+        ));
     }
 
     Ok(client)
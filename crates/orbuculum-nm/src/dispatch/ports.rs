@@ -0,0 +1,134 @@
+//! Ports Module
+//!
+//! The module provides the functions shared by the controller connections,
+//! e.g. bonds and bridges. A port is an ethernet connection whose controller
+//! is given by the interface name of the bond or bridge.
+use super::connections::add_connection;
+use crate::error::NetworkError;
+use eyre::Result;
+use nm::{
+    ConnectionExt, DeviceExt, DeviceType, SettingConnection, SimpleConnection,
+    SETTING_WIRED_SETTING_NAME,
+};
+
+/// Whether the connection is a port of the given type, whose controller is
+/// the interface name or the uuid.
+pub(crate) fn is_port_of(
+    connection: &nm::RemoteConnection,
+    port_type: &str,
+    interface: &str,
+    uuid: &str,
+) -> bool {
+    connection
+        .setting_connection()
+        .map(|x| {
+            x.slave_type().as_deref() == Some(port_type)
+                && matches!(x.master().as_deref(), Some(master) if master == interface || master == uuid)
+        })
+        .unwrap_or(false)
+}
+
+/// The port connections of the controller.
+pub(crate) fn port_connections(
+    client: &nm::Client,
+    port_type: &str,
+    interface: &str,
+    uuid: &str,
+) -> Vec<nm::RemoteConnection> {
+    client
+        .connections()
+        .into_iter()
+        .filter(|x| is_port_of(x, port_type, interface, uuid))
+        .collect()
+}
+
+/// Find the ethernet device which could be attached to a controller, the
+/// device which is already a port is refused.
+pub(crate) fn port_device(client: &nm::Client, device_name: &str) -> Result<nm::Device> {
+    let device = client.device_by_iface(device_name).ok_or_else(|| {
+        NetworkError::NotFound(format!(
+            "The given network device {} is not found.",
+            device_name
+        ))
+    })?;
+    if device.device_type() != DeviceType::Ethernet {
+        bail!(NetworkError::InvalidArgument(format!(
+            "The device {} is not an ethernet device",
+            device_name
+        )))
+    }
+    let is_port = client.connections().iter().any(|x| {
+        x.interface_name().as_deref() == Some(device_name)
+            && x.setting_connection()
+                .and_then(|x| x.slave_type())
+                .is_some()
+    });
+    if is_port {
+        bail!(NetworkError::Conflict(format!(
+            "The device {} is already a port",
+            device_name
+        )))
+    }
+    Ok(device)
+}
+
+/// Build the port connection of the device, the name of it is
+/// `<controller>-port-<device>`.
+pub(crate) fn port_connection(
+    controller: &str,
+    port_type: &str,
+    device_name: &str,
+) -> SimpleConnection {
+    let connection = SimpleConnection::new();
+    let s_connection = SettingConnection::new();
+    s_connection.set_type(Some(&SETTING_WIRED_SETTING_NAME));
+    s_connection.set_id(Some(&format!("{}-port-{}", controller, device_name)));
+    s_connection.set_interface_name(Some(device_name));
+    s_connection.set_master(Some(controller));
+    s_connection.set_slave_type(Some(port_type));
+    s_connection.set_autoconnect(true);
+    connection.add_setting(s_connection);
+    connection
+}
+
+/// Add the port connection and activate it on the device, the controller is
+/// activated by NetworkManager if it is not active.
+pub(crate) async fn activate_port(
+    client: &nm::Client,
+    connection: &SimpleConnection,
+    device: &nm::Device,
+) -> Result<()> {
+    let name = connection.id().map(|x| x.to_string()).unwrap_or_default();
+    let uuid = add_connection(client, connection, &name).await?;
+    if let Some(port) = client.connection_by_uuid(&uuid) {
+        client
+            .activate_connection_future(Some(&port), Some(device), None)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Delete the port connections of the device, the device is refused if it is
+/// not a port of the controller.
+pub(crate) async fn delete_ports(
+    client: &nm::Client,
+    port_type: &str,
+    interface: &str,
+    uuid: &str,
+    device_name: &str,
+) -> Result<()> {
+    let ports: Vec<nm::RemoteConnection> = port_connections(client, port_type, interface, uuid)
+        .into_iter()
+        .filter(|x| x.interface_name().as_deref() == Some(device_name))
+        .collect();
+    if ports.is_empty() {
+        bail!(NetworkError::NotFound(format!(
+            "The device {} is not a port of {}",
+            device_name, interface
+        )))
+    }
+    for port in ports {
+        port.delete_future().await?;
+    }
+    Ok(())
+}
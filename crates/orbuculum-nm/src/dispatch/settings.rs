@@ -0,0 +1,515 @@
+//! Settings Module
+//!
+//! The module creates or replaces a connection of any supported type from a
+//! settings document, which has the setting and property layout of
+//! NetworkManager, e.g.
+//!
+//! ```json
+//! {
+//!     "connection": {"id": "lan", "type": "vlan", "interface-name": "eth0.10"},
+//!     "vlan": {"id": 10, "parent": "eth0"},
+//!     "ipv4": {"method": "manual", "addresses": ["192.0.2.10/24"]},
+//!     "ipv6": {"method": "disabled"}
+//! }
+//! ```
+//!
+//! The properties are the GObject properties of the settings. The enums are
+//! given by their numbers or nicks, and the flags by their numbers. The
+//! `addresses` of `ipv4` and `ipv6` are `address/prefix` strings, and their
+//! `routes` are the route objects of the route api, the family is optional.
+//!
+//! Functions:
+//! - put_connection_settings
+use super::connections::{add_connection, ipnet2ipaddr, route_to_iproute};
+use super::{create_client, NetworkResponse};
+use crate::error::{NetworkError, PropertyError};
+use crate::net::Route;
+use eyre::Result;
+use glib::{
+    Cast, EnumClass, FlagsClass, ObjectExt, ParamFlags, ParamSpec, ParamSpecInt, ParamSpecInt64,
+    ParamSpecUInt, ParamSpecUInt64, StaticType, ToValue, Type,
+};
+use ipnet::IpNet;
+use nm::{
+    ConnectionExt, SettingBond, SettingBondPort, SettingBridge, SettingBridgePort,
+    SettingConnection, SettingDummy, SettingIP4Config, SettingIP6Config, SettingIPConfig,
+    SettingIPConfigExt, SettingIPTunnel, SettingMacvlan, SettingVeth, SettingVlan, SettingVxlan,
+    SettingWireGuard, SettingWired, SimpleConnection,
+};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The settings by name, and their properties by name
+pub type SettingsDocument = BTreeMap<String, BTreeMap<String, Value>>;
+
+/// The settings which could be given in a document
+const SETTING_NAMES: &[&str] = &[
+    "connection",
+    "802-3-ethernet",
+    "vlan",
+    "bond",
+    "bond-port",
+    "bridge",
+    "bridge-port",
+    "ipv4",
+    "ipv6",
+    "ip-tunnel",
+    "vxlan",
+    "macvlan",
+    "veth",
+    "dummy",
+    "wireguard",
+];
+
+/// The supported values of `connection.type`
+const CONNECTION_TYPES: &[&str] = &[
+    "802-3-ethernet",
+    "vlan",
+    "bond",
+    "bridge",
+    "ip-tunnel",
+    "vxlan",
+    "macvlan",
+    "veth",
+    "dummy",
+    "wireguard",
+];
+
+fn property_error(setting: &str, property: Option<&str>, message: &str) -> PropertyError {
+    PropertyError {
+        setting: setting.to_string(),
+        property: property.map(|x| x.to_string()),
+        message: message.to_string(),
+    }
+}
+
+/// The family of the ip setting, `None` for the other settings.
+pub(crate) fn ip_family(setting: &str) -> Option<i32> {
+    match setting {
+        "ipv4" => Some(4),
+        "ipv6" => Some(6),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_addresses(family: i32, value: &Value) -> Result<Vec<IpNet>, String> {
+    let items = value
+        .as_array()
+        .ok_or_else(|| "expected a list of address/prefix strings".to_string())?;
+    let mut addresses = vec![];
+    for item in items {
+        let address: IpNet = item
+            .as_str()
+            .and_then(|x| x.parse().ok())
+            .ok_or_else(|| format!("invalid address {}", item))?;
+        let is_ipv6 = matches!(address, IpNet::V6(_));
+        if is_ipv6 != (family == 6) {
+            return Err(format!(
+                "the address {} is not an IPv{} one",
+                address, family
+            ));
+        }
+        addresses.push(address);
+    }
+    Ok(addresses)
+}
+
+fn parse_routes(family: i32, value: &Value) -> Result<Vec<Route>, String> {
+    let items = value
+        .as_array()
+        .ok_or_else(|| "expected a list of routes".to_string())?;
+    let mut routes = vec![];
+    for item in items {
+        let mut item = item.clone();
+        if let Some(route) = item.as_object_mut() {
+            route.entry("family").or_insert_with(|| family.into());
+        }
+        let route: Route =
+            serde_json::from_value(item).map_err(|err| format!("invalid route: {}", err))?;
+        if route.dest.addr().is_ipv6() != (family == 6) {
+            return Err(format!(
+                "the route to {} is not an IPv{} one",
+                route.dest, family
+            ));
+        }
+        routes.push(route);
+    }
+    Ok(routes)
+}
+
+fn connection_property<'a>(document: &'a SettingsDocument, property: &str) -> Option<&'a str> {
+    document
+        .get("connection")
+        .and_then(|x| x.get(property))
+        .and_then(|x| x.as_str())
+        .filter(|x| !x.is_empty())
+}
+
+/// The uuid given in the document
+pub(crate) fn document_uuid(document: &SettingsDocument) -> Option<String> {
+    connection_property(document, "uuid").map(|x| x.to_string())
+}
+
+/// Check the parts of the document which don't need NetworkManager: the
+/// settings are supported, the connection id and type are given, and the
+/// addresses and routes are valid. All the errors found are returned.
+pub(crate) fn check_document(document: &SettingsDocument) -> Vec<PropertyError> {
+    let mut errors = vec![];
+    for name in document.keys() {
+        if !SETTING_NAMES.contains(&name.as_str()) {
+            errors.push(property_error(name, None, "unknown or unsupported setting"));
+        }
+    }
+    if !document.contains_key("connection") {
+        errors.push(property_error("connection", None, "setting is required"));
+    } else {
+        if connection_property(document, "id").is_none() {
+            errors.push(property_error(
+                "connection",
+                Some("id"),
+                "property is required",
+            ));
+        }
+        match connection_property(document, "type") {
+            Some(kind) if CONNECTION_TYPES.contains(&kind) => {}
+            Some(kind) => errors.push(property_error(
+                "connection",
+                Some("type"),
+                &format!("unsupported connection type {}", kind),
+            )),
+            None => errors.push(property_error(
+                "connection",
+                Some("type"),
+                "property is required",
+            )),
+        }
+    }
+    for (name, properties) in document.iter() {
+        let family = match ip_family(name) {
+            Some(family) => family,
+            None => continue,
+        };
+        if let Some(value) = properties.get("addresses") {
+            if let Err(message) = parse_addresses(family, value) {
+                errors.push(property_error(name, Some("addresses"), &message));
+            }
+        }
+        if let Some(value) = properties.get("routes") {
+            if let Err(message) = parse_routes(family, value) {
+                errors.push(property_error(name, Some("routes"), &message));
+            }
+        }
+    }
+    errors
+}
+
+/// Map a verify error of libnm, e.g. `vlan.parent: property is missing`, to
+/// the property error.
+pub(crate) fn verify_error(message: &str) -> PropertyError {
+    let (path, detail) = match message.split_once(": ") {
+        Some((path, detail)) if !path.contains(' ') => (path, detail),
+        _ => return property_error("connection", None, message),
+    };
+    match path.split_once('.') {
+        Some((setting, property)) => property_error(setting, Some(property), detail),
+        None => property_error(path, None, detail),
+    }
+}
+
+fn new_setting(name: &str) -> Option<nm::Setting> {
+    let setting: nm::Setting = match name {
+        "connection" => SettingConnection::new().upcast(),
+        "802-3-ethernet" => SettingWired::new().upcast(),
+        "vlan" => SettingVlan::new().upcast(),
+        "bond" => SettingBond::new().upcast(),
+        "bond-port" => SettingBondPort::new().upcast(),
+        "bridge" => SettingBridge::new().upcast(),
+        "bridge-port" => SettingBridgePort::new().upcast(),
+        "ipv4" => SettingIP4Config::new().upcast(),
+        "ipv6" => SettingIP6Config::new().upcast(),
+        "ip-tunnel" => SettingIPTunnel::new().upcast(),
+        "vxlan" => SettingVxlan::new().upcast(),
+        "macvlan" => SettingMacvlan::new().upcast(),
+        "veth" => SettingVeth::new().upcast(),
+        "dummy" => SettingDummy::new().upcast(),
+        "wireguard" => SettingWireGuard::new().upcast(),
+        _ => return None,
+    };
+    Some(setting)
+}
+
+/// Check the number is in the range of the property, glib panics when a
+/// value out of it is set.
+fn check_range<T: PartialOrd + std::fmt::Display>(
+    value: T,
+    minimum: T,
+    maximum: T,
+) -> Result<T, String> {
+    if value < minimum || value > maximum {
+        return Err(format!(
+            "the value {} is out of the range {} to {}",
+            value, minimum, maximum
+        ));
+    }
+    Ok(value)
+}
+
+/// Convert the JSON value to the type of the property, the numbers are
+/// checked with the range of the property.
+fn property_value(pspec: &ParamSpec, value: &Value) -> Result<glib::Value, String> {
+    let value_type = pspec.value_type();
+    let invalid = || format!("expected a value of type {}", value_type.name());
+    let number = |value: &Value| value.as_i64().or_else(|| value.as_u64().map(|x| x as i64));
+    if value_type == Type::BOOL {
+        Ok(value.as_bool().ok_or_else(invalid)?.to_value())
+    } else if value_type == Type::STRING {
+        match value {
+            Value::String(value) => Ok(value.to_value()),
+            Value::Null => Ok(None::<String>.to_value()),
+            _ => Err(invalid()),
+        }
+    } else if value_type == Type::I32 {
+        let value = number(value).and_then(|x| i32::try_from(x).ok());
+        let mut value = value.ok_or_else(invalid)?;
+        if let Some(pspec) = pspec.downcast_ref::<ParamSpecInt>() {
+            value = check_range(value, pspec.minimum(), pspec.maximum())?;
+        }
+        Ok(value.to_value())
+    } else if value_type == Type::U32 {
+        let value = number(value).and_then(|x| u32::try_from(x).ok());
+        let mut value = value.ok_or_else(invalid)?;
+        if let Some(pspec) = pspec.downcast_ref::<ParamSpecUInt>() {
+            value = check_range(value, pspec.minimum(), pspec.maximum())?;
+        }
+        Ok(value.to_value())
+    } else if value_type == Type::I64 {
+        let mut value = value.as_i64().ok_or_else(invalid)?;
+        if let Some(pspec) = pspec.downcast_ref::<ParamSpecInt64>() {
+            value = check_range(value, pspec.minimum(), pspec.maximum())?;
+        }
+        Ok(value.to_value())
+    } else if value_type == Type::U64 {
+        let mut value = value.as_u64().ok_or_else(invalid)?;
+        if let Some(pspec) = pspec.downcast_ref::<ParamSpecUInt64>() {
+            value = check_range(value, pspec.minimum(), pspec.maximum())?;
+        }
+        Ok(value.to_value())
+    } else if value_type == Vec::<String>::static_type() {
+        let items = value.as_array().ok_or_else(invalid)?;
+        let items: Option<Vec<String>> = items
+            .iter()
+            .map(|x| x.as_str().map(|x| x.to_string()))
+            .collect();
+        Ok(items.ok_or_else(invalid)?.to_value())
+    } else if value_type.is_a(Type::ENUM) {
+        let class = EnumClass::new(value_type).ok_or_else(invalid)?;
+        let number = match value {
+            Value::String(nick) => class.value_by_nick(nick).map(|x| x.value()),
+            _ => number(value).and_then(|x| i32::try_from(x).ok()),
+        };
+        let number = number.ok_or_else(invalid)?;
+        class.to_value(number).ok_or_else(invalid)
+    } else if value_type.is_a(Type::FLAGS) {
+        let class = FlagsClass::new(value_type).ok_or_else(invalid)?;
+        let number = number(value).and_then(|x| u32::try_from(x).ok());
+        class
+            .to_value(number.ok_or_else(invalid)?)
+            .ok_or_else(invalid)
+    } else {
+        Err(format!("unsupported property type {}", value_type.name()))
+    }
+}
+
+fn set_property(
+    setting: &nm::Setting,
+    setting_name: &str,
+    property: &str,
+    value: &Value,
+) -> Result<(), String> {
+    if let (Some(family), Some(ip_setting)) = (
+        ip_family(setting_name),
+        setting.downcast_ref::<SettingIPConfig>(),
+    ) {
+        if property == "addresses" {
+            for address in parse_addresses(family, value)? {
+                let address = ipnet2ipaddr(address).map_err(|err| err.to_string())?;
+                ip_setting.add_address(&address);
+            }
+            return Ok(());
+        }
+        if property == "routes" {
+            for route in parse_routes(family, value)? {
+                let route = route_to_iproute(route).map_err(|err| err.to_string())?;
+                ip_setting.add_route(&route);
+            }
+            return Ok(());
+        }
+    }
+    let pspec = setting
+        .find_property(property)
+        .filter(|x| x.flags().contains(ParamFlags::WRITABLE))
+        .ok_or_else(|| "unknown or read-only property".to_string())?;
+    let value = property_value(&pspec, value)?;
+    setting.set_property_from_value(property, &value);
+    Ok(())
+}
+
+/// Build the connection from the document, the errors of all the properties
+/// are collected.
+fn build_connection(document: &SettingsDocument) -> Result<SimpleConnection> {
+    let connection = SimpleConnection::new();
+    let mut errors = vec![];
+    for (name, properties) in document.iter() {
+        let setting = match new_setting(name) {
+            Some(setting) => setting,
+            None => {
+                errors.push(property_error(name, None, "unknown or unsupported setting"));
+                continue;
+            }
+        };
+        for (property, value) in properties.iter() {
+            if let Err(message) = set_property(&setting, name, property, value) {
+                errors.push(property_error(name, Some(property), &message));
+            }
+        }
+        connection.add_setting(setting);
+    }
+    if !errors.is_empty() {
+        bail!(NetworkError::invalid_settings(errors))
+    }
+    if let Err(err) = connection.verify() {
+        bail!(NetworkError::invalid_settings(vec![verify_error(
+            err.message()
+        )]))
+    }
+    Ok(connection)
+}
+
+/// Create a connection from the settings document, or replace the settings
+/// of the connection with `connection.uuid` if it exists. A uuid is
+/// generated if none is given. The uuid of the connection is returned.
+///
+/// The document is verified by libnm before it is sent, the errors are
+/// returned per property as `NetworkError::InvalidSettings`.
+pub async fn put_connection_settings(mut document: SettingsDocument) -> Result<NetworkResponse> {
+    let errors = check_document(&document);
+    if !errors.is_empty() {
+        bail!(NetworkError::invalid_settings(errors))
+    }
+    let client = create_client().await?;
+    let uuid = match document_uuid(&document) {
+        Some(uuid) => uuid,
+        None => {
+            let uuid = nm::utils_uuid_generate().to_string();
+            if let Some(properties) = document.get_mut("connection") {
+                properties.insert("uuid".into(), uuid.clone().into());
+            }
+            uuid
+        }
+    };
+    let connection = build_connection(&document)?;
+
+    match client.connection_by_uuid(&uuid) {
+        Some(current) => {
+            current.replace_settings_from_connection(&connection);
+            current.commit_changes_future(true).await?;
+            Ok(NetworkResponse::Created { uuid })
+        }
+        None => {
+            let name = connection_property(&document, "id").unwrap_or_default();
+            let uuid = add_connection(&client, &connection, name).await?;
+            Ok(NetworkResponse::Created { uuid })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn document(value: Value) -> SettingsDocument {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn invalid_properties(value: Value) -> Vec<(String, Option<String>)> {
+        check_document(&document(value))
+            .into_iter()
+            .map(|x| (x.setting, x.property))
+            .collect()
+    }
+
+    #[rstest]
+    #[case(json!({"connection": {"id": "lan", "type": "802-3-ethernet"}}), vec![])]
+    #[case(json!({"ipv4": {"method": "auto"}}), vec![("connection", None)])]
+    #[case(json!({"connection": {"type": "vlan"}}), vec![("connection", Some("id"))])]
+    #[case(json!({"connection": {"id": "lan", "type": "wifi"}}), vec![("connection", Some("type"))])]
+    #[case(json!({"connection": {"id": "lan", "type": "dummy"}, "team": {}}), vec![("team", None)])]
+    #[case(
+        json!({"connection": {"id": "lan", "type": "dummy"}, "ipv4": {"addresses": ["2001:db8::1/64"]}}),
+        vec![("ipv4", Some("addresses"))]
+    )]
+    #[case(
+        json!({"connection": {"id": "lan", "type": "dummy"}, "ipv6": {"addresses": ["2001:db8::1/64"]}}),
+        vec![]
+    )]
+    #[case(
+        json!({"connection": {"id": "lan", "type": "dummy"}, "ipv4": {"routes": [{"dest": "198.51.100.0/24", "next_hop": "192.0.2.1"}]}}),
+        vec![]
+    )]
+    #[case(
+        json!({"connection": {"id": "lan", "type": "dummy"}, "ipv4": {"routes": [{"dest": "2001:db8::/32"}]}}),
+        vec![("ipv4", Some("routes"))]
+    )]
+    fn test_check_document(#[case] value: Value, #[case] expected: Vec<(&str, Option<&str>)>) {
+        let expected: Vec<(String, Option<String>)> = expected
+            .into_iter()
+            .map(|(setting, property)| (setting.to_string(), property.map(|x| x.to_string())))
+            .collect();
+        assert_eq!(invalid_properties(value), expected);
+    }
+
+    #[rstest]
+    #[case(
+        "vlan.parent: property is missing",
+        "vlan",
+        Some("parent"),
+        "property is missing"
+    )]
+    #[case("ipv4: setting is required", "ipv4", None, "setting is required")]
+    #[case(
+        "the connection is invalid",
+        "connection",
+        None,
+        "the connection is invalid"
+    )]
+    fn test_verify_error(
+        #[case] message: &str,
+        #[case] setting: &str,
+        #[case] property: Option<&str>,
+        #[case] detail: &str,
+    ) {
+        assert_eq!(
+            verify_error(message),
+            property_error(setting, property, detail)
+        );
+    }
+
+    #[rstest]
+    #[case("vlan", "id", json!(10), true)]
+    #[case("vlan", "id", json!(5000), false)]
+    #[case("ip-tunnel", "ttl", json!(300), false)]
+    #[case("802-3-ethernet", "mtu", json!(-1), false)]
+    #[case("vlan", "flags", json!("reorder-headers"), false)]
+    fn test_set_property(
+        #[case] setting_name: &str,
+        #[case] property: &str,
+        #[case] value: Value,
+        #[case] is_valid: bool,
+    ) {
+        let setting = new_setting(setting_name).unwrap();
+        let result = set_property(&setting, setting_name, property, &value);
+        assert_eq!(result.is_ok(), is_valid, "{:?}", result);
+    }
+}
@@ -0,0 +1,170 @@
+//! Signal Module
+//!
+//! The module connects the NetworkManager client signals and forwards them
+//! as `NetworkEvent`s.
+use super::create_client;
+use crate::events::{publish, EventSender, NetworkEvent};
+use crate::utils::nm_display;
+use glib::{Cast, MainContext, ObjectExt, SignalHandlerId};
+use nm::{
+    Client, ConnectionExt, Device, DeviceEthernet, DeviceExt, ObjectExt as _, RemoteConnection,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The devices with their signal handlers by their D-Bus paths, the handlers
+/// are disconnected when the device is removed.
+type DeviceHandlers = Rc<RefCell<HashMap<String, (Device, Vec<SignalHandlerId>)>>>;
+
+/// Subscribe the NetworkManager signals in the current glib main context.
+///
+/// The client is kept alive by the spawned future, so the signals are
+/// received as long as the main context runs.
+pub fn subscribe_signals(events: EventSender) {
+    let context = MainContext::ref_thread_default();
+    context.spawn_local(async move {
+        let client = match create_client().await {
+            Ok(client) => client,
+            Err(err) => {
+                eprintln!("Failed to subscribe NetworkManager signals: {:#}", err);
+                return;
+            }
+        };
+        connect_client_signals(&client, events);
+        futures::future::pending::<()>().await;
+        drop(client);
+    });
+}
+
+fn connect_client_signals(client: &Client, events: EventSender) {
+    let handlers = DeviceHandlers::default();
+    for device in client.devices() {
+        connect_device_signals(&device, events.clone(), &handlers);
+    }
+    for connection in client.connections() {
+        connect_connection_signals(&connection, events.clone());
+    }
+
+    let sender = events.clone();
+    let added_handlers = handlers.clone();
+    client.connect_device_added(move |_, device| {
+        if let Some(device) = device.downcast_ref::<Device>() {
+            connect_device_signals(device, sender.clone(), &added_handlers);
+            publish(
+                &sender,
+                NetworkEvent::DeviceAdded {
+                    name: device_name(device),
+                },
+            );
+        }
+    });
+
+    let sender = events.clone();
+    client.connect_device_removed(move |_, device| {
+        if let Some(device) = device.downcast_ref::<Device>() {
+            disconnect_device_signals(device, &handlers);
+            publish(
+                &sender,
+                NetworkEvent::DeviceRemoved {
+                    name: device_name(device),
+                },
+            );
+        }
+    });
+
+    let sender = events.clone();
+    client.connect_connection_added(move |_, connection| {
+        connect_connection_signals(connection, sender.clone());
+        publish(
+            &sender,
+            connection_event(connection, ConnectionChange::Added),
+        );
+    });
+
+    let sender = events;
+    client.connect_connection_removed(move |_, connection| {
+        publish(
+            &sender,
+            connection_event(connection, ConnectionChange::Removed),
+        );
+    });
+}
+
+fn connect_device_signals(device: &Device, events: EventSender, handlers: &DeviceHandlers) {
+    // The handlers of a device added again are replaced
+    disconnect_device_signals(device, handlers);
+    let mut ids = vec![];
+    let sender = events.clone();
+    ids.push(
+        device.connect_state_changed(move |device, _new_state, _old_state, _reason| {
+            publish(
+                &sender,
+                NetworkEvent::DeviceStateChanged {
+                    name: device_name(device),
+                    state: nm_display(device.state()),
+                },
+            );
+        }),
+    );
+
+    if let Some(ethernet) = device.downcast_ref::<DeviceEthernet>() {
+        ids.push(ethernet.connect_carrier_notify(move |ethernet| {
+            publish(
+                &events,
+                NetworkEvent::CarrierChanged {
+                    name: device_name(ethernet.upcast_ref::<Device>()),
+                    carrier: ethernet.is_carrier(),
+                },
+            );
+        }));
+    }
+    handlers
+        .borrow_mut()
+        .insert(device_path(device), (device.clone(), ids));
+}
+
+fn disconnect_device_signals(device: &Device, handlers: &DeviceHandlers) {
+    let removed = handlers.borrow_mut().remove(&device_path(device));
+    if let Some((device, ids)) = removed {
+        for id in ids {
+            device.disconnect(id);
+        }
+    }
+}
+
+fn connect_connection_signals(connection: &RemoteConnection, events: EventSender) {
+    connection.connect_changed(move |connection| {
+        publish(
+            &events,
+            connection_event(connection, ConnectionChange::Changed),
+        );
+    });
+}
+
+fn device_path(device: &Device) -> String {
+    device.path().map(|x| x.to_string()).unwrap_or_default()
+}
+
+fn device_name(device: &Device) -> String {
+    device
+        .interface()
+        .map(|x| x.to_string())
+        .unwrap_or_default()
+}
+
+enum ConnectionChange {
+    Added,
+    Changed,
+    Removed,
+}
+
+fn connection_event(connection: &RemoteConnection, change: ConnectionChange) -> NetworkEvent {
+    let uuid = connection.uuid().map(|x| x.to_string()).unwrap_or_default();
+    let name = connection.id().map(|x| x.to_string()).unwrap_or_default();
+    match change {
+        ConnectionChange::Added => NetworkEvent::ConnectionAdded { uuid, name },
+        ConnectionChange::Changed => NetworkEvent::ConnectionChanged { uuid, name },
+        ConnectionChange::Removed => NetworkEvent::ConnectionRemoved { uuid, name },
+    }
+}
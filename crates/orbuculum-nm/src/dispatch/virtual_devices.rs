@@ -0,0 +1,395 @@
+//! Virtual Devices Module
+//!
+//! The module provides the api to create and delete the software devices,
+//! e.g. macvlan, ipvlan, dummy and veth devices, for the NetworkManager.
+//!
+//! The macvlan, dummy and veth devices are created by the NetworkManager
+//! profiles, so they are persistent. The libnm has no ipvlan setting before
+//! 1.52, the ipvlan devices are created by `ip link` instead, they are not
+//! managed by the profiles and don't persist across reboots. The command runs
+//! as a subprocess driven by the glib main loop, so it doesn't block the other
+//! requests.
+//!
+//! Functions:
+//! - create_virtual_device
+//! - delete_virtual_device
+use super::connections::{add_connection, set_ip_configs};
+use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
+use crate::net::NetInfo;
+use eyre::Result;
+use glib::translate::IntoGlib;
+use nm::{
+    ConnectionExt, SettingConnection, SettingDummy, SettingMacvlan, SettingMacvlanMode,
+    SettingVeth, SimpleConnection, SETTING_DUMMY_SETTING_NAME, SETTING_MACVLAN_SETTING_NAME,
+    SETTING_VETH_SETTING_NAME,
+};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+
+/// The kinds of the virtual devices
+const VIRTUAL_KINDS: &[&str] = &["macvlan", "ipvlan", "dummy", "veth"];
+
+/// The macvlan modes, and their names in `VirtualDevice`
+const MACVLAN_MODES: &[(SettingMacvlanMode, &str)] = &[
+    (SettingMacvlanMode::Vepa, "vepa"),
+    (SettingMacvlanMode::Bridge, "bridge"),
+    (SettingMacvlanMode::Private, "private"),
+    (SettingMacvlanMode::Passthru, "passthru"),
+    (SettingMacvlanMode::Source, "source"),
+];
+
+const IPVLAN_MODES: &[&str] = &["l2", "l3", "l3s"];
+
+/// The longest interface name of the kernel
+const MAX_INTERFACE_LEN: usize = 15;
+
+/// The virtual device
+///
+/// * `kind`: one of `macvlan`, `ipvlan`, `dummy` and `veth`.
+/// * `parent`: the parent device name of a macvlan or ipvlan device.
+/// * `mode`: the macvlan mode, one of `vepa`, `bridge`, `private`,
+///   `passthru` and `source`, it is `bridge` by default. Or the ipvlan mode,
+///   one of `l2`, `l3` and `l3s`, it is `l3` by default.
+/// * `peer`: the interface name of the other end of a veth pair.
+///
+/// The `uuid` is the uuid of the profile created, it is ignored when the
+/// device is created, and empty for an ipvlan device. The `name`,
+/// `ip4info` and `ip6info` are not supported by ipvlan devices.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct VirtualDevice {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub uuid: String,
+    pub interface: String,
+    pub kind: String,
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub mode: Option<String>,
+    #[serde(default)]
+    pub peer: Option<String>,
+    #[serde(default)]
+    pub ip4info: Option<NetInfo>,
+    #[serde(default)]
+    pub ip6info: Option<NetInfo>,
+}
+
+impl VirtualDevice {
+    /// Check the options of the device before it is created.
+    pub fn validate(&self) -> Result<()> {
+        check_interface(&self.interface)?;
+        if !VIRTUAL_KINDS.contains(&self.kind.as_str()) {
+            bail!(NetworkError::InvalidArgument(format!(
+                "Invalid virtual device kind {}, expected one of {}",
+                self.kind,
+                VIRTUAL_KINDS.join(", ")
+            )))
+        }
+        let needs_parent = matches!(self.kind.as_str(), "macvlan" | "ipvlan");
+        if needs_parent && self.parent.is_none() {
+            bail!(NetworkError::InvalidArgument(format!(
+                "The parent is required by {} devices",
+                self.kind
+            )))
+        }
+        if !needs_parent && self.parent.is_some() {
+            bail!(NetworkError::InvalidArgument(format!(
+                "The parent is not supported by {} devices",
+                self.kind
+            )))
+        }
+        if self.mode.is_some() && !needs_parent {
+            bail!(NetworkError::InvalidArgument(format!(
+                "The mode is not supported by {} devices",
+                self.kind
+            )))
+        }
+        self.device_mode()?;
+        match (self.kind.as_str(), self.peer.as_deref()) {
+            ("veth", Some(peer)) => {
+                check_interface(peer)?;
+                if peer == self.interface {
+                    bail!(NetworkError::InvalidArgument(
+                        "The veth peer must be different from the interface".into()
+                    ))
+                }
+            }
+            ("veth", None) => bail!(NetworkError::InvalidArgument(
+                "The peer is required by veth devices".into()
+            )),
+            (_, Some(_)) => bail!(NetworkError::InvalidArgument(format!(
+                "The peer is not supported by {} devices",
+                self.kind
+            ))),
+            (_, None) => {}
+        }
+        if self.kind == "ipvlan" {
+            if self.ip4info.is_some() || self.ip6info.is_some() {
+                bail!(NetworkError::InvalidArgument(
+                    "The ip configurations are not supported by ipvlan devices".into()
+                ))
+            }
+        } else if self.name.is_empty() {
+            bail!(NetworkError::InvalidArgument(
+                "The connection name is required".into()
+            ))
+        }
+        Ok(())
+    }
+
+    /// The mode of a macvlan or ipvlan device, the default one is used if
+    /// it is not given.
+    pub fn device_mode(&self) -> Result<Option<&str>> {
+        let (modes, default): (Vec<&str>, &str) = match self.kind.as_str() {
+            "macvlan" => (MACVLAN_MODES.iter().map(|(_, x)| *x).collect(), "bridge"),
+            "ipvlan" => (IPVLAN_MODES.to_vec(), "l3"),
+            _ => return Ok(None),
+        };
+        match self.mode.as_deref() {
+            None => Ok(Some(default)),
+            Some(mode) if modes.contains(&mode) => Ok(Some(mode)),
+            Some(mode) => bail!(NetworkError::InvalidArgument(format!(
+                "Invalid {} mode {}, expected one of {}",
+                self.kind,
+                mode,
+                modes.join(", ")
+            ))),
+        }
+    }
+}
+
+fn check_interface(interface: &str) -> Result<()> {
+    if interface.is_empty()
+        || interface.len() > MAX_INTERFACE_LEN
+        || interface.contains(|x: char| x == '/' || x.is_whitespace())
+    {
+        bail!(NetworkError::InvalidArgument(format!(
+            "Invalid interface name {}",
+            interface
+        )))
+    }
+    Ok(())
+}
+
+/// Build the profile of a macvlan, dummy or veth device.
+fn virtual_connection(device: &VirtualDevice) -> Result<SimpleConnection> {
+    let connection = SimpleConnection::new();
+    let s_connection = SettingConnection::new();
+    s_connection.set_id(Some(&device.name));
+    s_connection.set_interface_name(Some(&device.interface));
+    s_connection.set_autoconnect(true);
+    match device.kind.as_str() {
+        "macvlan" => {
+            s_connection.set_type(Some(&SETTING_MACVLAN_SETTING_NAME));
+            let setting = SettingMacvlan::new();
+            let mode = device.device_mode()?.unwrap_or_default();
+            if let Some((mode, _)) = MACVLAN_MODES.iter().find(|(_, x)| *x == mode) {
+                setting.set_mode(mode.into_glib() as u32);
+            }
+            setting.set_parent(device.parent.as_deref());
+            connection.add_setting(setting);
+        }
+        "veth" => {
+            s_connection.set_type(Some(&SETTING_VETH_SETTING_NAME));
+            let setting = SettingVeth::new();
+            setting.set_peer(device.peer.as_deref());
+            connection.add_setting(setting);
+        }
+        _ => {
+            s_connection.set_type(Some(&SETTING_DUMMY_SETTING_NAME));
+            connection.add_setting(SettingDummy::new());
+        }
+    }
+    connection.add_setting(s_connection);
+    set_ip_configs(
+        &connection,
+        device.ip4info.as_ref(),
+        device.ip6info.as_ref(),
+    )?;
+    Ok(connection)
+}
+
+/// Run the `ip` command, the error output is returned if it fails.
+async fn ip_command(args: &[&str]) -> Result<String> {
+    let argv: Vec<&OsStr> = std::iter::once("ip")
+        .chain(args.iter().copied())
+        .map(OsStr::new)
+        .collect();
+    let flags = gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_PIPE;
+    let process = gio::Subprocess::newv(&argv, flags)
+        .map_err(|e| NetworkError::Backend(format!("Failed to run ip: {}", e)))?;
+    let (stdout, stderr) = process
+        .communicate_utf8_future(None)
+        .await
+        .map_err(|e| NetworkError::Backend(format!("Failed to run ip: {}", e)))?;
+    if !process.is_successful() {
+        bail!(NetworkError::Backend(format!(
+            "Failed to run ip {}: {}",
+            args.join(" "),
+            stderr.as_deref().unwrap_or_default().trim()
+        )))
+    }
+    Ok(stdout.map(|x| x.to_string()).unwrap_or_default())
+}
+
+/// The link kind of the interface given by `ip -details link show`, e.g.
+/// `ipvlan`, it is `None` for the physical devices.
+async fn link_kind(interface: &str) -> Option<String> {
+    let output = ip_command(&["-json", "-details", "link", "show", "dev", interface])
+        .await
+        .ok()?;
+    let links: serde_json::Value = serde_json::from_str(&output).ok()?;
+    links[0]["linkinfo"]["info_kind"]
+        .as_str()
+        .map(|x| x.to_string())
+}
+
+/// Create the ipvlan device by `ip link`, and bring it up.
+async fn create_ipvlan(device: &VirtualDevice) -> Result<()> {
+    let parent = device.parent.as_deref().unwrap_or_default();
+    let mode = device.device_mode()?.unwrap_or_default();
+    ip_command(&[
+        "link",
+        "add",
+        "link",
+        parent,
+        "name",
+        &device.interface,
+        "type",
+        "ipvlan",
+        "mode",
+        mode,
+    ])
+    .await?;
+    ip_command(&["link", "set", "dev", &device.interface, "up"]).await?;
+    Ok(())
+}
+
+/// Create the virtual device, the device is returned with the uuid of the
+/// profile created.
+pub async fn create_virtual_device(device: VirtualDevice) -> Result<NetworkResponse> {
+    use nm::DeviceExt;
+    device.validate()?;
+    let client = create_client().await?;
+
+    for interface in [Some(&device.interface), device.peer.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        if client.device_by_iface(interface).is_some() {
+            bail!(NetworkError::Conflict(format!(
+                "The network device {} already exists",
+                interface
+            )))
+        }
+    }
+    if let Some(parent) = device.parent.as_deref() {
+        if client
+            .device_by_iface(parent)
+            .filter(|x| x.is_real())
+            .is_none()
+        {
+            bail!(NetworkError::NotFound(format!(
+                "The given network device {} is not found.",
+                parent
+            )))
+        }
+    }
+
+    let uuid = if device.kind == "ipvlan" {
+        create_ipvlan(&device).await?;
+        String::new()
+    } else {
+        let connection = virtual_connection(&device)?;
+        add_connection(&client, &connection, &device.name).await?
+    };
+    Ok(NetworkResponse::VirtualDevice(VirtualDevice {
+        uuid,
+        mode: device.device_mode()?.map(|x| x.to_string()),
+        ..device
+    }))
+}
+
+/// Delete the virtual device with the interface name.
+///
+/// The profiles of the device are deleted, the NetworkManager removes the
+/// device with them. The ipvlan device is deleted by `ip link`.
+pub async fn delete_virtual_device(interface: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let profiles: Vec<nm::RemoteConnection> = client
+        .connections()
+        .into_iter()
+        .filter(|x| {
+            x.interface_name().as_deref() == Some(&interface)
+                && x.connection_type().map_or(false, |x| {
+                    [
+                        SETTING_MACVLAN_SETTING_NAME,
+                        SETTING_DUMMY_SETTING_NAME,
+                        SETTING_VETH_SETTING_NAME,
+                    ]
+                    .contains(&x.as_str())
+                })
+        })
+        .collect();
+    if !profiles.is_empty() {
+        for profile in profiles {
+            profile.delete_future().await?;
+        }
+        return Ok(NetworkResponse::Success);
+    }
+    match link_kind(&interface).await.as_deref() {
+        Some("ipvlan") => {
+            ip_command(&["link", "delete", "dev", &interface]).await?;
+            Ok(NetworkResponse::Success)
+        }
+        _ => bail!(NetworkError::NotFound(format!(
+            "Virtual device {} not found",
+            interface
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    fn device(kind: &str) -> VirtualDevice {
+        VirtualDevice {
+            name: format!("{}0", kind),
+            interface: format!("{}0", kind),
+            kind: kind.into(),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(device("dummy"), true)]
+    #[case(device("tun"), false)]
+    #[case(VirtualDevice { interface: "a-very-long-interface".into(), ..device("dummy") }, false)]
+    #[case(VirtualDevice { parent: Some("eth0".into()), ..device("dummy") }, false)]
+    #[case(device("macvlan"), false)]
+    #[case(VirtualDevice { parent: Some("eth0".into()), ..device("macvlan") }, true)]
+    #[case(VirtualDevice { parent: Some("eth0".into()), mode: Some("private".into()), ..device("macvlan") }, true)]
+    #[case(VirtualDevice { parent: Some("eth0".into()), mode: Some("l2".into()), ..device("macvlan") }, false)]
+    #[case(VirtualDevice { parent: Some("eth0".into()), mode: Some("l3s".into()), ..device("ipvlan") }, true)]
+    #[case(VirtualDevice { parent: Some("eth0".into()), ip4info: Some(NetInfo::default()), ..device("ipvlan") }, false)]
+    #[case(device("veth"), false)]
+    #[case(VirtualDevice { peer: Some("veth1".into()), ..device("veth") }, true)]
+    #[case(VirtualDevice { peer: Some("veth0".into()), ..device("veth") }, false)]
+    #[case(VirtualDevice { peer: Some("veth1".into()), ..device("dummy") }, false)]
+    fn test_validate(#[case] device: VirtualDevice, #[case] valid: bool) {
+        assert_eq!(device.validate().is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case(device("macvlan"), Some("bridge"))]
+    #[case(device("ipvlan"), Some("l3"))]
+    #[case(VirtualDevice { mode: Some("l2".into()), ..device("ipvlan") }, Some("l2"))]
+    #[case(device("dummy"), None)]
+    fn test_device_mode(#[case] device: VirtualDevice, #[case] expected: Option<&str>) {
+        assert_eq!(device.device_mode().unwrap(), expected);
+    }
+}
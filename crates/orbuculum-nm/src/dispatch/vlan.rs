@@ -0,0 +1,321 @@
+//! Vlan Module
+//!
+//! The module provides the api about VLAN connections for the NetworkManager.
+//!
+//! Functions:
+//! - create_vlan
+//! - update_vlan
+//! - get_vlan
+//! - delete_vlan
+use super::connections::{add_connection, get_ip_config, set_ip_configs};
+use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
+use crate::net::NetInfo;
+use eyre::Result;
+use glib::IsA;
+use nm::{
+    ConnectionExt, SettingConnection, SettingVlan, SettingWired, SimpleConnection, VlanFlags,
+    VlanPriorityMap, SETTING_VLAN_SETTING_NAME,
+};
+use serde::{Deserialize, Serialize};
+
+/// The largest VLAN id
+const MAX_VLAN_ID: u32 = 4094;
+
+/// The VLAN flags, and their names in `VlanConnection`
+const VLAN_FLAGS: &[(VlanFlags, &str)] = &[
+    (VlanFlags::REORDER_HEADERS, "reorder-headers"),
+    (VlanFlags::GVRP, "gvrp"),
+    (VlanFlags::LOOSE_BINDING, "loose-binding"),
+    (VlanFlags::MVRP, "mvrp"),
+];
+
+/// The VLAN connection
+///
+/// * `parent`: the parent device name or mac address.
+/// * `interface`: the VLAN interface name, it is `<parent>.<id>` by default,
+///   or `vlan<id>` if the parent is a mac address.
+/// * `flags`: the names of the VLAN flags, e.g. `reorder-headers`.
+/// * `ingress_priority_map` and `egress_priority_map`: the priority mappings
+///   in the form of `from:to`, e.g. `2:3`.
+///
+/// The `uuid` is ignored when the connection is created. The `ip4info` and
+/// `ip6info` are always given when the connection is read, and are left
+/// unchanged when they are not given to an update.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct VlanConnection {
+    pub name: String,
+    #[serde(default)]
+    pub uuid: String,
+    #[serde(default)]
+    pub interface: Option<String>,
+    pub parent: String,
+    pub id: u32,
+    #[serde(default)]
+    pub flags: Vec<String>,
+    #[serde(default)]
+    pub ingress_priority_map: Vec<String>,
+    #[serde(default)]
+    pub egress_priority_map: Vec<String>,
+    #[serde(default)]
+    pub ip4info: Option<NetInfo>,
+    #[serde(default)]
+    pub ip6info: Option<NetInfo>,
+}
+
+impl VlanConnection {
+    /// Check the VLAN settings before they are sent to NetworkManager.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.parent.is_empty() {
+            bail!(NetworkError::InvalidArgument(
+                "Both the connection name and the parent are required".into()
+            ))
+        }
+        if self.id > MAX_VLAN_ID {
+            bail!(NetworkError::InvalidArgument(format!(
+                "Invalid VLAN id {}, expected 0 to {}",
+                self.id, MAX_VLAN_ID
+            )))
+        }
+        vlan_flags_from_human(&self.flags)?;
+        for map in self
+            .ingress_priority_map
+            .iter()
+            .chain(self.egress_priority_map.iter())
+        {
+            if parse_priority(map).is_none() {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "Invalid VLAN priority mapping {}, expected from:to",
+                    map
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// The interface name of the VLAN device.
+    pub fn interface_name(&self) -> String {
+        match self.interface.as_deref() {
+            Some(interface) if !interface.is_empty() => interface.to_string(),
+            _ if is_mac(&self.parent) => format!("vlan{}", self.id),
+            _ => format!("{}.{}", self.parent, self.id),
+        }
+    }
+}
+
+fn is_mac(parent: &str) -> bool {
+    parent.contains(':')
+}
+
+fn parse_priority(map: &str) -> Option<(u32, u32)> {
+    let (from, to) = map.split_once(':')?;
+    Some((from.trim().parse().ok()?, to.trim().parse().ok()?))
+}
+
+fn vlan_flags_from_human(names: &[String]) -> Result<VlanFlags> {
+    let mut flags = VlanFlags::empty();
+    for name in names {
+        match VLAN_FLAGS.iter().find(|(_, x)| *x == name.as_str()) {
+            Some((flag, _)) => flags |= *flag,
+            None => bail!(NetworkError::InvalidArgument(format!(
+                "Invalid VLAN flag {}",
+                name
+            ))),
+        }
+    }
+    Ok(flags)
+}
+
+fn vlan_flags_to_human(flags: VlanFlags) -> Vec<String> {
+    VLAN_FLAGS
+        .iter()
+        .filter(|(x, _)| flags.contains(*x))
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+fn read_priorities(setting: &SettingVlan, map: VlanPriorityMap) -> Vec<String> {
+    (0..setting.num_priorities(map) as u32)
+        .filter_map(|i| setting.priority(map, i))
+        .map(|(from, to)| format!("{}:{}", from, to))
+        .collect()
+}
+
+/// Write the VLAN settings and the parent into the connection.
+fn write_vlan_settings(vlan: &VlanConnection, connection: &impl IsA<nm::Connection>) -> Result<()> {
+    let setting = match connection.setting_vlan() {
+        Some(setting) => setting,
+        None => {
+            let setting = SettingVlan::new();
+            connection.add_setting(setting.clone());
+            setting
+        }
+    };
+    setting.set_id(vlan.id);
+    setting.set_flags(vlan_flags_from_human(&vlan.flags)?);
+
+    // The parent is given by the interface name, or by the mac address of
+    // the wired setting.
+    if is_mac(&vlan.parent) {
+        setting.set_parent(None);
+        let wired = match connection.setting_wired() {
+            Some(wired) => wired,
+            None => {
+                let wired = SettingWired::new();
+                connection.add_setting(wired.clone());
+                wired
+            }
+        };
+        wired.set_mac_address(Some(&vlan.parent));
+    } else {
+        setting.set_parent(Some(&vlan.parent));
+        if let Some(wired) = connection.setting_wired() {
+            wired.set_mac_address(None);
+        }
+    }
+
+    for (map, priorities) in [
+        (VlanPriorityMap::IngressMap, &vlan.ingress_priority_map),
+        (VlanPriorityMap::EgressMap, &vlan.egress_priority_map),
+    ] {
+        setting.clear_priorities(map);
+        for priority in priorities.iter() {
+            if !setting.add_priority_str(map, priority) {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "Invalid VLAN priority mapping {}",
+                    priority
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_vlan(nm_connection: &nm::RemoteConnection) -> Option<VlanConnection> {
+    let setting = nm_connection.setting_vlan()?;
+    let parent = setting.parent().map(|x| x.to_string()).or_else(|| {
+        nm_connection
+            .setting_wired()
+            .and_then(|x| x.mac_address())
+            .map(|x| x.to_string())
+    });
+    Some(VlanConnection {
+        name: nm_connection.id()?.to_string(),
+        uuid: nm_connection.uuid()?.to_string(),
+        interface: nm_connection.interface_name().map(|x| x.to_string()),
+        parent: parent.unwrap_or_default(),
+        id: setting.id(),
+        flags: vlan_flags_to_human(VlanFlags::from_bits_truncate(setting.flags())),
+        ingress_priority_map: read_priorities(&setting, VlanPriorityMap::IngressMap),
+        egress_priority_map: read_priorities(&setting, VlanPriorityMap::EgressMap),
+        ip4info: get_ip_config(nm_connection, 4).ok(),
+        ip6info: get_ip_config(nm_connection, 6).ok(),
+    })
+}
+
+/// Find the VLAN connection with the uuid.
+fn vlan_connection(client: &nm::Client, uuid: &str) -> Result<nm::RemoteConnection> {
+    match client.connection_by_uuid(uuid) {
+        Some(connection) if connection.setting_vlan().is_some() => Ok(connection),
+        _ => bail!(NetworkError::NotFound(format!(
+            "VLAN connection {} not found",
+            uuid
+        ))),
+    }
+}
+
+/// Create a VLAN connection on the parent device.
+pub async fn create_vlan(vlan: VlanConnection) -> Result<NetworkResponse> {
+    vlan.validate()?;
+    let client = create_client().await?;
+
+    let connection = SimpleConnection::new();
+    let s_connection = SettingConnection::new();
+    s_connection.set_type(Some(&SETTING_VLAN_SETTING_NAME));
+    s_connection.set_id(Some(&vlan.name));
+    s_connection.set_interface_name(Some(&vlan.interface_name()));
+    s_connection.set_autoconnect(true);
+    connection.add_setting(s_connection);
+    write_vlan_settings(&vlan, &connection)?;
+    set_ip_configs(&connection, vlan.ip4info.as_ref(), vlan.ip6info.as_ref())?;
+
+    let uuid = add_connection(&client, &connection, &vlan.name).await?;
+    Ok(NetworkResponse::Created { uuid })
+}
+
+/// Update the VLAN connection with the uuid, and read it again.
+pub async fn update_vlan(vlan: VlanConnection) -> Result<NetworkResponse> {
+    vlan.validate()?;
+    let client = create_client().await?;
+    let nm_connection = vlan_connection(&client, &vlan.uuid)?;
+
+    if let Some(setting) = nm_connection.setting_connection() {
+        setting.set_id(Some(&vlan.name));
+        setting.set_interface_name(Some(&vlan.interface_name()));
+    }
+    write_vlan_settings(&vlan, &nm_connection)?;
+    set_ip_configs(&nm_connection, vlan.ip4info.as_ref(), vlan.ip6info.as_ref())?;
+    nm_connection.commit_changes_future(true).await?;
+
+    let vlan = read_vlan(&nm_connection).ok_or_else(|| {
+        NetworkError::Backend(format!(
+            "Failed to read the VLAN connection {} after updating",
+            vlan.uuid
+        ))
+    })?;
+    Ok(NetworkResponse::Vlan(vlan))
+}
+
+/// Get the VLAN connection with the uuid.
+pub async fn get_vlan(uuid: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = vlan_connection(&client, &uuid)?;
+    let vlan = read_vlan(&nm_connection).ok_or_else(|| {
+        NetworkError::Backend(format!("Failed to read the VLAN connection {}", uuid))
+    })?;
+    Ok(NetworkResponse::Vlan(vlan))
+}
+
+/// Delete the VLAN connection with the uuid, the other connections are
+/// refused.
+pub async fn delete_vlan(uuid: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = vlan_connection(&client, &uuid)?;
+    nm_connection.delete_future().await?;
+    Ok(NetworkResponse::Success)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    fn vlan(parent: &str, id: u32) -> VlanConnection {
+        VlanConnection {
+            name: "vlan".into(),
+            parent: parent.into(),
+            id,
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(vlan("eth0", 100), "eth0.100")]
+    #[case(vlan("52:54:00:12:34:56", 100), "vlan100")]
+    #[case(VlanConnection { interface: Some("mgmt".into()), ..vlan("eth0", 10) }, "mgmt")]
+    fn test_interface_name(#[case] vlan: VlanConnection, #[case] interface: &str) {
+        assert_eq!(vlan.interface_name(), interface);
+    }
+
+    #[rstest]
+    #[case(vlan("eth0", 100), true)]
+    #[case(vlan("eth0", 4095), false)]
+    #[case(vlan("", 100), false)]
+    #[case(VlanConnection { flags: vec!["gvrp".into(), "reorder-headers".into()], ..vlan("eth0", 10) }, true)]
+    #[case(VlanConnection { flags: vec!["tagged".into()], ..vlan("eth0", 10) }, false)]
+    #[case(VlanConnection { egress_priority_map: vec!["2:3".into()], ..vlan("eth0", 10) }, true)]
+    #[case(VlanConnection { ingress_priority_map: vec!["2-3".into()], ..vlan("eth0", 10) }, false)]
+    fn test_validate(#[case] vlan: VlanConnection, #[case] valid: bool) {
+        assert_eq!(vlan.validate().is_ok(), valid);
+    }
+}
@@ -0,0 +1,243 @@
+//! VXLAN Module
+//!
+//! The module provides the api about VXLAN connections for the
+//! NetworkManager.
+//!
+//! Functions:
+//! - create_vxlan
+//! - update_vxlan
+//! - get_vxlan
+use super::connections::{add_connection, get_ip_config, set_ip_configs};
+use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
+use crate::net::NetInfo;
+use eyre::Result;
+use glib::IsA;
+use nm::{
+    ConnectionExt, SettingConnection, SettingVxlan, SimpleConnection, SETTING_VXLAN_SETTING_NAME,
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// The largest VXLAN network identifier
+const MAX_VXLAN_ID: u32 = 16777215;
+
+const MAX_PORT: u32 = 65535;
+
+/// The VXLAN connection
+///
+/// * `interface`: the VXLAN interface name, e.g. `vxlan10`.
+/// * `id`: the VXLAN network identifier, 0 to 16777215.
+/// * `local`: the source address of the outgoing packets.
+/// * `remote`: the unicast address of the remote end, or the multicast group
+///   to join.
+/// * `destination_port`: the UDP port of the remote end, NetworkManager uses
+///   8472 if it is not given, the IANA one is 4789.
+/// * `parent`: the device name to send the packets through, it is required
+///   by a multicast group.
+///
+/// The `uuid` is ignored when the connection is created. The options not
+/// given are left unchanged by an update.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct VxlanConnection {
+    pub name: String,
+    #[serde(default)]
+    pub uuid: String,
+    pub interface: String,
+    pub id: u32,
+    #[serde(default)]
+    pub local: Option<IpAddr>,
+    pub remote: Option<IpAddr>,
+    #[serde(default)]
+    pub destination_port: Option<u32>,
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub ip4info: Option<NetInfo>,
+    #[serde(default)]
+    pub ip6info: Option<NetInfo>,
+}
+
+impl VxlanConnection {
+    /// Check the VXLAN options before they are sent to NetworkManager.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.interface.is_empty() {
+            bail!(NetworkError::InvalidArgument(
+                "Both the connection name and the interface are required".into()
+            ))
+        }
+        if self.id > MAX_VXLAN_ID {
+            bail!(NetworkError::InvalidArgument(format!(
+                "Invalid VXLAN id {}, expected 0 to {}",
+                self.id, MAX_VXLAN_ID
+            )))
+        }
+        let remote = match self.remote {
+            Some(remote) => remote,
+            None => bail!(NetworkError::InvalidArgument(
+                "The remote address or multicast group of the VXLAN is required".into()
+            )),
+        };
+        if let Some(local) = self.local {
+            if local.is_ipv6() != remote.is_ipv6() {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "The local address {} and the remote {} are of different families",
+                    local, remote
+                )))
+            }
+        }
+        if remote.is_multicast() && self.parent.as_deref().unwrap_or_default().is_empty() {
+            bail!(NetworkError::InvalidArgument(format!(
+                "The parent device is required by the multicast group {}",
+                remote
+            )))
+        }
+        if let Some(port) = self.destination_port {
+            if port == 0 || port > MAX_PORT {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "Invalid destination port {}, expected 1 to {}",
+                    port, MAX_PORT
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write the VXLAN settings which are given into the connection.
+fn write_vxlan_settings(vxlan: &VxlanConnection, connection: &impl IsA<nm::Connection>) {
+    let setting = match connection.setting_vxlan() {
+        Some(setting) => setting,
+        None => {
+            let setting = SettingVxlan::new();
+            connection.add_setting(setting.clone());
+            setting
+        }
+    };
+    setting.set_id(vxlan.id);
+    if let Some(local) = vxlan.local {
+        setting.set_local(Some(&local.to_string()));
+    }
+    if let Some(remote) = vxlan.remote {
+        setting.set_remote(Some(&remote.to_string()));
+    }
+    if let Some(port) = vxlan.destination_port {
+        setting.set_destination_port(port);
+    }
+    if let Some(parent) = vxlan.parent.as_deref() {
+        setting.set_parent(Some(parent));
+    }
+}
+
+fn read_vxlan(nm_connection: &nm::RemoteConnection) -> Option<VxlanConnection> {
+    let setting = nm_connection.setting_vxlan()?;
+    Some(VxlanConnection {
+        name: nm_connection.id()?.to_string(),
+        uuid: nm_connection.uuid()?.to_string(),
+        interface: nm_connection.interface_name()?.to_string(),
+        id: setting.id(),
+        local: setting.local().and_then(|x| x.parse().ok()),
+        remote: setting.remote().and_then(|x| x.parse().ok()),
+        destination_port: Some(setting.destination_port()),
+        parent: setting.parent().map(|x| x.to_string()),
+        ip4info: get_ip_config(nm_connection, 4).ok(),
+        ip6info: get_ip_config(nm_connection, 6).ok(),
+    })
+}
+
+/// Find the VXLAN connection with the uuid.
+fn vxlan_connection(client: &nm::Client, uuid: &str) -> Result<nm::RemoteConnection> {
+    match client.connection_by_uuid(uuid) {
+        Some(connection) if connection.setting_vxlan().is_some() => Ok(connection),
+        _ => bail!(NetworkError::NotFound(format!(
+            "VXLAN connection {} not found",
+            uuid
+        ))),
+    }
+}
+
+fn vxlan_response(nm_connection: &nm::RemoteConnection) -> Result<NetworkResponse> {
+    let vxlan = read_vxlan(nm_connection).ok_or_else(|| {
+        NetworkError::Backend(format!(
+            "Failed to read the VXLAN connection {}",
+            nm_connection.uuid().unwrap_or_default()
+        ))
+    })?;
+    Ok(NetworkResponse::Vxlan(vxlan))
+}
+
+/// Create a VXLAN connection.
+pub async fn create_vxlan(vxlan: VxlanConnection) -> Result<NetworkResponse> {
+    vxlan.validate()?;
+    let client = create_client().await?;
+
+    let connection = SimpleConnection::new();
+    let s_connection = SettingConnection::new();
+    s_connection.set_type(Some(&SETTING_VXLAN_SETTING_NAME));
+    s_connection.set_id(Some(&vxlan.name));
+    s_connection.set_interface_name(Some(&vxlan.interface));
+    s_connection.set_autoconnect(true);
+    connection.add_setting(s_connection);
+    write_vxlan_settings(&vxlan, &connection);
+    set_ip_configs(&connection, vxlan.ip4info.as_ref(), vxlan.ip6info.as_ref())?;
+
+    let uuid = add_connection(&client, &connection, &vxlan.name).await?;
+    Ok(NetworkResponse::Created { uuid })
+}
+
+/// Update the VXLAN connection with the uuid, and read it again.
+pub async fn update_vxlan(vxlan: VxlanConnection) -> Result<NetworkResponse> {
+    vxlan.validate()?;
+    let client = create_client().await?;
+    let nm_connection = vxlan_connection(&client, &vxlan.uuid)?;
+
+    if let Some(setting) = nm_connection.setting_connection() {
+        setting.set_id(Some(&vxlan.name));
+        setting.set_interface_name(Some(&vxlan.interface));
+    }
+    write_vxlan_settings(&vxlan, &nm_connection);
+    set_ip_configs(
+        &nm_connection,
+        vxlan.ip4info.as_ref(),
+        vxlan.ip6info.as_ref(),
+    )?;
+    nm_connection.commit_changes_future(true).await?;
+    vxlan_response(&nm_connection)
+}
+
+/// Get the VXLAN connection with the uuid.
+pub async fn get_vxlan(uuid: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = vxlan_connection(&client, &uuid)?;
+    vxlan_response(&nm_connection)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    fn vxlan(id: u32, remote: &str) -> VxlanConnection {
+        VxlanConnection {
+            name: "overlay".into(),
+            interface: "vxlan10".into(),
+            id,
+            remote: Some(remote.parse().unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(vxlan(10, "203.0.113.1"), true)]
+    #[case(vxlan(16777215, "2001:db8::1"), true)]
+    #[case(vxlan(16777216, "203.0.113.1"), false)]
+    #[case(VxlanConnection { remote: None, ..vxlan(10, "203.0.113.1") }, false)]
+    #[case(VxlanConnection { local: Some("2001:db8::2".parse().unwrap()), ..vxlan(10, "203.0.113.1") }, false)]
+    #[case(vxlan(10, "239.1.1.1"), false)]
+    #[case(VxlanConnection { parent: Some("eth0".into()), ..vxlan(10, "239.1.1.1") }, true)]
+    #[case(VxlanConnection { destination_port: Some(4789), ..vxlan(10, "203.0.113.1") }, true)]
+    #[case(VxlanConnection { destination_port: Some(0), ..vxlan(10, "203.0.113.1") }, false)]
+    fn test_validate(#[case] vxlan: VxlanConnection, #[case] valid: bool) {
+        assert_eq!(vxlan.validate().is_ok(), valid);
+    }
+}
@@ -0,0 +1,329 @@
+//! Wired Module
+//!
+//! The module reads and writes the link settings of a wired connection, which
+//! are kept in the `SettingWired` of NetworkManager.
+//!
+//! The forced speed and duplex are checked against the link modes of the
+//! device, the same link modes shown in `NetDevice::net_link_modes`.
+use crate::error::NetworkError;
+use crate::utils::non_empty;
+use eyre::Result;
+use nm::{ConnectionExt, SettingWired, SettingWiredWakeOnLan};
+use serde::{Deserialize, Serialize};
+
+/// The Wake-on-LAN flags, and their names in `WiredSettings`
+const WAKE_ON_LAN_FLAGS: &[(SettingWiredWakeOnLan, &str)] = &[
+    (SettingWiredWakeOnLan::PHY, "phy"),
+    (SettingWiredWakeOnLan::UNICAST, "unicast"),
+    (SettingWiredWakeOnLan::MULTICAST, "multicast"),
+    (SettingWiredWakeOnLan::BROADCAST, "broadcast"),
+    (SettingWiredWakeOnLan::ARP, "arp"),
+    (SettingWiredWakeOnLan::MAGIC, "magic"),
+    (SettingWiredWakeOnLan::DEFAULT, "default"),
+    (SettingWiredWakeOnLan::IGNORE, "ignore"),
+];
+
+/// The special values of the cloned mac address
+const CLONED_MAC_MODES: &[&str] = &["preserve", "permanent", "random", "stable"];
+
+/// The link settings of a wired connection
+///
+/// Every setting is optional, a setting not given is left unchanged.
+///
+/// * `mtu`: `0` means the default MTU of the device.
+/// * `cloned_mac`: a mac address or one of `preserve`, `permanent`, `random`
+///   and `stable`, an empty string unsets it.
+/// * `speed` and `duplex`: the speed in Mbit/s and `full` or `half`. Without
+///   `auto_negotiate`, they are forced and must be given together, the link
+///   is left untouched if neither is given. With `auto_negotiate`, only the
+///   given speed and duplex are advertised.
+/// * `wake_on_lan`: the names of the Wake-on-LAN flags, e.g. `magic`, an empty
+///   list disables Wake-on-LAN. `default` and `ignore` can't be combined with
+///   the other flags.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct WiredSettings {
+    #[serde(default)]
+    pub mtu: Option<u32>,
+    #[serde(default)]
+    pub cloned_mac: Option<String>,
+    #[serde(default)]
+    pub speed: Option<u32>,
+    #[serde(default)]
+    pub duplex: Option<String>,
+    #[serde(default)]
+    pub auto_negotiate: Option<bool>,
+    #[serde(default)]
+    pub wake_on_lan: Option<Vec<String>>,
+    #[serde(default)]
+    pub wake_on_lan_password: Option<String>,
+}
+
+impl WiredSettings {
+    /// Fill the settings not given with the current settings.
+    pub fn merge(self, current: &WiredSettings) -> WiredSettings {
+        WiredSettings {
+            mtu: self.mtu.or(current.mtu),
+            cloned_mac: self.cloned_mac.or_else(|| current.cloned_mac.clone()),
+            speed: self.speed.or(current.speed),
+            duplex: self.duplex.or_else(|| current.duplex.clone()),
+            auto_negotiate: self.auto_negotiate.or(current.auto_negotiate),
+            wake_on_lan: self.wake_on_lan.or_else(|| current.wake_on_lan.clone()),
+            wake_on_lan_password: self
+                .wake_on_lan_password
+                .or_else(|| current.wake_on_lan_password.clone()),
+        }
+    }
+
+    /// Check the settings, the speed and duplex are checked only if the link
+    /// modes of the device are known.
+    pub fn validate(&self, link_modes: &[String]) -> Result<()> {
+        if let Some(cloned_mac) = self.cloned_mac.as_deref() {
+            if !cloned_mac.is_empty()
+                && !CLONED_MAC_MODES.contains(&cloned_mac)
+                && !is_mac_address(cloned_mac)
+            {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "Invalid cloned mac address {}",
+                    cloned_mac
+                )))
+            }
+        }
+
+        let duplex = match self.duplex.as_deref() {
+            None | Some("") => None,
+            Some("full") => Some("Full"),
+            Some("half") => Some("Half"),
+            Some(duplex) => bail!(NetworkError::InvalidArgument(format!(
+                "Invalid duplex {}, expected full or half",
+                duplex
+            ))),
+        };
+        let speed = self.speed.filter(|x| *x != 0);
+        if self.auto_negotiate == Some(false) && speed.is_some() != duplex.is_some() {
+            bail!(NetworkError::InvalidArgument(
+                "The speed and duplex must be given together without auto negotiation".into()
+            ))
+        }
+        if !link_modes.is_empty() {
+            if self.auto_negotiate == Some(true) && !link_modes.iter().any(|x| x == "Autoneg") {
+                bail!(NetworkError::InvalidArgument(
+                    "The device doesn't support auto negotiation".into()
+                ))
+            }
+            if let Some(speed) = speed {
+                let supported = link_modes
+                    .iter()
+                    .filter_map(|x| parse_link_mode(x))
+                    .any(|(x, y)| x == speed && duplex.map(|d| d == y).unwrap_or(true));
+                if !supported {
+                    bail!(NetworkError::InvalidArgument(format!(
+                        "The device doesn't support the link mode {}Mb/s {}",
+                        speed,
+                        duplex.unwrap_or_default()
+                    )))
+                }
+            }
+        }
+
+        if let Some(wake_on_lan) = self.wake_on_lan.as_ref() {
+            let flags = wake_on_lan_from_human(wake_on_lan)?;
+            let special = SettingWiredWakeOnLan::DEFAULT | SettingWiredWakeOnLan::IGNORE;
+            if flags.intersects(special) && wake_on_lan.len() > 1 {
+                bail!(NetworkError::InvalidArgument(
+                    "The Wake-on-LAN default or ignore can't be combined with other flags".into()
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse the link mode like `1000baseT/Full` into the speed and duplex.
+fn parse_link_mode(link_mode: &str) -> Option<(u32, &str)> {
+    let (speed, rest) = link_mode.split_once("base")?;
+    let (_, duplex) = rest.split_once('/')?;
+    Some((speed.parse().ok()?, duplex))
+}
+
+fn is_mac_address(mac: &str) -> bool {
+    let parts: Vec<&str> = mac.split(':').collect();
+    parts.len() == 6
+        && parts
+            .iter()
+            .all(|x| x.len() == 2 && x.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn wake_on_lan_to_human(flags: SettingWiredWakeOnLan) -> Vec<String> {
+    WAKE_ON_LAN_FLAGS
+        .iter()
+        .filter(|(x, _)| flags.contains(*x))
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+fn wake_on_lan_from_human(names: &[String]) -> Result<SettingWiredWakeOnLan> {
+    let mut flags = SettingWiredWakeOnLan::empty();
+    for name in names {
+        match WAKE_ON_LAN_FLAGS.iter().find(|(_, x)| *x == name.as_str()) {
+            Some((flag, _)) => flags |= *flag,
+            None => bail!(NetworkError::InvalidArgument(format!(
+                "Invalid Wake-on-LAN flag {}",
+                name
+            ))),
+        }
+    }
+    Ok(flags)
+}
+
+impl From<&SettingWired> for WiredSettings {
+    fn from(setting: &SettingWired) -> Self {
+        WiredSettings {
+            mtu: Some(setting.mtu()),
+            cloned_mac: setting.cloned_mac_address().map(|x| x.to_string()),
+            speed: Some(setting.speed()),
+            duplex: setting.duplex().map(|x| x.to_string()),
+            auto_negotiate: Some(setting.is_auto_negotiate()),
+            wake_on_lan: Some(wake_on_lan_to_human(setting.wake_on_lan())),
+            wake_on_lan_password: setting.wake_on_lan_password().map(|x| x.to_string()),
+        }
+    }
+}
+
+/// Read the wired settings of the connection, `None` if the connection is not
+/// a wired connection.
+pub(crate) fn get_wired_settings(connection: &nm::RemoteConnection) -> Option<WiredSettings> {
+    connection
+        .setting_wired()
+        .map(|setting| WiredSettings::from(&setting))
+}
+
+/// Validate the wired settings with the link modes of the device and write
+/// them into the connection, the settings not given are left unchanged.
+pub(crate) fn update_wired_settings(
+    settings: WiredSettings,
+    connection: &nm::RemoteConnection,
+    link_modes: &[String],
+) -> Result<()> {
+    let setting = match connection.setting_wired() {
+        Some(setting) => setting,
+        None => {
+            let setting = SettingWired::new();
+            connection.add_setting(setting.clone());
+            setting
+        }
+    };
+    let current = WiredSettings::from(&setting);
+    settings.clone().merge(&current).validate(link_modes)?;
+
+    if let Some(mtu) = settings.mtu {
+        setting.set_mtu(mtu);
+    }
+    if let Some(cloned_mac) = settings.cloned_mac.as_deref() {
+        setting.set_cloned_mac_address(non_empty(cloned_mac));
+    }
+    if let Some(speed) = settings.speed {
+        setting.set_speed(speed);
+    }
+    if let Some(duplex) = settings.duplex.as_deref() {
+        setting.set_duplex(non_empty(duplex));
+    }
+    if let Some(auto_negotiate) = settings.auto_negotiate {
+        setting.set_auto_negotiate(auto_negotiate);
+    }
+    if let Some(wake_on_lan) = settings.wake_on_lan.as_ref() {
+        setting.set_wake_on_lan(wake_on_lan_from_human(wake_on_lan)?.bits());
+    }
+    if let Some(password) = settings.wake_on_lan_password.as_deref() {
+        setting.set_wake_on_lan_password(non_empty(password));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    fn link_modes() -> Vec<String> {
+        [
+            "10baseT/Half",
+            "100baseT/Full",
+            "1000baseT/Full",
+            "Autoneg",
+            "TP",
+        ]
+        .iter()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    #[rstest]
+    #[case(Some(1000), Some("full"), Some(false), true)]
+    #[case(Some(10), Some("half"), Some(false), true)]
+    #[case(Some(10), Some("full"), Some(false), false)]
+    #[case(Some(2500), None, Some(true), false)]
+    #[case(Some(1000), None, Some(false), false)]
+    #[case(None, None, Some(true), true)]
+    #[case(None, None, Some(false), true)]
+    #[case(Some(1000), Some("both"), None, false)]
+    fn test_validate_link_modes(
+        #[case] speed: Option<u32>,
+        #[case] duplex: Option<&str>,
+        #[case] auto_negotiate: Option<bool>,
+        #[case] valid: bool,
+    ) {
+        let settings = WiredSettings {
+            speed,
+            duplex: duplex.map(|x| x.to_string()),
+            auto_negotiate,
+            ..Default::default()
+        };
+        assert_eq!(settings.validate(&link_modes()).is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case("52:54:00:12:34:56", true)]
+    #[case("stable", true)]
+    #[case("", true)]
+    #[case("52:54:00:12:34", false)]
+    #[case("fixed", false)]
+    fn test_validate_cloned_mac(#[case] cloned_mac: &str, #[case] valid: bool) {
+        let settings = WiredSettings {
+            cloned_mac: Some(cloned_mac.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(settings.validate(&[]).is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case(vec!["magic", "broadcast"], true)]
+    #[case(vec![], true)]
+    #[case(vec!["default"], true)]
+    #[case(vec!["default", "magic"], false)]
+    #[case(vec!["wol"], false)]
+    fn test_validate_wake_on_lan(#[case] flags: Vec<&str>, #[case] valid: bool) {
+        let settings = WiredSettings {
+            wake_on_lan: Some(flags.iter().map(|x| x.to_string()).collect()),
+            ..Default::default()
+        };
+        assert_eq!(settings.validate(&[]).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_merge() {
+        let current = WiredSettings {
+            mtu: Some(1500),
+            speed: Some(1000),
+            auto_negotiate: Some(true),
+            ..Default::default()
+        };
+        let settings = WiredSettings {
+            mtu: Some(9000),
+            ..Default::default()
+        }
+        .merge(&current);
+        assert_eq!(settings.mtu, Some(9000));
+        assert_eq!(settings.speed, Some(1000));
+        assert_eq!(settings.auto_negotiate, Some(true));
+    }
+}
@@ -0,0 +1,475 @@
+//! WireGuard Module
+//!
+//! The module provides the api about WireGuard connections for the
+//! NetworkManager.
+//!
+//! The private key of a connection is written only, it is never read back.
+//! The peer statistics are not provided by NetworkManager, they are read
+//! from `wg show <interface> dump`. The command runs as a subprocess driven
+//! by the glib main loop, so it doesn't block the other requests.
+//!
+//! Functions:
+//! - create_wireguard
+//! - update_wireguard
+//! - get_wireguard
+use super::connections::{add_connection, get_ip_config, set_ip_configs};
+use super::{create_client, NetworkResponse};
+use crate::error::NetworkError;
+use crate::net::NetInfo;
+use crate::utils::{check_wireguard_key, generate_private_key, public_key};
+use eyre::Result;
+use glib::IsA;
+use ipnet::IpNet;
+use nm::{
+    ConnectionExt, SettingConnection, SettingWireGuard, SimpleConnection,
+    SETTING_WIREGUARD_SETTING_NAME,
+};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+
+/// The WireGuard connection
+///
+/// * `interface`: the WireGuard interface name, e.g. `wg0`.
+/// * `private_key`: the base64 encoded private key. It is generated when the
+///   connection is created without it, and left unchanged when it is not
+///   given to an update. An empty key generates a new one. It is never read
+///   back.
+/// * `public_key`: the public key derived from the private key, it is ignored
+///   when the connection is created or updated.
+/// * `listen_port`: the UDP port to listen on, 0 means a random port.
+/// * `fwmark`: the firewall mark of the outgoing packets, 0 means none.
+/// * `peers`: the peers replace the current ones on update, they are left
+///   unchanged when they are not given.
+///
+/// The `uuid` is ignored when the connection is created.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct WireGuardConnection {
+    pub name: String,
+    #[serde(default)]
+    pub uuid: String,
+    pub interface: String,
+    #[serde(default, skip_serializing)]
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub listen_port: Option<u32>,
+    #[serde(default)]
+    pub fwmark: Option<u32>,
+    #[serde(default)]
+    pub peers: Option<Vec<WireGuardPeer>>,
+    #[serde(default)]
+    pub ip4info: Option<NetInfo>,
+    #[serde(default)]
+    pub ip6info: Option<NetInfo>,
+}
+
+/// The WireGuard peer
+///
+/// * `endpoint`: `host:port`, e.g. `vpn.example.com:51820` or
+///   `[2001:db8::1]:51820`.
+/// * `persistent_keepalive`: the keepalive interval in seconds, 0 means off.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct WireGuardPeer {
+    pub public_key: String,
+    #[serde(default)]
+    pub allowed_ips: Vec<IpNet>,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub persistent_keepalive: Option<u32>,
+}
+
+/// The runtime statistics of a WireGuard peer
+///
+/// * `last_handshake`: the unix time of the latest handshake in seconds,
+///   `None` if there is no handshake yet.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct WireGuardPeerStats {
+    pub public_key: String,
+    pub endpoint: Option<String>,
+    pub last_handshake: Option<u64>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+impl WireGuardConnection {
+    /// Check the WireGuard settings before they are sent to NetworkManager.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.interface.is_empty() {
+            bail!(NetworkError::InvalidArgument(
+                "Both the connection name and the interface are required".into()
+            ))
+        }
+        if let Some(private_key) = self.private_key.as_deref() {
+            if !private_key.is_empty() {
+                check_wireguard_key(private_key)?;
+            }
+        }
+        if let Some(listen_port) = self.listen_port {
+            check_u16("listen port", listen_port)?;
+        }
+        for peer in self.peers.iter().flatten() {
+            peer.validate()?;
+        }
+        Ok(())
+    }
+
+    /// The private key to write, a new one is generated if it is empty, or
+    /// if it is not given and the connection is created.
+    pub(crate) fn private_key_to_write(&self, create: bool) -> Option<String> {
+        match self.private_key.as_deref() {
+            Some("") => Some(generate_private_key()),
+            Some(private_key) => Some(private_key.trim().to_string()),
+            None if create => Some(generate_private_key()),
+            None => None,
+        }
+    }
+}
+
+impl WireGuardPeer {
+    /// Check the peer settings before they are sent to NetworkManager.
+    pub fn validate(&self) -> Result<()> {
+        check_wireguard_key(&self.public_key)?;
+        if let Some(endpoint) = self.endpoint.as_deref() {
+            let port = endpoint
+                .rsplit_once(':')
+                .map(|(_, port)| port.parse::<u16>());
+            if !matches!(port, Some(Ok(_))) {
+                bail!(NetworkError::InvalidArgument(format!(
+                    "Invalid WireGuard endpoint {}, expected host:port",
+                    endpoint
+                )))
+            }
+        }
+        if let Some(keepalive) = self.persistent_keepalive {
+            check_u16("persistent keepalive", keepalive)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_u16(kind: &str, value: u32) -> Result<()> {
+    if value > u16::MAX as u32 {
+        bail!(NetworkError::InvalidArgument(format!(
+            "Invalid {} {}, expected 0 to {}",
+            kind,
+            value,
+            u16::MAX
+        )))
+    }
+    Ok(())
+}
+
+/// Parse the peers of `wg show <interface> dump`, the first line is the
+/// interface itself.
+fn parse_dump(dump: &str) -> Vec<WireGuardPeerStats> {
+    dump.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 8 {
+                return None;
+            }
+            let last_handshake = fields[4].parse().ok().filter(|x| *x > 0);
+            Some(WireGuardPeerStats {
+                public_key: fields[0].to_string(),
+                endpoint: Some(fields[2].to_string()).filter(|x| x != "(none)"),
+                last_handshake,
+                rx_bytes: fields[5].parse().unwrap_or(0),
+                tx_bytes: fields[6].parse().unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// The peer statistics of the WireGuard interface, an empty list if they
+/// could not be read, e.g. the wireguard-tools are not installed or the
+/// interface is not readable without root.
+pub(crate) async fn wireguard_peer_stats(interface: &str) -> Vec<WireGuardPeerStats> {
+    let argv = [
+        OsStr::new("wg"),
+        OsStr::new("show"),
+        OsStr::new(interface),
+        OsStr::new("dump"),
+    ];
+    let flags = gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_SILENCE;
+    let process = match gio::Subprocess::newv(&argv, flags) {
+        Ok(process) => process,
+        Err(_) => return vec![],
+    };
+    match process.communicate_utf8_future(None).await {
+        Ok((Some(stdout), _)) if process.is_successful() => parse_dump(&stdout),
+        _ => vec![],
+    }
+}
+
+/// Write the WireGuard settings and the peers into the connection.
+fn write_wireguard_settings(
+    wireguard: &WireGuardConnection,
+    connection: &impl IsA<nm::Connection>,
+    private_key: Option<&str>,
+) -> Result<()> {
+    let setting = match connection.setting_wireguard() {
+        Some(setting) => setting,
+        None => {
+            let setting = SettingWireGuard::new();
+            connection.add_setting(setting.clone());
+            setting
+        }
+    };
+    if let Some(private_key) = private_key {
+        setting.set_private_key(Some(private_key));
+    }
+    if let Some(listen_port) = wireguard.listen_port {
+        setting.set_listen_port(listen_port);
+    }
+    if let Some(fwmark) = wireguard.fwmark {
+        setting.set_fwmark(fwmark);
+    }
+
+    let peers = match wireguard.peers.as_ref() {
+        Some(peers) => peers,
+        None => return Ok(()),
+    };
+    setting.clear_peers();
+    for peer in peers.iter() {
+        let nm_peer = nm::WireGuardPeer::new();
+        let mut valid = nm_peer.set_public_key(Some(&peer.public_key), false);
+        for allowed_ip in peer.allowed_ips.iter() {
+            valid &= nm_peer.append_allowed_ip(&allowed_ip.to_string(), false);
+        }
+        if let Some(endpoint) = peer.endpoint.as_deref() {
+            valid &= nm_peer.set_endpoint(Some(endpoint), false);
+        }
+        if let Some(keepalive) = peer.persistent_keepalive {
+            nm_peer.set_persistent_keepalive(keepalive as u16);
+        }
+        if !valid {
+            bail!(NetworkError::InvalidArgument(format!(
+                "Invalid WireGuard peer {}",
+                peer.public_key
+            )))
+        }
+        nm_peer.seal();
+        setting.append_peer(&nm_peer);
+    }
+    Ok(())
+}
+
+fn read_peer(peer: &nm::WireGuardPeer) -> Option<WireGuardPeer> {
+    let allowed_ips = (0..peer.allowed_ips_len())
+        .filter_map(|i| peer.allowed_ip(i, None))
+        .filter_map(|x| x.parse().ok())
+        .collect();
+    let keepalive = peer.persistent_keepalive();
+    Some(WireGuardPeer {
+        public_key: peer.public_key()?.to_string(),
+        allowed_ips,
+        endpoint: peer.endpoint().map(|x| x.to_string()),
+        persistent_keepalive: Some(keepalive as u32),
+    })
+}
+
+/// Load the secrets of the connection into its settings, they are not in the
+/// settings read from NetworkManager.
+async fn load_secrets(nm_connection: &nm::RemoteConnection) -> Result<()> {
+    let secrets = nm_connection
+        .secrets_future(SETTING_WIREGUARD_SETTING_NAME)
+        .await
+        .and_then(|x| nm_connection.update_secrets(SETTING_WIREGUARD_SETTING_NAME, &x));
+    if let Err(err) = secrets {
+        bail!(NetworkError::Backend(format!(
+            "Failed to read the WireGuard secrets: {}",
+            err
+        )))
+    }
+    Ok(())
+}
+
+/// Read the public key from the private key in the secrets of the connection.
+async fn read_public_key(nm_connection: &nm::RemoteConnection) -> Option<String> {
+    load_secrets(nm_connection).await.ok()?;
+    let private_key = nm_connection.setting_wireguard()?.private_key()?;
+    public_key(&private_key).ok()
+}
+
+async fn read_wireguard(nm_connection: &nm::RemoteConnection) -> Option<WireGuardConnection> {
+    let setting = nm_connection.setting_wireguard()?;
+    let peers = (0..setting.peers_len())
+        .filter_map(|i| setting.peer(i))
+        .filter_map(|x| read_peer(&x))
+        .collect();
+    Some(WireGuardConnection {
+        name: nm_connection.id()?.to_string(),
+        uuid: nm_connection.uuid()?.to_string(),
+        interface: nm_connection.interface_name()?.to_string(),
+        private_key: None,
+        public_key: read_public_key(nm_connection).await,
+        listen_port: Some(setting.listen_port()),
+        fwmark: Some(setting.fwmark()),
+        peers: Some(peers),
+        ip4info: get_ip_config(nm_connection, 4).ok(),
+        ip6info: get_ip_config(nm_connection, 6).ok(),
+    })
+}
+
+/// Find the WireGuard connection with the uuid.
+fn wireguard_connection(client: &nm::Client, uuid: &str) -> Result<nm::RemoteConnection> {
+    match client.connection_by_uuid(uuid) {
+        Some(connection) if connection.setting_wireguard().is_some() => Ok(connection),
+        _ => bail!(NetworkError::NotFound(format!(
+            "WireGuard connection {} not found",
+            uuid
+        ))),
+    }
+}
+
+async fn wireguard_response(nm_connection: &nm::RemoteConnection) -> Result<NetworkResponse> {
+    let wireguard = read_wireguard(nm_connection).await.ok_or_else(|| {
+        NetworkError::Backend(format!(
+            "Failed to read the WireGuard connection {}",
+            nm_connection.uuid().unwrap_or_default()
+        ))
+    })?;
+    Ok(NetworkResponse::WireGuard(wireguard))
+}
+
+/// Create a WireGuard connection, the private key is generated if it is not
+/// given.
+pub async fn create_wireguard(wireguard: WireGuardConnection) -> Result<NetworkResponse> {
+    wireguard.validate()?;
+    let client = create_client().await?;
+
+    let connection = SimpleConnection::new();
+    let s_connection = SettingConnection::new();
+    s_connection.set_type(Some(&SETTING_WIREGUARD_SETTING_NAME));
+    s_connection.set_id(Some(&wireguard.name));
+    s_connection.set_interface_name(Some(&wireguard.interface));
+    s_connection.set_autoconnect(true);
+    connection.add_setting(s_connection);
+    let private_key = wireguard.private_key_to_write(true);
+    write_wireguard_settings(&wireguard, &connection, private_key.as_deref())?;
+    set_ip_configs(
+        &connection,
+        wireguard.ip4info.as_ref(),
+        wireguard.ip6info.as_ref(),
+    )?;
+
+    let uuid = add_connection(&client, &connection, &wireguard.name).await?;
+    Ok(NetworkResponse::Created { uuid })
+}
+
+/// Update the WireGuard connection with the uuid, and read it again.
+pub async fn update_wireguard(wireguard: WireGuardConnection) -> Result<NetworkResponse> {
+    wireguard.validate()?;
+    let client = create_client().await?;
+    let nm_connection = wireguard_connection(&client, &wireguard.uuid)?;
+    // The secrets are committed with the settings, the private key and the
+    // preshared keys would be removed if they are not loaded.
+    load_secrets(&nm_connection).await?;
+
+    if let Some(setting) = nm_connection.setting_connection() {
+        setting.set_id(Some(&wireguard.name));
+        setting.set_interface_name(Some(&wireguard.interface));
+    }
+    let private_key = wireguard.private_key_to_write(false);
+    write_wireguard_settings(&wireguard, &nm_connection, private_key.as_deref())?;
+    set_ip_configs(
+        &nm_connection,
+        wireguard.ip4info.as_ref(),
+        wireguard.ip6info.as_ref(),
+    )?;
+    nm_connection.commit_changes_future(true).await?;
+    wireguard_response(&nm_connection).await
+}
+
+/// Get the WireGuard connection with the uuid, without the private key.
+pub async fn get_wireguard(uuid: String) -> Result<NetworkResponse> {
+    let client = create_client().await?;
+    let nm_connection = wireguard_connection(&client, &uuid)?;
+    wireguard_response(&nm_connection).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    const KEY: &str = "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=";
+
+    fn wireguard() -> WireGuardConnection {
+        WireGuardConnection {
+            name: "hq".into(),
+            interface: "wg0".into(),
+            ..Default::default()
+        }
+    }
+
+    fn peer() -> WireGuardPeer {
+        WireGuardPeer {
+            public_key: KEY.into(),
+            allowed_ips: vec!["10.0.0.0/24".parse().unwrap()],
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(wireguard(), true)]
+    #[case(WireGuardConnection { interface: "".into(), ..wireguard() }, false)]
+    #[case(WireGuardConnection { private_key: Some(KEY.into()), ..wireguard() }, true)]
+    #[case(WireGuardConnection { private_key: Some("".into()), ..wireguard() }, true)]
+    #[case(WireGuardConnection { private_key: Some("secret".into()), ..wireguard() }, false)]
+    #[case(WireGuardConnection { listen_port: Some(65536), ..wireguard() }, false)]
+    #[case(WireGuardConnection { peers: Some(vec![peer()]), ..wireguard() }, true)]
+    #[case(WireGuardConnection { peers: Some(vec![WireGuardPeer { endpoint: Some("hq.example.com:51820".into()), ..peer() }]), ..wireguard() }, true)]
+    #[case(WireGuardConnection { peers: Some(vec![WireGuardPeer { endpoint: Some("[2001:db8::1]:51820".into()), ..peer() }]), ..wireguard() }, true)]
+    #[case(WireGuardConnection { peers: Some(vec![WireGuardPeer { endpoint: Some("hq.example.com".into()), ..peer() }]), ..wireguard() }, false)]
+    #[case(WireGuardConnection { peers: Some(vec![WireGuardPeer { public_key: "peer".into(), ..peer() }]), ..wireguard() }, false)]
+    fn test_validate(#[case] wireguard: WireGuardConnection, #[case] valid: bool) {
+        assert_eq!(wireguard.validate().is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case(None, true, true)]
+    #[case(None, false, false)]
+    #[case(Some(""), false, true)]
+    #[case(Some(KEY), false, true)]
+    fn test_private_key_to_write(
+        #[case] private_key: Option<&str>,
+        #[case] create: bool,
+        #[case] written: bool,
+    ) {
+        let wireguard = WireGuardConnection {
+            private_key: private_key.map(|x| x.to_string()),
+            ..wireguard()
+        };
+        let key = wireguard.private_key_to_write(create);
+        assert_eq!(key.is_some(), written);
+        assert!(key.map(|x| check_wireguard_key(&x).is_ok()).unwrap_or(true));
+    }
+
+    #[test]
+    fn test_private_key_not_serialized() {
+        let wireguard = WireGuardConnection {
+            private_key: Some(KEY.into()),
+            ..wireguard()
+        };
+        let value = serde_json::to_value(wireguard).unwrap();
+        assert!(value.get("private_key").is_none());
+    }
+
+    #[test]
+    fn test_parse_dump() {
+        let dump = "cHJpdmF0ZQ==\tcHVibGlj\t51820\toff\n\
+            peerA=\t(none)\t203.0.113.1:51820\t10.0.0.0/24\t1700000000\t1024\t2048\t25\n\
+            peerB=\t(none)\t(none)\t10.0.1.0/24\t0\t0\t0\toff\n";
+        let peers = parse_dump(dump);
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].endpoint.as_deref(), Some("203.0.113.1:51820"));
+        assert_eq!(peers[0].last_handshake, Some(1700000000));
+        assert_eq!((peers[0].rx_bytes, peers[0].tx_bytes), (1024, 2048));
+        assert_eq!(peers[1].endpoint, None);
+        assert_eq!(peers[1].last_handshake, None);
+    }
+}
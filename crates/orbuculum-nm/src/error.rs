@@ -0,0 +1,95 @@
+//! ### Error Module
+//!
+//! The `NetworkError` is the error returned by `send_command`. The backends
+//! raise it with `bail!(NetworkError::NotFound(..))`, any other error raised by
+//! the backend is reported as `NetworkError::Backend`.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The error of a setting, or of one of its properties if `property` is
+/// given, in a settings document
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PropertyError {
+    pub setting: String,
+    pub property: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for PropertyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.property {
+            Some(property) => write!(f, "{}.{}: {}", self.setting, property, self.message),
+            None => write!(f, "{}: {}", self.setting, self.message),
+        }
+    }
+}
+
+/// The errors of the network commands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkError {
+    /// The device or connection doesn't exist.
+    NotFound(String),
+    /// The NetworkManager daemon or the network thread is not available.
+    NmUnavailable(String),
+    /// The arguments of the command are invalid.
+    InvalidArgument(String),
+    /// The command is not answered in time.
+    Timeout(String),
+    /// The command conflicts with the current state.
+    Conflict(String),
+    /// The backend failed to execute the command.
+    Backend(String),
+    /// The settings document is invalid, the summary and the errors of the
+    /// properties.
+    InvalidSettings(String, Vec<PropertyError>),
+}
+
+impl NetworkError {
+    pub fn invalid_settings(errors: Vec<PropertyError>) -> Self {
+        let summary = errors
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        NetworkError::InvalidSettings(summary, errors)
+    }
+
+    /// The message without the error kind
+    pub fn message(&self) -> &str {
+        match self {
+            NetworkError::NotFound(msg)
+            | NetworkError::NmUnavailable(msg)
+            | NetworkError::InvalidArgument(msg)
+            | NetworkError::Timeout(msg)
+            | NetworkError::Conflict(msg)
+            | NetworkError::Backend(msg)
+            | NetworkError::InvalidSettings(msg, _) => msg,
+        }
+    }
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            NetworkError::NotFound(_) => "not found",
+            NetworkError::NmUnavailable(_) => "NetworkManager unavailable",
+            NetworkError::InvalidArgument(_) => "invalid argument",
+            NetworkError::Timeout(_) => "timeout",
+            NetworkError::Conflict(_) => "conflict",
+            NetworkError::Backend(_) => "backend error",
+            NetworkError::InvalidSettings(..) => "invalid settings",
+        };
+        write!(f, "{}: {}", kind, self.message())
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<eyre::ErrReport> for NetworkError {
+    fn from(report: eyre::ErrReport) -> Self {
+        match report.downcast_ref::<NetworkError>() {
+            Some(err) => err.clone(),
+            None => NetworkError::Backend(format!("{:#}", report)),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! ### Event Module
+//!
+//! The backends publish what happens to the network as `NetworkEvent`s. The
+//! events are sent from the glib thread to a tokio broadcast channel, every
+//! tokio task could subscribe the channel via `State::subscribe`.
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// The number of events kept for the slow subscribers.
+pub const EVENT_CAPACITY: usize = 128;
+
+/// The sender half of the event channel
+pub type EventSender = broadcast::Sender<NetworkEvent>;
+
+/// The network events
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum NetworkEvent {
+    /// A network device is plugged or created.
+    DeviceAdded { name: String },
+    /// A network device is unplugged or destroyed.
+    DeviceRemoved { name: String },
+    /// The network manager state of the device is changed.
+    DeviceStateChanged { name: String, state: String },
+    /// The carrier of a wired device is changed, e.g. the cable is unplugged.
+    CarrierChanged { name: String, carrier: bool },
+    /// A connection is created.
+    ConnectionAdded { uuid: String, name: String },
+    /// The settings of a connection are changed.
+    ConnectionChanged { uuid: String, name: String },
+    /// A connection is deleted.
+    ConnectionRemoved { uuid: String, name: String },
+}
+
+/// Publish the event, it is fine that there is no subscriber.
+pub(crate) fn publish(events: &EventSender, event: NetworkEvent) {
+    let _ = events.send(event);
+}
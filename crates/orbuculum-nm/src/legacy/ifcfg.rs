@@ -0,0 +1,301 @@
+//! The ifcfg-rh files
+//!
+//! An `ifcfg-<name>` file is a list of shell variables, e.g.
+//!
+//! ```text
+//! DEVICE=eth0
+//! BOOTPROTO=none
+//! IPADDR=192.0.2.10
+//! PREFIX=24
+//! GATEWAY=192.0.2.1
+//! DNS1=192.0.2.53
+//! ```
+//!
+//! The numbered `IPADDRn`, `PREFIXn` and `NETMASKn` are the additional
+//! addresses.
+use super::{netmask_prefix, new_connection, parse_address, parse_ip};
+use super::{LegacyConfig, UnsupportedDirective};
+use crate::dispatch::wired::WiredSettings;
+use crate::error::NetworkError;
+use eyre::Result;
+use ipnet::IpNet;
+use std::collections::BTreeMap;
+
+/// The variables which are migrated when they have the value of the
+/// NetworkManager defaults.
+const DEFAULT_VALUES: &[(&str, &str)] = &[
+    ("TYPE", "Ethernet"),
+    ("ONBOOT", "yes"),
+    ("NM_CONTROLLED", "yes"),
+    ("PROXY_METHOD", "none"),
+    ("BROWSER_ONLY", "no"),
+    ("USERCTL", "no"),
+];
+
+/// The variables read into the connection, besides the numbered ones.
+const KNOWN_VARIABLES: &[&str] = &[
+    "NAME",
+    "DEVICE",
+    "UUID",
+    "HWADDR",
+    "MACADDR",
+    "MTU",
+    "BOOTPROTO",
+    "IPADDR",
+    "PREFIX",
+    "NETMASK",
+    "GATEWAY",
+    "DOMAIN",
+    "DEFROUTE",
+    "PEERDNS",
+    "IPV4_FAILURE_FATAL",
+    "IPV6INIT",
+    "IPV6_AUTOCONF",
+    "DHCPV6C",
+    "IPV6ADDR",
+    "IPV6ADDR_SECONDARIES",
+    "IPV6_DEFAULTGW",
+    "IPV6_DEFROUTE",
+    "IPV6_PEERDNS",
+    "IPV6_FAILURE_FATAL",
+];
+
+/// The value without the shell quotes
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(value) = value
+            .strip_prefix(quote)
+            .and_then(|x| x.strip_suffix(quote))
+        {
+            return value;
+        }
+    }
+    value
+}
+
+/// The number of the numbered variable, e.g. 2 for `DNS2`
+fn variable_number(key: &str, prefix: &str) -> Option<u32> {
+    key.strip_prefix(prefix)
+        .filter(|x| !x.is_empty() && x.chars().all(|x| x.is_ascii_digit()))
+        .and_then(|x| x.parse().ok())
+}
+
+/// Whether the variable is one of the numbered ones, e.g. `IPADDR1` or `DNS2`
+fn is_numbered(key: &str) -> bool {
+    ["IPADDR", "PREFIX", "NETMASK", "DNS"]
+        .iter()
+        .any(|prefix| variable_number(key, prefix).is_some())
+}
+
+fn is_yes(value: Option<&String>) -> Option<bool> {
+    value.map(|x| matches!(x.to_lowercase().as_str(), "yes" | "true" | "y" | "1"))
+}
+
+/// The IPv4 addresses, `IPADDR` goes first and then the numbered ones.
+fn ipv4_addresses(variables: &BTreeMap<String, String>) -> Result<Vec<IpNet>> {
+    let mut suffixes: Vec<(u32, &str)> = variables
+        .keys()
+        .filter_map(|key| {
+            let suffix = key.strip_prefix("IPADDR")?;
+            match suffix {
+                "" => Some((0, suffix)),
+                _ => variable_number(key, "IPADDR").map(|x| (x + 1, suffix)),
+            }
+        })
+        .collect();
+    suffixes.sort();
+    let mut addresses = vec![];
+    for (_, suffix) in suffixes {
+        let address = &variables[&format!("IPADDR{}", suffix)];
+        let prefix = match variables.get(&format!("PREFIX{}", suffix)) {
+            Some(prefix) => Some(prefix.parse::<u8>().map_err(|_| {
+                NetworkError::InvalidArgument(format!("Invalid PREFIX{} {}", suffix, prefix))
+            })?),
+            None => match variables.get(&format!("NETMASK{}", suffix)) {
+                Some(netmask) => Some(netmask_prefix(netmask).ok_or_else(|| {
+                    NetworkError::InvalidArgument(format!("Invalid NETMASK{} {}", suffix, netmask))
+                })?),
+                None => None,
+            },
+        };
+        addresses.push(parse_address(address, prefix)?);
+    }
+    Ok(addresses)
+}
+
+/// Parse the ifcfg file, `file_name` is the connection name if the file has
+/// neither `NAME` nor `DEVICE`.
+pub(super) fn parse_ifcfg(file_name: &str, content: &str) -> Result<LegacyConfig> {
+    let mut unsupported = vec![];
+    let mut variables = BTreeMap::new();
+    let mut is_ethernet = true;
+    for (number, line) in content.lines().enumerate() {
+        let location = format!("line {}", number + 1);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), unquote(value)),
+            None => bail!(NetworkError::InvalidArgument(format!(
+                "Invalid ifcfg {}: {}",
+                location, line
+            ))),
+        };
+        let is_default = DEFAULT_VALUES
+            .iter()
+            .any(|(x, default)| *x == key && value.eq_ignore_ascii_case(default));
+        if is_default {
+            continue;
+        }
+        if DEFAULT_VALUES.iter().any(|(x, _)| *x == key) {
+            let reason = match key {
+                "TYPE" => "only the Ethernet interfaces are supported",
+                _ => "the value differs from the NetworkManager default",
+            };
+            unsupported.push(UnsupportedDirective::new(location, line, reason));
+            is_ethernet = is_ethernet && key != "TYPE";
+            continue;
+        }
+        if !KNOWN_VARIABLES.contains(&key) && !is_numbered(key) {
+            unsupported.push(UnsupportedDirective::new(
+                location,
+                line,
+                "the variable is not supported",
+            ));
+            continue;
+        }
+        variables.insert(key.to_string(), value.to_string());
+    }
+    if !is_ethernet {
+        return Ok(LegacyConfig {
+            connections: vec![],
+            unsupported,
+        });
+    }
+
+    let file_name = file_name.rsplit('/').next().unwrap_or_default();
+    let file_name = file_name.strip_prefix("ifcfg-").unwrap_or(file_name);
+    let interface = variables.get("DEVICE");
+    let name = variables
+        .get("NAME")
+        .or(interface)
+        .map(|x| x.as_str())
+        .unwrap_or(file_name);
+    if name.is_empty() {
+        bail!(NetworkError::InvalidArgument(
+            "The ifcfg file has neither NAME nor DEVICE".into()
+        ));
+    }
+    let mut connection = new_connection(name, interface.cloned());
+    connection.uuid = variables.get("UUID").cloned().unwrap_or_default();
+    connection.mac = variables.get("HWADDR").map(|x| x.to_lowercase());
+    if variables.contains_key("MTU") || variables.contains_key("MACADDR") {
+        let mtu = match variables.get("MTU") {
+            Some(mtu) => Some(
+                mtu.parse::<u32>()
+                    .map_err(|_| NetworkError::InvalidArgument(format!("Invalid MTU {}", mtu)))?,
+            ),
+            None => None,
+        };
+        connection.wired = Some(WiredSettings {
+            mtu,
+            cloned_mac: variables.get("MACADDR").map(|x| x.to_lowercase()),
+            ..Default::default()
+        });
+    }
+
+    let mut servers: Vec<(u32, &String)> = variables
+        .iter()
+        .filter_map(|(key, value)| variable_number(key, "DNS").map(|x| (x, value)))
+        .collect();
+    servers.sort();
+    let mut dns = vec![];
+    for (_, server) in servers {
+        dns.push(parse_ip(server)?);
+    }
+    let dns_search: Vec<String> = variables
+        .get("DOMAIN")
+        .map(|x| x.split_whitespace().map(|x| x.to_string()).collect())
+        .unwrap_or_default();
+
+    let ip4info = &mut connection.ip4info;
+    ip4info.addresses = ipv4_addresses(&variables)?;
+    let bootproto = variables.get("BOOTPROTO").map(|x| x.to_lowercase());
+    ip4info.method = match bootproto.as_deref() {
+        Some("dhcp") | Some("bootp") => "auto".into(),
+        _ if !ip4info.addresses.is_empty() => "manual".into(),
+        _ => "disabled".into(),
+    };
+    ip4info.gateway = variables
+        .get("GATEWAY")
+        .map(String::as_str)
+        .map(parse_ip)
+        .transpose()?;
+    ip4info.dns = dns.iter().filter(|x| x.is_ipv4()).cloned().collect();
+    ip4info.dns_search = Some(dns_search.clone());
+    ip4info.never_default = is_yes(variables.get("DEFROUTE")).map(|x| !x);
+    ip4info.ignore_auto_dns = is_yes(variables.get("PEERDNS")).map(|x| !x);
+    ip4info.may_fail = is_yes(variables.get("IPV4_FAILURE_FATAL")).map(|x| !x);
+
+    if is_yes(variables.get("IPV6INIT")).unwrap_or(false) {
+        let ip6info = &mut connection.ip6info;
+        let mut addresses = vec![];
+        if let Some(address) = variables.get("IPV6ADDR") {
+            addresses.push(parse_address(address, None)?);
+        }
+        if let Some(secondaries) = variables.get("IPV6ADDR_SECONDARIES") {
+            for address in secondaries.split_whitespace() {
+                addresses.push(parse_address(address, None)?);
+            }
+        }
+        ip6info.method = if is_yes(variables.get("DHCPV6C")).unwrap_or(false) {
+            "dhcp".into()
+        } else if is_yes(variables.get("IPV6_AUTOCONF")).unwrap_or(addresses.is_empty()) {
+            "auto".into()
+        } else {
+            "manual".into()
+        };
+        ip6info.addresses = addresses;
+        // The gateway may be followed by the interface, e.g. `fe80::1%eth0`
+        ip6info.gateway = variables
+            .get("IPV6_DEFAULTGW")
+            .map(|x| parse_ip(x.split('%').next().unwrap_or_default()))
+            .transpose()?;
+        ip6info.dns = dns.iter().filter(|x| x.is_ipv6()).cloned().collect();
+        ip6info.dns_search = Some(dns_search);
+        ip6info.never_default = is_yes(variables.get("IPV6_DEFROUTE")).map(|x| !x);
+        ip6info.ignore_auto_dns = is_yes(variables.get("IPV6_PEERDNS")).map(|x| !x);
+        ip6info.may_fail = is_yes(variables.get("IPV6_FAILURE_FATAL")).map(|x| !x);
+    }
+
+    Ok(LegacyConfig {
+        connections: vec![connection],
+        unsupported,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("\"eth0\"", "eth0")]
+    #[case("'my lan'", "my lan")]
+    #[case("none", "none")]
+    fn test_unquote(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(unquote(value), expected);
+    }
+
+    #[rstest]
+    #[case("IPADDR1", true)]
+    #[case("DNS2", true)]
+    #[case("DNS", false)]
+    #[case("DNSSEC", false)]
+    #[case("GATEWAY", false)]
+    fn test_is_numbered(#[case] key: &str, #[case] expected: bool) {
+        assert_eq!(is_numbered(key), expected);
+    }
+}
@@ -0,0 +1,273 @@
+//! The Debian interfaces files
+//!
+//! The `iface` stanzas of an interface are merged into one connection, e.g.
+//!
+//! ```text
+//! auto eth0
+//! iface eth0 inet static
+//!     address 192.0.2.10/24
+//!     gateway 192.0.2.1
+//!     dns-nameservers 192.0.2.53
+//! iface eth0 inet6 auto
+//! ```
+//!
+//! The `loopback` stanzas are skipped, and the `auto` and `allow-*` lines are
+//! the default of NetworkManager which activates the connections by itself.
+use super::{netmask_prefix, new_connection, parse_address, parse_ip};
+use super::{LegacyConfig, UnsupportedDirective};
+use crate::dispatch::connections::Connection;
+use crate::dispatch::wired::WiredSettings;
+use crate::error::NetworkError;
+use eyre::Result;
+
+/// The options which run commands
+const COMMAND_OPTIONS: &[&str] = &["pre-up", "up", "post-up", "pre-down", "down", "post-down"];
+
+/// The `iface` stanza being parsed, the addresses are parsed at its end as
+/// the `netmask` may follow them.
+struct Stanza {
+    index: usize,
+    is_ipv6: bool,
+    addresses: Vec<String>,
+    netmask: Option<String>,
+}
+
+fn invalid(location: &str, line: &str) -> NetworkError {
+    NetworkError::InvalidArgument(format!("Invalid interfaces {}: {}", location, line))
+}
+
+/// The method of the connection for the method of the stanza, `None` if it
+/// is not supported.
+fn stanza_method(is_ipv6: bool, method: &str) -> Option<&'static str> {
+    match (is_ipv6, method) {
+        (_, "static") => Some("manual"),
+        (false, "dhcp") => Some("auto"),
+        (true, "dhcp") => Some("dhcp"),
+        (true, "auto") => Some("auto"),
+        (_, "manual") => Some("disabled"),
+        _ => None,
+    }
+}
+
+/// Parse the addresses of the stanza with its netmask, a dotted netmask or a
+/// prefix length.
+fn finish_stanza(stanza: Option<Stanza>, connections: &mut [Connection]) -> Result<()> {
+    let stanza = match stanza {
+        Some(stanza) => stanza,
+        None => return Ok(()),
+    };
+    let prefix = match stanza.netmask {
+        Some(netmask) => {
+            let prefix = netmask
+                .parse::<u8>()
+                .ok()
+                .or_else(|| netmask_prefix(&netmask));
+            Some(prefix.ok_or_else(|| invalid("netmask", &netmask))?)
+        }
+        None => None,
+    };
+    let connection = &mut connections[stanza.index];
+    let ipinfo = if stanza.is_ipv6 {
+        &mut connection.ip6info
+    } else {
+        &mut connection.ip4info
+    };
+    for address in stanza.addresses.iter() {
+        ipinfo.addresses.push(parse_address(address, prefix)?);
+    }
+    Ok(())
+}
+
+/// The lines joined with their continuations, with the numbers of their
+/// first lines.
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = vec![];
+    let mut continued = false;
+    for (number, line) in content.lines().enumerate() {
+        let (line, continues) = match line.trim_end().strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        match lines.last_mut() {
+            Some((_, last)) if continued => {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+            _ => lines.push((number + 1, line.trim().to_string())),
+        }
+        continued = continues;
+    }
+    lines
+}
+
+/// Parse the interfaces file.
+pub(super) fn parse_interfaces(content: &str) -> Result<LegacyConfig> {
+    let mut config = LegacyConfig::default();
+    let mut stanza: Option<Stanza> = None;
+    // Whether the options belong to a skipped stanza
+    let mut skipped = false;
+    for (number, line) in logical_lines(content) {
+        let location = format!("line {}", number);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (keyword, args) = (words[0], &words[1..]);
+        match keyword {
+            "auto" | "allow-auto" | "allow-hotplug" => {}
+            "iface" => {
+                finish_stanza(stanza.take(), &mut config.connections)?;
+                let (interface, family, method) = match args {
+                    [interface, family, method, ..] => (*interface, *family, *method),
+                    _ => bail!(invalid(&location, &line)),
+                };
+                skipped = true;
+                if method == "loopback" {
+                    continue;
+                }
+                let is_ipv6 = match family {
+                    "inet" => false,
+                    "inet6" => true,
+                    _ => {
+                        config.unsupported.push(UnsupportedDirective::new(
+                            location,
+                            line.as_str(),
+                            "only the inet and inet6 families are supported",
+                        ));
+                        continue;
+                    }
+                };
+                let method = match stanza_method(is_ipv6, method) {
+                    Some(method) => method,
+                    None => {
+                        config.unsupported.push(UnsupportedDirective::new(
+                            location,
+                            line.as_str(),
+                            "the method is not supported",
+                        ));
+                        continue;
+                    }
+                };
+                let index = match config.connections.iter().position(|x| x.name == interface) {
+                    Some(index) => index,
+                    None => {
+                        let connection = new_connection(interface, Some(interface.to_string()));
+                        config.connections.push(connection);
+                        config.connections.len() - 1
+                    }
+                };
+                let connection = &mut config.connections[index];
+                if is_ipv6 {
+                    connection.ip6info.method = method.into();
+                } else {
+                    connection.ip4info.method = method.into();
+                }
+                skipped = false;
+                stanza = Some(Stanza {
+                    index,
+                    is_ipv6,
+                    addresses: vec![],
+                    netmask: None,
+                });
+            }
+            "source" | "source-directory" | "mapping" | "no-auto-down" | "no-scripts" => {
+                finish_stanza(stanza.take(), &mut config.connections)?;
+                // The options of a mapping belong to it
+                skipped = true;
+                config.unsupported.push(UnsupportedDirective::new(
+                    location,
+                    line.as_str(),
+                    "the directive is not supported",
+                ));
+            }
+            _ if skipped => {}
+            _ => {
+                let current = match stanza.as_mut() {
+                    Some(current) => current,
+                    None => {
+                        config.unsupported.push(UnsupportedDirective::new(
+                            location,
+                            line.as_str(),
+                            "the directive is not supported",
+                        ));
+                        continue;
+                    }
+                };
+                let connection = &mut config.connections[current.index];
+                let ipinfo = if current.is_ipv6 {
+                    &mut connection.ip6info
+                } else {
+                    &mut connection.ip4info
+                };
+                match (keyword, args) {
+                    ("address", [address]) => current.addresses.push(address.to_string()),
+                    ("netmask", [netmask]) => current.netmask = Some(netmask.to_string()),
+                    ("gateway", [gateway]) => ipinfo.gateway = Some(parse_ip(gateway)?),
+                    ("dns-search", domains) => {
+                        ipinfo.dns_search = Some(domains.iter().map(|x| x.to_string()).collect())
+                    }
+                    ("dns-nameservers", servers) => {
+                        for server in servers {
+                            let server = parse_ip(server)?;
+                            if server.is_ipv4() {
+                                connection.ip4info.dns.push(server);
+                            } else {
+                                connection.ip6info.dns.push(server);
+                            }
+                        }
+                    }
+                    ("mtu", [mtu]) => {
+                        let mtu = mtu.parse().map_err(|_| invalid(&location, &line))?;
+                        let wired = connection.wired.get_or_insert_with(WiredSettings::default);
+                        wired.mtu = Some(mtu);
+                    }
+                    ("hwaddress", [mac]) | ("hwaddress", ["ether", mac]) => {
+                        let wired = connection.wired.get_or_insert_with(WiredSettings::default);
+                        wired.cloned_mac = Some(mac.to_lowercase());
+                    }
+                    _ if COMMAND_OPTIONS.contains(&keyword) => {
+                        config.unsupported.push(UnsupportedDirective::new(
+                            location,
+                            line.as_str(),
+                            "the commands are not run by NetworkManager",
+                        ))
+                    }
+                    _ => config.unsupported.push(UnsupportedDirective::new(
+                        location,
+                        line.as_str(),
+                        "the option is not supported",
+                    )),
+                }
+            }
+        }
+    }
+    finish_stanza(stanza, &mut config.connections)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_logical_lines() {
+        let lines = logical_lines("iface eth0 inet static\n  up ip link \\\n    set eth0 up\n");
+        assert_eq!(
+            lines,
+            vec![
+                (1, "iface eth0 inet static".to_string()),
+                (2, "up ip link set eth0 up".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_netmask_after_address() {
+        let content = "iface eth0 inet static\n address 192.0.2.10\n netmask 255.255.255.0\n";
+        let config = parse_interfaces(content).unwrap();
+        assert_eq!(
+            config.connections[0].ip4info.addresses,
+            vec!["192.0.2.10/24".parse().unwrap()]
+        );
+    }
+}
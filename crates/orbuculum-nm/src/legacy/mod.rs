@@ -0,0 +1,294 @@
+//! Legacy Module
+//!
+//! The module turns the network configurations of the older appliances into
+//! orbuculum `Connection`s, so that they are migrated to NetworkManager. The
+//! supported formats are:
+//!
+//! - `ifcfg`: an `ifcfg-<name>` file of the ifcfg-rh (RHEL, CentOS) network
+//!   scripts. The `route-<name>` files are not read.
+//! - `netplan`: a netplan YAML document, only the `ethernets` are supported.
+//! - `interfaces`: the Debian `/etc/network/interfaces` file.
+//!
+//! The directives which the connections can't represent are reported as
+//! `UnsupportedDirective`s instead of being dropped, and the invalid values
+//! are refused. An IP family which is not configured is disabled.
+//!
+//! Sub Modules:
+//! - `ifcfg`: parses the ifcfg-rh files.
+//! - `interfaces`: parses the Debian interfaces files.
+//! - `netplan`: parses the netplan documents.
+mod ifcfg;
+mod interfaces;
+mod netplan;
+
+use crate::backend::{with_checkpoint, NetworkBackend};
+use crate::dispatch::settings::SettingsDocument;
+use crate::error::NetworkError;
+use crate::net::NetInfo;
+use crate::{Connection, NetworkResponse};
+use eyre::Result;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+
+/// The formats of the legacy configurations
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LegacyFormat {
+    Ifcfg,
+    Netplan,
+    Interfaces,
+}
+
+impl FromStr for LegacyFormat {
+    type Err = NetworkError;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "ifcfg" => Ok(LegacyFormat::Ifcfg),
+            "netplan" => Ok(LegacyFormat::Netplan),
+            "interfaces" => Ok(LegacyFormat::Interfaces),
+            _ => Err(NetworkError::InvalidArgument(format!(
+                "Invalid legacy format {}, expected one of ifcfg, netplan, interfaces",
+                format
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for LegacyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            LegacyFormat::Ifcfg => "ifcfg",
+            LegacyFormat::Netplan => "netplan",
+            LegacyFormat::Interfaces => "interfaces",
+        };
+        write!(f, "{}", format)
+    }
+}
+
+/// A directive of the legacy configuration which is not migrated
+///
+/// * `location`: `line <number>` in the ifcfg and interfaces files, the key
+///   path in the netplan document, e.g. `network.ethernets.eth0.wakeonlan`.
+/// * `directive`: the key or the line of the directive.
+/// * `reason`: why it is not migrated.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct UnsupportedDirective {
+    pub location: String,
+    pub directive: String,
+    pub reason: String,
+}
+
+impl UnsupportedDirective {
+    fn new(location: impl Into<String>, directive: impl Into<String>, reason: &str) -> Self {
+        UnsupportedDirective {
+            location: location.into(),
+            directive: directive.into(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// The connections parsed from a legacy configuration
+///
+/// The uuid of a connection is empty until it is created, unless the
+/// configuration gives it, e.g. the `UUID` of an ifcfg file.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct LegacyConfig {
+    pub connections: Vec<Connection>,
+    pub unsupported: Vec<UnsupportedDirective>,
+}
+
+/// Parse the legacy configuration, nothing is changed. `name` is the file
+/// name, which is the connection name of an ifcfg file without `NAME` and
+/// `DEVICE`.
+pub fn parse_legacy_config(
+    format: LegacyFormat,
+    name: &str,
+    content: &str,
+) -> Result<LegacyConfig> {
+    match format {
+        LegacyFormat::Ifcfg => ifcfg::parse_ifcfg(name, content),
+        LegacyFormat::Netplan => netplan::parse_netplan(content),
+        LegacyFormat::Interfaces => interfaces::parse_interfaces(content),
+    }
+}
+
+/// Parse the legacy configuration and create its connections, a connection
+/// with the uuid of an existing one replaces it. The connections are created
+/// under a checkpoint of their devices, so that all of them are rolled back
+/// if one fails.
+pub(crate) async fn import_legacy_config<B: NetworkBackend>(
+    backend: &B,
+    format: LegacyFormat,
+    name: String,
+    content: String,
+) -> Result<NetworkResponse> {
+    let mut config = parse_legacy_config(format, &name, &content)?;
+    let interfaces: Vec<String> = config
+        .connections
+        .iter()
+        .filter_map(|x| x.interface.clone().or_else(|| x.mac.clone()))
+        .collect();
+    let connections = &mut config.connections;
+    with_checkpoint(backend, &interfaces, async move {
+        for connection in connections.iter_mut() {
+            let uuid = backend
+                .put_connection_settings(connection_document(connection))
+                .await?
+                .into_uuid()
+                .ok_or_else(|| NetworkError::Backend("The connection is not created".into()))?;
+            connection.uuid = uuid;
+        }
+        Ok(())
+    })
+    .await?;
+    Ok(NetworkResponse::LegacyConfig(config))
+}
+
+fn ip_document(ipinfo: &NetInfo) -> BTreeMap<String, Value> {
+    let mut properties = BTreeMap::new();
+    properties.insert("method".to_string(), Value::from(ipinfo.method.as_str()));
+    if !ipinfo.addresses.is_empty() {
+        let addresses: Vec<String> = ipinfo.addresses.iter().map(|x| x.to_string()).collect();
+        properties.insert("addresses".into(), addresses.into());
+    }
+    if let Some(gateway) = ipinfo.gateway {
+        properties.insert("gateway".into(), gateway.to_string().into());
+    }
+    if !ipinfo.dns.is_empty() {
+        let dns: Vec<String> = ipinfo.dns.iter().map(|x| x.to_string()).collect();
+        properties.insert("dns".into(), dns.into());
+    }
+    if let Some(dns_search) = ipinfo.dns_search.as_ref().filter(|x| !x.is_empty()) {
+        properties.insert("dns-search".into(), dns_search.clone().into());
+    }
+    if let Some(routes) = ipinfo.routes.as_ref().filter(|x| !x.is_empty()) {
+        let routes = serde_json::to_value(routes).unwrap_or_default();
+        properties.insert("routes".into(), routes);
+    }
+    let flags = [
+        ("ignore-auto-dns", ipinfo.ignore_auto_dns),
+        ("never-default", ipinfo.never_default),
+        ("may-fail", ipinfo.may_fail),
+    ];
+    for (property, flag) in flags {
+        if let Some(flag) = flag {
+            properties.insert(property.into(), flag.into());
+        }
+    }
+    properties
+}
+
+/// The settings document of the parsed wired connection
+pub(crate) fn connection_document(connection: &Connection) -> SettingsDocument {
+    let mut document = SettingsDocument::new();
+    let setting = document.entry("connection".into()).or_default();
+    setting.insert("id".into(), connection.name.as_str().into());
+    setting.insert("type".into(), "802-3-ethernet".into());
+    if !connection.uuid.is_empty() {
+        setting.insert("uuid".into(), connection.uuid.as_str().into());
+    }
+    if let Some(interface) = connection.interface.as_ref() {
+        setting.insert("interface-name".into(), interface.as_str().into());
+    }
+    let setting = document.entry("802-3-ethernet".into()).or_default();
+    if let Some(mac) = connection.mac.as_ref() {
+        setting.insert("mac-address".into(), mac.as_str().into());
+    }
+    if let Some(wired) = connection.wired.as_ref() {
+        if let Some(mtu) = wired.mtu {
+            setting.insert("mtu".into(), mtu.into());
+        }
+        if let Some(cloned_mac) = wired.cloned_mac.as_ref() {
+            setting.insert("cloned-mac-address".into(), cloned_mac.as_str().into());
+        }
+    }
+    document.insert("ipv4".into(), ip_document(&connection.ip4info));
+    document.insert("ipv6".into(), ip_document(&connection.ip6info));
+    document
+}
+
+/// The connection without addresses, both families are disabled.
+pub(crate) fn new_connection(name: &str, interface: Option<String>) -> Connection {
+    let disabled = NetInfo {
+        method: "disabled".into(),
+        ..Default::default()
+    };
+    Connection {
+        name: name.to_string(),
+        uuid: String::new(),
+        interface,
+        mac: None,
+        ip4info: disabled.clone(),
+        ip6info: disabled,
+        wired: None,
+    }
+}
+
+/// The prefix length of an IPv4 netmask, e.g. 24 for `255.255.255.0`
+fn netmask_prefix(netmask: &str) -> Option<u8> {
+    let netmask = u32::from(netmask.parse::<Ipv4Addr>().ok()?);
+    let prefix = netmask.leading_ones();
+    if netmask.checked_shl(prefix).unwrap_or(0) != 0 {
+        return None;
+    }
+    Some(prefix as u8)
+}
+
+/// Parse the address with the prefix length, e.g. `192.0.2.10/24`, or the
+/// address alone with the prefix length given apart.
+fn parse_address(address: &str, prefix: Option<u8>) -> Result<IpNet> {
+    let invalid = || NetworkError::InvalidArgument(format!("Invalid address {}", address));
+    if address.contains('/') {
+        return address.parse().map_err(|_| invalid().into());
+    }
+    let addr: IpAddr = address.parse().map_err(|_| invalid())?;
+    let prefix = prefix.unwrap_or(if addr.is_ipv4() { 32 } else { 128 });
+    IpNet::new(addr, prefix).map_err(|_| invalid().into())
+}
+
+fn parse_ip(address: &str) -> Result<IpAddr> {
+    address.parse().map_err(|_| {
+        NetworkError::InvalidArgument(format!("Invalid ip address {}", address)).into()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("255.255.255.0", Some(24))]
+    #[case("255.255.0.0", Some(16))]
+    #[case("0.0.0.0", Some(0))]
+    #[case("255.255.255.255", Some(32))]
+    #[case("255.0.255.0", None)]
+    #[case("24", None)]
+    fn test_netmask_prefix(#[case] netmask: &str, #[case] expected: Option<u8>) {
+        assert_eq!(netmask_prefix(netmask), expected);
+    }
+
+    #[test]
+    fn test_connection_document() {
+        let mut connection = new_connection("lan", Some("eth0".into()));
+        connection.ip4info.method = "manual".into();
+        connection.ip4info.addresses = vec!["192.0.2.10/24".parse().unwrap()];
+        connection.ip4info.never_default = Some(true);
+        let document = connection_document(&connection);
+        assert_eq!(document["connection"]["interface-name"], "eth0");
+        assert!(!document["connection"].contains_key("uuid"));
+        assert_eq!(
+            document["ipv4"]["addresses"],
+            serde_json::json!(["192.0.2.10/24"])
+        );
+        assert_eq!(document["ipv4"]["never-default"], true);
+        assert_eq!(document["ipv6"]["method"], "disabled");
+    }
+}
@@ -0,0 +1,368 @@
+//! The netplan documents
+//!
+//! Only the `ethernets` of a version 2 document are migrated, e.g.
+//!
+//! ```yaml
+//! network:
+//!   version: 2
+//!   ethernets:
+//!     eth0:
+//!       addresses: [192.0.2.10/24]
+//!       routes:
+//!         - to: default
+//!           via: 192.0.2.1
+//!       nameservers:
+//!         addresses: [192.0.2.53]
+//! ```
+//!
+//! The booleans may be given as `true`/`false` or `yes`/`no`, and the
+//! interface is the id of the ethernet unless it is matched by `name` or
+//! `macaddress`.
+use super::{new_connection, parse_address, parse_ip};
+use super::{LegacyConfig, UnsupportedDirective};
+use crate::dispatch::connections::Connection;
+use crate::dispatch::wired::WiredSettings;
+use crate::error::NetworkError;
+use crate::net::{NetInfo, Route};
+use eyre::Result;
+use ipnet::IpNet;
+use libc::{AF_INET, AF_INET6};
+use serde_yaml::{Mapping, Value};
+use std::net::IpAddr;
+
+fn invalid(location: &str, message: &str) -> NetworkError {
+    NetworkError::InvalidArgument(format!("Invalid netplan {}: {}", location, message))
+}
+
+fn key_name(key: &Value) -> String {
+    scalar(key).unwrap_or_default()
+}
+
+/// The string of the scalar, the numbers and booleans are converted.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn mapping<'a>(value: &'a Value, location: &str) -> Result<&'a Mapping> {
+    value
+        .as_mapping()
+        .ok_or_else(|| invalid(location, "expected a mapping").into())
+}
+
+fn sequence<'a>(value: &'a Value, location: &str) -> Result<&'a Vec<Value>> {
+    value
+        .as_sequence()
+        .ok_or_else(|| invalid(location, "expected a list").into())
+}
+
+fn boolean(value: &Value, location: &str) -> Result<bool> {
+    let flag = match value {
+        Value::Bool(flag) => Some(*flag),
+        Value::String(flag) => match flag.to_lowercase().as_str() {
+            "true" | "yes" | "on" => Some(true),
+            "false" | "no" | "off" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    };
+    flag.ok_or_else(|| invalid(location, "expected a boolean").into())
+}
+
+fn string(value: &Value, location: &str) -> Result<String> {
+    scalar(value).ok_or_else(|| invalid(location, "expected a string").into())
+}
+
+fn number(value: &Value, location: &str) -> Result<u32> {
+    scalar(value)
+        .and_then(|x| x.parse().ok())
+        .ok_or_else(|| invalid(location, "expected a number").into())
+}
+
+/// The route of the `routes` list, `to: default` is the default route of the
+/// family of `via`.
+fn parse_route(
+    value: &Value,
+    location: &str,
+    unsupported: &mut Vec<UnsupportedDirective>,
+) -> Result<Route> {
+    let mut route = Route {
+        metric: -1,
+        ..Default::default()
+    };
+    let mut to = None;
+    for (key, value) in mapping(value, location)? {
+        let key = key_name(key);
+        let location = format!("{}.{}", location, key);
+        match key.as_str() {
+            "to" => to = Some(string(value, &location)?),
+            "via" => route.next_hop = Some(parse_ip(&string(value, &location)?)?),
+            "metric" => route.metric = number(value, &location)?.into(),
+            "table" => route.table = Some(number(value, &location)?),
+            "on-link" => {
+                let flag = boolean(value, &location)?;
+                route.attributes.insert("onlink".into(), flag.to_string());
+            }
+            "from" => {
+                let source = parse_ip(&string(value, &location)?)?;
+                route.attributes.insert("src".into(), source.to_string());
+            }
+            _ => unsupported.push(UnsupportedDirective::new(
+                location,
+                key,
+                "the route key is not supported",
+            )),
+        }
+    }
+    let to = to.ok_or_else(|| invalid(location, "the route has no destination"))?;
+    route.dest = match to.as_str() {
+        "default" => match route.next_hop {
+            Some(IpAddr::V6(_)) => "::/0".parse()?,
+            _ => "0.0.0.0/0".parse()?,
+        },
+        _ => parse_address(&to, None)?,
+    };
+    route.family = match route.dest {
+        IpNet::V4(_) => AF_INET,
+        IpNet::V6(_) => AF_INET6,
+    };
+    Ok(route)
+}
+
+/// Set `ignore_auto_dns` by the `use-dns` of the DHCP overrides.
+fn parse_overrides(
+    value: &Value,
+    location: &str,
+    ipinfo: &mut NetInfo,
+    unsupported: &mut Vec<UnsupportedDirective>,
+) -> Result<()> {
+    for (key, value) in mapping(value, location)? {
+        let key = key_name(key);
+        let location = format!("{}.{}", location, key);
+        match key.as_str() {
+            "use-dns" => ipinfo.ignore_auto_dns = Some(!boolean(value, &location)?),
+            _ => unsupported.push(UnsupportedDirective::new(
+                location,
+                key,
+                "the DHCP override is not supported",
+            )),
+        }
+    }
+    Ok(())
+}
+
+fn parse_ethernet(
+    id: &str,
+    settings: &Value,
+    unsupported: &mut Vec<UnsupportedDirective>,
+) -> Result<Connection> {
+    let prefix = format!("network.ethernets.{}", id);
+    let mut connection = new_connection(id, Some(id.to_string()));
+    let mut wired = WiredSettings::default();
+    let mut addresses = vec![];
+    let mut routes = vec![];
+    let mut dns = vec![];
+    let mut dns_search = vec![];
+    let (mut dhcp4, mut dhcp6, mut accept_ra) = (false, false, false);
+    for (key, value) in mapping(settings, &prefix)? {
+        let key = key_name(key);
+        let location = format!("{}.{}", prefix, key);
+        match key.as_str() {
+            "match" => {
+                connection.interface = None;
+                for (key, value) in mapping(value, &location)? {
+                    let key = key_name(key);
+                    let location = format!("{}.{}", location, key);
+                    match key.as_str() {
+                        "name" => {
+                            let name = string(value, &location)?;
+                            if name.contains(['*', '?', '[']) {
+                                unsupported.push(UnsupportedDirective::new(
+                                    location,
+                                    key,
+                                    "the interface name patterns are not supported",
+                                ));
+                            } else {
+                                connection.interface = Some(name);
+                            }
+                        }
+                        "macaddress" => {
+                            connection.mac = Some(string(value, &location)?.to_lowercase())
+                        }
+                        _ => unsupported.push(UnsupportedDirective::new(
+                            location,
+                            key,
+                            "the match key is not supported",
+                        )),
+                    }
+                }
+            }
+            "dhcp4" => dhcp4 = boolean(value, &location)?,
+            "dhcp6" => dhcp6 = boolean(value, &location)?,
+            "accept-ra" => accept_ra = boolean(value, &location)?,
+            "addresses" => {
+                for (index, address) in sequence(value, &location)?.iter().enumerate() {
+                    let location = format!("{}.{}", location, index);
+                    match address.as_str() {
+                        Some(address) => addresses.push(parse_address(address, None)?),
+                        None => unsupported.push(UnsupportedDirective::new(
+                            location,
+                            key.as_str(),
+                            "the address options are not supported",
+                        )),
+                    }
+                }
+            }
+            "gateway4" => connection.ip4info.gateway = Some(parse_ip(&string(value, &location)?)?),
+            "gateway6" => connection.ip6info.gateway = Some(parse_ip(&string(value, &location)?)?),
+            "nameservers" => {
+                for (key, value) in mapping(value, &location)? {
+                    let key = key_name(key);
+                    let location = format!("{}.{}", location, key);
+                    match key.as_str() {
+                        "addresses" => {
+                            for server in sequence(value, &location)? {
+                                dns.push(parse_ip(&string(server, &location)?)?);
+                            }
+                        }
+                        "search" => {
+                            for domain in sequence(value, &location)? {
+                                dns_search.push(string(domain, &location)?);
+                            }
+                        }
+                        _ => unsupported.push(UnsupportedDirective::new(
+                            location,
+                            key,
+                            "the nameservers key is not supported",
+                        )),
+                    }
+                }
+            }
+            "routes" => {
+                for (index, route) in sequence(value, &location)?.iter().enumerate() {
+                    let location = format!("{}.{}", location, index);
+                    routes.push(parse_route(route, &location, unsupported)?);
+                }
+            }
+            "dhcp4-overrides" => {
+                parse_overrides(value, &location, &mut connection.ip4info, unsupported)?
+            }
+            "dhcp6-overrides" => {
+                parse_overrides(value, &location, &mut connection.ip6info, unsupported)?
+            }
+            "mtu" => wired.mtu = Some(number(value, &location)?),
+            "macaddress" => wired.cloned_mac = Some(string(value, &location)?.to_lowercase()),
+            _ => unsupported.push(UnsupportedDirective::new(
+                location,
+                key,
+                "the key is not supported",
+            )),
+        }
+    }
+    if wired.mtu.is_some() || wired.cloned_mac.is_some() {
+        connection.wired = Some(wired);
+    }
+
+    let (ip4_addresses, ip6_addresses): (Vec<IpNet>, Vec<IpNet>) = addresses
+        .into_iter()
+        .partition(|x| matches!(x, IpNet::V4(_)));
+    let ip4info = &mut connection.ip4info;
+    ip4info.method = if dhcp4 {
+        "auto".into()
+    } else if !ip4_addresses.is_empty() {
+        "manual".into()
+    } else {
+        "disabled".into()
+    };
+    ip4info.addresses = ip4_addresses;
+    let ip6info = &mut connection.ip6info;
+    ip6info.method = if dhcp6 || accept_ra {
+        "auto".into()
+    } else if !ip6_addresses.is_empty() {
+        "manual".into()
+    } else {
+        "disabled".into()
+    };
+    ip6info.addresses = ip6_addresses;
+    for route in routes {
+        let ipinfo = match route.family {
+            AF_INET => &mut connection.ip4info,
+            _ => &mut connection.ip6info,
+        };
+        ipinfo.routes.get_or_insert_with(Vec::new).push(route);
+    }
+    connection.ip4info.dns = dns.iter().filter(|x| x.is_ipv4()).cloned().collect();
+    connection.ip6info.dns = dns.iter().filter(|x| x.is_ipv6()).cloned().collect();
+    connection.ip4info.dns_search = Some(dns_search.clone());
+    connection.ip6info.dns_search = Some(dns_search);
+    Ok(connection)
+}
+
+/// Parse the netplan document.
+pub(super) fn parse_netplan(content: &str) -> Result<LegacyConfig> {
+    let document: Value =
+        serde_yaml::from_str(content).map_err(|err| invalid("document", &err.to_string()))?;
+    let network = document
+        .get("network")
+        .ok_or_else(|| invalid("document", "the network key is required"))?;
+    let mut config = LegacyConfig::default();
+    for (key, value) in mapping(network, "network")? {
+        let key = key_name(key);
+        let location = format!("network.{}", key);
+        match key.as_str() {
+            "version" => {
+                if number(value, &location)? != 2 {
+                    bail!(invalid(&location, "only the version 2 is supported"))
+                }
+            }
+            // Both networkd and NetworkManager are replaced by NetworkManager
+            "renderer" => {}
+            "ethernets" => {
+                for (id, settings) in mapping(value, &location)? {
+                    let id = key_name(id);
+                    let connection = parse_ethernet(&id, settings, &mut config.unsupported)?;
+                    config.connections.push(connection);
+                }
+            }
+            _ => config.unsupported.push(UnsupportedDirective::new(
+                location,
+                key,
+                "only the ethernets are supported",
+            )),
+        }
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("to: default\nvia: 192.0.2.1", "0.0.0.0/0", AF_INET)]
+    #[case("to: default\nvia: 2001:db8::1", "::/0", AF_INET6)]
+    #[case("to: 198.51.100.0/24\nvia: 192.0.2.1", "198.51.100.0/24", AF_INET)]
+    #[case("to: 198.51.100.7", "198.51.100.7/32", AF_INET)]
+    fn test_parse_route(#[case] route: &str, #[case] dest: &str, #[case] family: i32) {
+        let value: Value = serde_yaml::from_str(route).unwrap();
+        let mut unsupported = vec![];
+        let route = parse_route(&value, "routes.0", &mut unsupported).unwrap();
+        assert_eq!(route.dest, dest.parse::<IpNet>().unwrap());
+        assert_eq!(route.family, family);
+        assert!(unsupported.is_empty());
+    }
+
+    #[rstest]
+    #[case("yes", true)]
+    #[case("false", false)]
+    #[case("on", true)]
+    fn test_boolean(#[case] value: &str, #[case] expected: bool) {
+        let value: Value = serde_yaml::from_str(value).unwrap();
+        assert_eq!(boolean(&value, "dhcp4").unwrap(), expected);
+    }
+}
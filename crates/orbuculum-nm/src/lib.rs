@@ -5,6 +5,13 @@
 //!
 //! And , you can list all the devices' information as well.
 //!
+//! The commands are answered by a `NetworkBackend`. `LibnmBackend` talks to the
+//! NetworkManager daemon, while `FakeBackend` keeps everything in memory for
+//! the environments without NetworkManager.
+//!
+//! The changes of devices and connections are published as `NetworkEvent`s,
+//! subscribe them with `State::subscribe`.
+//!
 //! All commands are defined in the `NetworkCommand` enumeration.
 //!
 //! # How to use the library
@@ -16,19 +23,45 @@
 #[macro_use]
 extern crate eyre;
 
+mod backend;
 mod dispatch;
+mod error;
+mod events;
+mod legacy;
 mod net;
+mod netstate;
 mod tokio_client;
 mod utils;
 
 pub use crate::utils::gather_link_modes;
-pub use dispatch::connections::Connection;
-pub use dispatch::devices::NetDevice;
-use serde_json::Value;
+pub use backend::{FakeBackend, LibnmBackend, NetworkBackend};
+pub use dispatch::active_connections::ActiveConnectionInfo;
+pub use dispatch::bond::BondConnection;
+pub use dispatch::bridge::{BridgeConnection, BridgePort};
+pub use dispatch::checkpoint::CheckpointInfo;
+pub use dispatch::connections::{ApplyStrategy, Connection};
+pub use dispatch::connectivity::{ConnectivityInfo, DeviceConnectivity};
+pub use dispatch::devices::{ConnectionItem, NetDevice};
+pub use dispatch::ip_tunnel::IpTunnelConnection;
+pub use dispatch::keyfile::Keyfile;
+pub use dispatch::settings::SettingsDocument;
+pub use dispatch::virtual_devices::VirtualDevice;
+pub use dispatch::vlan::VlanConnection;
+pub use dispatch::vxlan::VxlanConnection;
+pub use dispatch::wired::WiredSettings;
+pub use dispatch::wireguard::{WireGuardConnection, WireGuardPeer, WireGuardPeerStats};
+pub use error::{NetworkError, PropertyError};
+pub use events::{EventSender, NetworkEvent};
+pub use legacy::{parse_legacy_config, LegacyConfig, LegacyFormat, UnsupportedDirective};
 
-pub use net::{NetInfo, Route};
+pub use net::{NetInfo, Route, RoutingRule};
+pub use netstate::{
+    AddressState, DnsConfig, DnsState, HostnameState, InterfaceState, InterfaceStatus, IpState,
+    NetworkState, RouteEntry, RouteStatus, RoutesState, StateChange,
+};
 pub use tokio_client::{
-    create_channel, run_network_manager_loop, send_command, NetworkRequest, State, TokioResponder,
+    create_channel, run_network_backend_loop, run_network_manager_loop, send_command,
+    NetworkRequest, State, TokioResponder,
 };
 
 /// The network command list
@@ -37,34 +70,265 @@ pub use tokio_client::{
 pub enum NetworkCommand {
     // list
     ListDeivces,
+    GetDevice(String),
     CreateWiredConnection(String, String),
     ListConnections,
+    ListActiveConnections,
     GetConnection(String),
     GetNetworking,
+    GetConnectivity,
     GetHostname,
     // modify
     SetNetworking(bool),
+    /// Check the connectivity again
+    CheckConnectivity,
     SetHostname(String),
     SetManage(String, bool),
-    UpdateConnection(Value),
+    UpdateConnection(Connection),
+    /// Create or replace a connection from a settings document
+    PutConnectionSettings(SettingsDocument),
+    /// Export the connection with the uuid, or all the connections, as
+    /// keyfiles, the secrets are included if the flag is set
+    ExportKeyfiles(Option<String>, bool),
+    /// Import the keyfile text as a new or replacing connection
+    ImportKeyfile(String),
+    /// Create the connections of a legacy configuration, by the format, the
+    /// file name and the content
+    ImportLegacyConfig(LegacyFormat, String, String),
+    /// Show the declarative network state
+    ShowNetworkState,
+    /// Reach the network state, only the planned changes are returned if the
+    /// flag of the dry run is set
+    ApplyNetworkState(NetworkState, bool),
     Reactive(String),
     RenameConnection(String, String),
     DeleteConnection(String),
+    DeleteConnectionByUuid(String),
+    AddRoute(String, Route),
+    RemoveRoute(String, Route),
+    // vlan
+    CreateVlan(VlanConnection),
+    UpdateVlan(VlanConnection),
+    GetVlan(String),
+    DeleteVlan(String),
+    // bond
+    CreateBond(BondConnection),
+    GetBond(String),
+    AttachBondPort(String, String),
+    DetachBondPort(String, String),
+    // bridge
+    CreateBridge(BridgeConnection),
+    UpdateBridge(BridgeConnection),
+    GetBridge(String),
+    /// The bridge uuid, the port, and whether the ip configurations of the
+    /// device are moved onto the bridge
+    AttachBridgePort(String, BridgePort, bool),
+    DetachBridgePort(String, String),
+    // wireguard
+    CreateWireGuard(WireGuardConnection),
+    UpdateWireGuard(WireGuardConnection),
+    GetWireGuard(String),
+    // tunnels
+    CreateIpTunnel(IpTunnelConnection),
+    UpdateIpTunnel(IpTunnelConnection),
+    GetIpTunnel(String),
+    CreateVxlan(VxlanConnection),
+    UpdateVxlan(VxlanConnection),
+    GetVxlan(String),
+    // virtual devices
+    CreateVirtualDevice(VirtualDevice),
+    /// The interface name of the virtual device
+    DeleteVirtualDevice(String),
+    // checkpoint
+    /// The interface names of the devices, all the devices are covered if
+    /// none is given, and the rollback timeout in seconds
+    BeginCheckpoint(Vec<String>, u32),
+    ConfirmCheckpoint(String),
+    RollbackCheckpoint(String),
 }
 
 /// The network response list
 /// provides all the responses supported by the server.
+#[derive(Debug)]
 pub enum NetworkResponse {
-    Return(Value),
+    Devices(Vec<NetDevice>),
+    Device(NetDevice),
     ListConnection(Vec<Connection>),
+    ActiveConnections(Vec<ActiveConnectionInfo>),
+    Keyfiles(Vec<Keyfile>),
+    LegacyConfig(LegacyConfig),
+    NetworkState(NetworkState),
+    StateChanges(Vec<StateChange>),
+    Connection(Connection),
+    Vlan(VlanConnection),
+    Bond(BondConnection),
+    Bridge(BridgeConnection),
+    WireGuard(WireGuardConnection),
+    IpTunnel(IpTunnelConnection),
+    Vxlan(VxlanConnection),
+    VirtualDevice(VirtualDevice),
+    Checkpoint(CheckpointInfo),
+    Applied(ApplyStrategy),
+    Hostname(String),
+    Networking(bool),
+    Connectivity(ConnectivityInfo),
+    Created { uuid: String },
     Success,
     Failed,
 }
 
 impl NetworkResponse {
-    pub fn into_value(self) -> Option<Value> {
+    pub fn into_devices(self) -> Option<Vec<NetDevice>> {
+        match self {
+            NetworkResponse::Devices(devices) => Some(devices),
+            _ => None,
+        }
+    }
+
+    pub fn into_device(self) -> Option<NetDevice> {
+        match self {
+            NetworkResponse::Device(device) => Some(device),
+            _ => None,
+        }
+    }
+
+    pub fn into_connections(self) -> Option<Vec<Connection>> {
+        match self {
+            NetworkResponse::ListConnection(connections) => Some(connections),
+            _ => None,
+        }
+    }
+
+    pub fn into_keyfiles(self) -> Option<Vec<Keyfile>> {
+        match self {
+            NetworkResponse::Keyfiles(keyfiles) => Some(keyfiles),
+            _ => None,
+        }
+    }
+
+    pub fn into_legacy_config(self) -> Option<LegacyConfig> {
+        match self {
+            NetworkResponse::LegacyConfig(config) => Some(config),
+            _ => None,
+        }
+    }
+
+    pub fn into_network_state(self) -> Option<NetworkState> {
+        match self {
+            NetworkResponse::NetworkState(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    pub fn into_state_changes(self) -> Option<Vec<StateChange>> {
+        match self {
+            NetworkResponse::StateChanges(changes) => Some(changes),
+            _ => None,
+        }
+    }
+
+    pub fn into_active_connections(self) -> Option<Vec<ActiveConnectionInfo>> {
+        match self {
+            NetworkResponse::ActiveConnections(connections) => Some(connections),
+            _ => None,
+        }
+    }
+
+    pub fn into_connection(self) -> Option<Connection> {
+        match self {
+            NetworkResponse::Connection(connection) => Some(connection),
+            _ => None,
+        }
+    }
+
+    pub fn into_vlan(self) -> Option<VlanConnection> {
+        match self {
+            NetworkResponse::Vlan(vlan) => Some(vlan),
+            _ => None,
+        }
+    }
+
+    pub fn into_bond(self) -> Option<BondConnection> {
+        match self {
+            NetworkResponse::Bond(bond) => Some(bond),
+            _ => None,
+        }
+    }
+
+    pub fn into_bridge(self) -> Option<BridgeConnection> {
+        match self {
+            NetworkResponse::Bridge(bridge) => Some(bridge),
+            _ => None,
+        }
+    }
+
+    pub fn into_wireguard(self) -> Option<WireGuardConnection> {
+        match self {
+            NetworkResponse::WireGuard(wireguard) => Some(wireguard),
+            _ => None,
+        }
+    }
+
+    pub fn into_ip_tunnel(self) -> Option<IpTunnelConnection> {
+        match self {
+            NetworkResponse::IpTunnel(tunnel) => Some(tunnel),
+            _ => None,
+        }
+    }
+
+    pub fn into_vxlan(self) -> Option<VxlanConnection> {
+        match self {
+            NetworkResponse::Vxlan(vxlan) => Some(vxlan),
+            _ => None,
+        }
+    }
+
+    pub fn into_virtual_device(self) -> Option<VirtualDevice> {
+        match self {
+            NetworkResponse::VirtualDevice(device) => Some(device),
+            _ => None,
+        }
+    }
+
+    pub fn into_connectivity(self) -> Option<ConnectivityInfo> {
+        match self {
+            NetworkResponse::Connectivity(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    pub fn into_checkpoint(self) -> Option<CheckpointInfo> {
+        match self {
+            NetworkResponse::Checkpoint(checkpoint) => Some(checkpoint),
+            _ => None,
+        }
+    }
+
+    pub fn into_applied(self) -> Option<ApplyStrategy> {
+        match self {
+            NetworkResponse::Applied(strategy) => Some(strategy),
+            _ => None,
+        }
+    }
+
+    pub fn into_hostname(self) -> Option<String> {
+        match self {
+            NetworkResponse::Hostname(hostname) => Some(hostname),
+            _ => None,
+        }
+    }
+
+    pub fn into_networking(self) -> Option<bool> {
+        match self {
+            NetworkResponse::Networking(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    /// The uuid of the created object
+    pub fn into_uuid(self) -> Option<String> {
         match self {
-            NetworkResponse::Return(val) => Some(val),
+            NetworkResponse::Created { uuid } => Some(uuid),
             _ => None,
         }
     }
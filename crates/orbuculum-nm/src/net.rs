@@ -2,18 +2,44 @@
 //!
 //! The `net` moudule contains structures used to represent ip and route objects
 //! and they are the bridges between common rust objects and Glib objects.
-use crate::utils::{addrs_to_string, ipnet_from_string, ipver_human, to_string};
+use crate::error::NetworkError;
+use crate::utils::{
+    addrs_to_string, from_string, ipnet_from_string, ipver_from_human, ipver_human,
+    opt_from_string, opt_to_string, to_string,
+};
 use eyre::Result;
+use glib::{Cast, ToVariant, Variant};
 use ipnet::IpNet;
-use nm::{IPAddress, IPConfig as NMIPConfig, IPRoute, SettingIPConfig, SettingIPConfigExt};
+use nm::{
+    IPAddress, IPConfig as NMIPConfig, IPRoute, IPRoutingRule, SettingIP4Config, SettingIP6Config,
+    SettingIP6ConfigAddrGenMode, SettingIP6ConfigPrivacy, SettingIPConfig, SettingIPConfigExt,
+};
 use serde::{Deserialize, Serialize};
 use std::boxed::Box;
+use std::collections::BTreeMap;
 use std::net::IpAddr;
 
 /// A representation of the net information
 ///
 /// The `NetInfo` type is a combination of addresses, gateway, dns and routes.
 /// The method is to idenitify how the net is initialized.
+///
+/// The `routes` are left unchanged on update when they are not given, an
+/// empty list removes them.
+///
+/// The `route_table` is the table of the routes without an explicit table, `0`
+/// means the main table. The `routing_rules` are the policy routing rules.
+/// Both are left unchanged on update when they are not given.
+///
+/// Besides the `dns` servers, the resolver is configured by the search
+/// domains, the resolver options, the priority of the dns servers and whether
+/// the dns servers from DHCP or SLAAC are ignored. The `dns_priority` and
+/// `ignore_auto_dns` are left unchanged when they are not given.
+///
+/// The method flags are optional too, a flag not given is left unchanged.
+/// An empty `dhcp_hostname` or `dhcp_client_id` unsets it. The
+/// `dhcp_client_id` is only for IPv4, the `addr_gen_mode` and `ip6_privacy`
+/// are only for IPv6.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct NetInfo {
     pub method: String,
@@ -24,8 +50,112 @@ pub struct NetInfo {
     pub addresses: Vec<IpNet>,
     pub gateway: Option<IpAddr>,
     pub dns: Vec<IpAddr>,
-    #[serde(skip_deserializing)]
-    pub routes: Vec<Route>,
+    #[serde(default)]
+    pub dns_search: Option<Vec<String>>,
+    #[serde(default)]
+    pub dns_options: Option<Vec<String>>,
+    #[serde(default)]
+    pub dns_priority: Option<i32>,
+    #[serde(default)]
+    pub ignore_auto_dns: Option<bool>,
+    #[serde(default)]
+    pub routes: Option<Vec<Route>>,
+    #[serde(default)]
+    pub route_table: Option<u32>,
+    #[serde(default)]
+    pub routing_rules: Option<Vec<RoutingRule>>,
+    #[serde(default)]
+    pub never_default: Option<bool>,
+    #[serde(default)]
+    pub may_fail: Option<bool>,
+    #[serde(default)]
+    pub dhcp_client_id: Option<String>,
+    #[serde(default)]
+    pub dhcp_hostname: Option<String>,
+    #[serde(default)]
+    pub dhcp_send_hostname: Option<bool>,
+    #[serde(default)]
+    pub dhcp_timeout: Option<i32>,
+    #[serde(default)]
+    pub addr_gen_mode: Option<String>,
+    #[serde(default)]
+    pub ip6_privacy: Option<String>,
+}
+
+/// The IPv6 address generation modes, and their names in `NetInfo`
+const ADDR_GEN_MODES: &[(SettingIP6ConfigAddrGenMode, &str)] = &[
+    (SettingIP6ConfigAddrGenMode::Eui64, "eui64"),
+    (SettingIP6ConfigAddrGenMode::StablePrivacy, "stable-privacy"),
+];
+
+/// The IPv6 privacy extensions, and their names in `NetInfo`
+const IP6_PRIVACIES: &[(SettingIP6ConfigPrivacy, &str)] = &[
+    (SettingIP6ConfigPrivacy::Unknown, "unknown"),
+    (SettingIP6ConfigPrivacy::Disabled, "disabled"),
+    (
+        SettingIP6ConfigPrivacy::PreferPublicAddr,
+        "prefer-public-addr",
+    ),
+    (SettingIP6ConfigPrivacy::PreferTempAddr, "prefer-temp-addr"),
+];
+
+fn human_name<T: PartialEq>(table: &[(T, &'static str)], value: T) -> Option<String> {
+    table
+        .iter()
+        .find(|(x, _)| *x == value)
+        .map(|(_, name)| name.to_string())
+}
+
+fn from_human_name<T: Copy>(table: &[(T, &str)], kind: &str, name: &str) -> Result<T> {
+    let value = table.iter().find(|(_, x)| *x == name).map(|(x, _)| *x);
+    match value {
+        Some(value) => Ok(value),
+        None => {
+            let names: Vec<&str> = table.iter().map(|(_, x)| *x).collect();
+            bail!(NetworkError::InvalidArgument(format!(
+                "Invalid {} {}, expected one of {}",
+                kind,
+                name,
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+pub(crate) fn addr_gen_mode_from_human(name: &str) -> Result<SettingIP6ConfigAddrGenMode> {
+    from_human_name(ADDR_GEN_MODES, "addr-gen-mode", name)
+}
+
+pub(crate) fn ip6_privacy_from_human(name: &str) -> Result<SettingIP6ConfigPrivacy> {
+    from_human_name(IP6_PRIVACIES, "ip6-privacy", name)
+}
+
+/// A representation of the policy routing rule
+///
+/// The rule belongs to the family of the `NetInfo` holding it. A packet
+/// matching all the given selectors (`from`, `to`, `iif`, `oif` and `fwmark`
+/// with `fwmask`) is routed by the `table`. The rules are applied in the
+/// `priority` order.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoutingRule {
+    pub priority: Option<u32>,
+    #[serde(
+        default,
+        serialize_with = "opt_to_string",
+        deserialize_with = "opt_from_string"
+    )]
+    pub from: Option<IpNet>,
+    #[serde(
+        default,
+        serialize_with = "opt_to_string",
+        deserialize_with = "opt_from_string"
+    )]
+    pub to: Option<IpNet>,
+    pub table: Option<u32>,
+    pub iif: Option<String>,
+    pub oif: Option<String>,
+    pub fwmark: Option<u32>,
+    pub fwmask: Option<u32>,
 }
 
 /// A representation of the route information
@@ -33,15 +163,107 @@ pub struct NetInfo {
 /// The `Route` type consists of four properties. The `family` property is meant
 /// for the v4 or v6. The `dest` property is meant for  route destination. The
 /// `next_hop` property is meant for the ip address of next hop. The `metric`
-/// is meant for linux route metric.
-#[derive(Debug, Default, Clone, Serialize)]
+/// is meant for linux route metric, `-1` means the default metric.
+///
+/// The `table` is the `table` route attribute, the other route attributes are
+/// kept in `attributes` with their string values, see `ROUTE_ATTRIBUTES` for
+/// the supported ones.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Route {
-    #[serde(serialize_with = "ipver_human")]
+    #[serde(serialize_with = "ipver_human", deserialize_with = "ipver_from_human")]
     pub family: i32,
-    #[serde(serialize_with = "to_string")]
+    #[serde(serialize_with = "to_string", deserialize_with = "from_string")]
     pub dest: IpNet,
+    #[serde(default)]
     pub next_hop: Option<IpAddr>,
+    #[serde(default = "default_metric")]
     pub metric: i64,
+    #[serde(default)]
+    pub table: Option<u32>,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+}
+
+fn default_metric() -> i64 {
+    -1
+}
+
+/// The value types of the route attributes
+#[derive(Clone, Copy)]
+enum AttributeType {
+    Bool,
+    Byte,
+    Uint32,
+    Str,
+}
+
+/// The route attributes supported by NetworkManager, the `table` attribute
+/// is the `table` field of `Route`.
+const ROUTE_ATTRIBUTES: &[(&str, AttributeType)] = &[
+    ("advmss", AttributeType::Uint32),
+    ("cwnd", AttributeType::Uint32),
+    ("from", AttributeType::Str),
+    ("initcwnd", AttributeType::Uint32),
+    ("initrwnd", AttributeType::Uint32),
+    ("lock-cwnd", AttributeType::Bool),
+    ("lock-initcwnd", AttributeType::Bool),
+    ("lock-initrwnd", AttributeType::Bool),
+    ("lock-mtu", AttributeType::Bool),
+    ("lock-window", AttributeType::Bool),
+    ("mtu", AttributeType::Uint32),
+    ("onlink", AttributeType::Bool),
+    ("quickack", AttributeType::Bool),
+    ("rto_min", AttributeType::Uint32),
+    ("scope", AttributeType::Byte),
+    ("src", AttributeType::Str),
+    ("tos", AttributeType::Byte),
+    ("type", AttributeType::Str),
+    ("weight", AttributeType::Uint32),
+    ("window", AttributeType::Uint32),
+];
+
+const TABLE_ATTRIBUTE: &str = "table";
+
+fn attribute_type(name: &str) -> Option<AttributeType> {
+    ROUTE_ATTRIBUTES
+        .iter()
+        .find(|(attr, _)| *attr == name)
+        .map(|(_, attr_type)| *attr_type)
+}
+
+fn attribute_to_string(value: &Variant) -> Option<String> {
+    value
+        .get::<bool>()
+        .map(|x| x.to_string())
+        .or_else(|| value.get::<u8>().map(|x| x.to_string()))
+        .or_else(|| value.get::<u32>().map(|x| x.to_string()))
+        .or_else(|| value.get::<String>())
+}
+
+fn attribute_from_string(name: &str, value: &str) -> Result<Variant> {
+    let invalid =
+        || NetworkError::InvalidArgument(format!("Invalid route attribute {}={}", name, value));
+    let variant = match attribute_type(name) {
+        Some(AttributeType::Bool) => value.parse::<bool>().map_err(|_| invalid())?.to_variant(),
+        Some(AttributeType::Byte) => value.parse::<u8>().map_err(|_| invalid())?.to_variant(),
+        Some(AttributeType::Uint32) => value.parse::<u32>().map_err(|_| invalid())?.to_variant(),
+        Some(AttributeType::Str) => value.to_variant(),
+        None => bail!(NetworkError::InvalidArgument(format!(
+            "Unsupported route attribute {}",
+            name
+        ))),
+    };
+    Ok(variant)
+}
+
+impl Route {
+    /// Whether the route is the one to remove, the next hop is compared only
+    /// if it is given.
+    pub fn matches(&self, other: &Route) -> bool {
+        self.family == other.family
+            && self.dest == other.dest
+            && (other.next_hop.is_none() || self.next_hop == other.next_hop)
+    }
 }
 
 impl TryFrom<SettingIPConfig> for NetInfo {
@@ -66,18 +288,60 @@ impl TryFrom<SettingIPConfig> for NetInfo {
                     config.dns.push(dns);
                 }
             }
+            let mut dns_search = vec![];
+            for i in 0..setting_ip_config.num_dns_searches() as i32 {
+                if let Some(search) = setting_ip_config.dns_search(i) {
+                    dns_search.push(search.to_string());
+                }
+            }
+            config.dns_search = Some(dns_search);
+            let mut dns_options = vec![];
+            for i in 0..setting_ip_config.num_dns_options() as i32 {
+                if let Some(option) = setting_ip_config.dns_option(i as u32) {
+                    dns_options.push(option.to_string());
+                }
+            }
+            config.dns_options = Some(dns_options);
+            config.dns_priority = Some(setting_ip_config.dns_priority());
+            config.ignore_auto_dns = Some(setting_ip_config.ignores_auto_dns());
 
             // Get the routes of the configuration
+            let mut routes = vec![];
             for i in 0..setting_ip_config.num_routes() as i32 {
                 if let Some(Ok(route)) = setting_ip_config.route(i).map(|x| x.try_into()) {
-                    config.routes.push(route);
+                    routes.push(route);
                 }
             }
+            config.routes = Some(routes);
 
             // Get the gateway of the configuration
             if let Some(Ok(gateway)) = setting_ip_config.gateway().map(|x| x.to_string().parse()) {
                 config.gateway = Some(gateway);
             }
+
+            // Get the policy routing of the configuration
+            config.route_table = Some(setting_ip_config.route_table());
+            let mut routing_rules = vec![];
+            for i in 0..setting_ip_config.num_routing_rules() {
+                if let Some(Ok(rule)) = setting_ip_config.routing_rule(i).map(|x| x.try_into()) {
+                    routing_rules.push(rule);
+                }
+            }
+            config.routing_rules = Some(routing_rules);
+
+            // Get the method flags of the configuration
+            config.never_default = Some(setting_ip_config.is_never_default());
+            config.may_fail = Some(setting_ip_config.is_may_fail());
+            config.dhcp_hostname = setting_ip_config.dhcp_hostname().map(|x| x.to_string());
+            config.dhcp_send_hostname = Some(setting_ip_config.is_dhcp_send_hostname());
+            config.dhcp_timeout = Some(setting_ip_config.dhcp_timeout());
+            if let Some(ip4_config) = setting_ip_config.downcast_ref::<SettingIP4Config>() {
+                config.dhcp_client_id = ip4_config.dhcp_client_id().map(|x| x.to_string());
+            }
+            if let Some(ip6_config) = setting_ip_config.downcast_ref::<SettingIP6Config>() {
+                config.addr_gen_mode = human_name(ADDR_GEN_MODES, ip6_config.addr_gen_mode());
+                config.ip6_privacy = human_name(IP6_PRIVACIES, ip6_config.ip6_privacy());
+            }
             config
         };
         if let Some(ip_config) = ipconfig {
@@ -123,18 +387,20 @@ impl TryFrom<NMIPConfig> for NetInfo {
                 .collect();
 
             // Get the routes of the configuration
-            netinfo.routes = nm_ip_config
-                .routes()
-                .iter()
-                .filter_map(|x| {
-                    let addr = x.clone();
-                    if let Ok(route) = Route::try_from(addr) {
-                        Some(route)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+            netinfo.routes = Some(
+                nm_ip_config
+                    .routes()
+                    .iter()
+                    .filter_map(|x| {
+                        let addr = x.clone();
+                        if let Ok(route) = Route::try_from(addr) {
+                            Some(route)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect(),
+            );
 
             // Get the gateway of the configuration
             if let Some(Ok(gateway)) = nm_ip_config.gateway().map(|x| x.to_string().parse()) {
@@ -157,8 +423,19 @@ impl TryFrom<IPRoute> for Route {
         if let Some(dest) = val.dest() {
             route.family = val.family();
             route.dest = format!("{}/{}", dest.to_string(), val.prefix()).parse()?;
-            route.next_hop = val.next_hop().map(|x| x.parse().unwrap());
+            route.next_hop = val.next_hop().map(|x| x.parse()).transpose()?;
             route.metric = val.metric();
+            for name in val.attribute_names() {
+                let value = match val.attribute(&name) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                if name == TABLE_ATTRIBUTE {
+                    route.table = value.get::<u32>();
+                } else if let Some(value) = attribute_to_string(&value) {
+                    route.attributes.insert(name.to_string(), value);
+                }
+            }
         } else {
             bail!("No dest found in route connection");
         }
@@ -177,10 +454,74 @@ impl TryInto<IPRoute> for Route {
                 .map(|x| &*Box::leak(x.to_string().into_boxed_str())),
             self.metric,
         )?;
+        if let Some(table) = self.table {
+            iproute.set_attribute(TABLE_ATTRIBUTE, Some(&table.to_variant()));
+        }
+        for (name, value) in self.attributes.iter() {
+            let variant = attribute_from_string(name, value)?;
+            iproute.set_attribute(name, Some(&variant));
+        }
         Ok(iproute)
     }
 }
 
+impl TryFrom<IPRoutingRule> for RoutingRule {
+    type Error = eyre::ErrReport;
+    fn try_from(val: IPRoutingRule) -> Result<RoutingRule> {
+        let selector = |addr: Option<glib::GString>, len: u8| -> Result<Option<IpNet>> {
+            match addr {
+                Some(addr) if len > 0 => Ok(Some(format!("{}/{}", addr, len).parse()?)),
+                _ => Ok(None),
+            }
+        };
+        let priority = val.priority();
+        let table = val.table();
+        let fwmask = val.fwmask();
+        Ok(RoutingRule {
+            priority: u32::try_from(priority).ok(),
+            from: selector(val.from(), val.from_len())?,
+            to: selector(val.to(), val.to_len())?,
+            table: if table == 0 { None } else { Some(table) },
+            iif: val.iifname().map(|x| x.to_string()),
+            oif: val.oifname().map(|x| x.to_string()),
+            fwmark: if fwmask == 0 {
+                None
+            } else {
+                Some(val.fwmark())
+            },
+            fwmask: if fwmask == 0 { None } else { Some(fwmask) },
+        })
+    }
+}
+
+impl RoutingRule {
+    /// Build the NetworkManager routing rule of the address family.
+    pub fn to_nm_rule(&self, family: i32) -> Result<IPRoutingRule> {
+        let rule = IPRoutingRule::new(family);
+        if let Some(priority) = self.priority {
+            rule.set_priority(priority as i64);
+        }
+        if let Some(from) = self.from {
+            rule.set_from(Some(&from.addr().to_string()), from.prefix_len());
+        }
+        if let Some(to) = self.to {
+            rule.set_to(Some(&to.addr().to_string()), to.prefix_len());
+        }
+        if let Some(table) = self.table {
+            rule.set_table(table);
+        }
+        rule.set_iifname(self.iif.as_deref());
+        rule.set_oifname(self.oif.as_deref());
+        if let Some(fwmark) = self.fwmark {
+            rule.set_fwmark(fwmark, self.fwmask.unwrap_or(u32::MAX));
+        }
+        rule.validate().map_err(|err| {
+            NetworkError::InvalidArgument(format!("Invalid routing rule: {}", err))
+        })?;
+        Ok(rule)
+    }
+}
+
 fn ipaddr2ipnet(ipaddr: IPAddress) -> Result<IpNet> {
     match ipaddr.address() {
         Some(addr) => {
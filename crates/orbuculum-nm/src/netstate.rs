@@ -0,0 +1,1223 @@
+//! ## Network state
+//!
+//! The `netstate` module describes the network in one declarative document,
+//! which follows a subset of the nmstate schema, e.g.
+//!
+//! ```yaml
+//! hostname:
+//!   config: gateway
+//! dns-resolver:
+//!   config:
+//!     server: [192.0.2.53]
+//!     search: [example.com]
+//! routes:
+//!   config:
+//!     - destination: 0.0.0.0/0
+//!       next-hop-interface: eth1
+//!       next-hop-address: 192.0.2.1
+//!     - destination: 198.51.100.0/24
+//!       next-hop-interface: eth1
+//!       next-hop-address: 192.0.2.254
+//!       state: absent
+//! interfaces:
+//!   - name: eth1
+//!     type: ethernet
+//!     state: up
+//!     mtu: 1500
+//!     ipv4:
+//!       enabled: true
+//!       dhcp: false
+//!       address:
+//!         - ip: 192.0.2.10
+//!           prefix-length: 24
+//!     ipv6:
+//!       enabled: false
+//! ```
+//!
+//! Only the physical ethernet interfaces are supported. An interface is
+//! configured by the connection of its device, which is created if the device
+//! has none, and `absent` deletes the connections of the device. Deactivating
+//! an interface is not supported, `down` only configures an interface which
+//! is not up. The interfaces, routes and settings which are not given are
+//! left unchanged.
+//!
+//! The default route without `metric` and `table-id` is the gateway of the
+//! connection, the other routes are its static routes. The DNS servers and
+//! search domains are kept in the connection of the first interface with a
+//! default gateway, or the first interface which is up, and are removed from
+//! the other connections.
+use crate::backend::{with_checkpoint, NetworkBackend};
+use crate::error::NetworkError;
+use crate::legacy::{connection_document, new_connection};
+use crate::net::{NetInfo, Route};
+use crate::utils::{from_string, to_string};
+use crate::{Connection, NetDevice, NetworkResponse};
+use eyre::Result;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+
+/// The network state document
+///
+/// The `running` sections of nmstate are accepted and ignored, only the
+/// `config` sections are applied.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct NetworkState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<HostnameState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_resolver: Option<DnsState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routes: Option<RoutesState>,
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceState>,
+}
+
+impl NetworkState {
+    /// Parse the YAML document, the keys out of the supported schema are
+    /// refused.
+    pub fn from_yaml(document: &str) -> Result<Self> {
+        serde_yaml::from_str(document).map_err(|err| {
+            NetworkError::InvalidArgument(format!("Invalid network state: {}", err)).into()
+        })
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yaml::to_string(self).map_err(|err| {
+            NetworkError::Backend(format!("Failed to write the network state: {}", err)).into()
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct HostnameState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct DnsState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running: Option<DnsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<DnsConfig>,
+}
+
+/// The DNS servers of both families and the search domains
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct DnsConfig {
+    #[serde(default)]
+    pub server: Vec<IpAddr>,
+    #[serde(default)]
+    pub search: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RoutesState {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub running: Vec<RouteEntry>,
+    #[serde(default)]
+    pub config: Vec<RouteEntry>,
+}
+
+/// A route of the network state
+///
+/// The route is added to the connection of the `next-hop-interface` unless
+/// it is there already, or removed from it if the `state` is `absent`. The
+/// next hop, metric and table of an absent route are compared only if they
+/// are given.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RouteEntry {
+    #[serde(serialize_with = "to_string", deserialize_with = "from_string")]
+    pub destination: IpNet,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_hop_interface: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_hop_address: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<RouteStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteStatus {
+    Absent,
+}
+
+/// An interface of the network state, the settings not given are left
+/// unchanged.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct InterfaceState {
+    pub name: String,
+    #[serde(rename = "type", default = "default_interface_type")]
+    pub interface_type: String,
+    #[serde(default)]
+    pub state: InterfaceStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv4: Option<IpState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<IpState>,
+}
+
+fn default_interface_type() -> String {
+    "ethernet".into()
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InterfaceStatus {
+    #[default]
+    Up,
+    Down,
+    Absent,
+}
+
+/// The ip configuration of a family
+///
+/// The method is `auto` with `dhcp` for IPv4 or `autoconf` for IPv6, `dhcp`
+/// with `dhcp` alone for IPv6, `manual` with addresses and `link-local`
+/// without them. `auto-dns: false` ignores the DNS servers from DHCP.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct IpState {
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dhcp: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoconf: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub address: Vec<AddressState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_dns: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct AddressState {
+    pub ip: IpAddr,
+    pub prefix_length: u8,
+}
+
+/// A change planned to reach the network state, in the order they are made
+///
+/// The connection of an update is the whole connection after the change,
+/// the `changes` describe what differs. An activated connection is reapplied
+/// to its device.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum StateChange {
+    SetHostname {
+        hostname: String,
+    },
+    CreateConnection {
+        connection: Connection,
+        activate: bool,
+    },
+    UpdateConnection {
+        connection: Connection,
+        changes: Vec<String>,
+        activate: bool,
+    },
+    ActivateConnection {
+        uuid: String,
+        name: String,
+    },
+    DeleteConnection {
+        uuid: String,
+        name: String,
+    },
+}
+
+impl fmt::Display for StateChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateChange::SetHostname { hostname } => write!(f, "set hostname to {}", hostname),
+            StateChange::CreateConnection {
+                connection,
+                activate,
+            } => {
+                write!(
+                    f,
+                    "create connection {} on {} (ipv4 {}, ipv6 {})",
+                    connection.name,
+                    connection.interface.as_deref().unwrap_or_default(),
+                    ip_summary(&connection.ip4info),
+                    ip_summary(&connection.ip6info)
+                )?;
+                if *activate {
+                    write!(f, " and activate it")?;
+                }
+                Ok(())
+            }
+            StateChange::UpdateConnection {
+                connection,
+                changes,
+                activate,
+            } => {
+                write!(
+                    f,
+                    "update connection {} ({}): {}",
+                    connection.name,
+                    connection.uuid,
+                    changes.join("; ")
+                )?;
+                if *activate {
+                    write!(f, " and apply it")?;
+                }
+                Ok(())
+            }
+            StateChange::ActivateConnection { uuid, name } => {
+                write!(f, "activate connection {} ({})", name, uuid)
+            }
+            StateChange::DeleteConnection { uuid, name } => {
+                write!(f, "delete connection {} ({})", name, uuid)
+            }
+        }
+    }
+}
+
+fn ip_summary(ipinfo: &NetInfo) -> String {
+    if ipinfo.addresses.is_empty() {
+        ipinfo.method.clone()
+    } else {
+        format!("{} {}", ipinfo.method, join(&ipinfo.addresses))
+    }
+}
+
+fn join<T: ToString>(items: &[T]) -> String {
+    if items.is_empty() {
+        return "none".into();
+    }
+    let items: Vec<String> = items.iter().map(|x| x.to_string()).collect();
+    items.join(",")
+}
+
+fn route_text(route: &Route) -> String {
+    let mut text = route.dest.to_string();
+    if let Some(next_hop) = route.next_hop {
+        text.push_str(&format!(" via {}", next_hop));
+    }
+    if route.metric >= 0 {
+        text.push_str(&format!(" metric {}", route.metric));
+    }
+    if let Some(table) = route.table {
+        text.push_str(&format!(" table {}", table));
+    }
+    text
+}
+
+fn is_ethernet(device: &NetDevice) -> bool {
+    device.device_type == "Ethernet" && !device.r#virtual
+}
+
+fn is_enabled(ipinfo: &NetInfo) -> bool {
+    !matches!(ipinfo.method.as_str(), "disabled" | "ignore")
+}
+
+/// The active connection of the device, or its first connection.
+fn device_connection<'a>(
+    device: &NetDevice,
+    connections: &'a [Connection],
+) -> Option<&'a Connection> {
+    let uuids = device.connection.uuid.iter().chain(device.conn.iter());
+    uuids
+        .filter_map(|uuid| connections.iter().find(|x| &x.uuid == uuid))
+        .next()
+}
+
+fn ip_state(ipinfo: &NetInfo, is_ipv6: bool) -> IpState {
+    let (enabled, dhcp, autoconf) = match ipinfo.method.as_str() {
+        "disabled" | "ignore" => (false, false, false),
+        "auto" => (true, true, is_ipv6),
+        "dhcp" => (true, true, false),
+        _ => (true, false, false),
+    };
+    if !enabled {
+        return IpState::default();
+    }
+    IpState {
+        enabled,
+        dhcp: Some(dhcp),
+        autoconf: is_ipv6.then_some(autoconf),
+        address: ipinfo
+            .addresses
+            .iter()
+            .map(|x| AddressState {
+                ip: x.addr(),
+                prefix_length: x.prefix_len(),
+            })
+            .collect(),
+        auto_dns: ipinfo.ignore_auto_dns.map(|x| !x),
+    }
+}
+
+/// The network state of the ethernet devices and their connections
+fn current_state(
+    devices: &[NetDevice],
+    connections: &[Connection],
+    hostname: String,
+) -> NetworkState {
+    let mut interfaces = vec![];
+    let mut routes = vec![];
+    let mut dns = DnsConfig::default();
+    for device in devices.iter().filter(|x| is_ethernet(x)) {
+        let mut interface = InterfaceState {
+            name: device.name.clone(),
+            interface_type: default_interface_type(),
+            state: if device.connection.uuid.is_some() {
+                InterfaceStatus::Up
+            } else {
+                InterfaceStatus::Down
+            },
+            mtu: None,
+            mac_address: Some(device.mac.to_lowercase()).filter(|x| !x.is_empty()),
+            ipv4: None,
+            ipv6: None,
+        };
+        if let Some(connection) = device_connection(device, connections) {
+            if let Some(wired) = connection.wired.as_ref() {
+                interface.mtu = wired.mtu.filter(|x| *x != 0);
+                // The cloned mac may be a mode, e.g. `random`
+                if let Some(cloned_mac) = wired.cloned_mac.as_ref().filter(|x| x.contains(':')) {
+                    interface.mac_address = Some(cloned_mac.to_lowercase());
+                }
+            }
+            interface.ipv4 = Some(ip_state(&connection.ip4info, false));
+            interface.ipv6 = Some(ip_state(&connection.ip6info, true));
+            for ipinfo in [&connection.ip4info, &connection.ip6info] {
+                if let Some(gateway) = ipinfo.gateway {
+                    routes.push(RouteEntry {
+                        destination: default_destination(gateway),
+                        next_hop_interface: Some(device.name.clone()),
+                        next_hop_address: Some(gateway),
+                        metric: None,
+                        table_id: None,
+                        state: None,
+                    });
+                }
+                for route in ipinfo.routes.iter().flatten() {
+                    routes.push(RouteEntry {
+                        destination: route.dest,
+                        next_hop_interface: Some(device.name.clone()),
+                        next_hop_address: route.next_hop,
+                        metric: Some(route.metric).filter(|x| *x >= 0),
+                        table_id: route.table,
+                        state: None,
+                    });
+                }
+                for server in ipinfo.dns.iter() {
+                    if !dns.server.contains(server) {
+                        dns.server.push(*server);
+                    }
+                }
+                for search in ipinfo.dns_search.iter().flatten() {
+                    if !dns.search.contains(search) {
+                        dns.search.push(search.clone());
+                    }
+                }
+            }
+        }
+        interfaces.push(interface);
+    }
+    NetworkState {
+        hostname: Some(HostnameState {
+            running: None,
+            config: Some(hostname),
+        }),
+        dns_resolver: Some(DnsState {
+            running: None,
+            config: Some(dns),
+        }),
+        routes: Some(RoutesState {
+            running: vec![],
+            config: routes,
+        }),
+        interfaces,
+    }
+}
+
+fn default_destination(gateway: IpAddr) -> IpNet {
+    IpNet::new(gateway, 0)
+        .map(|x| x.trunc())
+        .unwrap_or_default()
+}
+
+/// Apply the ip configuration to the connection settings of the family.
+fn apply_ip_state(ipinfo: &mut NetInfo, ip: &IpState, is_ipv6: bool) -> Result<()> {
+    let family = if is_ipv6 { "IPv6" } else { "IPv4" };
+    if !ip.enabled {
+        if is_enabled(ipinfo) {
+            ipinfo.method = "disabled".into();
+        }
+        ipinfo.addresses.clear();
+        ipinfo.gateway = None;
+        ipinfo.dns.clear();
+        ipinfo.dns_search = Some(vec![]);
+        ipinfo.routes = Some(vec![]);
+        return Ok(());
+    }
+    let mut addresses = vec![];
+    for address in ip.address.iter() {
+        if address.ip.is_ipv6() != is_ipv6 {
+            bail!(NetworkError::InvalidArgument(format!(
+                "The address {} is not an {} address",
+                address.ip, family
+            )));
+        }
+        let address = IpNet::new(address.ip, address.prefix_length).map_err(|_| {
+            NetworkError::InvalidArgument(format!(
+                "Invalid prefix length {} of the address {}",
+                address.prefix_length, address.ip
+            ))
+        })?;
+        addresses.push(address);
+    }
+    let dhcp = ip.dhcp.unwrap_or(false);
+    let autoconf = is_ipv6 && ip.autoconf.unwrap_or(false);
+    ipinfo.method = match (is_ipv6, dhcp, autoconf) {
+        (false, true, _) | (true, _, true) => "auto",
+        (true, true, false) => "dhcp",
+        _ if addresses.is_empty() => "link-local",
+        _ => "manual",
+    }
+    .into();
+    ipinfo.addresses = addresses;
+    if let Some(auto_dns) = ip.auto_dns {
+        ipinfo.ignore_auto_dns = Some(!auto_dns);
+    }
+    Ok(())
+}
+
+/// Whether the route of the state is the given route of a connection
+fn route_matches(entry: &RouteEntry, route: &Route) -> bool {
+    route.dest == entry.destination
+        && (entry.next_hop_address.is_none() || route.next_hop == entry.next_hop_address)
+        && (entry.metric.is_none() || Some(route.metric) == entry.metric)
+        && (entry.table_id.is_none() || route.table == entry.table_id)
+}
+
+/// An interface being planned with the connection of its device
+struct PlannedInterface {
+    device: NetDevice,
+    current: Option<Connection>,
+    /// The connection after the changes, `None` if the interface is absent
+    desired: Option<Connection>,
+    activate: bool,
+    /// Whether the connection is created if the device has none
+    configured: bool,
+}
+
+impl PlannedInterface {
+    fn has_gateway(&self) -> bool {
+        match self.desired.as_ref() {
+            Some(connection) => [&connection.ip4info, &connection.ip6info].iter().any(|x| {
+                x.gateway.is_some() || x.routes.iter().flatten().any(|x| x.dest.prefix_len() == 0)
+            }),
+            None => false,
+        }
+    }
+}
+
+struct Planner<'a> {
+    devices: &'a [NetDevice],
+    connections: &'a [Connection],
+    interfaces: Vec<PlannedInterface>,
+}
+
+impl<'a> Planner<'a> {
+    /// The planned interface, an interface not given in the state is left as
+    /// it is, it is only reapplied if it is up.
+    fn interface_mut(&mut self, name: &str) -> Result<&mut PlannedInterface> {
+        if let Some(index) = self.interfaces.iter().position(|x| x.device.name == name) {
+            return Ok(&mut self.interfaces[index]);
+        }
+        let device = self
+            .devices
+            .iter()
+            .find(|x| x.name == name && is_ethernet(x))
+            .ok_or_else(|| {
+                NetworkError::NotFound(format!("The ethernet interface {} is not found", name))
+            })?;
+        let current = device_connection(device, self.connections).cloned();
+        let desired = current
+            .clone()
+            .unwrap_or_else(|| new_connection(name, Some(name.to_string())));
+        self.interfaces.push(PlannedInterface {
+            device: device.clone(),
+            current,
+            desired: Some(desired),
+            activate: device.connection.uuid.is_some(),
+            configured: false,
+        });
+        let index = self.interfaces.len() - 1;
+        Ok(&mut self.interfaces[index])
+    }
+
+    fn plan_interface(&mut self, state: &InterfaceState) -> Result<()> {
+        if state.interface_type != "ethernet" {
+            bail!(NetworkError::InvalidArgument(format!(
+                "The interface {} of type {} is not supported, only the ethernet interfaces are",
+                state.name, state.interface_type
+            )));
+        }
+        let interface = self.interface_mut(&state.name)?;
+        let is_up = interface.device.connection.uuid.is_some();
+        match state.state {
+            InterfaceStatus::Absent => {
+                interface.desired = None;
+                interface.activate = false;
+                return Ok(());
+            }
+            InterfaceStatus::Down if is_up => bail!(NetworkError::InvalidArgument(format!(
+                "The interface {} is up, deactivating it is not supported",
+                state.name
+            ))),
+            InterfaceStatus::Down => interface.activate = false,
+            InterfaceStatus::Up => interface.activate = true,
+        }
+        let device_mac = interface.device.mac.clone();
+        let connection = match interface.desired.as_mut() {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+        if let Some(mtu) = state.mtu {
+            let wired = connection.wired.get_or_insert_with(Default::default);
+            wired.mtu = Some(mtu);
+        }
+        if let Some(mac) = state.mac_address.as_ref() {
+            let wired = connection.wired.get_or_insert_with(Default::default);
+            let current_mac = wired.cloned_mac.as_deref().unwrap_or(&device_mac);
+            if !mac.eq_ignore_ascii_case(current_mac) {
+                wired.cloned_mac = Some(mac.to_lowercase());
+            }
+        }
+        if let Some(ip) = state.ipv4.as_ref() {
+            apply_ip_state(&mut connection.ip4info, ip, false)?;
+        }
+        if let Some(ip) = state.ipv6.as_ref() {
+            apply_ip_state(&mut connection.ip6info, ip, true)?;
+        }
+        // The mac of the device itself is not a setting
+        let cloned_mac = connection
+            .wired
+            .as_ref()
+            .and_then(|x| x.cloned_mac.as_ref());
+        interface.configured = interface.activate
+            || cloned_mac.is_some()
+            || state.mtu.is_some()
+            || state.ipv4.is_some()
+            || state.ipv6.is_some();
+        Ok(())
+    }
+
+    fn plan_route(&mut self, entry: &RouteEntry) -> Result<()> {
+        let name = entry.next_hop_interface.as_deref().ok_or_else(|| {
+            NetworkError::InvalidArgument(format!(
+                "The route to {} has no next-hop-interface",
+                entry.destination
+            ))
+        })?;
+        let is_ipv6 = matches!(entry.destination, IpNet::V6(_));
+        if matches!(entry.next_hop_address, Some(x) if x.is_ipv6() != is_ipv6) {
+            bail!(NetworkError::InvalidArgument(format!(
+                "The next hop of the route to {} is of another family",
+                entry.destination
+            )));
+        }
+        let interface = self.interface_mut(name)?;
+        interface.configured = true;
+        let connection = match interface.desired.as_mut() {
+            Some(connection) => connection,
+            None => bail!(NetworkError::InvalidArgument(format!(
+                "The route to {} is on the absent interface {}",
+                entry.destination, name
+            ))),
+        };
+        let ipinfo = if is_ipv6 {
+            &mut connection.ip6info
+        } else {
+            &mut connection.ip4info
+        };
+        let is_default = entry.destination.prefix_len() == 0
+            && entry.metric.is_none()
+            && entry.table_id.is_none();
+        if entry.state == Some(RouteStatus::Absent) {
+            if is_default
+                && (entry.next_hop_address.is_none() || ipinfo.gateway == entry.next_hop_address)
+            {
+                ipinfo.gateway = None;
+            }
+            if let Some(routes) = ipinfo.routes.as_mut() {
+                routes.retain(|x| !route_matches(entry, x));
+            }
+            return Ok(());
+        }
+        if is_default && entry.next_hop_address.is_some() {
+            ipinfo.gateway = entry.next_hop_address;
+            return Ok(());
+        }
+        let routes = ipinfo.routes.get_or_insert_with(Vec::new);
+        if !routes.iter().any(|x| route_matches(entry, x)) {
+            routes.push(Route {
+                family: if is_ipv6 {
+                    libc::AF_INET6
+                } else {
+                    libc::AF_INET
+                },
+                dest: entry.destination,
+                next_hop: entry.next_hop_address,
+                metric: entry.metric.unwrap_or(-1),
+                table: entry.table_id,
+                attributes: Default::default(),
+            });
+        }
+        Ok(())
+    }
+
+    fn plan_dns(&mut self, config: &DnsConfig) -> Result<()> {
+        // The DNS is removed from all the connections but the target one
+        let names: Vec<String> = self
+            .devices
+            .iter()
+            .filter(|x| is_ethernet(x) && device_connection(x, self.connections).is_some())
+            .map(|x| x.name.clone())
+            .collect();
+        for name in names {
+            self.interface_mut(&name)?;
+        }
+        let target = self
+            .interfaces
+            .iter()
+            .position(|x| x.has_gateway())
+            .or_else(|| {
+                self.interfaces
+                    .iter()
+                    .position(|x| x.desired.is_some() && x.activate)
+            });
+        let is_empty = config.server.is_empty() && config.search.is_empty();
+        if target.is_none() && !is_empty {
+            bail!(NetworkError::InvalidArgument(
+                "The DNS needs an interface with a default gateway or an interface which is up"
+                    .into()
+            ));
+        }
+        for (index, interface) in self.interfaces.iter_mut().enumerate() {
+            let connection = match interface.desired.as_mut() {
+                Some(connection) => connection,
+                None => continue,
+            };
+            if Some(index) != target {
+                for ipinfo in [&mut connection.ip4info, &mut connection.ip6info] {
+                    ipinfo.dns.clear();
+                    ipinfo.dns_search = Some(vec![]);
+                }
+                continue;
+            }
+            for (ipinfo, is_ipv6) in [
+                (&mut connection.ip4info, false),
+                (&mut connection.ip6info, true),
+            ] {
+                let servers: Vec<IpAddr> = config
+                    .server
+                    .iter()
+                    .filter(|x| x.is_ipv6() == is_ipv6)
+                    .cloned()
+                    .collect();
+                if !servers.is_empty() && !is_enabled(ipinfo) {
+                    bail!(NetworkError::InvalidArgument(format!(
+                        "The DNS servers {} are on the interface {} without {}",
+                        join(&servers),
+                        interface.device.name,
+                        if is_ipv6 { "IPv6" } else { "IPv4" }
+                    )));
+                }
+                ipinfo.dns = servers;
+                ipinfo.dns_search = Some(vec![]);
+            }
+            // The search domains go with IPv4 unless it is disabled
+            if is_enabled(&connection.ip4info) {
+                connection.ip4info.dns_search = Some(config.search.clone());
+            } else {
+                connection.ip6info.dns_search = Some(config.search.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn into_changes(self) -> Vec<StateChange> {
+        let mut changes = vec![];
+        for interface in self.interfaces {
+            match (interface.current, interface.desired) {
+                (_, None) => {
+                    for uuid in interface.device.conn.iter() {
+                        let name = self
+                            .connections
+                            .iter()
+                            .find(|x| &x.uuid == uuid)
+                            .map(|x| x.name.clone())
+                            .unwrap_or_default();
+                        changes.push(StateChange::DeleteConnection {
+                            uuid: uuid.clone(),
+                            name,
+                        });
+                    }
+                }
+                (None, Some(connection)) if interface.configured => {
+                    changes.push(StateChange::CreateConnection {
+                        connection,
+                        activate: interface.activate,
+                    })
+                }
+                (None, Some(_)) => {}
+                (Some(current), Some(mut connection)) => {
+                    let differences = connection_changes(&current, &connection);
+                    let is_active =
+                        interface.device.connection.uuid.as_deref() == Some(current.uuid.as_str());
+                    if !differences.is_empty() {
+                        // The link settings are left unchanged
+                        let wired = |x: &Connection| x.wired.clone().unwrap_or_default();
+                        if wired(&connection) == wired(&current) {
+                            connection.wired = None;
+                        }
+                        changes.push(StateChange::UpdateConnection {
+                            connection,
+                            changes: differences,
+                            activate: interface.activate,
+                        });
+                    } else if interface.activate && !is_active {
+                        changes.push(StateChange::ActivateConnection {
+                            uuid: current.uuid,
+                            name: current.name,
+                        });
+                    }
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// The settings of the connection which differ, e.g.
+/// `ipv4 addresses 192.0.2.10/24 -> 192.0.2.11/24`.
+fn connection_changes(current: &Connection, desired: &Connection) -> Vec<String> {
+    let mut changes = vec![];
+    let mut compare = |setting: &str, current: String, desired: String| {
+        if current != desired {
+            changes.push(format!("{} {} -> {}", setting, current, desired));
+        }
+    };
+    let wired = |connection: &Connection| connection.wired.clone().unwrap_or_default();
+    let (current_wired, desired_wired) = (wired(current), wired(desired));
+    compare(
+        "mtu",
+        join(&current_wired.mtu.into_iter().collect::<Vec<u32>>()),
+        join(&desired_wired.mtu.into_iter().collect::<Vec<u32>>()),
+    );
+    compare(
+        "mac-address",
+        current_wired.cloned_mac.unwrap_or_else(|| "none".into()),
+        desired_wired.cloned_mac.unwrap_or_else(|| "none".into()),
+    );
+    for (family, current, desired) in [
+        ("ipv4", &current.ip4info, &desired.ip4info),
+        ("ipv6", &current.ip6info, &desired.ip6info),
+    ] {
+        let routes = |ipinfo: &NetInfo| {
+            let routes: Vec<String> = ipinfo.routes.iter().flatten().map(route_text).collect();
+            join(&routes)
+        };
+        let auto_dns = |ipinfo: &NetInfo| match ipinfo.ignore_auto_dns {
+            Some(ignore) => (!ignore).to_string(),
+            None => "none".into(),
+        };
+        let gateway = |ipinfo: &NetInfo| join(&ipinfo.gateway.into_iter().collect::<Vec<_>>());
+        compare(
+            &format!("{} method", family),
+            current.method.clone(),
+            desired.method.clone(),
+        );
+        compare(
+            &format!("{} addresses", family),
+            join(&current.addresses),
+            join(&desired.addresses),
+        );
+        compare(
+            &format!("{} gateway", family),
+            gateway(current),
+            gateway(desired),
+        );
+        compare(
+            &format!("{} routes", family),
+            routes(current),
+            routes(desired),
+        );
+        compare(
+            &format!("{} dns", family),
+            join(&current.dns),
+            join(&desired.dns),
+        );
+        compare(
+            &format!("{} dns-search", family),
+            join(current.dns_search.as_deref().unwrap_or_default()),
+            join(desired.dns_search.as_deref().unwrap_or_default()),
+        );
+        compare(
+            &format!("{} auto-dns", family),
+            auto_dns(current),
+            auto_dns(desired),
+        );
+    }
+    changes
+}
+
+/// The changes which turn the current network into the given state, nothing
+/// is changed.
+fn plan_changes(
+    state: &NetworkState,
+    devices: &[NetDevice],
+    connections: &[Connection],
+    hostname: &str,
+) -> Result<Vec<StateChange>> {
+    let mut changes = vec![];
+    let config = state.hostname.as_ref().and_then(|x| x.config.as_ref());
+    if let Some(config) = config.filter(|x| x.as_str() != hostname) {
+        changes.push(StateChange::SetHostname {
+            hostname: config.clone(),
+        });
+    }
+
+    let mut planner = Planner {
+        devices,
+        connections,
+        interfaces: vec![],
+    };
+    for (index, interface) in state.interfaces.iter().enumerate() {
+        if state.interfaces[..index]
+            .iter()
+            .any(|x| x.name == interface.name)
+        {
+            bail!(NetworkError::InvalidArgument(format!(
+                "The interface {} is given more than once",
+                interface.name
+            )));
+        }
+        planner.plan_interface(interface)?;
+    }
+    if let Some(routes) = state.routes.as_ref() {
+        for entry in routes.config.iter() {
+            planner.plan_route(entry)?;
+        }
+    }
+    if let Some(config) = state.dns_resolver.as_ref().and_then(|x| x.config.as_ref()) {
+        planner.plan_dns(config)?;
+    }
+    changes.extend(planner.into_changes());
+    Ok(changes)
+}
+
+fn unexpected_response(command: &str) -> NetworkError {
+    NetworkError::Backend(format!("Unexpected response of {}", command))
+}
+
+/// The hostname, devices and connections read for the network state
+async fn read_network<B: NetworkBackend>(
+    backend: &B,
+) -> Result<(String, Vec<NetDevice>, Vec<Connection>)> {
+    let hostname = backend
+        .get_hostname()
+        .await?
+        .into_hostname()
+        .ok_or_else(|| unexpected_response("get hostname"))?;
+    let devices = backend
+        .list_devices()
+        .await?
+        .into_devices()
+        .ok_or_else(|| unexpected_response("list devices"))?;
+    let connections = backend
+        .list_connections()
+        .await?
+        .into_connections()
+        .ok_or_else(|| unexpected_response("list connections"))?;
+    Ok((hostname, devices, connections))
+}
+
+/// Show the network state of the ethernet devices, their connections and
+/// the hostname.
+pub(crate) async fn show_network_state<B: NetworkBackend>(backend: &B) -> Result<NetworkResponse> {
+    let (hostname, devices, connections) = read_network(backend).await?;
+    let state = current_state(&devices, &connections, hostname);
+    Ok(NetworkResponse::NetworkState(state))
+}
+
+/// The interface names or mac addresses of the devices the changes touch
+fn touched_devices(changes: &[StateChange], connections: &[Connection]) -> Vec<String> {
+    let device_of = |connection: &Connection| {
+        connection
+            .interface
+            .clone()
+            .or_else(|| connection.mac.clone())
+    };
+    changes
+        .iter()
+        .filter_map(|change| match change {
+            StateChange::SetHostname { .. } => None,
+            StateChange::CreateConnection { connection, .. }
+            | StateChange::UpdateConnection { connection, .. } => device_of(connection),
+            StateChange::ActivateConnection { uuid, .. }
+            | StateChange::DeleteConnection { uuid, .. } => connections
+                .iter()
+                .find(|x| x.uuid == *uuid)
+                .and_then(device_of),
+        })
+        .collect()
+}
+
+/// Plan the changes to reach the network state and make them unless it is a
+/// dry run. The changes are made under a checkpoint of the touched devices,
+/// if one fails, all of them are rolled back and the hostname is restored.
+pub(crate) async fn apply_network_state<B: NetworkBackend>(
+    backend: &B,
+    state: NetworkState,
+    dry_run: bool,
+) -> Result<NetworkResponse> {
+    let (hostname, devices, connections) = read_network(backend).await?;
+    let changes = plan_changes(&state, &devices, &connections, &hostname)?;
+    if !dry_run {
+        let interfaces = touched_devices(&changes, &connections);
+        let result = with_checkpoint(backend, &interfaces, async {
+            for change in changes.iter() {
+                make_change(backend, change).await?;
+            }
+            Ok(())
+        })
+        .await;
+        if let Err(err) = result {
+            // The checkpoints don't cover the hostname
+            let hostname_changed = changes
+                .iter()
+                .any(|x| matches!(x, StateChange::SetHostname { .. }));
+            if hostname_changed {
+                let _ = backend.set_hostname(hostname).await;
+            }
+            return Err(err);
+        }
+    }
+    Ok(NetworkResponse::StateChanges(changes))
+}
+
+async fn make_change<B: NetworkBackend>(backend: &B, change: &StateChange) -> Result<()> {
+    match change {
+        StateChange::SetHostname { hostname } => {
+            backend.set_hostname(hostname.clone()).await?;
+        }
+        StateChange::CreateConnection {
+            connection,
+            activate,
+        } => {
+            let uuid = backend
+                .put_connection_settings(connection_document(connection))
+                .await?
+                .into_uuid()
+                .ok_or_else(|| NetworkError::Backend("The connection is not created".into()))?;
+            if *activate {
+                backend.reactive_connection(uuid).await?;
+            }
+        }
+        StateChange::UpdateConnection {
+            connection,
+            activate,
+            ..
+        } => {
+            backend.update_connection(connection.clone()).await?;
+            if *activate {
+                backend.reactive_connection(connection.uuid.clone()).await?;
+            }
+        }
+        StateChange::ActivateConnection { uuid, .. } => {
+            backend.reactive_connection(uuid.clone()).await?;
+        }
+        StateChange::DeleteConnection { uuid, .. } => {
+            backend.delete_connection_by_uuid(uuid.clone()).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ConnectionItem;
+    use rstest::rstest;
+
+    fn device(name: &str, uuid: Option<&str>) -> NetDevice {
+        NetDevice {
+            name: name.into(),
+            mac: "52:54:00:12:34:56".into(),
+            device_type: "Ethernet".into(),
+            connection: ConnectionItem {
+                id: uuid.map(|_| name.to_string()),
+                uuid: uuid.map(|x| x.to_string()),
+            },
+            conn: uuid.into_iter().map(|x| x.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn connection(name: &str, uuid: &str) -> Connection {
+        let mut connection = new_connection(name, Some(name.into()));
+        connection.uuid = uuid.into();
+        connection.ip4info.method = "manual".into();
+        connection.ip4info.addresses = vec!["192.0.2.10/24".parse().unwrap()];
+        connection.ip4info.gateway = Some("192.0.2.1".parse().unwrap());
+        connection.ip4info.dns = vec!["192.0.2.53".parse().unwrap()];
+        connection
+    }
+
+    #[test]
+    fn test_current_state_round_trip() {
+        let devices = vec![device("eth1", Some("uuid-1")), device("eth2", None)];
+        let connections = vec![connection("eth1", "uuid-1")];
+        let state = current_state(&devices, &connections, "host".into());
+        let yaml = state.to_yaml().unwrap();
+        let state = NetworkState::from_yaml(&yaml).unwrap();
+        assert_eq!(state.interfaces[0].state, InterfaceStatus::Up);
+        assert_eq!(state.interfaces[1].state, InterfaceStatus::Down);
+        assert_eq!(
+            state.routes.as_ref().unwrap().config[0].destination,
+            "0.0.0.0/0".parse().unwrap()
+        );
+        let changes = plan_changes(&state, &devices, &connections, "host").unwrap();
+        assert!(changes.is_empty(), "{:?}", changes);
+    }
+
+    #[test]
+    fn test_plan_changes() {
+        let devices = vec![device("eth1", Some("uuid-1")), device("eth2", None)];
+        let connections = vec![connection("eth1", "uuid-1")];
+        let state = NetworkState::from_yaml(
+            "hostname:\n  config: gateway\n\
+             dns-resolver:\n  config:\n    server: [192.0.2.54]\n\
+             interfaces:\n\
+             - name: eth1\n  mtu: 9000\n\
+             - name: eth2\n  ipv4:\n    enabled: true\n    dhcp: true\n",
+        )
+        .unwrap();
+        let changes = plan_changes(&state, &devices, &connections, "host").unwrap();
+        let changes: Vec<String> = changes.iter().map(|x| x.to_string()).collect();
+        assert_eq!(
+            changes,
+            vec![
+                "set hostname to gateway",
+                "update connection eth1 (uuid-1): mtu none -> 9000; \
+                 ipv4 dns 192.0.2.53 -> 192.0.2.54 and apply it",
+                "create connection eth2 on eth2 (ipv4 auto, ipv6 disabled) and activate it",
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("interfaces:\n- name: bond0\n  type: bond\n")]
+    #[case("interfaces:\n- name: eth1\n- name: eth1\n")]
+    #[case("interfaces:\n- name: eth1\n  state: down\n")]
+    #[case("interfaces:\n- name: eth1\n  ipv4:\n    enabled: true\n    address:\n    - ip: 2001:db8::1\n      prefix-length: 64\n")]
+    #[case("routes:\n  config:\n  - destination: 198.51.100.0/24\n")]
+    #[case("interfaces:\n- name: eth1\n  lldp:\n    enabled: true\n")]
+    fn test_plan_changes_invalid(#[case] document: &str) {
+        let devices = vec![device("eth1", Some("uuid-1"))];
+        let connections = vec![connection("eth1", "uuid-1")];
+        let err = NetworkState::from_yaml(document)
+            .and_then(|state| plan_changes(&state, &devices, &connections, "host"))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NetworkError>(),
+            Some(NetworkError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_plan_routes() {
+        let mut eth1 = connection("eth1", "uuid-1");
+        eth1.ip4info.routes = Some(vec![Route {
+            family: libc::AF_INET,
+            dest: "198.51.100.0/24".parse().unwrap(),
+            next_hop: Some("192.0.2.254".parse().unwrap()),
+            metric: -1,
+            ..Default::default()
+        }]);
+        let devices = vec![device("eth1", Some("uuid-1"))];
+        let state = NetworkState::from_yaml(concat!(
+            "routes:\n",
+            "  config:\n",
+            "  - destination: 0.0.0.0/0\n",
+            "    next-hop-interface: eth1\n",
+            "    next-hop-address: 192.0.2.2\n",
+            "  - destination: 198.51.100.0/24\n",
+            "    next-hop-interface: eth1\n",
+            "    state: absent\n",
+            "  - destination: 203.0.113.0/24\n",
+            "    next-hop-interface: eth1\n",
+            "    metric: 50\n",
+        ))
+        .unwrap();
+        let changes = plan_changes(&state, &devices, &[eth1], "host").unwrap();
+        match &changes[..] {
+            [StateChange::UpdateConnection { connection, .. }] => {
+                let ip4info = &connection.ip4info;
+                assert_eq!(ip4info.gateway, Some("192.0.2.2".parse().unwrap()));
+                let routes = ip4info.routes.as_ref().unwrap();
+                assert_eq!(routes.len(), 1);
+                assert_eq!(routes[0].dest, "203.0.113.0/24".parse().unwrap());
+                assert_eq!(routes[0].metric, 50);
+                assert!(connection.wired.is_none());
+            }
+            _ => panic!("Unexpected changes {:?}", changes),
+        }
+    }
+
+    #[test]
+    fn test_touched_devices() {
+        let mut eth2 = connection("eth2", "uuid-2");
+        eth2.interface = None;
+        eth2.mac = Some("52:54:00:12:34:57".into());
+        let connections = vec![connection("eth1", "uuid-1"), eth2];
+        let changes = vec![
+            StateChange::SetHostname {
+                hostname: "host".into(),
+            },
+            StateChange::CreateConnection {
+                connection: connection("eth3", ""),
+                activate: true,
+            },
+            StateChange::ActivateConnection {
+                uuid: "uuid-1".into(),
+                name: "eth1".into(),
+            },
+            StateChange::DeleteConnection {
+                uuid: "uuid-2".into(),
+                name: "eth2".into(),
+            },
+        ];
+        assert_eq!(
+            touched_devices(&changes, &connections),
+            ["eth3", "eth1", "52:54:00:12:34:57"]
+        );
+    }
+}
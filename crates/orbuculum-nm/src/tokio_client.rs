@@ -2,24 +2,45 @@
 //!
 //! The `Tokio` module used to provide
 use super::{NetworkCommand, NetworkResponse};
+use crate::backend::{LibnmBackend, NetworkBackend};
 use crate::dispatch::dispatch_command_requests;
-use eyre::{Result, WrapErr};
+use crate::error::NetworkError;
+use crate::events::{EventSender, NetworkEvent, EVENT_CAPACITY};
+use eyre::Result;
 use glib::{MainContext, MainLoop};
 use serde_json::Value;
+use std::rc::Rc;
 use std::sync::Arc;
-use tokio::sync::oneshot;
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+
+/// The longest time to wait for the network thread to answer a command.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(20);
 
 /// The shared state for tokio application to conmuicate with glib maincontext.
 pub struct State {
     glib_sender: glib::Sender<NetworkRequest>,
+    events: EventSender,
 }
 
 impl State {
     pub fn new(sender: glib::Sender<NetworkRequest>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
         State {
             glib_sender: sender,
+            events,
         }
     }
+
+    /// Receive the network events published after the call.
+    pub fn subscribe(&self) -> broadcast::Receiver<NetworkEvent> {
+        self.events.subscribe()
+    }
+
+    /// The sender given to the network loop to publish the events.
+    pub fn event_sender(&self) -> EventSender {
+        self.events.clone()
+    }
 }
 
 pub type TokioResponder = oneshot::Sender<Result<NetworkResponse>>;
@@ -35,21 +56,25 @@ impl NetworkRequest {
     }
 }
 
-pub async fn send_command(state: Arc<State>, command: NetworkCommand) -> Result<NetworkResponse> {
+pub async fn send_command(
+    state: Arc<State>,
+    command: NetworkCommand,
+) -> Result<NetworkResponse, NetworkError> {
     let (responder, receiver) = oneshot::channel();
 
     state
         .glib_sender
         .send(NetworkRequest::new(responder, command))
-        .unwrap();
+        .map_err(|_| NetworkError::NmUnavailable("The network thread is not running".into()))?;
 
-    let received = receiver
+    let received = tokio::time::timeout(COMMAND_TIMEOUT, receiver)
         .await
-        .context("Failed to receive network thread response");
+        .map_err(|_| NetworkError::Timeout("The network thread didn't respond in time".into()))?
+        .map_err(|_| {
+            NetworkError::NmUnavailable("Failed to receive network thread response".into())
+        })?;
 
-    received
-        .and_then(|r| r)
-        .or_else(|e| Err(e).context(format!("Execute command failed")))
+    received.map_err(NetworkError::from)
 }
 
 /// The glib channel
@@ -61,15 +86,27 @@ pub fn create_channel() -> (glib::Sender<NetworkRequest>, glib::Receiver<Network
 pub fn run_network_manager_loop(
     glib_receiver: glib::Receiver<NetworkRequest>,
     link_modes: Arc<Value>,
+    events: EventSender,
+) {
+    run_network_backend_loop(glib_receiver, LibnmBackend::new(link_modes), events);
+}
+
+/// the main loop in glibc, all the commands are answered by the given backend,
+/// the events of the backend are published to the `events` sender.
+pub fn run_network_backend_loop<B: NetworkBackend + 'static>(
+    glib_receiver: glib::Receiver<NetworkRequest>,
+    backend: B,
+    events: EventSender,
 ) {
     let context = MainContext::new();
     let loop_ = MainLoop::new(Some(&context), false);
+    let backend = Rc::new(backend);
 
     context
         .with_thread_default(|| {
+            backend.subscribe_events(events);
             glib_receiver.attach(None, move |request| {
-                let link_modes_cloned = Arc::clone(&link_modes);
-                dispatch_command_requests(request, link_modes_cloned)
+                dispatch_command_requests(request, Rc::clone(&backend))
             });
 
             loop_.run();
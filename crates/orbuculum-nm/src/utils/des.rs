@@ -2,6 +2,7 @@ use ipnet::IpNet;
 use serde::{de, Deserialize, Deserializer};
 use std::fmt;
 use std::marker::PhantomData;
+use std::str::FromStr;
 
 pub fn ipnet_from_string<'de, D>(deserializer: D) -> Result<Vec<IpNet>, D::Error>
 where
@@ -31,3 +32,43 @@ where
     }
     deserializer.deserialize_any(StringOrVec(PhantomData))
 }
+
+/// The reverse of `ipver_human`, both `4`/`6` and the address families are
+/// accepted.
+pub fn ipver_from_human<'de, D>(deserializer: D) -> Result<i32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match i32::deserialize(deserializer)? {
+        4 | 2 => Ok(2),
+        6 | 10 => Ok(10),
+        other => Err(de::Error::custom(format!(
+            "Only v4 or v6 family is valid, got {}",
+            other
+        ))),
+    }
+}
+
+/// Deserialize a value from its string representation.
+pub fn from_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(de::Error::custom)
+}
+
+/// Deserialize an optional value from its string representation.
+pub fn opt_from_string<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) => value.parse().map(Some).map_err(de::Error::custom),
+        None => Ok(None),
+    }
+}
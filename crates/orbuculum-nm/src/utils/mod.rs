@@ -2,7 +2,18 @@ mod des;
 mod link_modes;
 mod ser;
 mod udev;
+mod wireguard;
 pub use self::udev::get_dev_id_path;
-pub use des::ipnet_from_string;
+pub use des::{from_string, ipnet_from_string, ipver_from_human, opt_from_string};
 pub use link_modes::gather_link_modes;
-pub use ser::{addrs_to_string, ipver_human, nm_display, to_string};
+pub use ser::{addrs_to_string, ipver_human, nm_display, opt_to_string, to_string};
+pub use wireguard::{check_wireguard_key, generate_private_key, public_key};
+
+/// An empty string means the value is unset.
+pub fn non_empty(value: &str) -> Option<&str> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
@@ -19,6 +19,16 @@ pub fn ipver_human<S: Serializer>(family: &i32, serializer: S) -> Result<S::Ok,
     }
 }
 
+pub fn opt_to_string<T: ToString, S: Serializer>(
+    data: &Option<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match data {
+        Some(data) => serializer.serialize_some(&data.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
 /// Combine mulitiple addresses into lists of string
 pub fn addrs_to_string<S: Serializer>(
     addreses: &Vec<IpNet>,
@@ -0,0 +1,69 @@
+//! The module provides the WireGuard key functions.
+use crate::error::NetworkError;
+use eyre::Result;
+use rand_core::OsRng;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// The length of WireGuard keys in bytes
+const KEY_LEN: usize = 32;
+
+fn decode_key(key: &str) -> Result<[u8; KEY_LEN]> {
+    base64::decode(key.trim())
+        .ok()
+        .and_then(|x| <[u8; KEY_LEN]>::try_from(x).ok())
+        .ok_or_else(|| {
+            NetworkError::InvalidArgument(format!(
+                "Invalid WireGuard key {}, expected a base64 encoded 32 bytes key",
+                key
+            ))
+            .into()
+        })
+}
+
+/// Check whether the key is a base64 encoded 32 bytes key.
+pub fn check_wireguard_key(key: &str) -> Result<()> {
+    decode_key(key).map(|_| ())
+}
+
+/// Generate a base64 encoded WireGuard private key.
+pub fn generate_private_key() -> String {
+    let secret = StaticSecret::random_from_rng(OsRng);
+    base64::encode(secret.to_bytes())
+}
+
+/// Derive the base64 encoded public key from the private key.
+pub fn public_key(private_key: &str) -> Result<String> {
+    let secret = StaticSecret::from(decode_key(private_key)?);
+    Ok(base64::encode(PublicKey::from(&secret).as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    // The key pair of the wg(8) man page example
+    #[case(
+        "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=",
+        "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw="
+    )]
+    fn test_public_key(#[case] private_key: &str, #[case] expected: &str) {
+        assert_eq!(public_key(private_key).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=", true)]
+    #[case("yAnz5TF+lXXJte14tji3zlMNq+hd2rYU", false)]
+    #[case("not a key", false)]
+    fn test_check_wireguard_key(#[case] key: &str, #[case] valid: bool) {
+        assert_eq!(check_wireguard_key(key).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_generate_private_key() {
+        let key = generate_private_key();
+        assert!(public_key(&key).is_ok());
+        assert_ne!(key, generate_private_key());
+    }
+}
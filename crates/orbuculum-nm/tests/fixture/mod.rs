@@ -11,8 +11,10 @@ pub async fn start_instance() -> Arc<State> {
             .await
             .expect("Failed to gather nic_linkmodes, please check your permissions."),
     );
+    let state = Arc::new(State::new(glib_sender));
+    let events = state.event_sender();
     thread::spawn(move || {
-        run_network_manager_loop(glib_receiver, nic_linkmodes);
+        run_network_manager_loop(glib_receiver, nic_linkmodes, events);
     });
-    Arc::new(State::new(glib_sender))
+    state
 }
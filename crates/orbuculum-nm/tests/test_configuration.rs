@@ -35,7 +35,7 @@ async fn test_modify_ip_configuration(#[future] start_instance: Arc<State>) {
             });
             send_command(
                 start_instance_ref,
-                NetworkCommand::UpdateConnection(json_data),
+                NetworkCommand::UpdateConnection(serde_json::from_value(json_data).unwrap()),
             )
             .await
             .unwrap();
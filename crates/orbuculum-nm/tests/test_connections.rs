@@ -19,14 +19,9 @@ async fn test_list_connections(#[future] start_instance: Arc<State>) {
             let connections = send_command(start_instance_ref, NetworkCommand::ListConnections)
                 .await
                 .ok()
-                .map(|x| x.into_value().unwrap())
+                .map(|x| x.into_connections().unwrap())
                 .unwrap();
-            let mut names: Vec<&str> = connections
-                .as_array()
-                .unwrap()
-                .iter()
-                .filter_map(|conn| conn.get("name").and_then(|x| x.as_str()))
-                .collect();
+            let mut names: Vec<&str> = connections.iter().map(|conn| conn.name.as_str()).collect();
 
             let nmcli_con_names =
                 context::run_shell_cmd("nmcli -t connection show | awk -F: '{print $1}'").unwrap();
@@ -64,9 +59,9 @@ async fn test_create_connection(#[future] start_instance: Arc<State>) {
             )
             .await
             .ok()
-            .map(|x| x.into_value().unwrap())
+            .map(|x| x.into_uuid().unwrap())
             .unwrap();
-            let uuid = connection.as_str().unwrap();
+            let uuid = connection.as_str();
             let connection_string = format!("my_special_connection:{}", uuid);
             let output =
                 context::run_shell_cmd("nmcli -t connection show | grep my_special_connection")
@@ -140,10 +135,10 @@ async fn test_get_connection(#[future] start_instance: Arc<State>) {
             )
             .await
             .ok()
-            .map(|x| x.into_value().unwrap())
+            .map(|x| x.into_connection().unwrap())
             .unwrap();
-            let got_uuid = connection["uuid"].as_str().unwrap();
-            let got_name = connection["name"].as_str().unwrap();
+            let got_uuid = connection.uuid.as_str();
+            let got_name = connection.name.as_str();
             assert_eq!(got_uuid, connection_uuid);
             assert_eq!(got_name, "my_old_unique_connection");
         }) as Pin<Box<dyn Future<Output = ()>>>
@@ -161,3 +156,46 @@ async fn test_get_connection(#[future] start_instance: Arc<State>) {
     context::run_shell_cmd("nmcli connection delete my_testable_connection").unwrap();
     assert!(result.is_ok());
 }
+
+#[rstest]
+#[tokio::test]
+async fn test_get_mac_bound_connection(#[future] start_instance: Arc<State>) {
+    let start_instance_ref = &start_instance.await;
+    let async_wrapper = |start_instance_ref: Arc<State>| {
+        Box::pin(async move {
+            let uuid = send_command(
+                start_instance_ref.clone(),
+                NetworkCommand::CreateWiredConnection(
+                    "my_mac_bound_connection".to_string(),
+                    "52:54:00:12:34:56".to_string(),
+                ),
+            )
+            .await
+            .ok()
+            .map(|x| x.into_uuid().unwrap())
+            .unwrap();
+            let connection = send_command(start_instance_ref, NetworkCommand::GetConnection(uuid))
+                .await
+                .ok()
+                .map(|x| x.into_connection().unwrap())
+                .unwrap();
+            assert_eq!(connection.interface, None);
+            assert!(connection
+                .mac
+                .unwrap()
+                .eq_ignore_ascii_case("52:54:00:12:34:56"));
+        }) as Pin<Box<dyn Future<Output = ()>>>
+    };
+
+    // Actually run the async test
+    let result = async move {
+        panic::AssertUnwindSafe(async_wrapper(Arc::clone(start_instance_ref)))
+            .catch_unwind()
+            .await
+    }
+    .await;
+
+    // Test teardown
+    context::run_shell_cmd("nmcli connection delete my_mac_bound_connection").unwrap();
+    assert!(result.is_ok());
+}
@@ -28,7 +28,7 @@ async fn test_list_devices(#[future] start_instance: Arc<State>) {
                 send_command(Arc::clone(&start_instance_ref), NetworkCommand::ListDeivces)
                     .await
                     .ok()
-                    .map(|x| x.into_value().unwrap())
+                    .map(|x| serde_json::to_value(x.into_devices().unwrap()).unwrap())
                     .unwrap();
             match devices {
                 Value::Array(items) => {
@@ -0,0 +1,1433 @@
+//! Fake backend tests module
+//!
+//! The tests run the glib loop with the in-memory backend, so they don't need
+//! a NetworkManager daemon.
+use orbuculum_nm::{
+    create_channel, run_network_backend_loop, send_command, ApplyStrategy, BondConnection,
+    BridgeConnection, BridgePort, Connection, FakeBackend, IpTunnelConnection, LegacyFormat,
+    NetDevice, NetworkCommand, NetworkError, NetworkEvent, NetworkState, Route, State,
+    VirtualDevice, VlanConnection, VxlanConnection, WireGuardConnection, WireGuardPeer,
+    WiredSettings,
+};
+use rstest::{fixture, rstest};
+use serde_json::json;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[fixture]
+fn start_fake_instance() -> Arc<State> {
+    let (glib_sender, glib_receiver) = create_channel();
+    let backend = FakeBackend::new()
+        .with_hostname("fake")
+        .with_device(NetDevice {
+            name: "eth1".into(),
+            mac: "52:54:5E:13:7F:43".into(),
+            state: "Disconnected".into(),
+            device_type: "Ethernet".into(),
+            is_managed: true,
+            net_link_modes: vec!["100baseT/Full".into(), "1000baseT/Full".into()],
+            ..Default::default()
+        });
+    let state = Arc::new(State::new(glib_sender));
+    let events = state.event_sender();
+    thread::spawn(move || {
+        run_network_backend_loop(glib_receiver, backend, events);
+    });
+    state
+}
+
+/// Create a wired connection `eth1-conn` on eth1, the uuid is returned.
+async fn create_eth1_connection(state: &Arc<State>) -> String {
+    send_command(
+        state.clone(),
+        NetworkCommand::CreateWiredConnection("eth1-conn".into(), "eth1".into()),
+    )
+    .await
+    .unwrap()
+    .into_uuid()
+    .unwrap()
+}
+
+async fn get_connection(state: &Arc<State>, uuid: &str) -> Connection {
+    send_command(state.clone(), NetworkCommand::GetConnection(uuid.into()))
+        .await
+        .unwrap()
+        .into_connection()
+        .unwrap()
+}
+
+async fn update_connection(state: &Arc<State>, connection: Connection) -> Connection {
+    send_command(state.clone(), NetworkCommand::UpdateConnection(connection))
+        .await
+        .unwrap()
+        .into_connection()
+        .unwrap()
+}
+
+async fn get_device(state: &Arc<State>, device_name: &str) -> NetDevice {
+    send_command(state.clone(), NetworkCommand::GetDevice(device_name.into()))
+        .await
+        .unwrap()
+        .into_device()
+        .unwrap()
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_connection_lifecycle(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let uuid = create_eth1_connection(&state).await;
+
+    let devices = send_command(state.clone(), NetworkCommand::ListDeivces)
+        .await
+        .unwrap()
+        .into_devices()
+        .unwrap();
+    assert_eq!(devices[0].connection.uuid.as_ref(), Some(&uuid));
+    let device = get_device(&state, "eth1").await;
+    assert_eq!(device.conn, vec![uuid.clone()]);
+
+    send_command(
+        state.clone(),
+        NetworkCommand::UpdateConnection(
+            serde_json::from_value(json!({
+                "name": "eth1-conn",
+                "uuid": uuid,
+                "ip4info": {
+                    "addresses": ["192.168.100.1/24"],
+                    "gateway": "192.168.100.254",
+                    "method": "manual",
+                    "dns": []
+                },
+                "ip6info": {
+                    "addresses": [],
+                    "gateway": null,
+                    "method": "disabled",
+                    "dns": []
+                }
+            }))
+            .unwrap(),
+        ),
+    )
+    .await
+    .unwrap();
+    send_command(
+        state.clone(),
+        NetworkCommand::RenameConnection(uuid.clone(), "eth1-renamed".into()),
+    )
+    .await
+    .unwrap();
+
+    let connection = get_connection(&state, &uuid).await;
+    assert_eq!(connection.name, "eth1-renamed");
+    assert_eq!(connection.interface.as_deref(), Some("eth1"));
+    assert_eq!(
+        connection.ip4info.addresses[0].to_string(),
+        "192.168.100.1/24"
+    );
+
+    send_command(
+        state.clone(),
+        NetworkCommand::DeleteConnection("eth1-renamed".into()),
+    )
+    .await
+    .unwrap();
+    assert!(matches!(
+        send_command(state, NetworkCommand::GetConnection(uuid)).await,
+        Err(NetworkError::NotFound(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_reactive_connection(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let mut uuids = vec![];
+    for name in ["eth1-conn", "eth1-backup"] {
+        let uuid = send_command(
+            state.clone(),
+            NetworkCommand::CreateWiredConnection(name.into(), "eth1".into()),
+        )
+        .await
+        .unwrap()
+        .into_uuid()
+        .unwrap();
+        uuids.push(uuid);
+    }
+
+    for (uuid, expected) in [
+        (&uuids[0], ApplyStrategy::Reapply),
+        (&uuids[1], ApplyStrategy::Activate),
+        (&uuids[1], ApplyStrategy::Reapply),
+    ] {
+        let strategy = send_command(state.clone(), NetworkCommand::Reactive(uuid.clone()))
+            .await
+            .unwrap()
+            .into_applied()
+            .unwrap();
+        assert_eq!(strategy, expected);
+    }
+    let device = get_device(&state, "eth1").await;
+    assert_eq!(device.connection.uuid.as_ref(), Some(&uuids[1]));
+
+    assert!(matches!(
+        send_command(state, NetworkCommand::Reactive("missing".into())).await,
+        Err(NetworkError::NotFound(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_connection_settings(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let document = |name: &str, uuid: Option<&str>| {
+        serde_json::from_value(json!({
+            "connection": {
+                "id": name,
+                "uuid": uuid,
+                "type": "802-3-ethernet",
+                "interface-name": "eth1"
+            },
+            "ipv4": {"method": "manual", "addresses": ["192.0.2.10/24"]},
+            "ipv6": {"method": "disabled"}
+        }))
+        .unwrap()
+    };
+    let uuid = send_command(
+        state.clone(),
+        NetworkCommand::PutConnectionSettings(document("lan", None)),
+    )
+    .await
+    .unwrap()
+    .into_uuid()
+    .unwrap();
+    let connection = get_connection(&state, &uuid).await;
+    assert_eq!(connection.interface.as_deref(), Some("eth1"));
+    assert_eq!(connection.ip4info.addresses[0].to_string(), "192.0.2.10/24");
+
+    let replaced = send_command(
+        state.clone(),
+        NetworkCommand::PutConnectionSettings(document("lan-replaced", Some(&uuid))),
+    )
+    .await
+    .unwrap()
+    .into_uuid()
+    .unwrap();
+    assert_eq!(replaced, uuid);
+    let connections = send_command(state.clone(), NetworkCommand::ListConnections)
+        .await
+        .unwrap()
+        .into_connections()
+        .unwrap();
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].name, "lan-replaced");
+
+    let invalid = serde_json::from_value(json!({
+        "connection": {"type": "802-3-ethernet"},
+        "ipv4": {"addresses": ["2001:db8::1/64"]}
+    }))
+    .unwrap();
+    match send_command(state, NetworkCommand::PutConnectionSettings(invalid)).await {
+        Err(NetworkError::InvalidSettings(_, errors)) => {
+            let properties: Vec<_> = errors
+                .iter()
+                .map(|x| {
+                    format!(
+                        "{}.{}",
+                        x.setting,
+                        x.property.as_deref().unwrap_or_default()
+                    )
+                })
+                .collect();
+            assert_eq!(properties, vec!["connection.id", "ipv4.addresses"]);
+        }
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_keyfiles(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let private_key = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+    let wireguard = WireGuardConnection {
+        name: "hq".into(),
+        interface: "wg0".into(),
+        private_key: Some(private_key.into()),
+        ..Default::default()
+    };
+    let uuid = send_command(state.clone(), NetworkCommand::CreateWireGuard(wireguard))
+        .await
+        .unwrap()
+        .into_uuid()
+        .unwrap();
+
+    for (include_secrets, included) in [(false, false), (true, true)] {
+        let keyfiles = send_command(
+            state.clone(),
+            NetworkCommand::ExportKeyfiles(Some(uuid.clone()), include_secrets),
+        )
+        .await
+        .unwrap()
+        .into_keyfiles()
+        .unwrap();
+        assert_eq!(keyfiles.len(), 1);
+        assert!(keyfiles[0].content.contains("interface-name=wg0"));
+        assert_eq!(keyfiles[0].content.contains(private_key), included);
+    }
+
+    let content = "[connection]
+id=lan
+type=ethernet
+interface-name=eth1
+
+[ipv4]
+method=manual
+address1=192.0.2.10/24
+";
+    let imported = send_command(state.clone(), NetworkCommand::ImportKeyfile(content.into()))
+        .await
+        .unwrap()
+        .into_uuid()
+        .unwrap();
+    let keyfiles = send_command(state.clone(), NetworkCommand::ExportKeyfiles(None, false))
+        .await
+        .unwrap()
+        .into_keyfiles()
+        .unwrap();
+    assert_eq!(keyfiles.len(), 2);
+    let exported = keyfiles.iter().find(|x| x.uuid == imported).unwrap();
+    assert!(exported.content.contains("address1=192.0.2.10/24"));
+
+    let replaced = send_command(
+        state.clone(),
+        NetworkCommand::ImportKeyfile(exported.content.replace("id=lan", "id=lan-restored")),
+    )
+    .await
+    .unwrap()
+    .into_uuid()
+    .unwrap();
+    assert_eq!(replaced, imported);
+    let connection = get_connection(&state, &imported).await;
+    assert_eq!(connection.name, "lan-restored");
+
+    assert!(matches!(
+        send_command(state, NetworkCommand::ImportKeyfile("id=lan".into())).await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_legacy_config(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let content = include_str!("legacy_configs/interfaces");
+    let config = send_command(
+        state.clone(),
+        NetworkCommand::ImportLegacyConfig(
+            LegacyFormat::Interfaces,
+            "interfaces".into(),
+            content.into(),
+        ),
+    )
+    .await
+    .unwrap()
+    .into_legacy_config()
+    .unwrap();
+    assert_eq!(config.connections.len(), 2);
+    assert_eq!(config.unsupported.len(), 4);
+
+    let connection = get_connection(&state, &config.connections[0].uuid).await;
+    assert_eq!(connection.name, "eth1");
+    assert_eq!(
+        connection.ip4info.addresses,
+        vec!["192.0.2.10/24".parse().unwrap()]
+    );
+    let device = get_device(&state, "eth1").await;
+    assert_eq!(device.connection.uuid, Some(connection.uuid));
+
+    assert!(matches!(
+        send_command(
+            state,
+            NetworkCommand::ImportLegacyConfig(
+                LegacyFormat::Ifcfg,
+                "ifcfg-eth1".into(),
+                "IPADDR=192.0.2.300\n".into(),
+            ),
+        )
+        .await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_network_state(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let network_state = NetworkState::from_yaml(concat!(
+        "hostname:\n",
+        "  config: gateway\n",
+        "dns-resolver:\n",
+        "  config:\n",
+        "    server: [192.0.2.53]\n",
+        "routes:\n",
+        "  config:\n",
+        "  - destination: 0.0.0.0/0\n",
+        "    next-hop-interface: eth1\n",
+        "    next-hop-address: 192.0.2.1\n",
+        "interfaces:\n",
+        "- name: eth1\n",
+        "  type: ethernet\n",
+        "  ipv4:\n",
+        "    enabled: true\n",
+        "    address:\n",
+        "    - ip: 192.0.2.10\n",
+        "      prefix-length: 24\n",
+    ))
+    .unwrap();
+    let changes = send_command(
+        state.clone(),
+        NetworkCommand::ApplyNetworkState(network_state.clone(), true),
+    )
+    .await
+    .unwrap()
+    .into_state_changes()
+    .unwrap();
+    assert_eq!(changes.len(), 2);
+    let connections = send_command(state.clone(), NetworkCommand::ListConnections)
+        .await
+        .unwrap()
+        .into_connections()
+        .unwrap();
+    assert!(connections.is_empty());
+
+    send_command(
+        state.clone(),
+        NetworkCommand::ApplyNetworkState(network_state, false),
+    )
+    .await
+    .unwrap();
+    let shown = send_command(state.clone(), NetworkCommand::ShowNetworkState)
+        .await
+        .unwrap()
+        .into_network_state()
+        .unwrap();
+    assert_eq!(
+        shown.hostname.as_ref().and_then(|x| x.config.as_deref()),
+        Some("gateway")
+    );
+    let dns = shown.dns_resolver.as_ref().and_then(|x| x.config.as_ref());
+    assert_eq!(dns.unwrap().server, vec!["192.0.2.53".parse().unwrap()]);
+    let routes = &shown.routes.as_ref().unwrap().config;
+    assert_eq!(
+        routes[0].next_hop_address,
+        Some("192.0.2.1".parse().unwrap())
+    );
+    let ipv4 = shown.interfaces[0].ipv4.as_ref().unwrap();
+    assert_eq!(
+        ipv4.address[0].ip,
+        "192.0.2.10".parse::<std::net::IpAddr>().unwrap()
+    );
+
+    // The shown state is reached already
+    let changes = send_command(
+        state.clone(),
+        NetworkCommand::ApplyNetworkState(shown, false),
+    )
+    .await
+    .unwrap()
+    .into_state_changes()
+    .unwrap();
+    assert!(changes.is_empty());
+
+    let network_state = NetworkState::from_yaml("interfaces:\n- name: eth9\n").unwrap();
+    assert!(matches!(
+        send_command(
+            state,
+            NetworkCommand::ApplyNetworkState(network_state, true)
+        )
+        .await,
+        Err(NetworkError::NotFound(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_hostname_and_networking(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let hostname = send_command(state.clone(), NetworkCommand::GetHostname)
+        .await
+        .unwrap()
+        .into_hostname()
+        .unwrap();
+    assert_eq!(hostname, "fake");
+
+    send_command(state.clone(), NetworkCommand::SetNetworking(false))
+        .await
+        .unwrap();
+    let networking = send_command(state.clone(), NetworkCommand::GetNetworking)
+        .await
+        .unwrap()
+        .into_networking()
+        .unwrap();
+    assert!(!networking);
+
+    assert!(matches!(
+        send_command(state, NetworkCommand::SetManage("eth9".into(), false)).await,
+        Err(NetworkError::NotFound(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_connectivity(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let info = send_command(state.clone(), NetworkCommand::GetConnectivity)
+        .await
+        .unwrap()
+        .into_connectivity()
+        .unwrap();
+    assert_eq!(info.state, "full");
+    assert!(info.primary_connection.is_none());
+    assert_eq!(info.devices[0].device, "eth1");
+    assert_eq!(info.devices[0].ip4, "none");
+
+    let uuid = create_eth1_connection(&state).await;
+    let info = send_command(state.clone(), NetworkCommand::CheckConnectivity)
+        .await
+        .unwrap()
+        .into_connectivity()
+        .unwrap();
+    assert_eq!(info.primary_connection.unwrap().uuid, Some(uuid));
+    assert_eq!(info.devices[0].ip4, "full");
+    assert_eq!(info.devices[0].ip6, "full");
+
+    send_command(state.clone(), NetworkCommand::SetNetworking(false))
+        .await
+        .unwrap();
+    let info = send_command(state, NetworkCommand::GetConnectivity)
+        .await
+        .unwrap()
+        .into_connectivity()
+        .unwrap();
+    assert_eq!(info.state, "none");
+    assert!(info.primary_connection.is_none());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_active_connections(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let active = send_command(state.clone(), NetworkCommand::ListActiveConnections)
+        .await
+        .unwrap()
+        .into_active_connections()
+        .unwrap();
+    assert!(active.is_empty());
+
+    let uuid = create_eth1_connection(&state).await;
+    let active = send_command(state.clone(), NetworkCommand::ListActiveConnections)
+        .await
+        .unwrap()
+        .into_active_connections()
+        .unwrap();
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].uuid, uuid);
+    assert_eq!(active[0].state, "activated");
+    assert_eq!(active[0].devices, vec!["eth1".to_string()]);
+    assert!(active[0].default4);
+    assert!(active[0].ip4info.is_some());
+
+    send_command(state.clone(), NetworkCommand::SetNetworking(false))
+        .await
+        .unwrap();
+    let active = send_command(state, NetworkCommand::ListActiveConnections)
+        .await
+        .unwrap()
+        .into_active_connections()
+        .unwrap();
+    assert!(active.is_empty());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_events(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let mut events = state.subscribe();
+    let uuid = create_eth1_connection(&state).await;
+    send_command(
+        state.clone(),
+        NetworkCommand::SetManage("eth1".into(), false),
+    )
+    .await
+    .unwrap();
+    send_command(state, NetworkCommand::DeleteConnection("eth1-conn".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        events.recv().await.unwrap(),
+        NetworkEvent::ConnectionAdded {
+            uuid: uuid.clone(),
+            name: "eth1-conn".into()
+        }
+    );
+    assert_eq!(
+        events.recv().await.unwrap(),
+        NetworkEvent::DeviceStateChanged {
+            name: "eth1".into(),
+            state: "Unmanaged".into()
+        }
+    );
+    assert_eq!(
+        events.recv().await.unwrap(),
+        NetworkEvent::ConnectionRemoved {
+            uuid,
+            name: "eth1-conn".into()
+        }
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_routes(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let uuid = create_eth1_connection(&state).await;
+    let route: Route = serde_json::from_value(json!({
+        "family": 4,
+        "dest": "10.10.0.0/16",
+        "next_hop": "192.168.100.254",
+        "table": 100,
+        "attributes": {"mtu": "1400"}
+    }))
+    .unwrap();
+    assert_eq!(route.metric, -1);
+
+    let connection = send_command(
+        state.clone(),
+        NetworkCommand::AddRoute(uuid.clone(), route.clone()),
+    )
+    .await
+    .unwrap()
+    .into_connection()
+    .unwrap();
+    assert_eq!(connection.ip4info.routes, Some(vec![route.clone()]));
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::AddRoute(uuid.clone(), route.clone())
+        )
+        .await,
+        Err(NetworkError::Conflict(_))
+    ));
+
+    // The routes are carried by the connection, so the update keeps them.
+    let mut connection: Connection =
+        serde_json::from_value(serde_json::to_value(connection).unwrap()).unwrap();
+    connection.ip4info.method = "manual".into();
+    let mut connection = update_connection(&state, connection).await;
+    assert_eq!(connection.ip4info.routes, Some(vec![route.clone()]));
+
+    // The update without routes leaves them unchanged.
+    connection.ip4info = serde_json::from_value(json!({
+        "method": "manual",
+        "addresses": ["192.168.100.1/24"],
+        "gateway": null,
+        "dns": []
+    }))
+    .unwrap();
+    let connection = update_connection(&state, connection).await;
+    assert_eq!(connection.ip4info.routes, Some(vec![route.clone()]));
+
+    let connection = send_command(
+        state.clone(),
+        NetworkCommand::RemoveRoute(
+            uuid.clone(),
+            serde_json::from_value(json!({"family": 4, "dest": "10.10.0.0/16"})).unwrap(),
+        ),
+    )
+    .await
+    .unwrap()
+    .into_connection()
+    .unwrap();
+    assert_eq!(connection.ip4info.routes, Some(vec![]));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_routing_rules(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let uuid = create_eth1_connection(&state).await;
+    let mut connection = get_connection(&state, &uuid).await;
+    connection.ip4info = serde_json::from_value(json!({
+        "method": "manual",
+        "addresses": ["192.168.100.1/24"],
+        "gateway": null,
+        "dns": [],
+        "route_table": 100,
+        "routing_rules": [
+            {"priority": 1000, "from": "192.168.100.0/24", "table": 100},
+            {"priority": 1001, "iif": "eth1", "fwmark": 7, "table": 100}
+        ]
+    }))
+    .unwrap();
+
+    let mut connection = update_connection(&state, connection).await;
+    let rules = connection.ip4info.routing_rules.clone().unwrap();
+    assert_eq!(connection.ip4info.route_table, Some(100));
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].from.unwrap().to_string(), "192.168.100.0/24");
+    assert_eq!(rules[1].iif.as_deref(), Some("eth1"));
+    assert_eq!(rules[1].fwmark, Some(7));
+
+    // The update without the table and rules leaves them unchanged.
+    connection.ip4info.route_table = None;
+    connection.ip4info.routing_rules = None;
+    let connection = update_connection(&state, connection).await;
+    assert_eq!(connection.ip4info.route_table, Some(100));
+    assert_eq!(connection.ip4info.routing_rules, Some(rules));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_dns_settings(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let uuid = create_eth1_connection(&state).await;
+    let mut connection = get_connection(&state, &uuid).await;
+    connection.ip4info = serde_json::from_value(json!({
+        "method": "auto",
+        "addresses": [],
+        "gateway": null,
+        "dns": ["192.168.100.53"],
+        "dns_search": ["example.com", "lab.local"],
+        "dns_options": ["rotate"],
+        "dns_priority": -10,
+        "ignore_auto_dns": true,
+        "routes": [],
+        "routing_rules": []
+    }))
+    .unwrap();
+    let connection = update_connection(&state, connection).await;
+    assert_eq!(
+        connection.ip4info.dns_search.clone().unwrap(),
+        vec!["example.com", "lab.local"]
+    );
+    assert_eq!(
+        connection.ip4info.dns_options.clone().unwrap(),
+        vec!["rotate"]
+    );
+    assert_eq!(connection.ip4info.dns_priority, Some(-10));
+    assert_eq!(connection.ip4info.ignore_auto_dns, Some(true));
+
+    // The unset values are kept
+    let mut connection = connection;
+    connection.ip4info.dns_search = None;
+    connection.ip4info.dns_options = None;
+    connection.ip4info.dns_priority = None;
+    connection.ip4info.ignore_auto_dns = None;
+    let connection = update_connection(&state, connection).await;
+    assert_eq!(
+        connection.ip4info.dns_search.clone().unwrap(),
+        vec!["example.com", "lab.local"]
+    );
+    assert_eq!(
+        connection.ip4info.dns_options.clone().unwrap(),
+        vec!["rotate"]
+    );
+    assert_eq!(connection.ip4info.dns_priority, Some(-10));
+    assert_eq!(connection.ip4info.ignore_auto_dns, Some(true));
+
+    // An empty list removes them
+    let mut connection = connection;
+    connection.ip4info.dns_options = Some(vec![]);
+    let connection = update_connection(&state, connection).await;
+    assert_eq!(connection.ip4info.dns_options, Some(vec![]));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_ip_flags(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let uuid = create_eth1_connection(&state).await;
+    let mut connection = get_connection(&state, &uuid).await;
+    connection.ip4info.never_default = Some(true);
+    connection.ip4info.dhcp_hostname = Some("node1".into());
+    connection.ip4info.dhcp_timeout = Some(30);
+    connection.ip6info.may_fail = Some(true);
+    connection.ip6info.addr_gen_mode = Some("stable-privacy".into());
+    let connection = update_connection(&state, connection).await;
+    assert_eq!(connection.ip4info.never_default, Some(true));
+    assert_eq!(
+        connection.ip6info.addr_gen_mode.as_deref(),
+        Some("stable-privacy")
+    );
+
+    // The flags not given are kept, an empty hostname unsets it
+    let mut connection = connection;
+    connection.ip4info.never_default = None;
+    connection.ip4info.dhcp_hostname = Some("".into());
+    connection.ip6info = Default::default();
+    let connection = update_connection(&state, connection).await;
+    assert_eq!(connection.ip4info.never_default, Some(true));
+    assert_eq!(connection.ip4info.dhcp_hostname, None);
+    assert_eq!(connection.ip4info.dhcp_timeout, Some(30));
+    assert_eq!(connection.ip6info.may_fail, Some(true));
+    assert_eq!(
+        connection.ip6info.addr_gen_mode.as_deref(),
+        Some("stable-privacy")
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_wired_settings(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let uuid = create_eth1_connection(&state).await;
+    let mut connection = get_connection(&state, &uuid).await;
+    connection.wired = Some(WiredSettings {
+        mtu: Some(9000),
+        speed: Some(1000),
+        duplex: Some("full".into()),
+        auto_negotiate: Some(false),
+        wake_on_lan: Some(vec!["magic".into()]),
+        ..Default::default()
+    });
+    let connection = update_connection(&state, connection).await;
+    let wired = connection.wired.clone().unwrap();
+    assert_eq!(wired.mtu, Some(9000));
+    assert_eq!(wired.speed, Some(1000));
+
+    // The link mode is not supported by eth1
+    let mut connection = connection;
+    connection.wired = Some(WiredSettings {
+        speed: Some(10000),
+        ..Default::default()
+    });
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::UpdateConnection(connection.clone())
+        )
+        .await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+
+    // The settings not given are kept
+    connection.wired = Some(WiredSettings {
+        cloned_mac: Some("stable".into()),
+        ..Default::default()
+    });
+    let wired = update_connection(&state, connection).await.wired.unwrap();
+    assert_eq!(wired.cloned_mac.as_deref(), Some("stable"));
+    assert_eq!(wired.mtu, Some(9000));
+    assert_eq!(wired.wake_on_lan, Some(vec!["magic".to_string()]));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_vlan(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let vlan = VlanConnection {
+        name: "storage".into(),
+        parent: "eth1".into(),
+        id: 4095,
+        ..Default::default()
+    };
+    assert!(matches!(
+        send_command(state.clone(), NetworkCommand::CreateVlan(vlan.clone())).await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+
+    let vlan = VlanConnection {
+        id: 20,
+        egress_priority_map: vec!["0:3".into()],
+        ..vlan
+    };
+    let uuid = send_command(state.clone(), NetworkCommand::CreateVlan(vlan))
+        .await
+        .unwrap()
+        .into_uuid()
+        .unwrap();
+    let vlan = send_command(state.clone(), NetworkCommand::GetVlan(uuid.clone()))
+        .await
+        .unwrap()
+        .into_vlan()
+        .unwrap();
+    assert_eq!(vlan.interface.as_deref(), Some("eth1.20"));
+    assert_eq!(vlan.egress_priority_map, vec!["0:3"]);
+
+    let vlan = send_command(
+        state.clone(),
+        NetworkCommand::UpdateVlan(VlanConnection {
+            flags: vec!["reorder-headers".into()],
+            ip4info: None,
+            ..vlan
+        }),
+    )
+    .await
+    .unwrap()
+    .into_vlan()
+    .unwrap();
+    assert_eq!(vlan.flags, vec!["reorder-headers"]);
+    assert!(vlan.ip4info.is_some());
+
+    send_command(state.clone(), NetworkCommand::DeleteVlan(uuid.clone()))
+        .await
+        .unwrap();
+    assert!(matches!(
+        send_command(state, NetworkCommand::GetVlan(uuid)).await,
+        Err(NetworkError::NotFound(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_bond(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let bond = BondConnection {
+        name: "uplink".into(),
+        interface: "bond0".into(),
+        mode: "active-backup".into(),
+        lacp_rate: Some("fast".into()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        send_command(state.clone(), NetworkCommand::CreateBond(bond.clone())).await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+
+    let bond = BondConnection {
+        mode: "802.3ad".into(),
+        miimon: Some(100),
+        xmit_hash_policy: Some("layer3+4".into()),
+        ..bond
+    };
+    let uuid = send_command(state.clone(), NetworkCommand::CreateBond(bond))
+        .await
+        .unwrap()
+        .into_uuid()
+        .unwrap();
+
+    let bond = send_command(
+        state.clone(),
+        NetworkCommand::AttachBondPort(uuid.clone(), "eth1".into()),
+    )
+    .await
+    .unwrap()
+    .into_bond()
+    .unwrap();
+    assert_eq!(bond.ports, vec!["eth1"]);
+    let device = get_device(&state, "eth1").await;
+    assert_eq!(device.controller.as_deref(), Some("bond0"));
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::AttachBondPort(uuid.clone(), "eth1".into())
+        )
+        .await,
+        Err(NetworkError::Conflict(_))
+    ));
+
+    let bond = send_command(
+        state.clone(),
+        NetworkCommand::DetachBondPort(uuid.clone(), "eth1".into()),
+    )
+    .await
+    .unwrap()
+    .into_bond()
+    .unwrap();
+    assert!(bond.ports.is_empty());
+    let bond = send_command(state.clone(), NetworkCommand::GetBond(uuid))
+        .await
+        .unwrap()
+        .into_bond()
+        .unwrap();
+    assert_eq!(bond.miimon, Some(100));
+    let device = get_device(&state, "eth1").await;
+    assert!(device.controller.is_none());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_bridge(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let uuid = create_eth1_connection(&state).await;
+    let mut connection = get_connection(&state, &uuid).await;
+    connection.ip4info.method = "manual".into();
+    connection.ip4info.addresses = vec!["192.168.100.1/24".parse().unwrap()];
+    send_command(state.clone(), NetworkCommand::UpdateConnection(connection))
+        .await
+        .unwrap();
+
+    let bridge = BridgeConnection {
+        name: "vm-bridge".into(),
+        interface: "br0".into(),
+        forward_delay: Some(1),
+        ..Default::default()
+    };
+    assert!(matches!(
+        send_command(state.clone(), NetworkCommand::CreateBridge(bridge.clone())).await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+    let uuid = send_command(
+        state.clone(),
+        NetworkCommand::CreateBridge(BridgeConnection {
+            stp: Some(false),
+            forward_delay: None,
+            ..bridge
+        }),
+    )
+    .await
+    .unwrap()
+    .into_uuid()
+    .unwrap();
+
+    let port = BridgePort {
+        device: "eth1".into(),
+        priority: Some(64),
+        ..Default::default()
+    };
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::AttachBridgePort(uuid.clone(), port.clone(), true)
+        )
+        .await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+    let port = BridgePort {
+        priority: Some(16),
+        hairpin: Some(true),
+        ..port
+    };
+    let bridge = send_command(
+        state.clone(),
+        NetworkCommand::AttachBridgePort(uuid.clone(), port, true),
+    )
+    .await
+    .unwrap()
+    .into_bridge()
+    .unwrap();
+    assert_eq!(bridge.stp, Some(false));
+    assert_eq!(bridge.ports[0].device, "eth1");
+    assert_eq!(bridge.ports[0].priority, Some(16));
+    assert_eq!(bridge.ports[0].hairpin, Some(true));
+    let ip4info = bridge.ip4info.unwrap();
+    assert_eq!(ip4info.method, "manual");
+    assert_eq!(ip4info.addresses[0].to_string(), "192.168.100.1/24");
+
+    let bridge = send_command(
+        state.clone(),
+        NetworkCommand::UpdateBridge(BridgeConnection {
+            stp: Some(true),
+            ip4info: None,
+            ..bridge
+        }),
+    )
+    .await
+    .unwrap()
+    .into_bridge()
+    .unwrap();
+    assert_eq!(bridge.stp, Some(true));
+    assert_eq!(bridge.ports.len(), 1);
+    assert_eq!(bridge.ip4info.unwrap().method, "manual");
+
+    let bridge = send_command(
+        state.clone(),
+        NetworkCommand::DetachBridgePort(uuid.clone(), "eth1".into()),
+    )
+    .await
+    .unwrap()
+    .into_bridge()
+    .unwrap();
+    assert!(bridge.ports.is_empty());
+    assert!(matches!(
+        send_command(state, NetworkCommand::DetachBridgePort(uuid, "eth1".into())).await,
+        Err(NetworkError::NotFound(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_wireguard(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let peer = WireGuardPeer {
+        public_key: "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=".into(),
+        allowed_ips: vec!["10.192.122.0/24".parse().unwrap()],
+        endpoint: Some("hq.example.com:51820".into()),
+        persistent_keepalive: Some(25),
+    };
+    let wireguard = WireGuardConnection {
+        name: "hq".into(),
+        interface: "wg0".into(),
+        private_key: Some("yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=".into()),
+        listen_port: Some(51820),
+        peers: Some(vec![WireGuardPeer {
+            endpoint: Some("hq.example.com".into()),
+            ..peer.clone()
+        }]),
+        ..Default::default()
+    };
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::CreateWireGuard(wireguard.clone())
+        )
+        .await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+
+    let wireguard = WireGuardConnection {
+        peers: Some(vec![peer]),
+        ..wireguard
+    };
+    let uuid = send_command(state.clone(), NetworkCommand::CreateWireGuard(wireguard))
+        .await
+        .unwrap()
+        .into_uuid()
+        .unwrap();
+    let wireguard = send_command(state.clone(), NetworkCommand::GetWireGuard(uuid.clone()))
+        .await
+        .unwrap()
+        .into_wireguard()
+        .unwrap();
+    assert_eq!(wireguard.private_key, None);
+    assert_eq!(
+        wireguard.public_key.as_deref(),
+        Some("HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=")
+    );
+    assert_eq!(
+        wireguard.peers.as_ref().unwrap()[0].persistent_keepalive,
+        Some(25)
+    );
+    assert!(serde_json::to_value(&wireguard)
+        .unwrap()
+        .get("private_key")
+        .is_none());
+
+    // The private key and the peers are kept if they are not given, and the
+    // private key is generated if it is empty
+    let updated = send_command(
+        state.clone(),
+        NetworkCommand::UpdateWireGuard(WireGuardConnection {
+            fwmark: Some(51820),
+            peers: None,
+            ..wireguard.clone()
+        }),
+    )
+    .await
+    .unwrap()
+    .into_wireguard()
+    .unwrap();
+    assert_eq!(updated.public_key, wireguard.public_key);
+    assert_eq!(updated.fwmark, Some(51820));
+    assert_eq!(updated.peers, wireguard.peers);
+    let updated = send_command(
+        state,
+        NetworkCommand::UpdateWireGuard(WireGuardConnection {
+            private_key: Some("".into()),
+            ..updated
+        }),
+    )
+    .await
+    .unwrap()
+    .into_wireguard()
+    .unwrap();
+    assert!(updated.public_key.is_some());
+    assert_ne!(updated.public_key, wireguard.public_key);
+    assert_eq!(updated.private_key, None);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_ip_tunnel(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let tunnel = IpTunnelConnection {
+        name: "site-b".into(),
+        interface: "gre1".into(),
+        mode: "gre".into(),
+        remote: Some("2001:db8::1".parse().unwrap()),
+        key: Some("42".into()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::CreateIpTunnel(tunnel.clone())
+        )
+        .await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+
+    let tunnel = IpTunnelConnection {
+        remote: Some("203.0.113.1".parse().unwrap()),
+        ..tunnel
+    };
+    let uuid = send_command(state.clone(), NetworkCommand::CreateIpTunnel(tunnel))
+        .await
+        .unwrap()
+        .into_uuid()
+        .unwrap();
+    let tunnel = send_command(state.clone(), NetworkCommand::GetIpTunnel(uuid.clone()))
+        .await
+        .unwrap()
+        .into_ip_tunnel()
+        .unwrap();
+    assert_eq!(tunnel.ttl, Some(0));
+    assert_eq!(tunnel.key.as_deref(), Some("42"));
+
+    // The key is kept if it is not given
+    let tunnel = send_command(
+        state.clone(),
+        NetworkCommand::UpdateIpTunnel(IpTunnelConnection {
+            ttl: Some(64),
+            key: None,
+            ..tunnel
+        }),
+    )
+    .await
+    .unwrap()
+    .into_ip_tunnel()
+    .unwrap();
+    assert_eq!(tunnel.ttl, Some(64));
+    assert_eq!(tunnel.key.as_deref(), Some("42"));
+    assert!(matches!(
+        send_command(state, NetworkCommand::GetVxlan(uuid)).await,
+        Err(NetworkError::NotFound(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_vxlan(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let vxlan = VxlanConnection {
+        name: "overlay".into(),
+        interface: "vxlan10".into(),
+        id: 10,
+        remote: Some("239.1.1.1".parse().unwrap()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        send_command(state.clone(), NetworkCommand::CreateVxlan(vxlan.clone())).await,
+        Err(NetworkError::InvalidArgument(_))
+    ));
+
+    let vxlan = VxlanConnection {
+        parent: Some("eth1".into()),
+        ..vxlan
+    };
+    let uuid = send_command(state.clone(), NetworkCommand::CreateVxlan(vxlan))
+        .await
+        .unwrap()
+        .into_uuid()
+        .unwrap();
+    let vxlan = send_command(state.clone(), NetworkCommand::GetVxlan(uuid))
+        .await
+        .unwrap()
+        .into_vxlan()
+        .unwrap();
+    assert_eq!(vxlan.destination_port, Some(8472));
+
+    let vxlan = send_command(
+        state,
+        NetworkCommand::UpdateVxlan(VxlanConnection {
+            destination_port: Some(4789),
+            ..vxlan
+        }),
+    )
+    .await
+    .unwrap()
+    .into_vxlan()
+    .unwrap();
+    assert_eq!(vxlan.destination_port, Some(4789));
+    assert_eq!(vxlan.parent.as_deref(), Some("eth1"));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_virtual_devices(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let macvlan = VirtualDevice {
+        name: "tenant-a".into(),
+        interface: "macvlan0".into(),
+        kind: "macvlan".into(),
+        parent: Some("eth9".into()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::CreateVirtualDevice(macvlan.clone())
+        )
+        .await,
+        Err(NetworkError::NotFound(_))
+    ));
+    let macvlan = send_command(
+        state.clone(),
+        NetworkCommand::CreateVirtualDevice(VirtualDevice {
+            parent: Some("eth1".into()),
+            ..macvlan
+        }),
+    )
+    .await
+    .unwrap()
+    .into_virtual_device()
+    .unwrap();
+    assert!(!macvlan.uuid.is_empty());
+    assert_eq!(macvlan.mode.as_deref(), Some("bridge"));
+
+    let ipvlan = send_command(
+        state.clone(),
+        NetworkCommand::CreateVirtualDevice(VirtualDevice {
+            interface: "ipvlan0".into(),
+            kind: "ipvlan".into(),
+            parent: Some("eth1".into()),
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap()
+    .into_virtual_device()
+    .unwrap();
+    assert!(ipvlan.uuid.is_empty());
+    assert_eq!(ipvlan.mode.as_deref(), Some("l3"));
+
+    let veth = VirtualDevice {
+        name: "veth".into(),
+        interface: "veth0".into(),
+        kind: "veth".into(),
+        peer: Some("eth1".into()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::CreateVirtualDevice(veth.clone())
+        )
+        .await,
+        Err(NetworkError::Conflict(_))
+    ));
+    send_command(
+        state.clone(),
+        NetworkCommand::CreateVirtualDevice(VirtualDevice {
+            peer: Some("veth1".into()),
+            ..veth
+        }),
+    )
+    .await
+    .unwrap();
+
+    // The software devices are listed with the ethernet one
+    let devices = send_command(state.clone(), NetworkCommand::ListDeivces)
+        .await
+        .unwrap()
+        .into_devices()
+        .unwrap();
+    let names: Vec<&str> = devices.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, ["eth1", "macvlan0", "ipvlan0", "veth0", "veth1"]);
+    assert!(devices[1].r#virtual);
+
+    // Both ends of the veth pair are deleted with either of them
+    send_command(
+        state.clone(),
+        NetworkCommand::DeleteVirtualDevice("veth1".into()),
+    )
+    .await
+    .unwrap();
+    let devices = send_command(state.clone(), NetworkCommand::ListDeivces)
+        .await
+        .unwrap()
+        .into_devices()
+        .unwrap();
+    assert_eq!(devices.len(), 3);
+    assert!(matches!(
+        send_command(state, NetworkCommand::DeleteVirtualDevice("veth0".into())).await,
+        Err(NetworkError::NotFound(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fake_checkpoint(start_fake_instance: Arc<State>) {
+    let state = start_fake_instance;
+    let checkpoint = send_command(
+        state.clone(),
+        NetworkCommand::BeginCheckpoint(vec!["eth1".into()], 0),
+    )
+    .await
+    .unwrap()
+    .into_checkpoint()
+    .unwrap();
+    assert_eq!(checkpoint.devices, ["eth1"]);
+    assert!(matches!(
+        send_command(state.clone(), NetworkCommand::BeginCheckpoint(vec![], 0)).await,
+        Err(NetworkError::Conflict(_))
+    ));
+
+    // The connection created after the checkpoint is gone with the rollback
+    let uuid = create_eth1_connection(&state).await;
+    send_command(
+        state.clone(),
+        NetworkCommand::RollbackCheckpoint(checkpoint.id.clone()),
+    )
+    .await
+    .unwrap();
+    assert!(matches!(
+        send_command(state.clone(), NetworkCommand::GetConnection(uuid)).await,
+        Err(NetworkError::NotFound(_))
+    ));
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::ConfirmCheckpoint(checkpoint.id)
+        )
+        .await,
+        Err(NetworkError::NotFound(_))
+    ));
+
+    // The confirmed changes are kept
+    let checkpoint = send_command(state.clone(), NetworkCommand::BeginCheckpoint(vec![], 0))
+        .await
+        .unwrap()
+        .into_checkpoint()
+        .unwrap();
+    let uuid = create_eth1_connection(&state).await;
+    send_command(
+        state.clone(),
+        NetworkCommand::ConfirmCheckpoint(checkpoint.id),
+    )
+    .await
+    .unwrap();
+    assert!(
+        send_command(state.clone(), NetworkCommand::GetConnection(uuid.clone()))
+            .await
+            .is_ok()
+    );
+
+    // The changes are rolled back when the timeout expires
+    let checkpoint = send_command(state.clone(), NetworkCommand::BeginCheckpoint(vec![], 1))
+        .await
+        .unwrap()
+        .into_checkpoint()
+        .unwrap();
+    send_command(
+        state.clone(),
+        NetworkCommand::DeleteConnectionByUuid(uuid.clone()),
+    )
+    .await
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(matches!(
+        send_command(
+            state.clone(),
+            NetworkCommand::ConfirmCheckpoint(checkpoint.id)
+        )
+        .await,
+        Err(NetworkError::NotFound(_))
+    ));
+    assert!(send_command(state, NetworkCommand::GetConnection(uuid))
+        .await
+        .is_ok());
+}
@@ -19,8 +19,7 @@ async fn test_hostname(#[future] start_instance: Arc<State>) {
             let resp = send_command(start_instance_ref, NetworkCommand::GetHostname)
                 .await
                 .unwrap();
-            let ret = resp.into_value().unwrap();
-            let hostname = ret.as_str().unwrap();
+            let hostname = resp.into_hostname().unwrap();
             assert_eq!(hostname, "rocky9");
 
             send_command(
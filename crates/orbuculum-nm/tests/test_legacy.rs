@@ -0,0 +1,176 @@
+//! Legacy configurations tests module
+//!
+//! The fixtures are in `tests/legacy_configs`, the parsing doesn't need a
+//! NetworkManager daemon.
+use orbuculum_nm::{parse_legacy_config, LegacyFormat, NetworkError};
+use rstest::rstest;
+use std::net::IpAddr;
+
+const IFCFG: &str = include_str!("legacy_configs/ifcfg-eth1");
+const NETPLAN: &str = include_str!("legacy_configs/netplan.yaml");
+const INTERFACES: &str = include_str!("legacy_configs/interfaces");
+
+fn ips(addresses: &[&str]) -> Vec<IpAddr> {
+    addresses.iter().map(|x| x.parse().unwrap()).collect()
+}
+
+#[test]
+fn test_parse_ifcfg() {
+    let config = parse_legacy_config(LegacyFormat::Ifcfg, "ifcfg-eth1", IFCFG).unwrap();
+    assert_eq!(config.connections.len(), 1);
+    let connection = &config.connections[0];
+    assert_eq!(connection.name, "lan");
+    assert_eq!(connection.uuid, "5fb06bd0-0bb0-7ffb-45f1-d6edd65f3e03");
+    assert_eq!(connection.interface.as_deref(), Some("eth1"));
+    assert_eq!(connection.wired.as_ref().and_then(|x| x.mtu), Some(9000));
+
+    let ip4info = &connection.ip4info;
+    assert_eq!(ip4info.method, "manual");
+    assert_eq!(
+        ip4info.addresses,
+        vec![
+            "192.0.2.10/24".parse().unwrap(),
+            "198.51.100.10/24".parse().unwrap()
+        ]
+    );
+    assert_eq!(ip4info.gateway, Some("192.0.2.1".parse().unwrap()));
+    assert_eq!(ip4info.dns, ips(&["192.0.2.53"]));
+    assert_eq!(
+        ip4info.dns_search.clone().unwrap(),
+        vec!["example.com", "lab.local"]
+    );
+    assert_eq!(ip4info.never_default, Some(false));
+
+    let ip6info = &connection.ip6info;
+    assert_eq!(ip6info.method, "manual");
+    assert_eq!(ip6info.addresses, vec!["2001:db8::10/64".parse().unwrap()]);
+    assert_eq!(ip6info.gateway, Some("fe80::1".parse().unwrap()));
+    assert_eq!(ip6info.dns, ips(&["2001:db8::53"]));
+
+    let unsupported: Vec<(&str, &str)> = config
+        .unsupported
+        .iter()
+        .map(|x| (x.location.as_str(), x.directive.as_str()))
+        .collect();
+    assert_eq!(
+        unsupported,
+        vec![
+            ("line 24", "ETHTOOL_OPTS=\"-K eth1 gro off\""),
+            ("line 25", "ZONE=internal"),
+        ]
+    );
+}
+
+#[rstest]
+#[case("TYPE=Bond\nDEVICE=bond0\n", 0, 1)]
+#[case("DEVICE=eth1\nBOOTPROTO=dhcp\nONBOOT=no\n", 1, 1)]
+#[case("BOOTPROTO=dhcp\n", 1, 0)]
+fn test_parse_ifcfg_unsupported(
+    #[case] content: &str,
+    #[case] connections: usize,
+    #[case] unsupported: usize,
+) {
+    let config = parse_legacy_config(LegacyFormat::Ifcfg, "ifcfg-eth1", content).unwrap();
+    assert_eq!(config.connections.len(), connections);
+    assert_eq!(config.unsupported.len(), unsupported);
+    if let Some(connection) = config.connections.first() {
+        assert_eq!(connection.ip4info.method, "auto");
+        assert_eq!(connection.ip6info.method, "disabled");
+    }
+}
+
+#[test]
+fn test_parse_netplan() {
+    let config = parse_legacy_config(LegacyFormat::Netplan, "netplan.yaml", NETPLAN).unwrap();
+    assert_eq!(config.connections.len(), 2);
+
+    let lan = &config.connections[0];
+    assert_eq!(lan.name, "lan");
+    assert_eq!(lan.interface.as_deref(), Some("eth1"));
+    assert_eq!(lan.mac.as_deref(), Some("52:54:00:ab:cd:ef"));
+    assert_eq!(lan.ip4info.method, "manual");
+    assert_eq!(
+        lan.ip4info.addresses,
+        vec!["192.0.2.10/24".parse().unwrap()]
+    );
+    assert_eq!(lan.ip4info.dns, ips(&["192.0.2.53"]));
+    assert_eq!(lan.ip4info.dns_search.clone().unwrap(), vec!["example.com"]);
+    let routes = lan.ip4info.routes.as_ref().unwrap();
+    assert_eq!(routes.len(), 2);
+    assert_eq!(routes[0].dest, "0.0.0.0/0".parse().unwrap());
+    assert_eq!(routes[1].metric, 100);
+    assert_eq!(lan.ip6info.method, "manual");
+    assert_eq!(
+        lan.ip6info.addresses,
+        vec!["2001:db8::10/64".parse().unwrap()]
+    );
+    assert_eq!(lan.ip6info.dns, ips(&["2001:db8::53"]));
+
+    let eth2 = &config.connections[1];
+    assert_eq!(eth2.interface.as_deref(), Some("eth2"));
+    assert_eq!(eth2.ip4info.method, "auto");
+    assert_eq!(eth2.ip4info.ignore_auto_dns, Some(true));
+    assert_eq!(eth2.ip6info.method, "disabled");
+
+    let unsupported: Vec<&str> = config
+        .unsupported
+        .iter()
+        .map(|x| x.location.as_str())
+        .collect();
+    assert_eq!(
+        unsupported,
+        vec![
+            "network.ethernets.lan.routes.1.type",
+            "network.ethernets.lan.wakeonlan",
+            "network.ethernets.eth2.dhcp4-overrides.route-metric",
+            "network.bonds",
+        ]
+    );
+}
+
+#[rstest]
+#[case("network:\n  version: 1\n")]
+#[case("network:\n  ethernets:\n    eth1:\n      addresses: [192.0.2.300/24]\n")]
+#[case("network: [")]
+#[case("ethernets: {}\n")]
+fn test_parse_netplan_invalid(#[case] content: &str) {
+    let err = parse_legacy_config(LegacyFormat::Netplan, "netplan.yaml", content).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<NetworkError>(),
+        Some(NetworkError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn test_parse_interfaces() {
+    let config = parse_legacy_config(LegacyFormat::Interfaces, "interfaces", INTERFACES).unwrap();
+    let names: Vec<&str> = config.connections.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, vec!["eth1", "eth2"]);
+
+    let eth1 = &config.connections[0];
+    assert_eq!(eth1.ip4info.method, "manual");
+    assert_eq!(
+        eth1.ip4info.addresses,
+        vec!["192.0.2.10/24".parse().unwrap()]
+    );
+    assert_eq!(eth1.ip4info.gateway, Some("192.0.2.1".parse().unwrap()));
+    assert_eq!(eth1.ip4info.dns, ips(&["192.0.2.53"]));
+    assert_eq!(eth1.ip6info.dns, ips(&["2001:db8::53"]));
+    assert_eq!(eth1.wired.as_ref().and_then(|x| x.mtu), Some(9000));
+    assert_eq!(eth1.ip6info.method, "manual");
+    assert_eq!(
+        eth1.ip6info.addresses,
+        vec!["2001:db8::10/64".parse().unwrap()]
+    );
+
+    let eth2 = &config.connections[1];
+    assert_eq!(eth2.ip4info.method, "auto");
+    assert_eq!(eth2.ip6info.method, "disabled");
+
+    let unsupported: Vec<&str> = config
+        .unsupported
+        .iter()
+        .map(|x| x.location.as_str())
+        .collect();
+    assert_eq!(unsupported, vec!["line 5", "line 15", "line 24", "line 26"]);
+}
@@ -46,8 +46,7 @@ async fn test_get_networking(#[future] start_instance: Arc<State>) {
             let resp = send_command(start_instance_ref, NetworkCommand::GetNetworking)
                 .await
                 .unwrap();
-            let value = resp.into_value().unwrap();
-            let state = value["state"].as_bool().unwrap();
+            let state = resp.into_networking().unwrap();
             let cur_state = context::run_shell_cmd("nmcli networking").unwrap();
             if cur_state == "disabled" {
                 assert!(!state);
@@ -1,11 +1,72 @@
 use std::sync::Arc;
 
-use axum::extract::{Json, Path, State};
-use axum::http::StatusCode;
+use axum::extract::{Json, Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{delete, get, patch, post, put};
+use axum::Router;
 use orbuculum_grpc::{
-    ConnectionBody, ConnectionUuidRequest, HostnameBody, NetworkClient, NetworkingStateBody,
+    BondBody, BondPortRequest, ConnectionBody, ConnectionNameRequest, ConnectionUuidRequest,
+    CreateConnectionBody, HostnameBody, KeyfileExportRequest, KeyfileImportRequest,
+    LegacyConfigRequest, ManageBody, NetworkClient, NetworkingStateBody, RenameConnectionBody,
+    RouteRequest, Routes,
 };
-use serde_json::Value;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tonic::transport::Channel;
+use tonic::{Code, Status};
+
+/// The error response of the handlers, the body carries the gRPC code and message.
+pub type ApiError = (StatusCode, Json<Value>);
+
+/// Map the gRPC status to the HTTP status code.
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::OutOfRange => StatusCode::BAD_REQUEST,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted | Code::FailedPrecondition => StatusCode::CONFLICT,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn status_error(status: Status) -> ApiError {
+    (
+        http_status(status.code()),
+        Json(json!({"code": status.code() as i32, "msg": status.message()})),
+    )
+}
+
+fn internal_error(err: impl std::fmt::Display) -> ApiError {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"code": Code::Internal as i32, "msg": err.to_string()})),
+    )
+}
+
+async fn connect(grpc_info: &GrpcInfo) -> Result<NetworkClient<Channel>, ApiError> {
+    NetworkClient::connect(grpc_info.address())
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"code": Code::Unavailable as i32, "msg": err.to_string()})),
+            )
+        })
+}
+
+fn to_json<T: serde::Serialize>(reply: T) -> Result<Json<Value>, ApiError> {
+    serde_json::to_value(reply)
+        .map(Json)
+        .map_err(internal_error)
+}
+
 pub struct GrpcInfo {
     address: String,
 }
@@ -25,141 +86,525 @@ pub async fn health() -> StatusCode {
     StatusCode::OK
 }
 
-pub async fn list_devices(State(grpc_info): State<Arc<GrpcInfo>>) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+pub async fn list_devices(State(grpc_info): State<Arc<GrpcInfo>>) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     let request = tonic::Request::new(().into());
 
-    let response = client.list_devices(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client.list_devices(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
 }
 
 pub async fn list_connections(
     State(grpc_info): State<Arc<GrpcInfo>>,
-) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(().into());
+
+    let response = client
+        .list_connections(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn list_active_connections(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     let request = tonic::Request::new(().into());
 
-    let response = client.list_connections(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client
+        .list_active_connections(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
 }
 
 pub async fn update_connections(
     State(grpc_info): State<Arc<GrpcInfo>>,
     Json(connections): Json<Vec<ConnectionBody>>,
-) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     // Update connections one by one
     for connection in connections {
         let request = tonic::Request::new(connection);
-        client.update_connection(request).await.unwrap();
+        client
+            .update_connection(request)
+            .await
+            .map_err(status_error)?;
     }
 
     // Restart overall network
     let request = tonic::Request::new(().into());
-    client.restart_networking(request).await.unwrap();
+    client
+        .restart_networking(request)
+        .await
+        .map_err(status_error)?;
 
     // get current network connections
     let request = tonic::Request::new(().into());
-    let response = client.list_connections(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client
+        .list_connections(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
 }
 
-pub async fn get_hostname(State(grpc_info): State<Arc<GrpcInfo>>) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+pub async fn get_hostname(State(grpc_info): State<Arc<GrpcInfo>>) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     let request = tonic::Request::new(().into());
 
-    let response = client.get_hostname(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client.get_hostname(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
 }
 
 pub async fn set_hostname(
     State(grpc_info): State<Arc<GrpcInfo>>,
     Json(hostname_json): Json<HostnameBody>,
-) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     let request = tonic::Request::new(hostname_json);
 
-    let response = client.set_hostname(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client.set_hostname(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
 }
 
 pub async fn get_connection_by_uuid(
     Path(uuid): Path<String>,
     State(grpc_info): State<Arc<GrpcInfo>>,
-) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     let request = tonic::Request::new(ConnectionUuidRequest { uuid });
 
-    let response = client.get_connection_by_uuid(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client
+        .get_connection_by_uuid(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
 }
 
 pub async fn update_connection(
     State(grpc_info): State<Arc<GrpcInfo>>,
     Json(connection): Json<ConnectionBody>,
-) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     let request = tonic::Request::new(connection);
 
-    let response = client.update_connection(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client
+        .update_connection(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
 }
 
-pub async fn get_networking(State(grpc_info): State<Arc<GrpcInfo>>) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+pub async fn get_networking(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     let request = tonic::Request::new(().into());
 
-    let response = client.get_networking(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client.get_networking(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
 }
 
 pub async fn set_networking(
     State(grpc_info): State<Arc<GrpcInfo>>,
     Json(networking_state): Json<NetworkingStateBody>,
-) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     let request = tonic::Request::new(networking_state);
 
-    let response = client.set_networking(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client.set_networking(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
 }
 
 pub async fn restart_networking(
     State(grpc_info): State<Arc<GrpcInfo>>,
-) -> axum::extract::Json<Value> {
-    let grpc_addr = grpc_info.address();
-    let mut client = NetworkClient::connect(grpc_addr).await.unwrap();
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(().into());
+
+    let response = client
+        .restart_networking(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn get_connectivity(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(().into());
+
+    let response = client
+        .get_connectivity(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn check_connectivity(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
 
     let request = tonic::Request::new(().into());
 
-    let response = client.restart_networking(request).await.unwrap();
-    let json_val = serde_json::to_value(response.into_inner()).unwrap();
-    json_val.into()
+    let response = client
+        .check_connectivity(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn create_connection(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+    Json(connection): Json<CreateConnectionBody>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(connection);
+
+    let response = client
+        .create_connection(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn rename_connection(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+    Json(rename): Json<RenameConnectionBody>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(rename);
+
+    let response = client
+        .rename_connection(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn delete_connection_by_uuid(
+    Path(uuid): Path<String>,
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(ConnectionUuidRequest { uuid });
+
+    let response = client
+        .delete_connection_by_uuid(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn delete_connection_by_name(
+    Path(name): Path<String>,
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(ConnectionNameRequest { name });
+
+    let response = client
+        .delete_connection_by_name(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn set_manage(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+    Json(manage): Json<ManageBody>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(manage);
+
+    let response = client.set_manage(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn add_route(
+    Path(uuid): Path<String>,
+    State(grpc_info): State<Arc<GrpcInfo>>,
+    Json(route): Json<Routes>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(RouteRequest {
+        uuid,
+        route: Some(route),
+    });
+
+    let response = client.add_route(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn remove_route(
+    Path(uuid): Path<String>,
+    State(grpc_info): State<Arc<GrpcInfo>>,
+    Json(route): Json<Routes>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(RouteRequest {
+        uuid,
+        route: Some(route),
+    });
+
+    let response = client.remove_route(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn create_bond(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+    Json(bond): Json<BondBody>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(bond);
+
+    let response = client.create_bond(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn get_bond(
+    Path(uuid): Path<String>,
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(ConnectionUuidRequest { uuid });
+
+    let response = client.get_bond(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn attach_bond_port(
+    Path((uuid, device)): Path<(String, String)>,
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(BondPortRequest { uuid, device });
+
+    let response = client
+        .attach_bond_port(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+pub async fn detach_bond_port(
+    Path((uuid, device)): Path<(String, String)>,
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(BondPortRequest { uuid, device });
+
+    let response = client
+        .detach_bond_port(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+/// The query of the keyfile export, the secrets are redacted by default.
+#[derive(Deserialize, Debug, Default)]
+pub struct KeyfileQuery {
+    #[serde(default)]
+    include_secrets: bool,
+}
+
+/// The file name of the downloaded keyfile, the characters which are not
+/// safe in the header are replaced.
+fn keyfile_filename(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|x| {
+            if x.is_ascii_alphanumeric() || "-_. ".contains(x) {
+                x
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.nmconnection", name)
+}
+
+pub async fn export_keyfiles(
+    Query(query): Query<KeyfileQuery>,
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(KeyfileExportRequest {
+        uuid: None,
+        include_secrets: query.include_secrets,
+    });
+
+    let response = client
+        .export_keyfiles(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+/// Download the keyfile of the connection as `<name>.nmconnection`.
+pub async fn export_keyfile(
+    Path(uuid): Path<String>,
+    Query(query): Query<KeyfileQuery>,
+    State(grpc_info): State<Arc<GrpcInfo>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(KeyfileExportRequest {
+        uuid: Some(uuid.clone()),
+        include_secrets: query.include_secrets,
+    });
+
+    let response = client
+        .export_keyfiles(request)
+        .await
+        .map_err(status_error)?;
+    let keyfile = response
+        .into_inner()
+        .data
+        .into_iter()
+        .next()
+        .ok_or_else(|| status_error(Status::not_found(format!("Connection {} not found", uuid))))?;
+    let disposition = format!(
+        "attachment; filename=\"{}\"",
+        keyfile_filename(&keyfile.name)
+    );
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/plain; charset=utf-8".to_string(),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        keyfile.content,
+    ))
+}
+
+/// Upload a keyfile, the body is the keyfile text.
+pub async fn import_keyfile(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+    content: String,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(KeyfileImportRequest { content });
+
+    let response = client.import_keyfile(request).await.map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+/// Parse a legacy configuration, the profiles it would create are returned
+/// with the unsupported directives and nothing is changed.
+pub async fn preview_legacy_config(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+    Json(legacy_json): Json<LegacyConfigRequest>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(legacy_json);
+
+    let response = client
+        .preview_legacy_config(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+/// Create the profiles of a legacy configuration.
+pub async fn apply_legacy_config(
+    State(grpc_info): State<Arc<GrpcInfo>>,
+    Json(legacy_json): Json<LegacyConfigRequest>,
+) -> Result<Json<Value>, ApiError> {
+    let mut client = connect(&grpc_info).await?;
+
+    let request = tonic::Request::new(legacy_json);
+
+    let response = client
+        .apply_legacy_config(request)
+        .await
+        .map_err(status_error)?;
+    to_json(response.into_inner())
+}
+
+/// The routes of the proxy api and the health check, the caller gives the
+/// state and the layers.
+pub fn api_router() -> Router<Arc<GrpcInfo>> {
+    Router::new()
+        .route("/api/proxy/devices", get(list_devices))
+        .route("/api/proxy/hostname", get(get_hostname).post(set_hostname))
+        .route(
+            "/api/proxy/connections",
+            get(list_connections).post(update_connections),
+        )
+        .route(
+            "/api/proxy/connections/active",
+            get(list_active_connections),
+        )
+        .route(
+            "/api/proxy/connection/:uuid",
+            get(get_connection_by_uuid).delete(delete_connection_by_uuid),
+        )
+        .route(
+            "/api/proxy/connection",
+            put(update_connection).post(create_connection),
+        )
+        .route("/api/proxy/connection/rename", post(rename_connection))
+        .route(
+            "/api/proxy/connection/name/:name",
+            delete(delete_connection_by_name),
+        )
+        .route(
+            "/api/proxy/connection/:uuid/routes",
+            post(add_route).delete(remove_route),
+        )
+        .route(
+            "/api/proxy/keyfiles",
+            get(export_keyfiles).post(import_keyfile),
+        )
+        .route("/api/proxy/keyfile/:uuid", get(export_keyfile))
+        .route("/api/proxy/legacy/preview", post(preview_legacy_config))
+        .route("/api/proxy/legacy/apply", post(apply_legacy_config))
+        .route("/api/proxy/device/manage", patch(set_manage))
+        .route(
+            "/api/proxy/networking",
+            get(get_networking).patch(set_networking),
+        )
+        .route("/api/proxy/restart", post(restart_networking))
+        .route("/api/proxy/connectivity", get(get_connectivity))
+        .route("/api/proxy/connectivity/check", post(check_connectivity))
+        .route("/api/proxy/bond", post(create_bond))
+        .route("/api/proxy/bond/:uuid", get(get_bond))
+        .route(
+            "/api/proxy/bond/:uuid/ports/:device",
+            post(attach_bond_port).delete(detach_bond_port),
+        )
+        .route("/health", get(health))
 }
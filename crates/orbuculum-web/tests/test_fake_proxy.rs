@@ -0,0 +1,164 @@
+//! Fake proxy tests module
+//!
+//! The tests start the gRPC server on the in-memory backend and call the api
+//! router, so they don't need a NetworkManager daemon.
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::Router;
+use orbuculum_grpc::{create_server, NetworkClient};
+use orbuculum_nm::{create_channel, run_network_backend_loop, FakeBackend, NetDevice, State};
+use orbuculum_web::{api_router, GrpcInfo};
+use rstest::{fixture, rstest};
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tonic::Code;
+use tower::ServiceExt;
+
+/// Start the gRPC server on a free port of the localhost, the address to
+/// connect is returned.
+#[fixture]
+fn start_fake_server() -> String {
+    let (glib_sender, glib_receiver) = create_channel();
+    let backend = FakeBackend::new()
+        .with_hostname("fake")
+        .with_device(NetDevice {
+            name: "eth1".into(),
+            mac: "52:54:5E:13:7F:43".into(),
+            state: "Disconnected".into(),
+            device_type: "Ethernet".into(),
+            is_managed: true,
+            ..Default::default()
+        });
+    let state = Arc::new(State::new(glib_sender));
+    let events = state.event_sender();
+    thread::spawn(move || {
+        run_network_backend_loop(glib_receiver, backend, events);
+    });
+
+    let bind_address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|x| x.local_addr())
+        .unwrap()
+        .to_string();
+    let address = format!("http://{}", bind_address);
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(create_server(state, bind_address))
+            .unwrap();
+    });
+    address
+}
+
+/// The api router of the server, which may not listen yet.
+async fn start_router(address: &str) -> Router {
+    for _ in 0..50 {
+        if NetworkClient::connect(address.to_string()).await.is_ok() {
+            return api_router().with_state(Arc::new(GrpcInfo::new(address)));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Failed to connect the server {}", address)
+}
+
+/// Call the router, the status and the JSON body are returned, the body is
+/// null if it is not JSON.
+async fn call(app: &Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let builder = Request::builder().method(method).uri(uri);
+    let request = match body {
+        Some(body) => builder
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => builder.body(Body::empty()),
+    }
+    .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_web_hostname(start_fake_server: String) {
+    let app = start_router(&start_fake_server).await;
+    let (status, _) = call(&app, Method::GET, "/health", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = call(&app, Method::GET, "/api/proxy/devices", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["name"], "eth1");
+
+    let (status, body) = call(
+        &app,
+        Method::POST,
+        "/api/proxy/hostname",
+        Some(json!({"hostname": "orbuculum"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["code"], 0);
+    let (status, body) = call(&app, Method::GET, "/api/proxy/hostname", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["hostname"], "orbuculum");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_web_connection(start_fake_server: String) {
+    let app = start_router(&start_fake_server).await;
+    let (status, body) = call(
+        &app,
+        Method::POST,
+        "/api/proxy/connection",
+        Some(json!({"name": "eth1-conn", "device": "eth1"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["code"], 0);
+    assert_eq!(body["data"]["interface"], "eth1");
+
+    let uri = format!(
+        "/api/proxy/connection/{}",
+        body["data"]["uuid"].as_str().unwrap()
+    );
+    let (status, body) = call(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["name"], "eth1-conn");
+
+    let (status, _) = call(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = call(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], Code::NotFound as i32);
+    assert!(body["msg"].as_str().unwrap().contains("not found"));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_web_error_status(start_fake_server: String) {
+    let app = start_router(&start_fake_server).await;
+    let (status, body) = call(&app, Method::GET, "/api/proxy/connection/missing", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], Code::NotFound as i32);
+
+    let (status, body) = call(
+        &app,
+        Method::POST,
+        "/api/proxy/connection",
+        Some(json!({"name": "eth1-conn", "device": ""})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], Code::InvalidArgument as i32);
+
+    // The gRPC server is not reachable
+    let app = api_router().with_state(Arc::new(GrpcInfo::new("http://127.0.0.1:1")));
+    let (status, body) = call(&app, Method::GET, "/api/proxy/devices", None).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["code"], Code::Unavailable as i32);
+}
@@ -1,4 +1,4 @@
-use orbuculum_ctl::mainloop;
+use orbuculum_ctl::{apply_state, mainloop, show_state};
 use std::sync::Arc;
 use structopt::StructOpt;
 
@@ -7,16 +7,47 @@ use structopt::StructOpt;
 struct Argument {
     #[structopt(short, long, default_value = "http://127.0.0.1:15051")]
     grpc_address: String,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Show or apply the declarative network state
+    State(StateCommand),
+}
+
+#[derive(Debug, StructOpt)]
+enum StateCommand {
+    /// Print the network state in YAML
+    Show,
+    /// Apply the network state of a YAML file
+    Apply {
+        file: String,
+        /// Print the planned changes without applying them
+        #[structopt(long)]
+        dry_run: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() {
     let args = Argument::from_args();
     let grpc_addr = Arc::new(args.grpc_address.as_str());
-    loop {
-        match mainloop(grpc_addr.clone()).await {
-            Ok(_) => break,
-            _ => (),
+    let result = match args.command {
+        Some(Command::State(StateCommand::Show)) => show_state(grpc_addr).await,
+        Some(Command::State(StateCommand::Apply { file, dry_run })) => {
+            apply_state(grpc_addr, &file, dry_run).await
         }
+        None => loop {
+            match mainloop(grpc_addr.clone()).await {
+                Ok(_) => break Ok(()),
+                _ => (),
+            }
+        },
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
     }
 }
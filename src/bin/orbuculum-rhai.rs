@@ -17,15 +17,18 @@ async fn main() {
         .nth(1)
         .expect("Please provide the rule file name.");
 
+    let shared_state = Arc::new(State::new(glib_sender));
+    let events = shared_state.event_sender();
     thread::spawn(move || {
-        run_network_manager_loop(glib_receiver, arc_link_modes);
+        run_network_manager_loop(glib_receiver, arc_link_modes, events);
     });
 
-    let shared_state = Arc::new(State::new(glib_sender));
     let mut devices = send_command(shared_state, NetworkCommand::ListDeivces)
         .await
-        .and_then(|x| Ok(x.into_value().unwrap()))
-        .unwrap();
+        .ok()
+        .and_then(|x| x.into_devices())
+        .and_then(|x| serde_json::to_value(x).ok())
+        .expect("Failed to list network devices");
 
     if let Some(devices) = sort_devices(&nicrule_file, &mut devices) {
         debug_devices(&devices);
@@ -1,9 +1,5 @@
-use axum::routing::{get, post, put};
-use orbuculum_web::{
-    get_connection_by_uuid, get_hostname, get_networking, health, list_connections, list_devices,
-    restart_networking, set_hostname, set_networking, update_connection, update_connections,
-    GrpcInfo,
-};
+use axum::routing::get;
+use orbuculum_web::{api_router, health, GrpcInfo};
 use std::sync::Arc;
 use structopt::StructOpt;
 use tower_http::{
@@ -29,22 +25,8 @@ async fn main() {
     let addr = args.bind_address.parse().unwrap();
 
     // Build our application by creating our router.
-    let app = axum::Router::new()
-        .route("/api/proxy/devices", get(list_devices))
-        .route("/api/proxy/hostname", get(get_hostname).post(set_hostname))
-        .route(
-            "/api/proxy/connections",
-            get(list_connections).post(update_connections),
-        )
-        .route("/api/proxy/connection/:uuid", get(get_connection_by_uuid))
-        .route("/api/proxy/connection", put(update_connection))
-        .route(
-            "/api/proxy/networking",
-            get(get_networking).patch(set_networking),
-        )
-        .route("/api/proxy/restart", post(restart_networking))
-        // health with tracing
-        .route("/health", get(health))
+    let app = api_router()
+        // api and health with tracing
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(DefaultMakeSpan::new().include_headers(true))
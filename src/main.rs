@@ -82,15 +82,17 @@ async fn main() {
     let link_modes = gather_link_modes(None).await.unwrap();
     let arc_link_modes = Arc::new(link_modes);
 
+    let shared_state = Arc::new(State::new(glib_sender));
+    let events = shared_state.event_sender();
     thread::spawn(move || {
-        run_network_manager_loop(glib_receiver, arc_link_modes);
+        run_network_manager_loop(glib_receiver, arc_link_modes, events);
     });
 
-    let shared_state = Arc::new(State::new(glib_sender));
-
     if !args.no_initialize {
         let config_path = lookup_config_path(args.config_dir, &PathBuf::from(SYS_DMI_DIR)).unwrap();
-        initialize_network_manager(shared_state.clone(), config_path).await;
+        initialize_network_manager(shared_state.clone(), config_path)
+            .await
+            .unwrap();
     }
     create_server(shared_state, args.bind_address)
         .await